@@ -0,0 +1,77 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use necromancer::necro::{Engine, Necromancer, OutputBuffer, RitualConfig};
+use necromancer::scroll::Scroll;
+
+/// A zombie counting to a thousand, checking on another zombie for every step.
+const COUNT: &str = "\
+Counter is a zombie
+summon
+    remember 0
+    task Count
+        shamble
+            remember Counter moan Counter moan Step
+        until remembering 1000
+    animate
+animate
+
+Step is a zombie
+summon
+    remember 1
+bind";
+
+/// A zombie copying a long text to another zombie a thousand times.
+const COPY: &str = "\
+Counter is a zombie
+summon
+    remember 0
+    task Copy
+        shamble
+            remember Echo moan Text
+            remember Counter moan Counter moan Step
+        until remembering 1000
+    animate
+animate
+
+Text is a zombie
+summon
+    remember \"What was buried does not stay buried, and what was written is copied forever.\"
+bind
+
+Echo is a zombie
+summon
+bind
+
+Step is a zombie
+summon
+    remember 1
+bind";
+
+fn perform(scroll: Scroll) {
+    Necromancer::unroll(scroll)
+        .with_config(
+            RitualConfig::default()
+                .output(OutputBuffer::new())
+                .engine(Engine::CurrentThreadDeterministic)
+                .seed(0),
+        )
+        .initiate();
+}
+
+fn shamble(c: &mut Criterion) {
+    let scroll = necromancer::parse::parse(COUNT).unwrap();
+    c.bench_function("shamble", |b| b.iter(|| perform(scroll.clone())));
+}
+
+fn copy(c: &mut Criterion) {
+    let scroll = necromancer::parse::parse(COPY).unwrap();
+    c.bench_function("copy", |b| b.iter(|| perform(scroll.clone())));
+}
+
+fn fibonacci(c: &mut Criterion) {
+    let code = include_str!("../examples/Fibonacci.z");
+    let scroll = necromancer::parse::parse(code).unwrap();
+    c.bench_function("fibonacci", |b| b.iter(|| perform(scroll.clone())));
+}
+
+criterion_group!(benches, shamble, copy, fibonacci);
+criterion_main!(benches);
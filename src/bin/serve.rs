@@ -0,0 +1,171 @@
+//! A playground server performing rituals for scrolls sent over HTTP.
+//!
+//! `POST /run` with the scroll as the request body. Every ritual runs in isolation on a runtime
+//! of its own, with a time limit and a limit on the number of spirits. Access to the environment,
+//! the file system and the network is never granted.
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::{Json, Router};
+use clap::{command, value_parser, Arg};
+use necromancer::necro::{Necromancer, OutputBuffer, RitualConfig, RitualReport};
+use serde_json::{json, Map, Value as JsonValue};
+use tokio::net::TcpListener;
+use tokio::sync::Semaphore;
+use tracing::{error, info};
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+
+struct Limits {
+    timeout: Duration,
+    max_spirits: usize,
+    /// Permits for rituals running at the same time.
+    rituals: Semaphore,
+}
+
+#[tokio::main]
+async fn main() {
+    let matches = command!()
+        .arg(
+            Arg::new("bind")
+                .long("bind")
+                .value_name("ADDRESS")
+                .default_value("127.0.0.1:6660")
+                .help("Where to listen for scrolls."),
+        )
+        .arg(
+            Arg::new("log_format")
+                .long("log-format")
+                .value_name("FORMAT")
+                .value_parser(["pretty", "json"])
+                .default_value("pretty")
+                .help("Write logs for humans, or as one JSON object per line."),
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .value_name("MILLIS")
+                .value_parser(value_parser!(u64))
+                .default_value("5000")
+                .help("Abort rituals taking longer than this."),
+        )
+        .arg(
+            Arg::new("max_spirits")
+                .long("max-spirits")
+                .value_name("COUNT")
+                .value_parser(value_parser!(usize))
+                .default_value("1000")
+                .help("Refuse to summon more spirits than this in a single ritual."),
+        )
+        .arg(
+            Arg::new("max_rituals")
+                .long("max-rituals")
+                .value_name("COUNT")
+                .value_parser(value_parser!(usize))
+                .default_value("4")
+                .help("Perform at most this many rituals at the same time."),
+        )
+        .get_matches();
+
+    let logger = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env().add_directive(LevelFilter::INFO.into()))
+        .with_writer(io::stderr);
+    match matches.get_one::<String>("log_format").unwrap().as_str() {
+        "json" => logger.json().with_span_list(true).init(),
+        _ => logger.init(),
+    }
+
+    let limits = Arc::new(Limits {
+        timeout: Duration::from_millis(*matches.get_one::<u64>("timeout").unwrap()),
+        max_spirits: *matches.get_one::<usize>("max_spirits").unwrap(),
+        rituals: Semaphore::new(*matches.get_one::<usize>("max_rituals").unwrap()),
+    });
+
+    let app = Router::new().route("/run", post(run)).with_state(limits);
+
+    let address = matches.get_one::<String>("bind").unwrap();
+    let listener = match TcpListener::bind(address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Cannot listen on {}: {}", address, e);
+            std::process::exit(1);
+        }
+    };
+    info!("Listening on {}", address);
+    if let Err(e) = axum::serve(listener, app).await {
+        error!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Perform the ritual for the scroll in the request body.
+async fn run(State(limits): State<Arc<Limits>>, code: String) -> (StatusCode, Json<JsonValue>) {
+    let scroll = match necromancer::parse::parse(&code) {
+        Ok(scroll) => scroll,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": e.to_string() })),
+            )
+        }
+    };
+
+    let Ok(_permit) = limits.rituals.acquire().await else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "error": "the server is shutting down" })),
+        );
+    };
+
+    let output = OutputBuffer::new();
+    let config = RitualConfig::default()
+        .output(output.clone())
+        .timeout(limits.timeout)
+        .max_spirits(limits.max_spirits);
+
+    // `initiate` brings its own runtime, so it has to run outside of the server's one.
+    let ritual = tokio::task::spawn_blocking(move || {
+        Necromancer::unroll(scroll).with_config(config).initiate()
+    });
+    match ritual.await {
+        Ok(report) => (StatusCode::OK, Json(to_json(&report, output.contents()))),
+        Err(e) => {
+            error!("Ritual failed: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "the ritual failed" })),
+            )
+        }
+    }
+}
+
+fn to_json(report: &RitualReport, output: String) -> JsonValue {
+    let state: Map<String, JsonValue> = report
+        .final_state()
+        .iter()
+        .map(|(name, (memory, active))| {
+            (
+                name.to_string(),
+                json!({ "memory": memory.to_string(), "active": active }),
+            )
+        })
+        .collect();
+    let retries: Map<String, JsonValue> = report
+        .retries()
+        .iter()
+        .map(|(name, retries)| (name.to_string(), json!(retries)))
+        .collect();
+    json!({
+        "output": output,
+        "state": state,
+        "spirits": report.spirits(),
+        "runtime_ms": report.runtime().as_millis() as u64,
+        "termination": report.termination().to_string(),
+        "error": report.error().map(ToString::to_string),
+        "warnings": report.warnings().iter().map(ToString::to_string).collect::<Vec<_>>(),
+        "retries": retries,
+    })
+}
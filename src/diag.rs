@@ -0,0 +1,502 @@
+//! Rendering of errors and warnings for humans, in the style of the Rust compiler.
+//!
+//! ```text
+//! error[E0001]: cannot parse the scroll: expected a keyword
+//!  --> scroll.z:1:12
+//!   |
+//! 1 | Peter is a wombat
+//!   |            ^
+//! ```
+use std::fmt::Write;
+
+use nom::error::ErrorKind;
+
+use crate::validate::lint::Lint;
+use crate::validate::Diagnostic;
+
+/// How bad a [`Diag`] is.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn label(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+
+    /// ANSI escape code for the colour of the severity.
+    fn colour(self) -> &'static str {
+        match self {
+            Severity::Error => "\x1b[1;31m",
+            Severity::Warning => "\x1b[1;33m",
+        }
+    }
+}
+
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// An error or warning about a scroll, ready to be rendered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diag {
+    severity: Severity,
+    code: &'static str,
+    message: String,
+    /// Byte offset into the source code that the diagnostic points at, if known.
+    offset: Option<usize>,
+    /// Number of characters to underline, starting at the offset.
+    length: usize,
+    /// How to fix the problem, if known.
+    help: Option<String>,
+}
+
+impl Diag {
+    pub fn new(severity: Severity, code: &'static str, message: impl Into<String>) -> Diag {
+        Diag {
+            severity,
+            code,
+            message: message.into(),
+            offset: None,
+            length: 1,
+            help: None,
+        }
+    }
+
+    /// Point at the given byte offset in the source code.
+    pub fn at(mut self, offset: usize) -> Diag {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Underline the given number of characters, starting at the offset.
+    pub fn spanning(mut self, length: usize) -> Diag {
+        self.length = length.max(1);
+        self
+    }
+
+    /// Add a note on how to fix the problem, like a [`Suggestion`](crate::parse::Suggestion).
+    pub fn help(mut self, help: impl Into<String>) -> Diag {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Describe an error returned by [`parse`](crate::parse::parse) for the given source code.
+    pub fn from_parse_error(code: &str, error: &nom::error::Error<&str>) -> Diag {
+        // The parser reports the remaining input, which is a slice of the source code. It is not
+        // necessarily a suffix, since entities and tasks are parsed from slices of their own.
+        let offset = (error.input.as_ptr() as usize)
+            .checked_sub(code.as_ptr() as usize)
+            .filter(|offset| offset + error.input.len() <= code.len())
+            .unwrap_or_else(|| code.len().saturating_sub(error.input.len()));
+        // Malformed numbers make the parser fail right at the start of the literal.
+        if error.code == ErrorKind::Digit
+            && error
+                .input
+                .starts_with(|c: char| c.is_ascii_digit() || c == '+' || c == '-')
+        {
+            let literal = error
+                .input
+                .split(char::is_whitespace)
+                .next()
+                .unwrap_or_default();
+            return Diag::new(Severity::Error, "E0002", "invalid number literal")
+                .at(offset)
+                .spanning(literal.chars().count());
+        }
+        Diag::new(
+            Severity::Error,
+            "E0001",
+            format!("cannot parse the scroll: expected {}", expected(error.code)),
+        )
+        .at(offset)
+    }
+
+    /// Describe a finding of the validation pass. Points at the offending statement in the
+    /// given source code if it can be found there, since the syntax tree doesn't keep positions.
+    pub fn from_diagnostic(code: &str, diagnostic: &Diagnostic) -> Diag {
+        let diag = Diag::from(diagnostic);
+        let (entity, task, stmt) = match diagnostic {
+            Diagnostic::AnimateOnNonZombie {
+                entity,
+                task,
+                target,
+                ..
+            } => (entity, task, format!("animate {}", target)),
+            Diagnostic::DisturbOnNonGhost {
+                entity,
+                task,
+                target,
+                ..
+            } => (entity, task, format!("disturb {}", target)),
+            _ => return diag,
+        };
+        let path = [format!("{} is", entity), format!("task {}", task), stmt];
+        match find_in_order(code, &path) {
+            Some(offset) => diag.at(offset).spanning(path[2].chars().count()),
+            None => diag,
+        }
+    }
+
+    /// Describe a finding of the linter, pointing at what it is about in the given source code if
+    /// it can be found there. The lint is a warning unless [denied](Diag::deny).
+    pub fn from_lint(code: &str, lint: &Lint) -> Diag {
+        let diag = Diag::new(Severity::Warning, lint.rule().code(), lint.to_string());
+        let path = match lint {
+            Lint::UnusedEntity { entity } | Lint::WriteOnlyEntity { entity } => {
+                // Underline the name where the entity is declared.
+                return match find_in_order(code, &[format!("{} is", entity)]) {
+                    Some(offset) => diag.at(offset).spanning(entity.chars().count()),
+                    None => diag,
+                };
+            }
+            Lint::EmptyTask { entity, task } => {
+                vec![format!("{} is", entity), format!("task {}", task)]
+            }
+            Lint::ConstantCondition {
+                entity,
+                task,
+                keyword,
+            } => {
+                // The condition of a shamble follows its block.
+                let keyword = if *keyword == "shamble" {
+                    "until"
+                } else {
+                    keyword
+                };
+                vec![
+                    format!("{} is", entity),
+                    format!("task {}", task),
+                    String::from(keyword),
+                ]
+            }
+            Lint::MagicNumber {
+                entity,
+                task,
+                number,
+            } => vec![
+                format!("{} is", entity),
+                format!("task {}", task),
+                number.to_string(),
+            ],
+        };
+        let last = path.last().unwrap();
+        match find_in_order(code, &path) {
+            Some(offset) => diag.at(offset).spanning(last.chars().count()),
+            None => diag,
+        }
+    }
+
+    /// Turn the diagnostic into an error, e.g. for a warning that is not tolerated.
+    pub fn deny(mut self) -> Diag {
+        self.severity = Severity::Error;
+        self
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    /// Render the diagnostic, with a snippet of the source code if the position is known.
+    ///
+    /// Uses ANSI escape codes for colours if `colour` is set.
+    pub fn render(&self, path: &str, code: &str, colour: bool) -> String {
+        let paint = |style: &'static str| if colour { style } else { "" };
+        let reset = paint(RESET);
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{}{}[{}]{}{}: {}{}",
+            paint(self.severity.colour()),
+            self.severity.label(),
+            self.code,
+            reset,
+            paint(BOLD),
+            self.message,
+            reset
+        );
+
+        let Some(offset) = self.offset else {
+            let _ = writeln!(out, " {}-->{} {}", paint(BLUE), reset, path);
+            if let Some(help) = &self.help {
+                let _ = writeln!(
+                    out,
+                    " {}={} {}help{}: {}",
+                    paint(BLUE),
+                    reset,
+                    paint(BOLD),
+                    reset,
+                    help
+                );
+            }
+            return out;
+        };
+        let (line, column, text) = locate(code, offset);
+        let gutter = " ".repeat(line.to_string().len());
+        let _ = writeln!(
+            out,
+            "{}{}-->{} {}:{}:{}",
+            gutter,
+            paint(BLUE),
+            reset,
+            path,
+            line,
+            column
+        );
+        let _ = writeln!(out, "{} {}|{}", gutter, paint(BLUE), reset);
+        let _ = writeln!(out, "{}{} |{} {}", paint(BLUE), line, reset, text);
+        let _ = writeln!(
+            out,
+            "{} {}|{} {}{}{}{}",
+            gutter,
+            paint(BLUE),
+            reset,
+            " ".repeat(column - 1),
+            paint(self.severity.colour()),
+            "^".repeat(self.length),
+            reset
+        );
+        if let Some(help) = &self.help {
+            let _ = writeln!(out, "{} {}|{}", gutter, paint(BLUE), reset);
+            let _ = writeln!(
+                out,
+                "{} {}={} {}help{}: {}",
+                gutter,
+                paint(BLUE),
+                reset,
+                paint(BOLD),
+                reset,
+                help
+            );
+        }
+        out
+    }
+}
+
+impl From<&Diagnostic> for Diag {
+    fn from(diagnostic: &Diagnostic) -> Diag {
+        let code = match diagnostic {
+            Diagnostic::DeadTask { .. } => "W0001",
+            Diagnostic::UnreachableStmts { .. } => "W0002",
+            Diagnostic::ShadowedTask { .. } => "W0003",
+            Diagnostic::AnimateOnNonZombie { .. } => "W0004",
+            Diagnostic::DisturbOnNonGhost { .. } => "W0005",
+            Diagnostic::UnspokenConstruct { .. } => "E0004",
+            Diagnostic::UnknownLanguage { .. } => "E0005",
+        };
+        let severity = if diagnostic.is_error() {
+            Severity::Error
+        } else {
+            Severity::Warning
+        };
+        Diag::new(severity, code, diagnostic.to_string())
+    }
+}
+
+/// Describe what the parser expected when failing with the given kind of error.
+pub(crate) fn expected(kind: ErrorKind) -> String {
+    match kind {
+        ErrorKind::Tag => String::from("a keyword"),
+        ErrorKind::Alpha | ErrorKind::AlphaNumeric | ErrorKind::Satisfy => String::from("a name"),
+        ErrorKind::Digit => String::from("a number"),
+        ErrorKind::Char => String::from("a character"),
+        ErrorKind::MultiSpace | ErrorKind::Space => String::from("whitespace"),
+        ErrorKind::Eof => String::from("the end of the scroll"),
+        kind => kind.description().to_lowercase(),
+    }
+}
+
+/// Find the words one after another in the code, and return the byte offset of the last one.
+/// Words only match where they are not part of a longer name.
+fn find_in_order(code: &str, words: &[String]) -> Option<usize> {
+    let is_name = |c: char| c.is_alphanumeric() || c == '_';
+    let mut start = 0;
+    let mut found = None;
+    for word in words {
+        let offset = code[start..]
+            .match_indices(word.as_str())
+            .find_map(|(i, _)| {
+                let offset = start + i;
+                let before = code[..offset].chars().next_back();
+                let after = code[offset + word.len()..].chars().next();
+                (!before.is_some_and(is_name) && !after.is_some_and(is_name)).then_some(offset)
+            })?;
+        found = Some(offset);
+        start = offset + word.len();
+    }
+    found
+}
+
+/// Find the line and column (both starting at 1) of the byte offset, and the text of the line.
+fn locate(code: &str, offset: usize) -> (usize, usize, &str) {
+    let mut offset = offset.min(code.len());
+    while !code.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let start = code[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let end = code[offset..].find('\n').map_or(code.len(), |i| offset + i);
+    let line = code[..offset].matches('\n').count() + 1;
+    let column = code[start..offset].chars().count() + 1;
+    (line, column, code[start..end].trim_end_matches('\r'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    #[test]
+    fn render_parse_error() {
+        let code = "\
+Peter is a wombat
+summon
+animate
+";
+        let error = parse(code).unwrap_err();
+        let diag = Diag::from_parse_error(code, &error);
+        assert_eq!(diag.severity(), Severity::Error);
+
+        let rendered = diag.render("scroll.z", code, false);
+        let mut lines = rendered.lines();
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("error[E0001]: cannot parse the scroll: expected a keyword"));
+        assert!(lines.next().unwrap().contains("--> scroll.z:1:"));
+        assert!(!rendered.contains('\x1b'));
+    }
+
+    #[test]
+    fn render_snippet() {
+        let code = "first\nsecond line\nthird";
+        let diag = Diag::new(Severity::Warning, "W0000", "look here").at(13);
+        assert_eq!(
+            diag.render("scroll.z", code, false),
+            "\
+warning[W0000]: look here
+ --> scroll.z:2:8
+  |
+2 | second line
+  |        ^
+"
+        );
+        assert!(diag.render("scroll.z", code, true).contains("\x1b[1;33m"));
+
+        assert_eq!(
+            diag.help("look elsewhere").render("scroll.z", code, false),
+            "\
+warning[W0000]: look here
+ --> scroll.z:2:8
+  |
+2 | second line
+  |        ^
+  |
+  = help: look elsewhere
+"
+        );
+    }
+
+    #[test]
+    fn point_at_statement() {
+        let code = "\
+Lisa is a ghost
+summon
+animate
+
+Peter is a zombie
+summon
+    task Wake
+        animate Lisa
+    animate
+animate
+";
+        let scroll = parse(code).unwrap();
+        let diagnostics = crate::validate::validate(&scroll);
+        let diag = Diag::from_diagnostic(code, &diagnostics[0]);
+        assert_eq!(diag.code(), "W0004");
+        assert_eq!(
+            diag.deny().render("scroll.z", code, false),
+            "\
+error[W0004]: task Wake of Peter animates Lisa, which is a Ghost and not a zombie
+ --> scroll.z:8:9
+  |
+8 |         animate Lisa
+  |         ^^^^^^^^^^^^
+"
+        );
+    }
+
+    #[test]
+    fn point_at_lint() {
+        use crate::validate::lint::{lint, Level, LintConfig, Rule};
+
+        let code = "\
+Peter is a zombie
+summon
+    task Count
+        shamble
+            remember moan 10
+        until 1
+    animate
+animate
+";
+        let scroll = parse(code).unwrap();
+        let config = LintConfig::default().level(Rule::MagicNumber, Level::Warn);
+        let rendered: Vec<String> = lint(&scroll, &config)
+            .iter()
+            .map(|lint| Diag::from_lint(code, lint).render("scroll.z", code, false))
+            .collect();
+        assert_eq!(
+            rendered,
+            [
+                "\
+warning[L0004]: the condition of shamble in task Count of Peter is always the same
+ --> scroll.z:6:9
+  |
+6 |         until 1
+  |         ^^^^^
+",
+                "\
+warning[L0005]: task Count of Peter uses the magic number 10
+ --> scroll.z:5:27
+  |
+5 |             remember moan 10
+  |                           ^^
+"
+            ]
+        );
+    }
+
+    #[test]
+    fn render_invalid_number() {
+        let code = "\
+Peter is a zombie
+summon
+    remember 12abc
+animate
+";
+        let error = parse(code).unwrap_err();
+        let diag = Diag::from_parse_error(code, &error);
+        assert_eq!(diag.code(), "E0002");
+        assert_eq!(
+            diag.render("scroll.z", code, false),
+            "\
+error[E0002]: invalid number literal
+ --> scroll.z:3:14
+  |
+3 |     remember 12abc
+  |              ^^^^^
+"
+        );
+    }
+}
@@ -0,0 +1,208 @@
+//! A small package manager for community scrolls.
+//!
+//! Scrolls are fetched by name from an index and kept in a local cache directory. An index is
+//! either a base URL, under which the scroll `name` is found at `<index>/<name>.z`, or a path to
+//! a local directory laid out the same way.
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+use tracing::debug;
+
+/// Scrolls larger than this are refused.
+const MAX_SCROLL_SIZE: u64 = 1 << 20;
+
+/// An error that occurred while managing community scrolls.
+#[derive(thiserror::Error, Debug)]
+pub enum GrimoireError {
+    /// Names may only consist of letters, digits, `-` and `_`.
+    #[error("invalid scroll name: {0}")]
+    InvalidName(String),
+    /// No index to fetch scrolls from was configured.
+    #[error("no index configured, set NECROMANCER_GRIMOIRE or use --index")]
+    NoIndex,
+    /// The scroll is not in the cache. Add it first.
+    #[error("scroll {0} is not in the grimoire")]
+    Missing(String),
+    /// The scroll could not be fetched from the index.
+    #[error("cannot fetch scroll {name}: {reason}")]
+    Fetch { name: String, reason: String },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// A collection of community scrolls, cached in a local directory.
+#[derive(Debug, Clone)]
+pub struct Grimoire {
+    index: Option<String>,
+    cache: PathBuf,
+}
+
+impl Grimoire {
+    /// Create a grimoire caching scrolls in the given directory, without an index.
+    pub fn new(cache: impl Into<PathBuf>) -> Grimoire {
+        Grimoire {
+            index: None,
+            cache: cache.into(),
+        }
+    }
+
+    /// Configure the grimoire from the environment.
+    ///
+    /// The index is read from `NECROMANCER_GRIMOIRE` and the cache directory from
+    /// `NECROMANCER_CACHE`. The cache defaults to `necromancer/grimoire` inside the user's cache
+    /// directory.
+    pub fn from_env() -> Grimoire {
+        let cache = env::var_os("NECROMANCER_CACHE")
+            .map(PathBuf::from)
+            .or_else(|| {
+                env::var_os("XDG_CACHE_HOME")
+                    .map(|dir| Path::new(&dir).join("necromancer/grimoire"))
+            })
+            .or_else(|| {
+                env::var_os("HOME").map(|dir| Path::new(&dir).join(".cache/necromancer/grimoire"))
+            })
+            .unwrap_or_else(|| PathBuf::from(".grimoire"));
+        Grimoire {
+            index: env::var("NECROMANCER_GRIMOIRE").ok(),
+            cache,
+        }
+    }
+
+    /// Fetch scrolls from the given index.
+    pub fn with_index(mut self, index: impl Into<String>) -> Grimoire {
+        self.index = Some(index.into());
+        self
+    }
+
+    pub fn index(&self) -> Option<&str> {
+        self.index.as_deref()
+    }
+
+    pub fn cache(&self) -> &Path {
+        &self.cache
+    }
+
+    /// Fetch the named scroll from the index and store it in the cache, replacing any older copy.
+    /// Returns the path of the cached scroll.
+    pub fn add(&self, name: &str) -> Result<PathBuf, GrimoireError> {
+        check_name(name)?;
+        let code = self.fetch(name)?;
+        fs::create_dir_all(&self.cache)?;
+        let path = self.cache.join(format!("{}.z", name));
+        fs::write(&path, code)?;
+        Ok(path)
+    }
+
+    /// Return the names of all cached scrolls, sorted.
+    pub fn list(&self) -> Result<Vec<String>, GrimoireError> {
+        let entries = match fs::read_dir(&self.cache) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut names = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "z") {
+                if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    names.push(String::from(name));
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Return the path of the named scroll in the cache.
+    pub fn path(&self, name: &str) -> Result<PathBuf, GrimoireError> {
+        check_name(name)?;
+        let path = self.cache.join(format!("{}.z", name));
+        if path.is_file() {
+            Ok(path)
+        } else {
+            Err(GrimoireError::Missing(String::from(name)))
+        }
+    }
+
+    fn fetch(&self, name: &str) -> Result<String, GrimoireError> {
+        let fail = |reason: String| GrimoireError::Fetch {
+            name: String::from(name),
+            reason,
+        };
+        let index = self
+            .index
+            .as_deref()
+            .ok_or(GrimoireError::NoIndex)?
+            .trim_end_matches('/');
+        if index.starts_with("http://") || index.starts_with("https://") {
+            let url = format!("{}/{}.z", index, name);
+            debug!("Fetching {}", url);
+            let response = ureq::get(&url).call().map_err(|e| fail(e.to_string()))?;
+            let mut code = String::new();
+            response
+                .into_reader()
+                .take(MAX_SCROLL_SIZE)
+                .read_to_string(&mut code)
+                .map_err(|e| fail(e.to_string()))?;
+            Ok(code)
+        } else {
+            let path = Path::new(index.strip_prefix("file://").unwrap_or(index))
+                .join(format!("{}.z", name));
+            debug!("Copying {}", path.display());
+            fs::read_to_string(&path).map_err(|e| fail(format!("{}: {}", path.display(), e)))
+        }
+    }
+}
+
+/// Make sure the name can't be used to leave the cache directory or the index.
+fn check_name(name: &str) -> Result<(), GrimoireError> {
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(())
+    } else {
+        Err(GrimoireError::InvalidName(String::from(name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_from_local_index() {
+        let root = env::temp_dir().join(format!("grimoire-{}", std::process::id()));
+        let index = root.join("index");
+        fs::create_dir_all(&index).unwrap();
+        fs::write(index.join("hello.z"), "Peter is a zombie").unwrap();
+
+        let grimoire = Grimoire::new(root.join("cache"));
+        assert!(matches!(grimoire.add("hello"), Err(GrimoireError::NoIndex)));
+
+        let grimoire = grimoire.with_index(index.to_str().unwrap());
+        assert_eq!(grimoire.list().unwrap(), Vec::<String>::new());
+        assert!(matches!(
+            grimoire.path("hello"),
+            Err(GrimoireError::Missing(_))
+        ));
+
+        let path = grimoire.add("hello").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Peter is a zombie");
+        assert_eq!(grimoire.path("hello").unwrap(), path);
+        assert_eq!(grimoire.list().unwrap(), vec![String::from("hello")]);
+
+        assert!(matches!(
+            grimoire.add("nothing"),
+            Err(GrimoireError::Fetch { .. })
+        ));
+        assert!(matches!(
+            grimoire.add("../hello"),
+            Err(GrimoireError::InvalidName(_))
+        ));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -1,17 +1,26 @@
 #![allow(uncommon_codepoints)]
 // #![warn(missing_docs)]
 #![doc = include_str!("../README.md")]
-use std::fs;
+use std::fs::File;
+use std::io::BufReader;
 
-use log::debug;
+use tracing::debug;
 
+pub mod diag;
+#[cfg(feature = "grimoire")]
+pub mod grimoire;
 pub mod necro;
 pub mod parse;
 pub mod scroll;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod validate;
 pub mod value;
 
-use necro::Necromancer;
+use necro::{Necromancer, RitualConfig, RitualReport, RuntimeError, Termination};
+use parse::{ParseError, ReadError};
 use scroll::Scroll;
+use validate::Diagnostic;
 
 /// The error type for this library.
 #[derive(thiserror::Error, Debug)]
@@ -21,22 +30,80 @@ pub enum Error {
     Io(#[from] std::io::Error),
     /// An error occurred while trying to unroll and read the scroll.
     #[error(transparent)]
-    Parse(#[from] nom::error::Error<&'static str>),
+    Parse(#[from] ParseError),
+    /// The scroll was read, but static analysis found problems with it.
+    #[error("the scroll has problems: {}", join(.0))]
+    Semantic(Vec<Diagnostic>),
+    /// An error occurred during the ritual.
+    #[error(transparent)]
+    Runtime(#[from] RuntimeError),
+    /// The ritual took longer than allowed.
+    #[error("the ritual took longer than allowed")]
+    Timeout,
+    /// The ritual was ended from the outside through an [`Interrupt`](necro::Interrupt).
+    #[error("the ritual was interrupted")]
+    Cancelled,
+}
+
+impl From<ReadError> for Error {
+    fn from(error: ReadError) -> Error {
+        match error {
+            ReadError::Io(e) => Error::Io(e),
+            ReadError::Parse(e) => Error::Parse(e),
+        }
+    }
+}
+
+// Scrolls are shared between the spirits of a ritual, and with the threads of host applications.
+const _: fn() = || {
+    fn shareable<T: Send + Sync + 'static>() {}
+    shareable::<Scroll>();
+    shareable::<scroll::entity::Entity>();
+    shareable::<scroll::task::Task>();
+    shareable::<scroll::statement::Stmt>();
+    shareable::<scroll::expression::Expr>();
+    shareable::<value::Value>();
+};
+
+fn join(diagnostics: &[Diagnostic]) -> String {
+    let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+    messages.join("; ")
 }
 
 /// Load the scroll from the given path and parse it.
 pub fn parse(path: &str) -> Result<Scroll, Error> {
-    let code: &'static str = Box::new(fs::read_to_string(path)?).leak();
-
-    let scroll = parse::parse(code)?;
+    let reader = BufReader::new(File::open(path)?);
+    let scroll = parse::parse_reader(reader, parse::ParseConfig::default())?;
     Ok(scroll)
 }
 
+/// Load the scroll from the given path, parse it and make sure that static analysis finds no
+/// problems with it.
+pub fn check(path: &str) -> Result<Scroll, Error> {
+    let scroll = parse(path)?;
+    let diagnostics = validate::validate(&scroll);
+    if diagnostics.is_empty() {
+        Ok(scroll)
+    } else {
+        Err(Error::Semantic(diagnostics))
+    }
+}
+
 /// Perform the necromancy ritual with the scroll at the given location.
-pub fn summon(path: &str) -> Result<(), Error> {
+pub fn summon(path: &str) -> Result<RitualReport, Error> {
+    summon_with(path, RitualConfig::default())
+}
+
+/// Perform the necromancy ritual with the scroll at the given location and the given settings.
+pub fn summon_with(path: &str, config: RitualConfig) -> Result<RitualReport, Error> {
     let scroll = parse(path)?;
 
     debug!("{:?}", &scroll);
-    Necromancer::unroll(scroll).initiate();
-    Ok(())
+    let report = Necromancer::unroll(scroll).with_config(config).initiate();
+    match (report.termination(), report.error()) {
+        (Termination::Timeout, _) => Err(Error::Timeout),
+        (Termination::Interrupted, _) => Err(Error::Cancelled),
+        (_, Some(e)) => Err(e.clone().into()),
+        (_, None) => Ok(report),
+    }
 }
@@ -1,19 +1,60 @@
-use std::process;
+use std::io::{self, IsTerminal, Read};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime};
+use std::{env, fs, process, thread};
 
-use clap::{command, value_parser, Arg, ArgAction, ArgGroup, ValueHint};
-use env_logger::Builder;
-use log::{error, info, LevelFilter};
+use clap::error::ErrorKind;
+use clap::{command, value_parser, Arg, ArgAction, ArgGroup, ArgMatches, Command, ValueHint};
+use clap_complete::Shell;
+use indexmap::IndexMap;
+use necromancer::diag::{Diag, Severity};
+use necromancer::necro::{
+    timeline, Engine, Interrupt, Necromancer, Reanimation, RitualConfig, RitualEvent, RitualReport,
+    StatementHook, Termination,
+};
+use necromancer::parse::{ParseConfig, SyntaxError};
+use necromancer::scroll::graph::graph;
+use necromancer::scroll::listing::listing;
+use necromancer::scroll::statement::Stmt;
+use necromancer::scroll::summary::summary;
+use necromancer::scroll::Scroll;
+use necromancer::validate;
+use necromancer::validate::lint::{self, Level, LintConfig};
+use smol_str::SmolStr;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+use tracing::{error, info};
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::{self, FormatTime, Uptime};
 
 fn main() {
     // Parse command line arguments.
-    let matches = command!()
+    let command = command!()
+        .after_long_help(EXIT_CODES)
         .arg(
             Arg::new("path")
                 .value_name("PATH")
-                .help("Where to find the Zombie Scroll.")
+                .help("Where to find the Zombie Scroll, or - to read it from stdin. Several scrolls are merged with --merge.")
                 .index(1)
+                .num_args(1..)
                 .value_hint(ValueHint::FilePath)
-                .required(true),
+                .required_unless_present("eval"),
+        )
+        .arg(
+            Arg::new("eval")
+                .short('e')
+                .long("eval")
+                .value_name("CODE")
+                .conflicts_with("path")
+                .help("Perform the scroll given as CODE instead of reading it from a file."),
+        )
+        .arg(
+            Arg::new("merge")
+                .long("merge")
+                .action(ArgAction::SetTrue)
+                .help("Merge all scrolls at PATH into one and perform a single ritual."),
         )
         .arg(
             Arg::new("syntax_tree_mode")
@@ -22,46 +63,1109 @@ fn main() {
                 .action(ArgAction::SetTrue)
                 .help("Stop after parsing the scroll and print the AST."),
         )
-        .group(ArgGroup::new("mode").args(["syntax_tree_mode"]))
+        .arg(
+            Arg::new("listing_mode")
+                .short('l')
+                .long("list")
+                .action(ArgAction::SetTrue)
+                .help("Stop after parsing the scroll and print one line per statement."),
+        )
+        .arg(
+            Arg::new("graph_mode")
+                .short('g')
+                .long("graph")
+                .action(ArgAction::SetTrue)
+                .help("Stop after parsing the scroll and print the entity graph in DOT format."),
+        )
+        .arg(
+            Arg::new("info_mode")
+                .short('i')
+                .long("info")
+                .action(ArgAction::SetTrue)
+                .help("Stop after parsing the scroll and print its title, author and version."),
+        )
+        .arg(
+            Arg::new("check_mode")
+                .short('c')
+                .long("check")
+                .action(ArgAction::SetTrue)
+                .help("Stop after checking the scroll and report all errors and warnings."),
+        )
+        .arg(
+            Arg::new("diff_mode")
+                .long("diff")
+                .value_name("OLD")
+                .value_hint(ValueHint::FilePath)
+                .help("Stop after comparing the scroll with its older version at OLD and print the differences."),
+        )
+        .arg(
+            Arg::new("summary")
+                .long("summary")
+                .action(ArgAction::SetTrue)
+                .requires("syntax_tree_mode")
+                .help("With --tree, print counts of tasks, statements and expressions per entity instead."),
+        )
+        .group(ArgGroup::new("mode").args([
+            "syntax_tree_mode",
+            "listing_mode",
+            "graph_mode",
+            "info_mode",
+            "check_mode",
+            "diff_mode",
+        ]))
+        .arg(
+            Arg::new("optimize")
+                .short('O')
+                .long("optimize")
+                .action(ArgAction::SetTrue)
+                .help("Strip tasks and statements that can never be executed."),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .action(ArgAction::SetTrue)
+                .help("Refuse scrolls that break the species rules, e.g. by animating a ghost."),
+        )
+        .arg(
+            Arg::new("relaxed")
+                .long("relaxed")
+                .action(ArgAction::SetTrue)
+                .help("Accept keywords regardless of case, like Summon or ANIMATE."),
+        )
+        .arg(
+            Arg::new("watch")
+                .short('w')
+                .long("watch")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("mode")
+                .help("Perform the ritual again whenever the scroll changes."),
+        )
+        .arg(
+            Arg::new("report")
+                .long("report")
+                .value_name("FORMAT")
+                .value_parser(["text", "json"])
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("text")
+                .conflicts_with_all(["mode", "watch"])
+                .help("Print the final memories, spirits, runtime and termination after the ritual."),
+        )
+        .arg(
+            Arg::new("progress")
+                .long("progress")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["mode", "watch"])
+                .help("Show the statements performed per entity, the spirits and the elapsed time on a status line while the ritual lasts."),
+        )
+        .arg(
+            Arg::new("visualize")
+                .long("visualize")
+                .value_name("FILE")
+                .value_hint(ValueHint::FilePath)
+                .conflicts_with_all(["mode", "watch", "progress"])
+                .help("Write a timeline of the tasks performed by each entity and the changes of its memory to FILE as SVG."),
+        )
+        .arg(
+            Arg::new("allow_env")
+                .long("allow-env")
+                .action(ArgAction::SetTrue)
+                .help("Allow the scroll to read environment variables."),
+        )
+        .arg(
+            Arg::new("allow_fs")
+                .long("allow-fs")
+                .value_name("ROOT")
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value(".")
+                .value_hint(ValueHint::DirPath)
+                .help("Allow the scroll to read and write files inside ROOT (default: working directory)."),
+        )
+        .arg(
+            Arg::new("allow_net")
+                .long("allow-net")
+                .action(ArgAction::SetTrue)
+                .help("Allow the scroll to communicate over TCP."),
+        )
+        .arg(
+            Arg::new("inspect")
+                .long("inspect")
+                .value_name("PORT")
+                .value_parser(value_parser!(u16))
+                .help("Let external tools inspect and control the ritual over TCP on PORT of the local host."),
+        )
+        .arg(
+            Arg::new("no_curse")
+                .long("no-curse")
+                .action(ArgAction::SetTrue)
+                .help("Say corrupted values in plain text as <infernal:...> instead of cursing them."),
+        )
+        .arg(
+            Arg::new("digit_grouping")
+                .long("digit-grouping")
+                .action(ArgAction::SetTrue)
+                .help("Say integers with their digits grouped in threes, like 1_000_000."),
+        )
+        .arg(
+            Arg::new("fail_on_infernal")
+                .long("fail-on-infernal")
+                .action(ArgAction::SetTrue)
+                .help("End the ritual with an error as soon as a value is corrupted."),
+        )
+        .arg(
+            Arg::new("warnings_as_errors")
+                .long("warnings-as-errors")
+                .action(ArgAction::SetTrue)
+                .help("End the ritual with an error at the first warning."),
+        )
+        .arg(
+            Arg::new("history")
+                .long("history")
+                .value_name("DEPTH")
+                .value_parser(value_parser!(usize))
+                .default_value("0")
+                .help("Let entities reminisce about up to DEPTH values they remembered before."),
+        )
+        .arg(
+            Arg::new("engine")
+                .long("engine")
+                .value_name("ENGINE")
+                .value_parser(["multi-thread", "current-thread-deterministic"])
+                .default_value("multi-thread")
+                .help("Perform the spirits in parallel, or one after another as decided by the seed."),
+        )
+        .arg(
+            Arg::new("reanimation")
+                .long("reanimation")
+                .value_name("POLICY")
+                .value_parser(["spawn", "ignore", "error"])
+                .default_value("spawn")
+                .help("Summon another spirit, do nothing, or fail when an active entity is animated or disturbed again."),
+        )
+        .arg(
+            Arg::new("retries")
+                .long("retries")
+                .value_name("COUNT")
+                .value_parser(value_parser!(usize))
+                .help("Let revenants retry a task that stumbles up to COUNT times [default: 3]."),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_name("SEED")
+                .value_parser(value_parser!(u64))
+                .help("Seed the random decisions of the spirits to reproduce a ritual."),
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .value_name("SECONDS")
+                .value_parser(value_parser!(u64))
+                .help("Abort the ritual if it takes longer than SECONDS."),
+        )
+        .arg(
+            Arg::new("log_format")
+                .long("log-format")
+                .value_name("FORMAT")
+                .value_parser(["pretty", "json"])
+                .default_value("pretty")
+                .help("Write logs for humans, or as one JSON object per line."),
+        )
+        .arg(
+            Arg::new("log_time")
+                .long("log-time")
+                .value_name("CLOCK")
+                .value_parser(["utc", "uptime"])
+                .default_value("utc")
+                .help("Stamp logs with the time of day, or the time since the ritual started."),
+        )
         .arg(
             Arg::new("verbose")
                 .short('v')
                 .action(ArgAction::Count)
                 .value_parser(value_parser!(u8).range(..=2))
                 .help("Hear the screams from the underworld more clearly."),
-        )
-        .get_matches();
+        );
+    let command = command
+        .subcommand_negates_reqs(true)
+        .subcommand(completions_command())
+        .subcommand(man_command())
+        .subcommand(lint_command());
+    #[cfg(feature = "grimoire")]
+    let command = command.subcommand(grimoire_command());
+    #[cfg(feature = "tui")]
+    let command = command.arg(
+        Arg::new("tui")
+            .long("tui")
+            .action(ArgAction::SetTrue)
+            .conflicts_with_all(["mode", "watch", "progress", "visualize"])
+            .help(
+                "Watch the ritual on a dashboard with a pane per entity and a log of what is said.",
+            ),
+    );
+    let mut command = command;
+    let matches = command.get_matches_mut();
 
     // Initialize the logger. The log level depends on the number of -v flags in the CLI arguments.
-    let mut builder = Builder::from_default_env();
-    match matches.get_count("verbose") {
-        0 => builder.filter_level(LevelFilter::Error),
-        1 => builder.filter_level(LevelFilter::Info),
-        2 => builder.filter_level(LevelFilter::Debug),
+    // Further directives in RUST_LOG can narrow it down, e.g. `[spirit{entity=Peter}]=debug` for
+    // everything a spirit of Peter does.
+    let level = match matches.get_count("verbose") {
+        0 => LevelFilter::ERROR,
+        1 => LevelFilter::INFO,
+        2 => LevelFilter::DEBUG,
         _ => unreachable!("Invalid log level!"),
     };
-    builder.init();
+    let logger = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env().add_directive(level.into()))
+        .with_writer(io::stderr)
+        .with_timer(
+            match matches.get_one::<String>("log_time").unwrap().as_str() {
+                "uptime" => LogTime::Uptime(Uptime::default()),
+                _ => LogTime::Utc,
+            },
+        );
+    match matches.get_one::<String>("log_format").unwrap().as_str() {
+        "json" => logger.json().with_span_list(true).init(),
+        _ => logger.with_ansi(io::stderr().is_terminal()).init(),
+    }
+
+    let colour = io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none();
+    let parser = ParseConfig::default().relaxed(matches.get_flag("relaxed"));
+
+    if let Some(("completions", matches_completions)) = matches.subcommand() {
+        let shell = *matches_completions.get_one::<Shell>("shell").unwrap();
+        let name = env!("CARGO_BIN_NAME");
+        clap_complete::generate(shell, &mut command, name, &mut io::stdout());
+        return;
+    }
+
+    if let Some(("man", _)) = matches.subcommand() {
+        let man = clap_mangen::Man::new(command.name(env!("CARGO_BIN_NAME")));
+        if let Err(e) = man.render(&mut io::stdout()) {
+            error!("Cannot print the man page: {}", e);
+            Exit::Failure.exit();
+        }
+        return;
+    }
 
-    let path = matches.get_one::<String>("path").unwrap();
+    if let Some(("lint", matches_lint)) = matches.subcommand() {
+        if let Err(exit) = lint(matches_lint, parser, colour) {
+            exit.exit();
+        }
+        return;
+    }
+
+    #[cfg(feature = "grimoire")]
+    if let Some(("grimoire", matches_grimoire)) = matches.subcommand() {
+        grimoire(matches_grimoire, config(&matches), parser, colour);
+        return;
+    }
+
+    let sources: Vec<Source> = match matches.get_one::<String>("eval") {
+        Some(code) => vec![Source::inline(code)],
+        None => matches
+            .get_many::<String>("path")
+            .unwrap()
+            .map(|path| Source::path(path))
+            .collect(),
+    };
+    if sources.len() > 1 && !matches.get_flag("merge") {
+        command
+            .error(
+                ErrorKind::TooManyValues,
+                "several scrolls can only be performed together with --merge",
+            )
+            .exit();
+    }
+    if matches.get_flag("watch") && sources.iter().any(|source| source.code.is_some()) {
+        command
+            .error(
+                ErrorKind::ArgumentConflict,
+                "only scrolls read from files can be watched",
+            )
+            .exit();
+    }
+    let path = &names(&sources);
 
     // If the -t flag is set, print the AST and exit.
     // Otherwise, perfom the necromancy ritual.
     if matches.get_flag("syntax_tree_mode") {
-        info!("Printing AST for file {}", path);
-        match necromancer::parse(path) {
-            Ok(scroll) => {
-                print!("{:#?}", scroll);
-            }
-            Err(e) => {
-                error!("{}", e);
-                process::exit(1);
+        let scroll = unroll(&sources, parser, colour);
+        if matches.get_flag("summary") {
+            info!("Printing summary for file {}", path);
+            print!("{}", summary(&scroll));
+        } else {
+            info!("Printing AST for file {}", path);
+            print!("{:#?}", scroll);
+        }
+    } else if matches.get_flag("listing_mode") {
+        info!("Printing listing for file {}", path);
+        let scroll = unroll(&sources, parser, colour);
+        print!("{}", listing(&scroll));
+    } else if matches.get_flag("graph_mode") {
+        info!("Printing entity graph for file {}", path);
+        let scroll = unroll(&sources, parser, colour);
+        print!("{}", graph(&scroll));
+    } else if matches.get_flag("info_mode") {
+        info!("Printing information for file {}", path);
+        let scroll = unroll(&sources, parser, colour);
+        let meta = scroll.meta().cloned().unwrap_or_default();
+        match &meta.title {
+            Some(title) => {
+                println!("Title:    {}", title);
+                println!("Author:   {}", meta.author.as_deref().unwrap_or("unknown"));
+                println!("Version:  {}", meta.version.as_deref().unwrap_or("unknown"));
             }
+            None => println!("Title:    untitled"),
+        }
+        println!("Language: zombie {}", scroll.language());
+        println!("Entities: {}", scroll.creatures().len());
+    } else if let Some(old) = matches.get_one::<String>("diff_mode") {
+        info!("Comparing file {} with {}", path, old);
+        let old = unroll(&[Source::path(old)], parser, colour);
+        let scroll = unroll(&sources, parser, colour);
+        print!("{}", old.diff(&scroll));
+    } else if matches.get_flag("check_mode") {
+        info!("Checking file {}", path);
+        if let Err(exit) = check(&sources, parser, colour, matches.get_flag("strict")) {
+            exit.exit();
         }
     } else {
+        let optimize = matches.get_flag("optimize");
+        let strict = matches.get_flag("strict");
+        let config = config(&matches);
+
+        if matches.get_flag("watch") {
+            info!("Watching file {}", path);
+            watch(&sources, parser, colour, optimize, strict, config);
+        }
+
         info!("Executing file {}", path);
-        if let Err(err) = necromancer::summon(path) {
-            error!("{}", err);
-            process::exit(1);
+        let scroll =
+            prepare(&sources, parser, colour, optimize, strict).unwrap_or_else(|exit| exit.exit());
+        let report = perform(scroll, config, &matches);
+        match matches.get_one::<String>("report").map(String::as_str) {
+            Some("json") => println!("{}", report.to_json()),
+            Some(_) => print!("{}", report),
+            None => {}
+        }
+        if let Some(e) = report.error() {
+            error!("{}", e);
+        }
+        if let Some(exit) = Exit::of(&report) {
+            exit.exit();
         }
     }
 }
+
+fn completions_command() -> Command {
+    Command::new("completions")
+        .about("Print the shell completions for summon.")
+        .arg(
+            Arg::new("shell")
+                .value_name("SHELL")
+                .value_parser(value_parser!(Shell))
+                .help("The shell to complete for.")
+                .required(true),
+        )
+}
+
+fn man_command() -> Command {
+    Command::new("man").about("Print the man page of summon in roff format.")
+}
+
+fn lint_command() -> Command {
+    Command::new("lint")
+        .about("Look for questionable code in scrolls, with the rules set in .necrolint.toml.")
+        .arg(
+            Arg::new("path")
+                .value_name("PATH")
+                .num_args(1..)
+                .required(true)
+                .value_hint(ValueHint::FilePath)
+                .help("The scrolls to lint, each on its own, or - to read one from stdin."),
+        )
+        .arg(
+            Arg::new("fix")
+                .long("fix")
+                .action(ArgAction::SetTrue)
+                .help("Apply unambiguous fixes for parse errors to the scrolls. A scroll read from stdin is printed instead."),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("FILE")
+                .value_hint(ValueHint::FilePath)
+                .help("Read the rules from FILE instead of the nearest .necrolint.toml."),
+        )
+}
+
+/// Lint each of the given scrolls on its own and print the findings. Fails with the first kind
+/// of error found if a scroll can't be parsed or has lints that are denied.
+fn lint(matches: &ArgMatches, parser: ParseConfig, colour: bool) -> Result<(), Exit> {
+    let config = lint_config(matches.get_one::<String>("config"))?;
+    let mut failure = None;
+    for path in matches.get_many::<String>("path").unwrap() {
+        let source = Source::path(path);
+        let path = &source.name;
+        let mut code = source.read()?;
+        if matches.get_flag("fix") {
+            code = fix(&source, code, parser)?;
+        }
+        let scroll = match necromancer::parse::parse_with(&code, parser) {
+            Ok(scroll) => scroll,
+            Err(e) => {
+                eprint!(
+                    "{}",
+                    parse_diag(&code, &e, parser).render(path, &code, colour)
+                );
+                failure = failure.or(Some(Exit::Parse));
+                continue;
+            }
+        };
+        for lint in lint::lint(&scroll, &config) {
+            let mut diag = Diag::from_lint(&code, &lint);
+            if config.level_of(lint.rule()) == Level::Deny {
+                diag = diag.deny();
+                failure = failure.or(Some(Exit::Validation));
+            }
+            eprint!("{}", diag.render(path, &code, colour));
+        }
+    }
+    failure.map_or(Ok(()), Err)
+}
+
+/// Apply the unambiguous fixes for parse errors to the code of the scroll, and write it back to
+/// its file, or print it if it has none. Returns the fixed code, or fails if it can't be written.
+fn fix(source: &Source, code: String, parser: ParseConfig) -> Result<String, Exit> {
+    let (fixed, suggestions) = necromancer::parse::fix(&code, parser);
+    for suggestion in &suggestions {
+        eprintln!("Fixed {}: {}", source.name, suggestion);
+    }
+    if source.code.is_some() {
+        print!("{}", fixed);
+    } else if !suggestions.is_empty() {
+        fs::write(&source.name, &fixed).map_err(|e| {
+            error!("Cannot write {}: {}", source.name, e);
+            Exit::Failure
+        })?;
+    }
+    Ok(fixed)
+}
+
+/// Read the settings of the linter from the given file, or else from the nearest
+/// [`LINT_CONFIG`] in the working directory or one of its parents. Without one, every rule keeps
+/// its default. Prints the reason and fails if the settings can't be read.
+fn lint_config(path: Option<&String>) -> Result<LintConfig, Exit> {
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let found = env::current_dir().ok().and_then(|dir| {
+                dir.ancestors()
+                    .map(|dir| dir.join(LINT_CONFIG))
+                    .find(|path| path.is_file())
+            });
+            match found {
+                Some(path) => path,
+                None => return Ok(LintConfig::default()),
+            }
+        }
+    };
+    let toml = fs::read_to_string(&path).map_err(|e| {
+        error!("Cannot read {}: {}", path.display(), e);
+        Exit::Failure
+    })?;
+    LintConfig::from_toml(&toml).map_err(|e| {
+        error!("Invalid lint settings in {}: {}", path.display(), e);
+        Exit::Failure
+    })
+}
+
+/// The name of the file with the settings of the linter.
+const LINT_CONFIG: &str = ".necrolint.toml";
+
+#[cfg(feature = "grimoire")]
+fn grimoire_command() -> Command {
+    let name = || {
+        Arg::new("name")
+            .value_name("NAME")
+            .help("The name of the scroll.")
+            .required(true)
+    };
+    Command::new("grimoire")
+        .about("Fetch and perform community scrolls.")
+        .arg(
+            Arg::new("index")
+                .long("index")
+                .value_name("URL")
+                .help("Where to fetch scrolls from: a base URL or a local directory (default: $NECROMANCER_GRIMOIRE)."),
+        )
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("add")
+                .about("Fetch a scroll from the index and keep it.")
+                .arg(name()),
+        )
+        .subcommand(Command::new("list").about("List the kept scrolls."))
+        .subcommand(
+            Command::new("run")
+                .about("Perform the ritual of a kept scroll, with limits on time and spirits.")
+                .arg(name()),
+        )
+}
+
+/// Manage community scrolls. Exits if anything fails.
+#[cfg(feature = "grimoire")]
+fn grimoire(matches: &ArgMatches, config: RitualConfig, parser: ParseConfig, colour: bool) {
+    use necromancer::grimoire::Grimoire;
+
+    let mut grimoire = Grimoire::from_env();
+    if let Some(index) = matches.get_one::<String>("index") {
+        grimoire = grimoire.with_index(index);
+    }
+    let result = match matches.subcommand() {
+        Some(("add", matches)) => grimoire
+            .add(matches.get_one::<String>("name").unwrap())
+            .map(|path| println!("Added {}", path.display())),
+        Some(("list", _)) => grimoire.list().map(|names| {
+            for name in names {
+                println!("{}", name);
+            }
+        }),
+        Some(("run", matches)) => grimoire
+            .path(matches.get_one::<String>("name").unwrap())
+            .map(|path| {
+                let source = Source::path(&path.to_string_lossy());
+                let scroll = prepare(&[source], parser, colour, false, false)
+                    .unwrap_or_else(|exit| exit.exit());
+                let config = config
+                    .timeout(GRIMOIRE_TIMEOUT)
+                    .max_spirits(GRIMOIRE_MAX_SPIRITS);
+                let report = Necromancer::unroll(scroll).with_config(config).initiate();
+                if let Some(e) = report.error() {
+                    error!("{}", e);
+                }
+                if let Some(exit) = Exit::of(&report) {
+                    exit.exit();
+                }
+            }),
+        _ => unreachable!("Subcommand is required!"),
+    };
+    if let Err(e) = result {
+        error!("{}", e);
+        Exit::Failure.exit();
+    }
+}
+
+/// Limits for scrolls from the grimoire, which might not be trustworthy.
+#[cfg(feature = "grimoire")]
+const GRIMOIRE_TIMEOUT: Duration = Duration::from_secs(60);
+#[cfg(feature = "grimoire")]
+const GRIMOIRE_MAX_SPIRITS: usize = 1000;
+
+/// The clock that log lines are stamped with.
+enum LogTime {
+    Utc,
+    Uptime(Uptime),
+}
+
+impl FormatTime for LogTime {
+    fn format_time(&self, writer: &mut Writer<'_>) -> std::fmt::Result {
+        match self {
+            LogTime::Utc => time::SystemTime.format_time(writer),
+            LogTime::Uptime(uptime) => uptime.format_time(writer),
+        }
+    }
+}
+
+/// Build the settings of the ritual from the command line arguments.
+fn config(matches: &ArgMatches) -> RitualConfig {
+    let mut config = RitualConfig::default()
+        .allow_env(matches.get_flag("allow_env"))
+        .allow_net(matches.get_flag("allow_net"))
+        .curse(!matches.get_flag("no_curse"))
+        .digit_grouping(matches.get_flag("digit_grouping"))
+        .fail_on_infernal(matches.get_flag("fail_on_infernal"))
+        .warnings_as_errors(matches.get_flag("warnings_as_errors"))
+        .history(*matches.get_one::<usize>("history").unwrap());
+    if let Some(root) = matches.get_one::<String>("allow_fs") {
+        config = config.allow_fs(root);
+    }
+    if let Some(port) = matches.get_one::<u16>("inspect") {
+        config = config.inspect(*port);
+    }
+    if matches.get_one::<String>("engine").unwrap() == "current-thread-deterministic" {
+        config = config.engine(Engine::CurrentThreadDeterministic);
+    }
+    match matches.get_one::<String>("reanimation").unwrap().as_str() {
+        "ignore" => config = config.reanimation(Reanimation::Ignore),
+        "error" => config = config.reanimation(Reanimation::Error),
+        _ => {}
+    }
+    if let Some(retries) = matches.get_one::<usize>("retries") {
+        config = config.revenant_retries(*retries);
+    }
+    if let Some(seed) = matches.get_one::<u64>("seed") {
+        config = config.seed(*seed);
+    }
+    if let Some(seconds) = matches.get_one::<u64>("timeout") {
+        config = config.timeout(Duration::from_secs(*seconds));
+    }
+    config
+}
+
+/// Perform the ritual again and again, whenever one of the given scrolls is modified. A running
+/// ritual is interrupted when that happens.
+fn watch(
+    sources: &[Source],
+    parser: ParseConfig,
+    colour: bool,
+    optimize: bool,
+    strict: bool,
+    config: RitualConfig,
+) -> ! {
+    loop {
+        let stamps = modified(sources);
+        if let Ok(scroll) = prepare(sources, parser, colour, optimize, strict) {
+            let interrupt = Interrupt::new();
+            let config = config.clone().interrupt(interrupt.clone());
+            let ritual =
+                thread::spawn(move || Necromancer::unroll(scroll).with_config(config).initiate());
+            while modified(sources) == stamps && !ritual.is_finished() {
+                thread::sleep(WATCH_INTERVAL);
+            }
+            interrupt.trigger();
+            match ritual.join() {
+                Ok(report) => {
+                    if let Some(e) = report.error() {
+                        error!("{}", e);
+                    }
+                }
+                Err(_) => error!("The ritual failed."),
+            }
+        }
+        loop {
+            let changed: Vec<&str> = sources
+                .iter()
+                .zip(stamps.iter().zip(modified(sources)))
+                .filter(|(_, (old, new))| *old != new)
+                .map(|(source, _)| source.name.as_str())
+                .collect();
+            if !changed.is_empty() {
+                println!(
+                    "---- {} changed, performing the ritual again ----",
+                    changed.join(", ")
+                );
+                break;
+            }
+            thread::sleep(WATCH_INTERVAL);
+        }
+    }
+}
+
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// When the files of the given scrolls were last modified, if it can be told.
+fn modified(sources: &[Source]) -> Vec<Option<SystemTime>> {
+    sources.iter().map(Source::modified).collect()
+}
+
+/// Perform the ritual, on the dashboard with `--tui`, or recording its timeline with
+/// `--visualize`. With `--progress`, shows how it progresses on a status line below its output,
+/// as long as the standard error is a terminal.
+fn perform(scroll: Scroll, config: RitualConfig, matches: &ArgMatches) -> RitualReport {
+    #[cfg(feature = "tui")]
+    if matches.get_flag("tui") {
+        // The dashboard takes over the terminal, so the output is printed once it is closed.
+        let output = necromancer::necro::OutputBuffer::new();
+        let report = necromancer::tui::dashboard(scroll, config.output(output.clone()))
+            .unwrap_or_else(|e| {
+                error!("Cannot show the dashboard: {}", e);
+                Exit::Failure.exit()
+            });
+        print!("{}", output.contents());
+        return report;
+    }
+    if let Some(path) = matches.get_one::<String>("visualize") {
+        let (report, timeline) = timeline::record(scroll, config);
+        if let Err(e) = fs::write(path, timeline.svg()) {
+            error!("Cannot write {}: {}", path, e);
+            Exit::Failure.exit();
+        }
+        return report;
+    }
+    if !matches.get_flag("progress") || !io::stderr().is_terminal() {
+        return Necromancer::unroll(scroll).with_config(config).initiate();
+    }
+    let status = Arc::new(Mutex::new(Status::default()));
+    let config = config
+        .hook(StatusHook(status.clone()))
+        .output(StatusOutput(status.clone()));
+    let mut necromancer = Necromancer::unroll(scroll).with_config(config);
+    let events = necromancer.subscribe();
+    let display = thread::spawn(move || display(events, status));
+    let report = necromancer.initiate();
+    let _ = display.join();
+    report
+}
+
+/// Keep the status line up to date with the events of the ritual until it ends, then clear it.
+fn display(mut events: Receiver<RitualEvent>, status: Arc<Mutex<Status>>) {
+    let start = Instant::now();
+    let display = thread::current();
+    let listener = {
+        let status = status.clone();
+        thread::spawn(move || {
+            loop {
+                match events.blocking_recv() {
+                    Ok(RitualEvent::SpiritSummoned(_)) => Status::lock(&status).spirits += 1,
+                    Ok(RitualEvent::SpiritFinished(_)) => {
+                        let mut status = Status::lock(&status);
+                        status.spirits = status.spirits.saturating_sub(1);
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                }
+            }
+            display.unpark();
+        })
+    };
+    while !listener.is_finished() {
+        Status::lock(&status).draw(start.elapsed());
+        thread::park_timeout(PROGRESS_INTERVAL);
+    }
+    Status::lock(&status).clear();
+}
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What the status line of `--progress` shows, and whether it is on the screen.
+#[derive(Default)]
+struct Status {
+    /// How many statements the spirits of each entity performed so far.
+    statements: IndexMap<SmolStr, u64>,
+    /// How many spirits are performing right now.
+    spirits: usize,
+    /// Whether the status line is on the screen.
+    shown: bool,
+    /// Whether the output of the ritual stopped in the middle of a line, which the status line
+    /// must not be drawn into.
+    midline: bool,
+}
+
+impl Status {
+    fn lock(status: &Mutex<Status>) -> MutexGuard<'_, Status> {
+        status.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Draw the status line in place of the previous one, cut off at the width of the terminal.
+    fn draw(&mut self, elapsed: Duration) {
+        if self.midline {
+            return;
+        }
+        let mut line = format!("{:.1}s | spirits: {}", elapsed.as_secs_f64(), self.spirits);
+        for (index, (name, count)) in self.statements.iter().enumerate() {
+            line.push_str(if index == 0 { " | " } else { ", " });
+            line.push_str(&format!("{}: {}", name, count));
+        }
+        let width = env::var("COLUMNS")
+            .ok()
+            .and_then(|columns| columns.parse::<usize>().ok())
+            .unwrap_or(80);
+        let line: String = line.chars().take(width.saturating_sub(1)).collect();
+        eprint!("\r\x1b[2K{}", line);
+        self.shown = true;
+    }
+
+    fn clear(&mut self) {
+        if self.shown {
+            eprint!("\r\x1b[2K");
+            self.shown = false;
+        }
+    }
+}
+
+/// Counts the statements for the status line.
+struct StatusHook(Arc<Mutex<Status>>);
+
+impl StatementHook for StatusHook {
+    fn after_stmt(&self, spirit: &str, _task: &str, _stmt: &Stmt) {
+        let mut status = Status::lock(&self.0);
+        match status.statements.get_mut(spirit) {
+            Some(count) => *count += 1,
+            None => {
+                status.statements.insert(SmolStr::from(spirit), 1);
+            }
+        }
+    }
+}
+
+/// Writes the output of the ritual to the standard output, clearing the status line first so
+/// that the two don't mix. The status line is drawn again below the output.
+struct StatusOutput(Arc<Mutex<Status>>);
+
+impl io::Write for StatusOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut status = Status::lock(&self.0);
+        status.clear();
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(buf)?;
+        stdout.flush()?;
+        if let Some(last) = buf.last() {
+            status.midline = *last != b'\n';
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stdout().flush()
+    }
+}
+
+/// Read, parse, merge and validate the given scrolls, and strip the result if asked to. Prints diagnostics and returns `None` if the scrolls can't be parsed or merged, or if they
+/// break the species rules in strict mode.
+fn prepare(
+    sources: &[Source],
+    parser: ParseConfig,
+    colour: bool,
+    optimize: bool,
+    strict: bool,
+) -> Result<Scroll, Exit> {
+    let mut scroll = load(sources, parser, colour)?;
+    if !validate(&scroll, sources, colour, strict) {
+        return Err(Exit::Validation);
+    }
+    if optimize {
+        validate::optimize(&mut scroll);
+    }
+    Ok(scroll)
+}
+
+/// Parse, merge and validate the given scrolls, reporting every error instead of only the first
+/// one. Fails with the first kind of error found.
+fn check(sources: &[Source], parser: ParseConfig, colour: bool, strict: bool) -> Result<(), Exit> {
+    let mut failure = None;
+    let mut merged: Option<Scroll> = None;
+    for source in sources {
+        let path = &source.name;
+        let code = source.read()?;
+        let (scroll, errors) = necromancer::parse::parse_recovering(&code, parser);
+        for (i, e) in errors.iter().enumerate() {
+            // Suggestions are only made for the first error.
+            let diag = match i {
+                0 => parse_diag(&code, e, parser),
+                _ => Diag::from_parse_error(&code, e),
+            };
+            eprint!("{}", diag.render(path, &code, colour));
+        }
+        if !errors.is_empty() {
+            failure = failure.or(Some(Exit::Parse));
+        }
+        merged = Some(merge(merged, scroll, path, colour)?);
+    }
+    if let Some(scroll) = merged {
+        if !validate(&scroll, sources, colour, strict) {
+            failure = failure.or(Some(Exit::Validation));
+        }
+    }
+    failure.map_or(Ok(()), Err)
+}
+
+/// Print the findings of the validation pass for the scroll merged from the given ones.
+/// Returns whether the scroll may be performed, which it may not if it breaks the species rules
+/// in strict mode.
+fn validate(scroll: &Scroll, sources: &[Source], colour: bool, strict: bool) -> bool {
+    // Statements can only be pointed at in the code of a single scroll.
+    let code = match sources {
+        [source] => match &source.code {
+            Some(code) => code.clone(),
+            None => fs::read_to_string(&source.name).unwrap_or_default(),
+        },
+        _ => String::new(),
+    };
+    let path = names(sources);
+    let mut ok = true;
+    for diagnostic in validate::validate(scroll) {
+        let mut diag = Diag::from_diagnostic(&code, &diagnostic);
+        if diagnostic.is_error() {
+            ok = false;
+        } else if strict && diagnostic.breaks_species_rules() {
+            diag = diag.deny();
+            ok = false;
+        }
+        eprint!("{}", diag.render(&path, &code, colour));
+    }
+    ok
+}
+
+/// Read, parse and merge the given scrolls. Exits if that fails.
+fn unroll(sources: &[Source], parser: ParseConfig, colour: bool) -> Scroll {
+    load(sources, parser, colour).unwrap_or_else(|exit| exit.exit())
+}
+
+/// Read, parse and merge the given scrolls. Prints the reason and fails if that fails.
+fn load(sources: &[Source], parser: ParseConfig, colour: bool) -> Result<Scroll, Exit> {
+    let mut merged = None;
+    for source in sources {
+        let path = &source.name;
+        let code = source.read()?;
+        let scroll = match necromancer::parse::parse_with(&code, parser) {
+            Ok(scroll) => scroll,
+            Err(e) => {
+                eprint!(
+                    "{}",
+                    parse_diag(&code, &e, parser).render(path, &code, colour)
+                );
+                return Err(Exit::Parse);
+            }
+        };
+        merged = Some(merge(merged, scroll, path, colour)?);
+    }
+    merged.ok_or(Exit::Failure)
+}
+
+/// Describe the error of the parser, with a suggestion how to fix the code if there is an
+/// unambiguous one.
+fn parse_diag(code: &str, error: &SyntaxError, parser: ParseConfig) -> Diag {
+    let diag = Diag::from_parse_error(code, error);
+    match necromancer::parse::suggest(code, parser) {
+        Some(suggestion) => diag.help(suggestion.to_string()),
+        None => diag,
+    }
+}
+
+/// Add the scroll read from the given path to the ones merged so far. Prints the conflicting
+/// entities and fails validation if they can't be merged.
+fn merge(merged: Option<Scroll>, scroll: Scroll, path: &str, colour: bool) -> Result<Scroll, Exit> {
+    let Some(merged) = merged else {
+        return Ok(scroll);
+    };
+    match merged.merge(scroll) {
+        Ok(merged) => Ok(merged),
+        Err(e) => {
+            eprint!(
+                "{}",
+                Diag::new(
+                    Severity::Error,
+                    "E0003",
+                    format!("cannot merge the scroll: {}", e)
+                )
+                .render(path, "", colour)
+            );
+            Err(Exit::Validation)
+        }
+    }
+}
+
+/// How `summon` exits if the scroll is not performed to its natural end, so that scripts can tell
+/// what went wrong. Listed in the help as [`EXIT_CODES`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Exit {
+    /// Something outside the scroll failed, e.g. reading it.
+    Failure = 1,
+    /// The scroll cannot be parsed.
+    Parse = 2,
+    /// The scroll cannot be merged, breaks the species rules in strict mode, or has lints that
+    /// are denied.
+    Validation = 3,
+    /// A spirit failed, or all spirits waited for each other.
+    Runtime = 4,
+    /// The ritual took longer than allowed with `--timeout`.
+    Timeout = 5,
+    /// The watchdog ended the ritual, since no spirit was left to do anything.
+    Watchdog = 6,
+}
+
+impl Exit {
+    /// How the ritual of the report exits, unless it finished naturally.
+    fn of(report: &RitualReport) -> Option<Exit> {
+        match report.termination() {
+            Termination::Finished => None,
+            Termination::Timeout => Some(Exit::Timeout),
+            Termination::Watchdog => Some(Exit::Watchdog),
+            Termination::Deadlock | Termination::Interrupted | Termination::Failed => {
+                Some(Exit::Runtime)
+            }
+        }
+    }
+
+    fn exit(self) -> ! {
+        process::exit(self as i32)
+    }
+}
+
+const EXIT_CODES: &str = "\
+Exit codes:
+  0  The ritual finished naturally.
+  1  Something else failed, e.g. reading the scroll.
+  2  The scroll cannot be parsed, or the arguments are invalid.
+  3  The scroll cannot be merged, breaks the species rules with --strict, or has denied lints.
+  4  A spirit failed, or all spirits waited for each other.
+  5  The ritual took longer than --timeout allows.
+  6  The watchdog ended the ritual, since no spirit was left to do anything.";
+
+/// A scroll to perform: a file, or code given on the command line or read from stdin.
+struct Source {
+    /// What the scroll is called in logs and diagnostics: the path of the file, if any.
+    name: String,
+    /// The code of the scroll, unless it is read from the file.
+    code: Option<String>,
+}
+
+impl Source {
+    /// The scroll at the given path, or the one read from stdin for `-`.
+    /// Exits if stdin cannot be read.
+    fn path(path: &str) -> Source {
+        if path != "-" {
+            return Source {
+                name: String::from(path),
+                code: None,
+            };
+        }
+        let mut code = String::new();
+        if let Err(e) = io::stdin().read_to_string(&mut code) {
+            error!("Cannot read the scroll from stdin: {}", e);
+            Exit::Failure.exit();
+        }
+        Source {
+            name: String::from("<stdin>"),
+            code: Some(code),
+        }
+    }
+
+    /// The scroll with the given code.
+    fn inline(code: &str) -> Source {
+        Source {
+            name: String::from("<inline>"),
+            code: Some(String::from(code)),
+        }
+    }
+
+    /// Read the code of the scroll. Prints the reason and fails if that fails.
+    fn read(&self) -> Result<String, Exit> {
+        if let Some(code) = &self.code {
+            return Ok(code.clone());
+        }
+        fs::read_to_string(&self.name).map_err(|e| {
+            error!("Cannot read {}: {}", self.name, e);
+            Exit::Failure
+        })
+    }
+
+    /// When the file of the scroll was last modified, if it can be told.
+    fn modified(&self) -> Option<SystemTime> {
+        if self.code.is_some() {
+            return None;
+        }
+        fs::metadata(&self.name)
+            .and_then(|meta| meta.modified())
+            .ok()
+    }
+}
+
+/// The names of the given scrolls, for logs and diagnostics.
+fn names(sources: &[Source]) -> String {
+    let names: Vec<&str> = sources.iter().map(|source| source.name.as_str()).collect();
+    names.join(", ")
+}
@@ -0,0 +1,442 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter, Result};
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use smol_str::SmolStr;
+
+use super::hook::StatementHook;
+use super::interrupt::Interrupt;
+use super::output::Output;
+use crate::value::Value;
+
+/// How often revenants retry a task unless configured otherwise.
+const DEFAULT_RETRIES: usize = 3;
+
+/// How many messages may wait for the ritual unless configured otherwise.
+const DEFAULT_MESSAGE_CAPACITY: usize = 1024;
+
+/// Settings that control how a [`Necromancer`](super::Necromancer) performs the ritual.
+#[derive(Debug, Clone, Default)]
+pub struct RitualConfig {
+    bound: HashMap<SmolStr, BoundSpirit>,
+    allow_env: bool,
+    fs_root: Option<PathBuf>,
+    allow_net: bool,
+    output: Output,
+    timeout: Option<Duration>,
+    max_spirits: Option<usize>,
+    history: usize,
+    budget: Option<(usize, Duration)>,
+    interrupt: Option<Interrupt>,
+    inspect: Option<u16>,
+    hooks: Vec<Hook>,
+    uncursed: bool,
+    digit_grouping: bool,
+    fail_on_infernal: bool,
+    warnings_as_errors: bool,
+    snapshots: Option<usize>,
+    engine: Engine,
+    seed: Option<u64>,
+    reanimation: Reanimation,
+    retries: Option<usize>,
+    message_capacity: Option<usize>,
+    overflow: Overflow,
+    /// How many rituals this one is performed within.
+    depth: usize,
+}
+
+impl RitualConfig {
+    /// Bind a host function as a spirit that scrolls can refer to by name, like any other entity.
+    ///
+    /// A bound spirit remembers values like an entity does. `moan` calls the function with the
+    /// remembered value and evaluates to the result, while the remembered value stays the same.
+    /// `invoke` calls the function with the remembered value and remembers the result instead.
+    ///
+    /// Entities of the scroll take precedence over bound spirits of the same name.
+    pub fn register(
+        mut self,
+        name: &str,
+        spirit: impl Fn(Value) -> Value + Send + Sync + 'static,
+    ) -> RitualConfig {
+        self.bound
+            .insert(SmolStr::from(name), BoundSpirit(Arc::new(spirit)));
+        self
+    }
+
+    /// Allow scrolls to read environment variables with `divine`. Disallowed by default.
+    pub fn allow_env(mut self, allow: bool) -> RitualConfig {
+        self.allow_env = allow;
+        self
+    }
+
+    /// Whether scrolls may read environment variables.
+    pub fn env_allowed(&self) -> bool {
+        self.allow_env
+    }
+
+    /// Allow scrolls to read and write files with `exhume` and `entomb`. Disallowed by default.
+    ///
+    /// Paths in the scroll are relative to the given root directory and may not leave it.
+    pub fn allow_fs(mut self, root: impl Into<PathBuf>) -> RitualConfig {
+        self.fs_root = Some(root.into());
+        self
+    }
+
+    /// Resolve a path used in a scroll inside the root directory for file access.
+    ///
+    /// Returns `None` if file access is not allowed, or if the path is absolute or contains `..`.
+    /// The check is purely lexical, i.e. symbolic links inside the root are not resolved.
+    pub fn sandboxed(&self, path: &str) -> Option<PathBuf> {
+        let root = self.fs_root.as_ref()?;
+        let path = Path::new(path);
+        path.components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+            .then(|| root.join(path))
+    }
+
+    /// Allow scrolls to communicate over TCP with `channel` and `whisper`. Disallowed by default.
+    ///
+    /// Requires the `ouija` feature.
+    pub fn allow_net(mut self, allow: bool) -> RitualConfig {
+        self.allow_net = allow;
+        self
+    }
+
+    /// Whether scrolls may communicate over TCP.
+    pub fn net_allowed(&self) -> bool {
+        self.allow_net
+    }
+
+    /// Write the output of `say` to the given sink instead of the standard output.
+    ///
+    /// Use an [`OutputBuffer`](super::OutputBuffer) to capture the output in memory.
+    pub fn output(mut self, sink: impl Write + Send + 'static) -> RitualConfig {
+        self.output = Output::new(sink);
+        self
+    }
+
+    pub(crate) fn sink(&self) -> &Output {
+        &self.output
+    }
+
+    /// Whether `say` curses corrupted values with zalgo. If not, they are written in plain text
+    /// as `<infernal:text>`, which keeps terminals and logs intact. Cursed by default.
+    pub fn curse(mut self, curse: bool) -> RitualConfig {
+        self.uncursed = !curse;
+        self
+    }
+
+    pub fn cursed(&self) -> bool {
+        !self.uncursed
+    }
+
+    /// Whether `say` groups the digits of integers in threes by underscores, like `1_000_000`.
+    /// The grouping is the same in every locale, and the output is still a valid integer literal.
+    /// Integers are written without grouping by default.
+    pub fn digit_grouping(mut self, group: bool) -> RitualConfig {
+        self.digit_grouping = group;
+        self
+    }
+
+    pub fn groups_digits(&self) -> bool {
+        self.digit_grouping
+    }
+
+    /// End the ritual with a [`RuntimeError::Corruption`](super::RuntimeError::Corruption) as
+    /// soon as an expression corrupts a value. The offending spirit performs nothing afterwards.
+    /// Corrupted values are tolerated by default.
+    pub fn fail_on_infernal(mut self, fail: bool) -> RitualConfig {
+        self.fail_on_infernal = fail;
+        self
+    }
+
+    pub fn fails_on_infernal(&self) -> bool {
+        self.fail_on_infernal
+    }
+
+    /// End the ritual with a [`RuntimeError::Warning`](super::RuntimeError::Warning) at the first
+    /// [`Warning`](super::Warning). Warnings are only reported by default.
+    pub fn warnings_as_errors(mut self, treat: bool) -> RitualConfig {
+        self.warnings_as_errors = treat;
+        self
+    }
+
+    /// Whether warnings are treated as errors.
+    pub fn treats_warnings_as_errors(&self) -> bool {
+        self.warnings_as_errors
+    }
+
+    /// Abort the ritual if it takes longer than the given duration. Unlimited by default.
+    pub fn timeout(mut self, timeout: Duration) -> RitualConfig {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn time_limit(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Refuse to summon more than the given number of spirits, counting every copy.
+    /// Unlimited by default.
+    pub fn max_spirits(mut self, max: usize) -> RitualConfig {
+        self.max_spirits = Some(max);
+        self
+    }
+
+    pub fn spirit_limit(&self) -> Option<usize> {
+        self.max_spirits
+    }
+
+    /// Abort the ritual once the given interrupt is triggered.
+    pub fn interrupt(mut self, interrupt: Interrupt) -> RitualConfig {
+        self.interrupt = Some(interrupt);
+        self
+    }
+
+    pub fn interruption(&self) -> Option<&Interrupt> {
+        self.interrupt.as_ref()
+    }
+
+    /// Let external tools inspect and control the ritual over TCP on the given port of the local
+    /// host. See the `inspect` module for the protocol.
+    ///
+    /// Requires the `inspect` feature.
+    pub fn inspect(mut self, port: u16) -> RitualConfig {
+        self.inspect = Some(port);
+        self
+    }
+
+    pub fn inspect_port(&self) -> Option<u16> {
+        self.inspect
+    }
+
+    /// Make every task pause for the given duration after executing the given number of
+    /// statements, so that runaway loops leave some room for everyone else. Unlimited by default.
+    pub fn budget(mut self, statements: usize, pause: Duration) -> RitualConfig {
+        self.budget = Some((statements.max(1), pause));
+        self
+    }
+
+    pub fn statement_budget(&self) -> Option<(usize, Duration)> {
+        self.budget
+    }
+
+    /// Let every entity recall the given number of values it remembered before its current one,
+    /// for use with `reminisce`. Entities recall nothing by default.
+    pub fn history(mut self, depth: usize) -> RitualConfig {
+        self.history = depth;
+        self
+    }
+
+    pub fn history_depth(&self) -> usize {
+        self.history
+    }
+
+    /// Take a snapshot of the state after every given number of statements executed by any
+    /// spirit, see [`RitualReport::state_history`](super::RitualReport::state_history). No
+    /// snapshots are taken by default.
+    pub fn state_history(mut self, every: usize) -> RitualConfig {
+        self.snapshots = Some(every.max(1));
+        self
+    }
+
+    pub fn snapshot_interval(&self) -> Option<usize> {
+        self.snapshots
+    }
+
+    /// Observe the statements executed during the ritual. Hooks are called in the order of their
+    /// registration.
+    pub fn hook(mut self, hook: impl StatementHook + 'static) -> RitualConfig {
+        self.hooks.push(Hook(Arc::new(hook)));
+        self
+    }
+
+    pub(crate) fn hooks(&self) -> impl Iterator<Item = &dyn StatementHook> {
+        self.hooks.iter().map(|hook| hook.0.as_ref())
+    }
+
+    /// Perform the ritual on the given engine. [`Engine::MultiThread`] by default.
+    pub fn engine(mut self, engine: Engine) -> RitualConfig {
+        self.engine = engine;
+        self
+    }
+
+    pub fn runtime_engine(&self) -> Engine {
+        self.engine
+    }
+
+    /// Seed the random decisions of the ritual, like the order in which vampires perform their
+    /// tasks or how long ghosts rest. Seeded from entropy by default, or with 0 on the
+    /// [`Engine::CurrentThreadDeterministic`] engine.
+    pub fn seed(mut self, seed: u64) -> RitualConfig {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn rng_seed(&self) -> Option<u64> {
+        match (self.seed, self.engine) {
+            (None, Engine::CurrentThreadDeterministic) => Some(0),
+            (seed, _) => seed,
+        }
+    }
+
+    /// Decide what happens when a zombie is animated or a ghost is disturbed while it is active
+    /// already. [`Reanimation::Spawn`] by default.
+    pub fn reanimation(mut self, reanimation: Reanimation) -> RitualConfig {
+        self.reanimation = reanimation;
+        self
+    }
+
+    pub fn reanimation_policy(&self) -> Reanimation {
+        self.reanimation
+    }
+
+    /// Let revenants perform a task that ends with `stumble` again up to the given number of
+    /// times. 3 by default.
+    pub fn revenant_retries(mut self, retries: usize) -> RitualConfig {
+        self.retries = Some(retries);
+        self
+    }
+
+    pub fn retry_limit(&self) -> usize {
+        self.retries.unwrap_or(DEFAULT_RETRIES)
+    }
+
+    /// Let at most the given number of messages from spirits wait for the ritual, e.g. for
+    /// animating or invoking others. 1024 by default.
+    pub fn message_capacity(mut self, capacity: usize) -> RitualConfig {
+        self.message_capacity = Some(capacity.max(1));
+        self
+    }
+
+    pub fn message_limit(&self) -> usize {
+        self.message_capacity.unwrap_or(DEFAULT_MESSAGE_CAPACITY)
+    }
+
+    /// Decide what happens when a spirit sends a message while the
+    /// [capacity](RitualConfig::message_capacity) is exhausted. [`Overflow::Wait`] by default.
+    pub fn overflow(mut self, overflow: Overflow) -> RitualConfig {
+        self.overflow = overflow;
+        self
+    }
+
+    pub fn overflow_policy(&self) -> Overflow {
+        self.overflow
+    }
+
+    /// Settings for a ritual performed within this one with `summon ... within`. The inner
+    /// ritual ends with the outer one, so it has no time limit or interrupt of its own, and it
+    /// can't be inspected.
+    pub(crate) fn within(&self) -> RitualConfig {
+        RitualConfig {
+            timeout: None,
+            interrupt: None,
+            inspect: None,
+            depth: self.depth + 1,
+            ..self.clone()
+        }
+    }
+
+    /// How many rituals this one is performed within.
+    pub(crate) fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Return the spirits bound to host functions.
+    pub fn bound_spirits(&self) -> &HashMap<SmolStr, BoundSpirit> {
+        &self.bound
+    }
+}
+
+/// The runtime that spirits are performed on. See [`RitualConfig::engine`].
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Engine {
+    /// Spirits are performed in parallel on all cores.
+    #[default]
+    MultiThread,
+    /// Spirits are performed one after another on the current thread. How they interleave only
+    /// depends on the [seed](RitualConfig::seed), so rituals can be reproduced exactly.
+    ///
+    /// Time is paused and only advances while every spirit waits, e.g. for resting ghosts, so
+    /// nobody waits for the clock. Time limits are measured in this paused time as well.
+    CurrentThreadDeterministic,
+}
+
+/// What happens when an active entity is animated or disturbed again.
+/// See [`RitualConfig::reanimation`].
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Reanimation {
+    /// Summon another spirit of the entity, and warn about it.
+    #[default]
+    Spawn,
+    /// Leave the entity alone, and warn about it.
+    Ignore,
+    /// End the ritual with a [`RuntimeError::Reanimated`](super::RuntimeError::Reanimated).
+    Error,
+}
+
+/// What happens when a spirit sends a message to the ritual while too many messages wait
+/// already. See [`RitualConfig::overflow`].
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// The spirit waits until there is room for the message.
+    #[default]
+    Wait,
+    /// The message is dropped, and a [`Warning::MessageDropped`](super::Warning::MessageDropped)
+    /// is emitted.
+    Drop,
+}
+
+/// A host function that can be called from a scroll. See [`RitualConfig::register`].
+#[derive(Clone)]
+pub struct BoundSpirit(Arc<dyn Fn(Value) -> Value + Send + Sync>);
+
+impl BoundSpirit {
+    /// Call the host function.
+    pub fn call(&self, value: Value) -> Value {
+        (self.0)(value)
+    }
+}
+
+impl Debug for BoundSpirit {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        write!(fmt, "BoundSpirit")
+    }
+}
+
+/// A registered [`StatementHook`].
+#[derive(Clone)]
+struct Hook(Arc<dyn StatementHook>);
+
+impl Debug for Hook {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        write!(fmt, "Hook")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sandboxed_paths() {
+        let config = RitualConfig::default();
+        assert_eq!(config.sandboxed("grave.txt"), None);
+
+        let config = config.allow_fs("/crypt");
+        assert_eq!(
+            config.sandboxed("grave.txt"),
+            Some(PathBuf::from("/crypt/grave.txt"))
+        );
+        assert_eq!(
+            config.sandboxed("./deep/grave.txt"),
+            Some(PathBuf::from("/crypt/deep/grave.txt"))
+        );
+        assert_eq!(config.sandboxed("/etc/passwd"), None);
+        assert_eq!(config.sandboxed("../grave.txt"), None);
+        assert_eq!(config.sandboxed("deep/../../grave.txt"), None);
+    }
+}
@@ -0,0 +1,278 @@
+//! The semantics of expressions, shared by every way of performing a ritual.
+//!
+//! Only expressions are evaluated here so far. Statements are still executed by
+//! `Spirit::exec_stmt`, since most of them await the ritual: they send messages, pause, perform
+//! nested rituals, or access files and the ouija board. Moving them here needs an asynchronous
+//! counterpart of [`World`] and is deferred until a second engine needs it.
+use tracing::{debug, trace};
+
+use crate::scroll::expression::Expr;
+use crate::value::Value;
+
+/// What expressions see of the ritual, from the point of view of a spirit performing a task.
+///
+/// The semantics of expressions only depend on this, so every engine evaluates them alike.
+/// Names of the task's parameter refer to its argument instead of an entity.
+pub(crate) trait World {
+    /// The value the named entity moans, or the entity of the context for `None`. Bound spirits
+    /// may answer with something else than they remember. Unknown entities moan the void.
+    fn moan(&self, name: Option<&str>) -> Value;
+
+    /// The value the named entity remembers, or the entity of the context for `None`. Unknown
+    /// entities remember the void.
+    fn memory(&self, name: Option<&str>) -> Value;
+
+    /// The n-th value the named entity remembered before its current one, or the void if it
+    /// does not recall that far back.
+    fn recall(&self, name: Option<&str>, n: usize) -> Value;
+
+    /// The value of the environment variable, or the void if it cannot be read.
+    fn divine(&self, var: &str) -> Value;
+
+    /// Report that the described operation corrupted a value.
+    fn corrupted(&self, operation: String);
+}
+
+/// Evaluate the expressions from right to left and return the value on top of the stack.
+///
+/// The stack starts out with the void and never runs empty: rending a stack of one value
+/// corrupts that value instead of popping it.
+pub(crate) fn eval_exprs(world: &impl World, exprs: &[Expr]) -> Value {
+    let mut stack = vec![Value::default()];
+    for expr in exprs.iter().rev() {
+        eval_expr(world, expr, &mut stack);
+        debug!("Evaluating expression {:?} (Stack {:?})", expr, stack);
+    }
+    stack.pop().unwrap()
+}
+
+/// Evaluate the expression on the given stack, which must not be empty. Operators act on the top
+/// of the stack, and anything else puts its value on top.
+pub(crate) fn eval_expr(world: &impl World, expr: &Expr, stack: &mut Vec<Value>) {
+    match expr {
+        Expr::Moan(name) => {
+            let value = world.moan(name.as_deref());
+            add(world, stack.last_mut().unwrap(), value, "moan");
+        }
+        Expr::Remembering(name, value) => {
+            stack.push(Value::Boolean(value == world.memory(name.as_deref())))
+        }
+        Expr::Reminisce(name, n) => {
+            let value = world.recall(name.as_deref(), *n);
+            add(world, stack.last_mut().unwrap(), value, "reminisce");
+        }
+        Expr::Rend => match stack.pop() {
+            Some(top) if !stack.is_empty() => {
+                let last = stack.last_mut().unwrap();
+                let result = &*last / &top;
+                if corrupts(&[last, &top], &result) {
+                    let operation = format!("rend {} / {}", describe(last), describe(&top));
+                    world.corrupted(operation);
+                }
+                *last = result;
+            }
+            // Rending needs two values. Tearing apart the last one corrupts it.
+            Some(top) => {
+                trace!("Rending a stack of one, corrupting it");
+                if !matches!(top, Value::Infernal(_)) {
+                    let operation = format!("rend of the single value {}", describe(&top));
+                    world.corrupted(operation);
+                }
+                stack.push(Value::corrupted());
+            }
+            None => unreachable!("the stack never runs empty"),
+        },
+        Expr::Turn => {
+            let last = stack.last_mut().unwrap();
+            let result = -&*last;
+            if corrupts(&[last], &result) {
+                world.corrupted(format!("turn -{}", describe(last)));
+            }
+            *last = result;
+        }
+        Expr::Fester(exponent) => {
+            let last = stack.last_mut().unwrap();
+            let result = last.pow(*exponent);
+            if corrupts(&[last], &result) {
+                let operation = format!("fester {} ^ {}", describe(last), exponent);
+                world.corrupted(operation);
+            }
+            *last = result;
+        }
+        Expr::Divine(var) => stack.push(world.divine(var)),
+        Expr::Compose(format) => {
+            // The void at the bottom of the stack may be taken as well.
+            let taken = stack.len().saturating_sub(Value::arity(format));
+            let arguments: Vec<Value> = stack.drain(taken..).rev().collect();
+            let result = Value::compose(format, &arguments);
+            let operands: Vec<&Value> = arguments.iter().collect();
+            if corrupts(&operands, &result) {
+                let arguments: Vec<String> = arguments.iter().map(describe).collect();
+                let operation = format!("compose {:?} of {}", format, arguments.join(", "));
+                world.corrupted(operation);
+            }
+            stack.push(result);
+        }
+        Expr::Transcribe(radix) => {
+            let last = stack.last_mut().unwrap();
+            let result = last.transcribe(*radix);
+            if corrupts(&[last], &result) {
+                let operation = format!("transcribe {} as {}", describe(last), radix);
+                world.corrupted(operation);
+            }
+            *last = result;
+        }
+        Expr::Value(value) => stack.push(value.clone()),
+        Expr::Group(exprs) => stack.push(eval_exprs(world, exprs)),
+    }
+}
+
+/// Add the value to the top of the stack, as done by the named expression.
+fn add(world: &impl World, top: &mut Value, value: Value, expr: &str) {
+    // Only the left operand is consumed by the addition. Keep it around for the trace.
+    let left = (!matches!(value, Value::Infernal(_))).then(|| value.clone());
+    let result = value + top;
+    if let Some(left) = left.filter(|left| corrupts(&[left, top], &result)) {
+        let operation = format!("{} {} + {}", expr, describe(&left), describe(top));
+        world.corrupted(operation);
+    }
+    *top = result;
+}
+
+/// Describe the value for a trace, quoting strings to set them apart.
+fn describe(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("{:?}", s),
+        Value::Void => String::from("void"),
+        value => value.uncursed().to_string(),
+    }
+}
+
+/// Whether an operation on the operands corrupted a value to get the result. Operands that are
+/// corrupted already are not to blame.
+fn corrupts(operands: &[&Value], result: &Value) -> bool {
+    matches!(result, Value::Infernal(_))
+        && !operands
+            .iter()
+            .any(|operand| matches!(operand, Value::Infernal(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    use malachite::Integer;
+    use smol_str::SmolStr;
+
+    use super::*;
+
+    /// Entities with a history of values, the first of which is the current one. Bare
+    /// expressions refer to Peter.
+    #[derive(Default)]
+    struct Graveyard {
+        memories: HashMap<&'static str, Vec<Value>>,
+        corruptions: RefCell<Vec<String>>,
+    }
+
+    impl World for Graveyard {
+        fn moan(&self, name: Option<&str>) -> Value {
+            self.memory(name)
+        }
+
+        fn memory(&self, name: Option<&str>) -> Value {
+            self.recall(name, 0)
+        }
+
+        fn recall(&self, name: Option<&str>, n: usize) -> Value {
+            self.memories
+                .get(name.unwrap_or("Peter"))
+                .and_then(|history| history.get(n))
+                .cloned()
+                .unwrap_or_default()
+        }
+
+        fn divine(&self, _var: &str) -> Value {
+            Value::Void
+        }
+
+        fn corrupted(&self, operation: String) {
+            self.corruptions.borrow_mut().push(operation);
+        }
+    }
+
+    fn int(i: i64) -> Value {
+        Value::Integer(Integer::from(i))
+    }
+
+    fn lit(i: i64) -> Expr {
+        Expr::Value(int(i))
+    }
+
+    #[test]
+    fn evaluate_right_to_left() {
+        let world = Graveyard {
+            memories: HashMap::from([
+                ("Peter", vec![int(8), int(5)]),
+                ("Lisa", vec![Value::from("boo")]),
+            ]),
+            ..Graveyard::default()
+        };
+        let lisa = || Some(SmolStr::from("Lisa"));
+        assert_eq!(eval_exprs(&world, &[Expr::Moan(None)]), int(8));
+        assert_eq!(
+            eval_exprs(&world, &[Expr::Moan(None), Expr::Moan(None)]),
+            int(16)
+        );
+        assert_eq!(
+            eval_exprs(&world, &[Expr::Rend, lit(2), Expr::Moan(None)]),
+            int(4)
+        );
+        let group = |exprs: Vec<Expr>| Expr::Group(exprs);
+        assert_eq!(
+            eval_exprs(
+                &world,
+                &[
+                    Expr::Rend,
+                    group(vec![lit(2)]),
+                    group(vec![Expr::Reminisce(None, 1)])
+                ]
+            ),
+            int(2)
+        );
+        assert_eq!(
+            eval_exprs(&world, &[Expr::Moan(lisa()), Expr::Moan(lisa())]),
+            Value::from("booboo")
+        );
+        assert_eq!(
+            eval_exprs(&world, &[Expr::Remembering(lisa(), Value::from("boo"))]),
+            Value::from(true)
+        );
+        assert_eq!(
+            eval_exprs(&world, &[Expr::Remembering(None, int(5))]),
+            Value::from(false)
+        );
+        assert_eq!(
+            eval_exprs(&world, &[Expr::Moan(Some(SmolStr::from("Jay")))]),
+            Value::Void
+        );
+        assert!(world.corruptions.borrow().is_empty());
+    }
+
+    #[test]
+    fn report_corruptions() {
+        let world = Graveyard::default();
+        let rend = eval_exprs(&world, &[Expr::Rend, lit(0), lit(1)]);
+        assert!(matches!(rend, Value::Infernal(_)));
+        assert!(matches!(
+            eval_exprs(&world, &[Expr::Rend]),
+            Value::Infernal(_)
+        ));
+        let turned = eval_exprs(&world, &[Expr::Turn, Expr::Rend, lit(0), lit(1)]);
+        assert!(matches!(turned, Value::Infernal(_)));
+        assert_eq!(
+            *world.corruptions.borrow(),
+            ["rend 1 / 0", "rend of the single value void", "rend 1 / 0"]
+        );
+    }
+}
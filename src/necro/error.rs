@@ -0,0 +1,49 @@
+use smol_str::SmolStr;
+
+use super::Warning;
+
+/// An error that ended a ritual prematurely.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum RuntimeError {
+    /// Every remaining spirit waits to be reactivated, which nobody is left to do.
+    #[error("deadlock: {} wait(s) to be reactivated, but no one is left to do so", .0.join(", "))]
+    Deadlock(Vec<SmolStr>),
+    /// A spirit corrupted a value, while corrupted values were not tolerated.
+    /// See [`RitualConfig::fail_on_infernal`](super::RitualConfig::fail_on_infernal).
+    #[error("{spirit} corrupted a value: {operation}")]
+    Corruption {
+        spirit: SmolStr,
+        /// The operation that corrupted the value, with its operands.
+        operation: String,
+    },
+    /// Tasks performed each other too deeply nested, e.g. in an endless recursion.
+    #[error("{spirit} performed task {task} nested too deeply")]
+    RecursionLimit { spirit: SmolStr, task: SmolStr },
+    /// Rituals were performed within each other too deeply nested, e.g. by a scroll that
+    /// summons itself.
+    #[error("{spirit} summoned {path} within too many other rituals")]
+    NestingLimit { spirit: SmolStr, path: String },
+    /// A ritual performed within this one with `summon ... within` failed.
+    #[error("{spirit} summoned {path}, which failed: {error}")]
+    Within {
+        spirit: SmolStr,
+        path: String,
+        error: Box<RuntimeError>,
+    },
+    /// A spirit tried to summon something that is not a creature of the scroll, like a bound
+    /// spirit.
+    #[error("cannot summon {0}, which is not a creature of the scroll")]
+    NotSummonable(SmolStr),
+    /// An active entity was animated or disturbed again, while this was not allowed.
+    /// See [`RitualConfig::reanimation`](super::RitualConfig::reanimation).
+    #[error("cannot reanimate {0}, which is active already")]
+    Reanimated(SmolStr),
+    /// A spirit panicked, e.g. in a bound spirit or a hook. The panic ended only the spirit,
+    /// and the ritual with this error.
+    #[error("{spirit} panicked: {message}")]
+    Panicked { spirit: SmolStr, message: String },
+    /// A warning, while warnings were treated as errors.
+    /// See [`RitualConfig::warnings_as_errors`](super::RitualConfig::warnings_as_errors).
+    #[error("{0}")]
+    Warning(Warning),
+}
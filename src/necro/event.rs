@@ -0,0 +1,69 @@
+use smol_str::SmolStr;
+use tokio::sync::broadcast::Sender;
+
+use super::hook::StatementHook;
+use super::Warning;
+use crate::value::Value;
+
+/// How many events a subscriber may fall behind before it misses some.
+pub(crate) const EVENT_CAPACITY: usize = 1024;
+
+/// Something that happened during a ritual. Subscribe to the events of a ritual with
+/// [`Necromancer::subscribe`](super::Necromancer::subscribe).
+#[derive(Debug, Clone, PartialEq)]
+pub enum RitualEvent {
+    /// A spirit of the named entity was summoned, including copies.
+    SpiritSummoned(SmolStr),
+    /// A spirit of the named entity finished, because it performed all its tasks, or because it
+    /// was banished or the ritual ended.
+    SpiritFinished(SmolStr),
+    /// The named spirit said the value.
+    Said { spirit: SmolStr, value: Value },
+    /// The memory or the activity of the named entity changed.
+    StateChanged {
+        name: SmolStr,
+        memory: Value,
+        active: bool,
+    },
+    /// A warning was emitted, see [`Warning`] for the kinds of warnings.
+    Warned(Warning),
+}
+
+/// Publishes the events that statement hooks observe.
+pub(crate) struct EventHook(pub(crate) Sender<RitualEvent>);
+
+impl StatementHook for EventHook {
+    fn on_say(&self, spirit: &str, value: &Value) {
+        // Nobody may be listening, which is fine.
+        let _ = self.0.send(RitualEvent::Said {
+            spirit: SmolStr::from(spirit),
+            value: value.clone(),
+        });
+    }
+
+    fn on_state_change(&self, name: &str, memory: &Value, active: bool) {
+        let _ = self.0.send(RitualEvent::StateChanged {
+            name: SmolStr::from(name),
+            memory: memory.clone(),
+            active,
+        });
+    }
+
+    fn on_warning(&self, warning: &Warning) {
+        let _ = self.0.send(RitualEvent::Warned(warning.clone()));
+    }
+}
+
+/// Publishes [`RitualEvent::SpiritFinished`] when dropped, however the spirit ends.
+pub(crate) struct Departure {
+    pub(crate) name: SmolStr,
+    pub(crate) events: Sender<RitualEvent>,
+}
+
+impl Drop for Departure {
+    fn drop(&mut self) {
+        let _ = self
+            .events
+            .send(RitualEvent::SpiritFinished(self.name.clone()));
+    }
+}
@@ -0,0 +1,26 @@
+use super::Warning;
+use crate::scroll::statement::Stmt;
+use crate::value::Value;
+
+/// Callbacks for observing a ritual statement by statement, e.g. for tracing, debuggers or
+/// coverage. Register hooks with [`RitualConfig::hook`](super::RitualConfig::hook).
+///
+/// Hooks are called from the spirits while they perform, possibly from several threads at the
+/// same time. All methods do nothing by default.
+pub trait StatementHook: Send + Sync {
+    /// The named spirit is about to execute the statement as part of the named task.
+    /// Statements nested in loops and branches are reported as well.
+    fn before_stmt(&self, _spirit: &str, _task: &str, _stmt: &Stmt) {}
+
+    /// The named spirit finished executing the statement as part of the named task.
+    fn after_stmt(&self, _spirit: &str, _task: &str, _stmt: &Stmt) {}
+
+    /// The named spirit says the value.
+    fn on_say(&self, _spirit: &str, _value: &Value) {}
+
+    /// The memory or the activity of the named entity changed. Receives the new state.
+    fn on_state_change(&self, _name: &str, _memory: &Value, _active: bool) {}
+
+    /// A warning was emitted, see [`Warning`] for the kinds of warnings.
+    fn on_warning(&self, _warning: &Warning) {}
+}
@@ -0,0 +1,130 @@
+//! Inspection and control of a running ritual over TCP, for building external tools.
+//!
+//! Clients send one command per line and receive zero or more lines of data, followed by a line
+//! saying `ok` or `error <reason>`:
+//!
+//! ```text
+//! list                      one line per entity: <name> <active> <memory>
+//! get <name>                the memory of the entity
+//! activate <name>           make the entity active
+//! deactivate <name>         make the entity inactive
+//! animate <name>            summon the zombie, like `animate <name>` in a scroll
+//! disturb <name>            summon the ghost, like `disturb <name>` in a scroll
+//! invoke <name>             summon a copy of the entity, like `invoke <name>` in a scroll
+//! ```
+//!
+//! Line breaks in memories are sent as `\n`.
+use std::io;
+use std::sync::Arc;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::error::TrySendError;
+use tracing::{debug, error, info};
+
+use super::{Message, Ritual};
+
+/// Accept inspectors on the given port of the local host until the ritual ends.
+pub(super) async fn serve(ritual: Arc<Ritual>, port: u16) {
+    let listener = match TcpListener::bind(("127.0.0.1", port)).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Cannot inspect the ritual on port {}: {}", port, e);
+            return;
+        }
+    };
+    info!("Inspecting the ritual on port {}", port);
+    loop {
+        match listener.accept().await {
+            Ok((stream, address)) => {
+                debug!("Inspector connected from {}", address);
+                tokio::spawn(session(Arc::clone(&ritual), stream));
+            }
+            Err(e) => error!("Cannot accept inspector: {}", e),
+        }
+    }
+}
+
+/// Answer the commands of a single inspector.
+async fn session(ritual: Arc<Ritual>, stream: TcpStream) -> io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        let response = match execute(&ritual, line.trim()) {
+            Ok(data) => data + "ok\n",
+            Err(reason) => format!("error {}\n", reason),
+        };
+        writer.write_all(response.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+/// Execute the command and return the lines of data to send back.
+fn execute(ritual: &Ritual, command: &str) -> Result<String, String> {
+    let (command, argument) = match command.split_once(char::is_whitespace) {
+        Some((command, argument)) => (command, Some(argument.trim())),
+        None => (command, None),
+    };
+    let state = &ritual.state;
+    match (command, argument) {
+        ("list", None) => Ok(ritual
+            .creatures
+            .keys()
+            .map(|&id| {
+                let spirit = state.spirit(id);
+                format!(
+                    "{} {} {}\n",
+                    state.name(id),
+                    spirit.active(),
+                    escape(&spirit.memory().to_string())
+                )
+            })
+            .collect()),
+        ("get", Some(name)) => match state.id(name) {
+            Some(id) => {
+                let memory = state.spirit(id).memory().to_string();
+                Ok(format!("{}\n", escape(&memory)))
+            }
+            None => Err(format!("unknown entity {}", name)),
+        },
+        ("activate" | "deactivate", Some(name)) => {
+            let active = command == "activate";
+            match state.id(name) {
+                Some(id) => *state.spirit(id).active_mut() = active,
+                None => return Err(format!("unknown entity {}", name)),
+            }
+            if active {
+                ritual.state.notifier().notify_waiters();
+            }
+            Ok(String::new())
+        }
+        ("animate" | "disturb" | "invoke", Some(name)) => {
+            let Some(id) = state
+                .id(name)
+                .filter(|id| ritual.creatures.contains_key(id))
+            else {
+                return Err(format!("unknown entity {}", name));
+            };
+            let message = match command {
+                "animate" => Message::Animate(id),
+                "disturb" => Message::Disturb(id),
+                _ => Message::Invoke(id, None),
+            };
+            ritual.state.post();
+            ritual.sender.try_send(message).map_err(|e| {
+                ritual.state.handled();
+                match e {
+                    TrySendError::Full(_) => String::from("the ritual is busy, try again"),
+                    TrySendError::Closed(_) => String::from("the ritual is over"),
+                }
+            })?;
+            Ok(String::new())
+        }
+        _ => Err(format!("unknown command {}", command)),
+    }
+}
+
+/// Keep the value on a single line.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\n")
+}
@@ -0,0 +1,46 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::Notify;
+
+/// A handle for ending a ritual from the outside, e.g. from another thread.
+///
+/// Pass a clone to [`RitualConfig::interrupt`](super::RitualConfig::interrupt) and call
+/// [`Interrupt::trigger`] to abort the ritual. Clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct Interrupt(Arc<Inner>);
+
+#[derive(Debug, Default)]
+struct Inner {
+    triggered: AtomicBool,
+    notify: Notify,
+}
+
+impl Interrupt {
+    pub fn new() -> Interrupt {
+        Interrupt::default()
+    }
+
+    /// Abort the ritual. Takes effect immediately if the ritual is running, or as soon as it
+    /// begins otherwise.
+    pub fn trigger(&self) {
+        self.0.triggered.store(true, Ordering::SeqCst);
+        self.0.notify.notify_waiters();
+    }
+
+    /// Whether the interrupt was triggered.
+    pub fn triggered(&self) -> bool {
+        self.0.triggered.load(Ordering::SeqCst)
+    }
+
+    /// Wait until the interrupt is triggered.
+    pub(crate) async fn wait(&self) {
+        let notified = self.0.notify.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+        if self.triggered() {
+            return;
+        }
+        notified.await;
+    }
+}
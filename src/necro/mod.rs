@@ -1,33 +1,89 @@
-use std::sync::Arc;
-use std::time::Duration;
-
-use dashmap::DashSet;
-use futures::future::{AbortHandle, Abortable};
-use futures::stream::FuturesUnordered;
-use futures::StreamExt;
-use log::{debug, warn};
+use std::any::Any;
+use std::cmp::Reverse;
+use std::future;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use futures::FutureExt;
+use indexmap::IndexMap;
 use smol_str::SmolStr;
 use state::State;
-use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
-use tokio::sync::{Mutex, RwLock};
-use tokio::task::JoinHandle;
-use tokio::time;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::sync::{broadcast, oneshot, Mutex, Notify};
+use tokio::task::{JoinError, JoinHandle, JoinSet};
+use tokio::{runtime, time};
+use tracing::{debug, error, info, info_span, warn, Instrument};
 
-use crate::necro::summon::{Candle, Spirit};
+use crate::necro::event::{Departure, EventHook, EVENT_CAPACITY};
+use crate::necro::name::NameId;
+use crate::necro::summon::Spirit;
 use crate::scroll::entity::{Entity, Species};
-use crate::scroll::{EntityList, Scroll};
+use crate::scroll::Scroll;
 use crate::value::Value;
 
+mod config;
+mod core;
+mod error;
+mod event;
+mod hook;
+#[cfg(feature = "inspect")]
+pub mod inspect;
+mod interrupt;
+mod name;
+#[cfg(feature = "ouija")]
+mod ouija;
+mod output;
+mod report;
 mod state;
 mod summon;
+pub mod timeline;
+mod warning;
+
+pub use config::{BoundSpirit, Engine, Overflow, Reanimation, RitualConfig};
+pub use error::RuntimeError;
+pub use event::RitualEvent;
+pub use hook::StatementHook;
+pub use interrupt::Interrupt;
+pub use output::OutputBuffer;
+pub use report::{RitualReport, Termination};
+pub use warning::Warning;
 
 pub struct Necromancer {
     scroll: Scroll,
+    config: RitualConfig,
+    /// Where the events of the ritual are published, once anyone subscribed.
+    events: Option<broadcast::Sender<RitualEvent>>,
 }
 
 impl Necromancer {
     pub fn unroll(scroll: Scroll) -> Necromancer {
-        Necromancer { scroll }
+        Necromancer {
+            scroll,
+            config: RitualConfig::default(),
+            events: None,
+        }
+    }
+
+    /// Perform the ritual with the given settings instead of the default ones.
+    pub fn with_config(mut self, config: RitualConfig) -> Necromancer {
+        self.config = config;
+        self
+    }
+
+    /// Receive the [`RitualEvent`]s of the ritual while it is performed, e.g. to show its
+    /// progress in a user interface. Subscribe before initiating the ritual, and receive the
+    /// events from another thread or task.
+    ///
+    /// The channel closes once the ritual ended. Receivers that fall more than 1024 events
+    /// behind miss the oldest ones, see [`broadcast::Receiver::recv`].
+    pub fn subscribe(&mut self) -> broadcast::Receiver<RitualEvent> {
+        self.events
+            .get_or_insert_with(|| broadcast::channel(EVENT_CAPACITY).0)
+            .subscribe()
     }
 
     // calling this runs the interpreter
@@ -36,14 +92,33 @@ impl Necromancer {
     // since they're shared between threads.
     // Ritual spawns a tokio task for every entity. Every entity itself spawns a tokio task for each
     // of their tasks.
-    #[tokio::main(flavor = "multi_thread")]
-    pub async fn initiate(self) {
-        // we need a static reference to the AST
-        // TODO rewrite (this is too hacky imo)
-        let scroll: &'static Scroll = Box::leak(Box::new(self.scroll));
+    // Every call runs on a runtime of its own, which is shut down afterwards. This makes sure
+    // that nothing is left running from the ritual, even if it was aborted.
+    pub fn initiate(self) -> RitualReport {
+        let mut builder = match self.config.runtime_engine() {
+            Engine::MultiThread => runtime::Builder::new_multi_thread(),
+            Engine::CurrentThreadDeterministic => {
+                let mut builder = runtime::Builder::new_current_thread();
+                builder.start_paused(true);
+                builder
+            }
+        };
+        let runtime = builder
+            .enable_all()
+            .build()
+            .expect("Failed building the runtime");
+        runtime.block_on(self.unfold())
+    }
 
-        let creatures = scroll.creatures();
-        let ritual = Ritual::new(creatures).await;
+    /// Perform the ritual on the current runtime. Everything spawned for the ritual is aborted
+    /// once it ended, or once the returned future is dropped before.
+    pub(crate) async fn unfold(self) -> RitualReport {
+        let start = Instant::now();
+        let config = match &self.events {
+            Some(events) => self.config.hook(EventHook(events.clone())),
+            None => self.config,
+        };
+        let ritual = Ritual::new(self.scroll, config, self.events).await;
 
         // Abort futures (i.e. kill program) if every entity is inactive.
         // poll `Ritual::watchdog()` every second.
@@ -62,132 +137,579 @@ impl Necromancer {
         let ritual_msg = Arc::clone(&ritual);
         let message_handler = tokio::spawn(async move {
             while let Some(message) = Ritual::received(Arc::clone(&ritual_msg)).await {
-                match message {
-                    Message::Animate(name) => {
-                        let creature = creatures.get(&name).unwrap();
-                        if matches!(creature.species(), Species::Zombie) {
-                            Arc::clone(&ritual_msg).summon(creature).await;
-                        }
-                    }
-                    Message::Disturb(name) => {
-                        let creature = creatures.get(&name).unwrap();
-                        if matches!(creature.species(), Species::Ghost) {
-                            Arc::clone(&ritual_msg).summon(creature).await;
-                        }
-                    }
-                    Message::Invoke(name) => {
-                        let creature = creatures.get(&name).unwrap();
-                        Arc::clone(&ritual_msg).summon(creature).await;
+                Arc::clone(&ritual_msg).handle(message).await;
+                ritual_msg.state.handled();
+                // The ritual may be waiting for the message to be handled before it finishes.
+                ritual_msg.summoned.notify_one();
+            }
+        });
+
+        let inspector = ritual
+            .config
+            .inspect_port()
+            .and_then(|port| Ritual::inspect(Arc::clone(&ritual), port));
+
+        let mut background = vec![watchdog.abort_handle(), message_handler.abort_handle()];
+        background.extend(inspector.map(|inspector| inspector.abort_handle()));
+        let teardown = Teardown {
+            ritual: Arc::clone(&ritual),
+            background,
+        };
+
+        let finished = async {
+            let finished = Ritual::finished(Arc::clone(&ritual));
+            match ritual.config.time_limit() {
+                Some(limit) => {
+                    if time::timeout(limit, finished).await.is_err() {
+                        warn!("Ritual timed out after {:?}. Aborting.", limit);
+                        ritual.abort(Termination::Timeout).await;
                     }
-                    Message::Say(value) => {
-                        println!("{}", value);
+                }
+                None => finished.await,
+            }
+        };
+        match ritual.config.interruption() {
+            Some(interrupt) => {
+                tokio::select! {
+                    _ = finished => {}
+                    _ = interrupt.wait() => {
+                        warn!("Ritual interrupted. Aborting.");
+                        ritual.abort(Termination::Interrupted).await;
                     }
                 }
             }
-        });
+            None => finished.await,
+        }
+
+        // The watchdog, the messages and the inspector are no longer needed.
+        // Necessary since they do not exit on their own.
+        drop(teardown);
+
+        if let Err(e) = ritual.config.sink().flush() {
+            error!("Failed to flush the output: {}", e);
+        }
 
-        Ritual::finished(ritual).await;
+        ritual.report(start.elapsed())
+    }
+}
 
-        // watchdog useless now
-        watchdog.abort();
+/// Aborts everything spawned for a ritual when dropped.
+struct Teardown {
+    ritual: Arc<Ritual>,
+    /// The watchdog, the message handler and the inspector.
+    background: Vec<tokio::task::AbortHandle>,
+}
 
-        // Messages are no longer needed.
-        // Necessary since message does not exit on its own.
-        message_handler.abort();
+impl Drop for Teardown {
+    fn drop(&mut self) {
+        for handle in &self.background {
+            handle.abort();
+        }
+        self.ritual.hauntings.retain(|_, timer| {
+            timer.abort();
+            false
+        });
+        self.ritual.abort_spirits();
     }
 }
 
 pub struct Ritual {
     /// The global state. Reference shared with the [`Spirit`]s.
     state: Arc<State>,
-    /// Collection of `Future`s that are associated with an entity.
-    /// A future completes when the corresponding entity is finished,
-    /// i.e. the Tokio task finishes.
-    /// [`Abortable`] provides a way to abort the computation.
-    tasks: RwLock<FuturesUnordered<Abortable<JoinHandle<()>>>>,
-    /// [`AbortHandles`] for aborting the computations.
-    abort_handles: RwLock<Vec<AbortHandle>>,
-    /// A candle is lit for every copy of an entity. This is used to count
-    /// how many copies of an entity are alive.
-    /// The `Ritual` is finished if all candles go out and the program can be killed.
-    candles: DashSet<Candle>,
-    /// Sender of an unbounded channel. To be distibuted to the entities.
-    sender: UnboundedSender<Message>,
-    /// Receiver of an unbounded channel. To be kept to receive messages from entities.
-    receiver: Mutex<UnboundedReceiver<Message>>,
+    /// The summoned spirits, for [`Ritual::finished`] to supervise them. Never locked across
+    /// an `await`.
+    running: std::sync::Mutex<JoinSet<Passing>>,
+    /// Notified whenever a spirit was summoned, a message was handled or a creature stopped
+    /// haunting the ritual.
+    summoned: Notify,
+    /// Sender of a bounded channel, see [`RitualConfig::message_capacity`]. To be distibuted to
+    /// the entities.
+    sender: Sender<Message>,
+    /// Receiver of a bounded channel. To be kept to receive messages from entities.
+    receiver: Mutex<Receiver<Message>>,
+    /// The settings of the ritual. Reference shared with the [`Spirit`]s.
+    config: Arc<RitualConfig>,
+    /// The creatures listed in the scroll by id, in the order of their definition. Shared with
+    /// the [`Spirit`]s summoned from them.
+    creatures: IndexMap<NameId, Arc<Entity>>,
+    /// Timers of the creatures that haunt the ritual. See [`Entity::haunt`].
+    hauntings: DashMap<NameId, tokio::task::AbortHandle>,
+    /// The number of spirits summoned so far, including copies.
+    spirits: AtomicUsize,
+    /// Why the ritual ended, if it was ended early.
+    termination: OnceLock<Termination>,
+    /// Where spirits coming and going are published, if anyone subscribed.
+    events: Option<broadcast::Sender<RitualEvent>>,
 }
 
-impl<'a: 'static> Ritual {
+/// What a spirit leaves behind once it finished: its name, and the payload of the panic that
+/// ended it, if any.
+type Passing = (SmolStr, Result<(), Box<dyn Any + Send>>);
+
+impl Ritual {
     /// Prepare the ritual and summon any of the listed creatures.
-    async fn new(entities: &'a EntityList) -> Arc<Ritual> {
-        let (tx, rx) = mpsc::unbounded_channel();
+    async fn new(
+        scroll: Scroll,
+        config: RitualConfig,
+        events: Option<broadcast::Sender<RitualEvent>>,
+    ) -> Arc<Ritual> {
+        let (tx, rx) = mpsc::channel(config.message_limit());
+        let entities = scroll.creatures();
+        let mut state = State::from(entities.values());
+        state.set_history(config.history_depth());
+        state.set_covens(scroll.covens().clone());
+        if let Some(seed) = config.rng_seed() {
+            info!("Seeding the ritual with {}.", seed);
+            state.set_seed(seed);
+        }
+        for (name, spirit) in config.bound_spirits() {
+            state.bind(name, spirit.clone());
+        }
+        let creatures = entities
+            .iter()
+            .map(|(name, creature)| (state.id(name).unwrap(), Arc::new(creature.clone())))
+            .collect();
         let ritual = Arc::new(Ritual {
-            state: Arc::new(State::from(entities.values())),
-            tasks: RwLock::new(FuturesUnordered::new()),
-            abort_handles: RwLock::new(Vec::new()),
-            candles: DashSet::new(),
+            state: Arc::new(state),
+            running: std::sync::Mutex::new(JoinSet::new()),
+            summoned: Notify::new(),
             sender: tx,
             receiver: Mutex::new(rx),
+            config: Arc::new(config),
+            creatures,
+            hauntings: DashMap::new(),
+            spirits: AtomicUsize::new(0),
+            termination: OnceLock::new(),
+            events,
         });
 
         debug!("{:?}", ritual.state);
 
-        for creature in entities.values() {
-            Self::summon(Arc::clone(&ritual), creature).await;
+        // Summon in the order of the scroll, higher ranks first.
+        let mut creatures: Vec<_> = ritual
+            .creatures
+            .iter()
+            .map(|(&id, creature)| (id, Arc::clone(creature)))
+            .collect();
+        creatures.sort_by_key(|(_, creature)| Reverse(creature.rank()));
+        for (id, creature) in creatures {
+            Self::summon(Arc::clone(&ritual), id, creature).await;
         }
 
         ritual
     }
 
+    /// Serve inspectors on the given port in the background.
+    #[cfg(feature = "inspect")]
+    fn inspect(self: Arc<Self>, port: u16) -> Option<JoinHandle<()>> {
+        Some(tokio::spawn(inspect::serve(self, port)))
+    }
+
+    #[cfg(not(feature = "inspect"))]
+    fn inspect(self: Arc<Self>, port: u16) -> Option<JoinHandle<()>> {
+        warn!(
+            "Cannot inspect the ritual on port {}: compiled without the inspect feature",
+            port
+        );
+        None
+    }
+
+    /// Return the creature of the given id. Ends the ritual with an error if the scroll has no
+    /// such creature, e.g. since the id belongs to a bound spirit.
+    async fn creature(&self, id: NameId) -> Option<Arc<Entity>> {
+        let creature = self.creatures.get(&id).cloned();
+        if creature.is_none() {
+            let name = self.state.name(id).clone();
+            self.fail(RuntimeError::NotSummonable(name)).await;
+        }
+        creature
+    }
+
+    /// Act on a message sent by a spirit.
+    async fn handle(self: Arc<Self>, message: Message) {
+        match message {
+            Message::Animate(id) => {
+                let Some(creature) = self.creature(id).await else {
+                    return;
+                };
+                if creature.species().responds_like(Species::Zombie) {
+                    self.wake(id, creature).await;
+                } else {
+                    let name = creature.name();
+                    let species = creature.species();
+                    self.warn(Warning::AnimateOnNonZombie { name, species })
+                        .await;
+                }
+            }
+            Message::Disturb(id) => {
+                let Some(creature) = self.creature(id).await else {
+                    return;
+                };
+                if creature.species().responds_like(Species::Ghost) {
+                    self.wake(id, creature).await;
+                } else {
+                    let name = creature.name();
+                    let species = creature.species();
+                    self.warn(Warning::DisturbOnNonGhost { name, species })
+                        .await;
+                }
+            }
+            Message::Invoke(id, harvest) => {
+                let Some(creature) = self.creature(id).await else {
+                    return;
+                };
+                self.summon_harvested(id, creature, None, harvest).await;
+            }
+            Message::Call(id, task, argument) => {
+                let Some(creature) = self.creature(id).await else {
+                    return;
+                };
+                self.summon_harvested(id, creature, Some((task, argument)), None)
+                    .await;
+            }
+            Message::Broadcast(species) => {
+                let creatures: Vec<(NameId, Arc<Entity>)> = self
+                    .creatures
+                    .iter()
+                    .filter(|(_, creature)| creature.species().responds_like(species))
+                    .map(|(&id, creature)| (id, Arc::clone(creature)))
+                    .collect();
+                for (id, creature) in creatures {
+                    Arc::clone(&self).wake(id, creature).await;
+                    // Reanimating active creatures may end the ritual.
+                    if self.termination.get().is_some() {
+                        return;
+                    }
+                }
+            }
+            Message::Fail => self.abort(Termination::Failed).await,
+        }
+    }
+
     /// Summon a creature in the [`Ritual`].
-    async fn summon(self: Arc<Self>, creature: &'a Entity) {
+    async fn summon(self: Arc<Self>, id: NameId, creature: Arc<Entity>) {
+        if let Some(period) = creature.haunt() {
+            Arc::clone(&self).haunt(id, Arc::clone(&creature), period);
+        }
+        self.summon_harvested(id, creature, None, None).await
+    }
+
+    /// Wake up a creature that was animated or disturbed. Wraiths respond to their queue of
+    /// disturbances, everyone else is reanimated.
+    async fn wake(self: Arc<Self>, id: NameId, creature: Arc<Entity>) {
+        if creature.species() == Species::Wraith {
+            self.state.disturb(id);
+        } else {
+            self.reanimate(id, creature).await;
+        }
+    }
+
+    /// Summon a creature that was animated or disturbed, following the reanimation policy if
+    /// it is active already.
+    async fn reanimate(self: Arc<Self>, id: NameId, creature: Arc<Entity>) {
+        let name = creature.name();
+        let active = self.state.spirit(id).active();
+        if active {
+            let policy = self.config.reanimation_policy();
+            debug!("Reanimating active creature {} ({:?})", name, policy);
+            match policy {
+                Reanimation::Spawn => {
+                    self.warn(Warning::ReanimatedActive {
+                        name,
+                        spawned: true,
+                    })
+                    .await;
+                    // Warnings may end the ritual.
+                    if self.termination.get().is_some() {
+                        return;
+                    }
+                }
+                Reanimation::Ignore => {
+                    self.warn(Warning::ReanimatedActive {
+                        name,
+                        spawned: false,
+                    })
+                    .await;
+                    return;
+                }
+                Reanimation::Error => {
+                    self.fail(RuntimeError::Reanimated(name)).await;
+                    return;
+                }
+            }
+        } else if creature.species() == Species::Lich {
+            // Banished liches rise again, unlike anyone else. A lich that was never active still
+            // has a spirit waiting for that, which carries on instead of a new one.
+            debug!("Reactivating lich {}", name);
+            self.state.reactivate(id);
+            if self.state.lingers(id) {
+                return;
+            }
+        }
+        self.summon(id, creature).await
+    }
+
+    /// Summon the creature again every period for as long as it stays active. Does nothing if
+    /// the creature haunts the ritual already.
+    fn haunt(self: Arc<Self>, id: NameId, creature: Arc<Entity>, period: Duration) {
+        let Entry::Vacant(entry) = self.hauntings.entry(id) else {
+            return;
+        };
+        let ritual = Arc::clone(&self);
+        let timer = tokio::spawn(async move {
+            let mut interval = time::interval_at(time::Instant::now() + period, period);
+            loop {
+                interval.tick().await;
+                let active = ritual.state.spirit(id).active();
+                if !active {
+                    debug!("{} stopped haunting the ritual", creature.name());
+                    break;
+                }
+                Arc::clone(&ritual)
+                    .summon_harvested(id, Arc::clone(&creature), None, None)
+                    .await;
+            }
+            ritual.hauntings.remove(&id);
+            ritual.summoned.notify_one();
+        });
+        entry.insert(timer.abort_handle());
+    }
+
+    /// Summon a creature in the [`Ritual`]. If a task and an argument are given, the spirit
+    /// performs only that task. Once the spirit finished, its memory is sent to `harvest`, if
+    /// given. `harvest` is dropped without a value if the creature can't be summoned.
+    async fn summon_harvested(
+        self: Arc<Self>,
+        id: NameId,
+        creature: Arc<Entity>,
+        call: Option<(SmolStr, Value)>,
+        harvest: Option<oneshot::Sender<Value>>,
+    ) {
+        let count = self.spirits.fetch_add(1, Ordering::Relaxed);
+        if self.config.spirit_limit().is_some_and(|max| count >= max) {
+            self.spirits.fetch_sub(1, Ordering::Relaxed);
+            warn!(
+                "Not summoning {}: too many spirits in the ritual.",
+                creature.name()
+            );
+            return;
+        }
+
         let spirit = Spirit::summon(
+            id,
+            self.state.handle(id),
             creature.name(),
-            creature,
-            UnboundedSender::clone(&self.sender),
+            Arc::clone(&creature),
+            Sender::clone(&self.sender),
+            Arc::clone(&self.config),
+            call,
         );
-        // light a candle
-        let candle = Arc::new(creature.name());
-        self.candles.insert(Arc::clone(&candle));
-
-        // handle for killing the entity
-        let (abort_handle, abort_reg) = AbortHandle::new_pair();
-        self.abort_handles.write().await.push(abort_handle);
+        // light a candle that burns until the spirit is finished
+        let candle = self.state.light(id);
+        let departure = self.events.as_ref().map(|events| {
+            // Nobody may be listening anymore, which is fine.
+            let _ = events.send(RitualEvent::SpiritSummoned(creature.name()));
+            Departure {
+                name: creature.name(),
+                events: events.clone(),
+            }
+        });
 
-        // spawn the task and create corresponding future
+        // spawn the task, catching any panic to report it once the spirit is supervised
         let state = Arc::clone(&self.state);
-        let join_handle = tokio::spawn(spirit.unleash(state, candle));
-        let future = Abortable::new(join_handle, abort_reg);
-        self.tasks.read().await.push(future); // TODO Potential dead-lock with (1)
+        let name = creature.name();
+        let span = info_span!("spirit", entity = %name);
+        let spirit = AssertUnwindSafe(
+            async move {
+                let _candle = candle;
+                let _departure = departure;
+                let _present = state.enter();
+                spirit.unleash(Arc::clone(&state)).await;
+                if let Some(harvest) = harvest {
+                    let memory = state.spirit(id).memory().clone();
+                    // The invoker may be gone already, e.g. after it was banished.
+                    let _ = harvest.send(memory);
+                }
+            }
+            .instrument(span),
+        )
+        .catch_unwind()
+        .map(|result| (name, result));
+        let abort_handle = {
+            let mut running = self.running();
+            // Spirits summoned while the ritual is aborted would outlive it.
+            if self.termination.get().is_some() {
+                return;
+            }
+            running.spawn(spirit)
+        };
+        self.state.track(id, abort_handle);
+        self.summoned.notify_one();
     }
 
     /// Poll the watchdog
     async fn watchdog(self: Arc<Self>) {
-        if self.state.knowledge().iter().all(|c| {
-            !c.value().active() || Arc::strong_count(&self.candles.get(c.key()).unwrap()) <= 1
-        }) {
+        if let Some(stuck) = self.state.deadlocked() {
+            let error = RuntimeError::Deadlock(stuck);
+            error!("Watchdog triggered! Aborting: {}", error);
+            self.state.fail(error);
+            self.abort(Termination::Deadlock).await;
+            return;
+        }
+
+        // Messages on their way may still summon or wake up spirits.
+        if self.state.unhandled() > 0 {
+            return;
+        }
+
+        // Without any spirits left, the ritual is about to finish on its own.
+        if self.state.forsaken() {
             warn!("Watchdog triggered! Aborting: only inactive tasks left.");
-            for handle in self.abort_handles.read().await.iter() {
-                handle.abort()
+            self.abort(Termination::Watchdog).await;
+            return;
+        }
+
+        // Haunting creatures may still summon spirits that disturb the waiting ones.
+        if self.hauntings.is_empty() && self.state.idle() {
+            // Wraiths wait for disturbances forever, even once they did everything they should.
+            if self.state.settled() {
+                info!("Only wraiths waiting for disturbances are left, finishing the ritual.");
+                self.abort(Termination::Finished).await;
+            } else {
+                warn!("Watchdog triggered! Aborting: every spirit waits for something.");
+                self.abort(Termination::Watchdog).await;
             }
         }
     }
 
+    /// Emit the warning, or end the ritual with it if warnings are treated as errors.
+    async fn warn(&self, warning: Warning) {
+        warn!("{}", warning);
+        for hook in self.config.hooks() {
+            hook.on_warning(&warning);
+        }
+        self.state.warn(warning.clone());
+        if self.config.treats_warnings_as_errors() {
+            self.fail(RuntimeError::Warning(warning)).await;
+        }
+    }
+
+    /// End the ritual with the error.
+    async fn fail(&self, error: RuntimeError) {
+        error!("Spirit failed! Aborting: {}", error);
+        self.state.fail(error);
+        self.abort(Termination::Failed).await;
+    }
+
+    /// Abort all spirits, ending the ritual for the given reason.
+    async fn abort(&self, reason: Termination) {
+        let _ = self.termination.set(reason);
+        self.hauntings.retain(|_, timer| {
+            timer.abort();
+            false
+        });
+        self.abort_spirits();
+    }
+
+    /// Abort every spirit that is still running.
+    fn abort_spirits(&self) {
+        self.running().abort_all();
+    }
+
+    /// Summarize the ritual after it ended.
+    fn report(&self, runtime: Duration) -> RitualReport {
+        RitualReport {
+            state: self.state.snapshot(),
+            state_history: self.state.snapshots(),
+            spirits: self.spirits.load(Ordering::Relaxed),
+            runtime,
+            // Spirits may fail right before the end, before the ritual could abort.
+            termination: self.termination.get().copied().unwrap_or_else(|| {
+                if self.state.error().is_some() {
+                    Termination::Failed
+                } else {
+                    Termination::Finished
+                }
+            }),
+            error: self.state.error().cloned(),
+            warnings: self.state.warnings(),
+            retries: self.state.retries(),
+        }
+    }
+
     async fn received(self: Arc<Self>) -> Option<Message> {
         self.receiver.lock().await.recv().await
     }
 
-    /// Use the returned `Future` to `await` the end of the ritual.
+    /// Use the returned `Future` to `await` the end of the ritual. Supervises the summoned
+    /// spirits until all of them finished, and nothing can summon any more.
     async fn finished(self: Arc<Self>) {
-        // iterate until a None appears, all tasks are finished then
-        while let Some(_) = self.tasks.write().await.next().await {} // TODO Potential dead-lock (1)
+        loop {
+            // Haunting creatures are summoned again later on, and messages may summon more.
+            // Look before the spirits, since handling a message summons first.
+            let quiet = self.hauntings.is_empty() && self.state.unhandled() == 0;
+            if quiet && self.running().is_empty() {
+                break;
+            }
+            let joined = future::poll_fn(|context| self.running().poll_join_next(context));
+            tokio::select! {
+                Some(passing) = joined => self.pass(passing).await,
+                _ = self.summoned.notified() => {}
+            }
+        }
+    }
+
+    /// Lock the running spirits. The guard must not be held across an `await`.
+    fn running(&self) -> std::sync::MutexGuard<'_, JoinSet<Passing>> {
+        self.running.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Look after a spirit that finished. Ends the ritual with an error if the spirit panicked.
+    async fn pass(&self, passing: Result<Passing, JoinError>) {
+        match passing {
+            Ok((_, Ok(()))) => {}
+            Ok((spirit, Err(payload))) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| String::from("unknown cause"));
+                self.fail(RuntimeError::Panicked { spirit, message }).await;
+            }
+            // Spirits are only cancelled when they are banished or the ritual is aborted.
+            Err(error) => debug!("Spirit ended early: {}", error),
+        }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub enum Message {
-    Animate(SmolStr),
-    Disturb(SmolStr),
-    Invoke(SmolStr),
-    Say(Value),
+    Animate(NameId),
+    Disturb(NameId),
+    /// Animate or disturb every creature that responds like the given species, depending on what
+    /// the species responds to. See [`Species::responds_like`].
+    Broadcast(Species),
+    /// Invoke a new copy of the entity. The memory of the copy is sent back over the channel
+    /// once it finished, if one is given.
+    Invoke(NameId, Option<oneshot::Sender<Value>>),
+    /// Invoke a new copy of the entity that performs only the named task, with the given
+    /// argument.
+    Call(NameId, SmolStr, Value),
+    /// A spirit failed with the error recorded in the `State`. Ends the ritual.
+    Fail,
+}
+
+impl Message {
+    /// Describe the message like the statement that sent it.
+    fn describe(&self, state: &State) -> String {
+        match self {
+            Message::Animate(id) => format!("animate {}", state.name(*id)),
+            Message::Disturb(id) => format!("disturb {}", state.name(*id)),
+            Message::Broadcast(species) => format!("broadcast to every {}", species),
+            Message::Invoke(id, None) => format!("invoke {}", state.name(*id)),
+            Message::Invoke(id, Some(_)) => format!("invoke {} harvest", state.name(*id)),
+            Message::Call(id, task, _) => format!("invoke {} {}", state.name(*id), task),
+            Message::Fail => String::from("failure"),
+        }
+    }
 }
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+use smol_str::SmolStr;
+
+/// A name interned in the [`Names`] of a ritual. Cheap to copy, hash and compare, unlike the name
+/// itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NameId(u32);
+
+impl NameId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// The names of the entities and bound spirits of a ritual, each with an id of its own. Ids are
+/// handed out in order, starting at zero, so they can index into other collections.
+#[derive(Debug, Default)]
+pub struct Names {
+    ids: HashMap<SmolStr, NameId>,
+    names: Vec<SmolStr>,
+}
+
+impl Names {
+    /// Return the id of the name, interning the name first if it is new.
+    pub fn intern(&mut self, name: &SmolStr) -> NameId {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = NameId(u32::try_from(self.names.len()).expect("too many names in the ritual"));
+        self.ids.insert(name.clone(), id);
+        self.names.push(name.clone());
+        id
+    }
+
+    /// Return the id of the name, if it was interned.
+    pub fn id(&self, name: &str) -> Option<NameId> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: NameId) -> &SmolStr {
+        &self.names[id.index()]
+    }
+
+    /// Return every interned name with its id, in the order of interning.
+    pub fn iter(&self) -> impl Iterator<Item = (NameId, &SmolStr)> {
+        self.names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| (NameId(index as u32), name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intern_names() {
+        let mut names = Names::default();
+        let peter = names.intern(&SmolStr::from("Peter"));
+        let lisa = names.intern(&SmolStr::from("Lisa"));
+        assert_ne!(peter, lisa);
+        assert_eq!(names.intern(&SmolStr::from("Peter")), peter);
+        assert_eq!(names.id("Lisa"), Some(lisa));
+        assert_eq!(names.id("Jay"), None);
+        assert_eq!(names.name(peter), "Peter");
+        assert_eq!(
+            names
+                .iter()
+                .map(|(_, name)| name.as_str())
+                .collect::<Vec<_>>(),
+            ["Peter", "Lisa"]
+        );
+    }
+}
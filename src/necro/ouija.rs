@@ -0,0 +1,55 @@
+//! Communication with spirits beyond the ritual over TCP.
+//!
+//! Values are sent as their display form, terminated by a newline. Received text is remembered
+//! as an integer if it looks like one, and as a string otherwise.
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+
+use malachite::Integer;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+
+use crate::value::Value;
+
+/// Keeps the listeners of all channels opened during the ritual.
+#[derive(Debug, Default)]
+pub struct Ouija {
+    listeners: Mutex<HashMap<u16, Arc<TcpListener>>>,
+}
+
+impl Ouija {
+    /// Wait for a single message on the given port.
+    ///
+    /// The port is opened on first use and stays open for the rest of the ritual.
+    pub async fn channel(&self, port: u16) -> io::Result<Value> {
+        let listener = {
+            let mut listeners = self.listeners.lock().await;
+            match listeners.get(&port) {
+                Some(listener) => Arc::clone(listener),
+                None => {
+                    let listener = Arc::new(TcpListener::bind(("0.0.0.0", port)).await?);
+                    listeners.insert(port, Arc::clone(&listener));
+                    listener
+                }
+            }
+        };
+
+        let (stream, _) = listener.accept().await?;
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).await?;
+        let line = line.trim_end_matches(['\r', '\n']);
+        Ok(match line.parse::<Integer>() {
+            Ok(i) => Value::Integer(i),
+            Err(_) => Value::from(line),
+        })
+    }
+
+    /// Send a single message to the given address.
+    pub async fn whisper(address: &str, value: &Value) -> io::Result<()> {
+        let mut stream = TcpStream::connect(address).await?;
+        stream.write_all(format!("{}\n", value).as_bytes()).await?;
+        stream.shutdown().await
+    }
+}
@@ -0,0 +1,63 @@
+use std::fmt::{Debug, Display, Formatter};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+/// Where the words of `say` end up. Writes to the standard output by default.
+#[derive(Clone)]
+pub(crate) struct Output(Arc<Mutex<dyn Write + Send>>);
+
+impl Output {
+    pub(crate) fn new(sink: impl Write + Send + 'static) -> Output {
+        Output(Arc::new(Mutex::new(sink)))
+    }
+
+    /// Write the value on a line of its own.
+    pub(crate) fn say(&self, value: &dyn Display) -> io::Result<()> {
+        let mut sink = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        writeln!(sink, "{}", value)?;
+        sink.flush()
+    }
+
+    /// Flush anything the sink still buffers.
+    pub(crate) fn flush(&self) -> io::Result<()> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).flush()
+    }
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Output::new(io::stdout())
+    }
+}
+
+impl Debug for Output {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "Output")
+    }
+}
+
+/// An in-memory sink for the output of a ritual. Clones share the same buffer.
+#[derive(Debug, Clone, Default)]
+pub struct OutputBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl OutputBuffer {
+    pub fn new() -> OutputBuffer {
+        OutputBuffer::default()
+    }
+
+    /// Return everything written so far.
+    pub fn contents(&self) -> String {
+        let buffer = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        String::from_utf8_lossy(&buffer).into_owned()
+    }
+}
+
+impl Write for OutputBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter, Result};
+use std::time::Duration;
+
+use smol_str::SmolStr;
+
+use super::{RuntimeError, Warning};
+use crate::value::Value;
+
+/// The outcome of a ritual, returned by [`Necromancer::initiate`](super::Necromancer::initiate).
+#[derive(Debug, Clone)]
+pub struct RitualReport {
+    pub(crate) state: BTreeMap<SmolStr, (Value, bool)>,
+    pub(crate) state_history: Vec<BTreeMap<SmolStr, (Value, bool)>>,
+    pub(crate) spirits: usize,
+    pub(crate) runtime: Duration,
+    pub(crate) termination: Termination,
+    pub(crate) error: Option<RuntimeError>,
+    pub(crate) warnings: Vec<Warning>,
+    pub(crate) retries: BTreeMap<SmolStr, usize>,
+}
+
+impl RitualReport {
+    /// The remembered value and the active flag of every entity when the ritual ended.
+    pub fn final_state(&self) -> &BTreeMap<SmolStr, (Value, bool)> {
+        &self.state
+    }
+
+    /// Snapshots of the state taken during the ritual, in the same shape as
+    /// [`final_state`](RitualReport::final_state). Empty unless enabled with
+    /// [`RitualConfig::state_history`](super::RitualConfig::state_history).
+    pub fn state_history(&self) -> &[BTreeMap<SmolStr, (Value, bool)>] {
+        &self.state_history
+    }
+
+    /// The value that the named entity remembered when the ritual ended.
+    pub fn memory(&self, name: &str) -> Option<&Value> {
+        self.state.get(name).map(|(memory, _)| memory)
+    }
+
+    /// The number of spirits summoned during the ritual, including copies.
+    pub fn spirits(&self) -> usize {
+        self.spirits
+    }
+
+    /// How long the ritual took.
+    pub fn runtime(&self) -> Duration {
+        self.runtime
+    }
+
+    /// Why the ritual ended.
+    pub fn termination(&self) -> Termination {
+        self.termination
+    }
+
+    /// The error that ended the ritual, if any.
+    pub fn error(&self) -> Option<&RuntimeError> {
+        self.error.as_ref()
+    }
+
+    /// The warnings emitted during the ritual, in order.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// How often every revenant retried a task that stumbled. Revenants that never retried a task
+    /// are not listed.
+    pub fn retries(&self) -> &BTreeMap<SmolStr, usize> {
+        &self.retries
+    }
+
+    /// Describe the outcome of the ritual as a JSON object on a single line. Memories are written
+    /// as strings, without cursing corrupted values.
+    pub fn to_json(&self) -> String {
+        let state: Vec<String> = self
+            .state
+            .iter()
+            .map(|(name, (memory, active))| {
+                format!(
+                    "{}:{{\"memory\":{},\"active\":{}}}",
+                    json_string(name),
+                    json_string(&memory.uncursed().to_string()),
+                    active
+                )
+            })
+            .collect();
+        let warnings: Vec<String> = self
+            .warnings
+            .iter()
+            .map(|warning| json_string(&warning.to_string()))
+            .collect();
+        let retries: Vec<String> = self
+            .retries
+            .iter()
+            .map(|(name, retries)| format!("{}:{}", json_string(name), retries))
+            .collect();
+        let error = self.error.as_ref().map_or(String::from("null"), |error| {
+            json_string(&error.to_string())
+        });
+        format!(
+            "{{\"state\":{{{}}},\"spirits\":{},\"runtime_ms\":{},\"termination\":\"{}\",\"error\":{},\"warnings\":[{}],\"retries\":{{{}}}}}",
+            state.join(","),
+            self.spirits,
+            self.runtime.as_millis(),
+            self.termination,
+            error,
+            warnings.join(","),
+            retries.join(",")
+        )
+    }
+}
+
+/// Describe the outcome of the ritual for humans, one fact per line. Memories are written without
+/// cursing corrupted values.
+impl Display for RitualReport {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        writeln!(fmt, "Termination: {}", self.termination)?;
+        if let Some(error) = &self.error {
+            writeln!(fmt, "Error:       {}", error)?;
+        }
+        writeln!(fmt, "Runtime:     {} ms", self.runtime.as_millis())?;
+        writeln!(fmt, "Spirits:     {}", self.spirits)?;
+        writeln!(fmt, "Warnings:    {}", self.warnings.len())?;
+        if !self.retries.is_empty() {
+            writeln!(fmt, "Retries:     {}", self.retries.values().sum::<usize>())?;
+        }
+        writeln!(fmt, "Memories:")?;
+        let width = self.state.keys().map(|name| name.len()).max().unwrap_or(0);
+        for (name, (memory, active)) in &self.state {
+            let activity = if *active { "active" } else { "inactive" };
+            writeln!(
+                fmt,
+                "  {:width$}  {:8}  {}",
+                name,
+                activity,
+                memory.uncursed(),
+                width = width
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Quote the text as a JSON string.
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// The reason why a ritual ended.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Termination {
+    /// All spirits finished their tasks. Wraiths count as finished once nobody is left to
+    /// disturb them.
+    Finished,
+    /// The watchdog aborted the ritual, since only inactive entities, or spirits waiting for
+    /// something that nobody is left to do, were left.
+    Watchdog,
+    /// The ritual took longer than allowed.
+    Timeout,
+    /// The watchdog aborted the ritual, since all spirits waited to be reactivated.
+    /// See [`RitualReport::error`] for the entities involved.
+    Deadlock,
+    /// The ritual was ended from the outside through an [`Interrupt`](super::Interrupt).
+    Interrupted,
+    /// A spirit failed with an error. See [`RitualReport::error`].
+    Failed,
+}
+
+impl Display for Termination {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        match self {
+            Termination::Finished => write!(fmt, "finished"),
+            Termination::Watchdog => write!(fmt, "watchdog"),
+            Termination::Timeout => write!(fmt, "timeout"),
+            Termination::Deadlock => write!(fmt, "deadlock"),
+            Termination::Interrupted => write!(fmt, "interrupted"),
+            Termination::Failed => write!(fmt, "failed"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_as_json() {
+        let report = RitualReport {
+            state: BTreeMap::from([
+                (SmolStr::from("Lisa"), (Value::from("\"boo\"\n"), false)),
+                (
+                    SmolStr::from("Peter"),
+                    (Value::Infernal(String::from("x")), true),
+                ),
+            ]),
+            state_history: Vec::new(),
+            spirits: 2,
+            runtime: Duration::from_millis(42),
+            termination: Termination::Finished,
+            error: None,
+            warnings: Vec::new(),
+            retries: BTreeMap::from([(SmolStr::from("Rex"), 2)]),
+        };
+        assert_eq!(
+            report.to_json(),
+            "{\"state\":{\"Lisa\":{\"memory\":\"\\\"boo\\\"\\n\",\"active\":false},\
+             \"Peter\":{\"memory\":\"<infernal:x>\",\"active\":true}},\"spirits\":2,\
+             \"runtime_ms\":42,\"termination\":\"finished\",\"error\":null,\"warnings\":[],\"retries\":{\"Rex\":2}}"
+        );
+        assert!(report
+            .to_string()
+            .contains("  Peter  active    <infernal:x>\n"));
+        assert!(report.to_string().contains("Retries:     2\n"));
+    }
+}
@@ -1,45 +1,539 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+
 use dashmap::DashMap;
 use smol_str::SmolStr;
 use tokio::sync::Notify;
+use tokio::task::AbortHandle;
+use tracing::warn;
 
+use super::config::BoundSpirit;
+use super::name::{NameId, Names};
+#[cfg(feature = "ouija")]
+use super::ouija::Ouija;
+use super::warning::Warning;
+use super::RuntimeError;
 use crate::scroll::entity::Entity;
+use crate::scroll::CovenList;
 use crate::value::Value;
 
 #[derive(Debug)]
 pub struct State {
-    knowledge: DashMap<SmolStr, SpiritState>,
+    /// The names of all entities and bound spirits. Everything else refers to them by id.
+    names: Names,
+    /// The memory and the activity of every entity and bound spirit, indexed by id.
+    knowledge: Vec<SpiritHandle>,
+    /// The creatures listed in the scroll, for performing tasks of other entities.
+    creatures: HashMap<NameId, Entity>,
+    /// The entities that aliases of the creatures stand for.
+    aliases: HashMap<SmolStr, NameId>,
+    /// The members of every coven of the scroll.
+    covens: CovenList,
+    /// Spirits bound to host functions. Their memory is kept in `knowledge`, too.
+    bound: HashMap<NameId, BoundSpirit>,
+    /// Handles for cancelling the running spirits of every entity.
+    spirits: DashMap<NameId, Vec<AbortHandle>>,
+    /// The number of spirits currently running.
+    present: AtomicUsize,
+    /// The number of spirits of every entity that are alive, i.e. summoned and not finished yet.
+    /// Entities without any living spirits are not listed.
+    alive: DashMap<NameId, usize>,
+    /// The number of messages sent to the ritual that it did not handle yet.
+    unhandled: AtomicUsize,
+    /// The number of spirits waiting for their entity to become active.
+    waiting: AtomicUsize,
+    /// The number of waiting spirits that are wraiths waiting for a disturbance.
+    listening: AtomicUsize,
+    /// The number of spirits of every entity that were banished while performing their tasks and
+    /// wait to be reactivated.
+    stuck: DashMap<NameId, usize>,
+    /// The number of tasks that every lich completed in its current pass through its tasks.
+    /// Liches without a pass in progress are not listed.
+    checkpoints: DashMap<NameId, usize>,
+    /// The disturbances that every wraith has yet to respond to.
+    disturbances: DashMap<NameId, usize>,
+    /// How often every revenant retried a task that stumbled. Revenants that never retried a
+    /// task are not listed.
+    retries: DashMap<NameId, usize>,
     notifier: Notify,
+    /// How many past values every entity recalls.
+    history: usize,
+    /// The highest rank of all creatures in the scroll.
+    highest_rank: u32,
+    /// The source of all random decisions of the spirits.
+    rng: Mutex<fastrand::Rng>,
+    /// The number of statements executed so far, for taking snapshots.
+    executed: AtomicUsize,
+    /// Snapshots of the state taken during the ritual, in order. See [`State::observe`].
+    snapshots: Mutex<Vec<BTreeMap<SmolStr, (Value, bool)>>>,
+    /// The warnings emitted so far, in order.
+    warnings: Mutex<Vec<Warning>>,
+    /// The error that ended the ritual, if any.
+    error: OnceLock<RuntimeError>,
+    #[cfg(feature = "ouija")]
+    ouija: Ouija,
 }
 
 impl State {
     fn new() -> State {
         State {
-            knowledge: DashMap::new(),
+            names: Names::default(),
+            knowledge: Vec::new(),
+            creatures: HashMap::new(),
+            aliases: HashMap::new(),
+            covens: CovenList::new(),
+            bound: HashMap::new(),
+            spirits: DashMap::new(),
+            present: AtomicUsize::new(0),
+            alive: DashMap::new(),
+            unhandled: AtomicUsize::new(0),
+            waiting: AtomicUsize::new(0),
+            listening: AtomicUsize::new(0),
+            stuck: DashMap::new(),
+            checkpoints: DashMap::new(),
+            disturbances: DashMap::new(),
+            retries: DashMap::new(),
             notifier: Notify::new(),
+            history: 0,
+            highest_rank: 0,
+            rng: Mutex::new(fastrand::Rng::new()),
+            executed: AtomicUsize::new(0),
+            snapshots: Mutex::new(Vec::new()),
+            warnings: Mutex::new(Vec::new()),
+            error: OnceLock::new(),
+            #[cfg(feature = "ouija")]
+            ouija: Ouija::default(),
+        }
+    }
+
+    /// Bind a host function to the given name, unless an entity of that name exists already.
+    pub fn bind(&mut self, name: &SmolStr, spirit: BoundSpirit) {
+        if self.names.id(name).is_some() {
+            warn!(
+                "Not binding spirit {}: an entity of that name exists.",
+                name
+            );
+            return;
+        }
+        // Bound spirits are never active, so they don't keep the ritual alive.
+        let id = self.admit(name, SpiritState::new(Value::Void, false));
+        self.bound.insert(id, spirit);
+    }
+
+    /// Intern the name of a new entity or bound spirit, and keep track of its state.
+    fn admit(&mut self, name: &SmolStr, spirit: SpiritState) -> NameId {
+        let id = self.names.intern(name);
+        debug_assert_eq!(id.index(), self.knowledge.len(), "{} admitted twice", name);
+        self.knowledge.push(Arc::new(Mutex::new(spirit)));
+        id
+    }
+
+    /// Let every entity recall the given number of past values.
+    pub fn set_history(&mut self, depth: usize) {
+        self.history = depth;
+    }
+
+    /// Let statements act on the members of the given covens.
+    pub fn set_covens(&mut self, covens: CovenList) {
+        self.covens = covens;
+    }
+
+    /// Make the random decisions of the spirits reproducible.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Mutex::new(fastrand::Rng::with_seed(seed));
+    }
+
+    pub fn rng(&self) -> MutexGuard<'_, fastrand::Rng> {
+        self.rng.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn history(&self) -> usize {
+        self.history
+    }
+
+    /// How many ranks the given rank is below the highest rank in the ritual.
+    pub fn rank_gap(&self, rank: u32) -> u32 {
+        self.highest_rank.saturating_sub(rank)
+    }
+
+    /// Return the id of the entity or bound spirit that the given name or alias refers to, if
+    /// there is one.
+    ///
+    /// Names are resolved in this order: parameters of the running task, in expressions only,
+    /// then the names of entities and bound spirits, then aliases. Tasks are never referred to
+    /// by a bare name, so a task sharing its name with an entity does not hide the entity.
+    pub fn id(&self, name: &str) -> Option<NameId> {
+        self.names
+            .id(name)
+            .or_else(|| self.aliases.get(name).copied())
+    }
+
+    pub fn name(&self, id: NameId) -> &SmolStr {
+        self.names.name(id)
+    }
+
+    /// Return the memory and the activity of the entity or bound spirit.
+    pub fn spirit(&self, id: NameId) -> MutexGuard<'_, SpiritState> {
+        lock(&self.knowledge[id.index()])
+    }
+
+    /// Return a handle to the memory and the activity of the entity or bound spirit, for
+    /// spirits to look at their own state without going through the whole state.
+    pub fn handle(&self, id: NameId) -> SpiritHandle {
+        Arc::clone(&self.knowledge[id.index()])
+    }
+
+    /// Return the members of the named coven, unless the name refers to an entity or a bound
+    /// spirit, or is an alias.
+    pub fn coven(&self, name: &str) -> Option<&[SmolStr]> {
+        if self.id(name).is_some() {
+            return None;
+        }
+        self.covens.get(name).map(Vec::as_slice)
+    }
+
+    /// Return the ids of all creatures listed in the scroll.
+    pub fn creature_ids(&self) -> impl Iterator<Item = NameId> + '_ {
+        self.creatures.keys().copied()
+    }
+
+    /// Return the creature of the given id, as listed in the scroll.
+    pub fn creature(&self, id: NameId) -> Option<&Entity> {
+        self.creatures.get(&id)
+    }
+
+    pub fn bound(&self, id: NameId) -> Option<&BoundSpirit> {
+        self.bound.get(&id)
+    }
+
+    /// Record a warning for the report.
+    pub fn warn(&self, warning: Warning) {
+        self.warnings
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(warning);
+    }
+
+    /// Return the remembered value and the active flag of every entity.
+    pub fn snapshot(&self) -> BTreeMap<SmolStr, (Value, bool)> {
+        self.names
+            .iter()
+            .map(|(id, name)| {
+                let spirit = self.spirit(id);
+                (name.clone(), (spirit.memory().clone(), spirit.active()))
+            })
+            .collect()
+    }
+
+    /// Count an executed statement, and take a snapshot after every `every` statements.
+    pub fn observe(&self, every: usize) {
+        let executed = self.executed.fetch_add(1, Ordering::SeqCst) + 1;
+        if executed.is_multiple_of(every) {
+            let snapshot = self.snapshot();
+            self.snapshots
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(snapshot);
+        }
+    }
+
+    /// Return the snapshots taken so far, in order.
+    pub fn snapshots(&self) -> Vec<BTreeMap<SmolStr, (Value, bool)>> {
+        self.snapshots
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Record the error that ends the ritual. Only the first error is kept.
+    pub fn fail(&self, error: RuntimeError) {
+        let _ = self.error.set(error);
+    }
+
+    pub fn error(&self) -> Option<&RuntimeError> {
+        self.error.get()
+    }
+
+    pub fn warnings(&self) -> Vec<Warning> {
+        self.warnings
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Keep track of a spirit summoned from the entity, so it can be cancelled later.
+    pub fn track(&self, id: NameId, spirit: AbortHandle) {
+        let mut spirits = self.spirits.entry(id).or_default();
+        spirits.retain(|spirit| !spirit.is_finished());
+        spirits.push(spirit);
+    }
+
+    /// Tell whether any spirit summoned from the entity is still running, i.e. neither finished
+    /// nor cancelled.
+    pub fn lingers(&self, id: NameId) -> bool {
+        self.spirits
+            .get(&id)
+            .is_some_and(|spirits| spirits.iter().any(|spirit| !spirit.is_finished()))
+    }
+
+    /// Cancel all running spirits of the entity, including any copies.
+    pub fn cancel(&self, id: NameId) {
+        if let Some((_, spirits)) = self.spirits.remove(&id) {
+            for spirit in spirits {
+                spirit.abort();
+            }
         }
     }
 
-    pub fn knowledge(&self) -> &DashMap<SmolStr, SpiritState> {
-        &self.knowledge
+    /// Return how many tasks the lich completed in its current pass through its tasks.
+    pub fn checkpoint(&self, id: NameId) -> usize {
+        self.checkpoints.get(&id).map_or(0, |completed| *completed)
+    }
+
+    /// Record how many tasks the lich completed in its current pass. Completing all of them
+    /// ends the pass, so the next one starts over.
+    pub fn set_checkpoint(&self, id: NameId, completed: usize, total: usize) {
+        if completed < total {
+            self.checkpoints.insert(id, completed);
+        } else {
+            self.checkpoints.remove(&id);
+        }
+    }
+
+    /// Queue a disturbance for the wraith to respond to.
+    pub fn disturb(&self, id: NameId) {
+        *self.disturbances.entry(id).or_default() += 1;
+        self.notifier.notify_waiters();
+    }
+
+    /// Take the next disturbance of the wraith from the queue, if there is one.
+    pub fn next_disturbance(&self, id: NameId) -> bool {
+        match self.disturbances.get_mut(&id) {
+            Some(mut pending) if *pending > 0 => {
+                *pending -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Count a retry of a task of the revenant.
+    pub fn retry(&self, id: NameId) {
+        *self.retries.entry(id).or_default() += 1;
+    }
+
+    /// Return how often every revenant retried a task.
+    pub fn retries(&self) -> BTreeMap<SmolStr, usize> {
+        self.retries
+            .iter()
+            .map(|entry| (self.name(*entry.key()).clone(), *entry.value()))
+            .collect()
+    }
+
+    /// Make the entity active again, waking up any of its waiting spirits.
+    pub fn reactivate(&self, id: NameId) {
+        *self.spirit(id).active_mut() = true;
+        self.notifier.notify_waiters();
+    }
+
+    /// Count a message as sent to the ritual, until it is [handled](State::handled).
+    pub fn post(&self) {
+        self.unhandled.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Count a message sent to the ritual as handled.
+    pub fn handled(&self) {
+        self.unhandled.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// The number of messages sent to the ritual that it did not handle yet. Spirits may still
+    /// be summoned for them.
+    pub fn unhandled(&self) -> usize {
+        self.unhandled.load(Ordering::SeqCst)
+    }
+
+    /// Count a spirit as running for as long as the returned guard lives.
+    pub fn enter(self: &Arc<Self>) -> Presence {
+        self.present.fetch_add(1, Ordering::SeqCst);
+        Presence(Arc::clone(self))
+    }
+
+    /// Count a spirit of the entity as alive for as long as the returned candle burns.
+    pub fn light(self: &Arc<Self>, id: NameId) -> Candle {
+        *self.alive.entry(id).or_default() += 1;
+        Candle {
+            state: Arc::clone(self),
+            id,
+        }
+    }
+
+    /// Tell whether spirits are alive, but none of them belongs to an active entity. Such spirits
+    /// can't do anything anymore, so the ritual can be ended.
+    pub fn forsaken(&self) -> bool {
+        !self.alive.is_empty()
+            && self
+                .alive
+                .iter()
+                .all(|entry| !self.spirit(*entry.key()).active())
+    }
+
+    /// Count a spirit of the entity as waiting for the entity to become active for as long as
+    /// the returned guard lives. `awake` tells whether the spirit performed anything before,
+    /// i.e. whether it got stuck after being banished instead of never having been active.
+    pub fn wait(&self, id: NameId, awake: bool) -> Waiting<'_> {
+        self.waiting.fetch_add(1, Ordering::SeqCst);
+        if awake {
+            *self.stuck.entry(id).or_default() += 1;
+        }
+        Waiting {
+            state: self,
+            id,
+            awake,
+            listening: false,
+        }
+    }
+
+    /// Count a spirit of the wraith as waiting for a disturbance for as long as the returned
+    /// guard lives.
+    pub fn listen(&self, id: NameId) -> Waiting<'_> {
+        self.waiting.fetch_add(1, Ordering::SeqCst);
+        self.listening.fetch_add(1, Ordering::SeqCst);
+        Waiting {
+            state: self,
+            id,
+            awake: false,
+            listening: true,
+        }
+    }
+
+    /// Tell whether every running spirit waits, either for its entity to become active or for a
+    /// disturbance, and no disturbance is left to respond to. Nothing can happen anymore then.
+    pub fn idle(&self) -> bool {
+        // Spirits are alive as soon as they are summoned, even before they begin running.
+        let alive: usize = self.alive.iter().map(|entry| *entry.value()).sum();
+        alive > 0
+            && self.waiting.load(Ordering::SeqCst) >= alive
+            && self.disturbances.iter().all(|pending| *pending == 0)
+    }
+
+    /// Tell whether every living spirit is a wraith waiting for a disturbance, and no disturbance
+    /// is left to respond to. Wraiths wait like this once they responded to everything, so the
+    /// ritual is done then.
+    pub fn settled(&self) -> bool {
+        let alive: usize = self.alive.iter().map(|entry| *entry.value()).sum();
+        alive > 0
+            && self.listening.load(Ordering::SeqCst) >= alive
+            && self.disturbances.iter().all(|pending| *pending == 0)
+    }
+
+    /// Return the entities whose spirits got stuck, if every running spirit waits for its entity
+    /// to become active and at least one of them got stuck after being banished.
+    ///
+    /// Messages that were not handled yet may still summon spirits that wake up the stuck ones,
+    /// so there is no deadlock while any are on their way.
+    pub fn deadlocked(&self) -> Option<Vec<SmolStr>> {
+        let present = self.present.load(Ordering::SeqCst);
+        if present == 0 || self.waiting.load(Ordering::SeqCst) < present || self.unhandled() > 0 {
+            return None;
+        }
+        let mut stuck: Vec<SmolStr> = self
+            .stuck
+            .iter()
+            .filter(|entry| *entry.value() > 0)
+            .map(|entry| self.name(*entry.key()).clone())
+            .collect();
+        stuck.sort();
+        (!stuck.is_empty()).then_some(stuck)
     }
 
     pub fn notifier(&self) -> &Notify {
         &self.notifier
     }
+
+    #[cfg(feature = "ouija")]
+    pub fn ouija(&self) -> &Ouija {
+        &self.ouija
+    }
+}
+
+/// Marks a running spirit. See [`State::enter`].
+pub struct Presence(Arc<State>);
+
+impl Drop for Presence {
+    fn drop(&mut self) {
+        self.0.present.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Marks a living spirit of an entity. See [`State::light`].
+#[derive(Debug)]
+pub struct Candle {
+    state: Arc<State>,
+    id: NameId,
+}
+
+impl Drop for Candle {
+    fn drop(&mut self) {
+        if let Some(mut count) = self.state.alive.get_mut(&self.id) {
+            *count -= 1;
+        }
+        self.state.alive.remove_if(&self.id, |_, count| *count == 0);
+    }
+}
+
+/// Marks a spirit waiting to be reactivated. See [`State::wait`].
+pub struct Waiting<'s> {
+    state: &'s State,
+    id: NameId,
+    awake: bool,
+    /// Whether the spirit is a wraith waiting for a disturbance. See [`State::listen`].
+    listening: bool,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.state.waiting.fetch_sub(1, Ordering::SeqCst);
+        if self.listening {
+            self.state.listening.fetch_sub(1, Ordering::SeqCst);
+        }
+        if self.awake {
+            if let Some(mut stuck) = self.state.stuck.get_mut(&self.id) {
+                *stuck -= 1;
+            }
+        }
+    }
 }
 
 impl<'a, I: Iterator<Item = &'a Entity>> From<I> for State {
     fn from(creatures: I) -> Self {
-        let state = State::new();
+        let mut state = State::new();
+        let mut aliases = Vec::new();
         for creature in creatures {
-            state
-                .knowledge
-                .insert(creature.name(), SpiritState::from(creature));
+            let id = state.admit(&creature.name(), SpiritState::from(creature));
+            state.creatures.insert(id, creature.clone());
+            state.highest_rank = state.highest_rank.max(creature.rank());
+            aliases.extend(creature.aliases().iter().map(|alias| (alias.clone(), id)));
+        }
+        // Names take precedence over aliases, and earlier aliases over later ones.
+        for (alias, id) in aliases {
+            if state.names.id(&alias).is_none() {
+                state.aliases.entry(alias).or_insert(id);
+            }
         }
         state
     }
 }
 
+/// Shared access to the [`SpiritState`] of an entity. See [`State::handle`].
+pub type SpiritHandle = Arc<Mutex<SpiritState>>;
+
+/// Lock the state behind the handle. A spirit that panicked while holding the lock leaves the
+/// state as it was, which is as good as any.
+pub fn lock(handle: &Mutex<SpiritState>) -> MutexGuard<'_, SpiritState> {
+    handle.lock().unwrap_or_else(|e| e.into_inner())
+}
+
 /// Holds owned data of an entity.
 ///
 /// Is a reduced version of a [`Creature`] that allows mutability,
@@ -51,23 +545,46 @@ impl<'a, I: Iterator<Item = &'a Entity>> From<I> for State {
 pub struct SpiritState {
     memory: Value,
     active: bool,
+    /// Values remembered before the current one, the most recent one first.
+    history: VecDeque<Value>,
 }
 
 impl SpiritState {
     fn new(memory: Value, active: bool) -> SpiritState {
-        SpiritState { memory, active }
+        SpiritState {
+            memory,
+            active,
+            history: VecDeque::new(),
+        }
     }
 
     pub fn memory(&self) -> &Value {
         &self.memory
     }
 
-    pub fn active(&self) -> bool {
-        self.active
+    /// Remember a new value, recalling at most `depth` of the values remembered before.
+    pub fn remember(&mut self, value: Value, depth: usize) {
+        let old = std::mem::replace(&mut self.memory, value);
+        if depth > 0 {
+            self.history.push_front(old);
+            self.history.truncate(depth);
+        }
+    }
+
+    /// Return the n-th value remembered before the current one. `0` is the current value.
+    pub fn recall(&self, n: usize) -> Option<&Value> {
+        match n {
+            0 => Some(&self.memory),
+            n => self.history.get(n - 1),
+        }
     }
 
-    pub fn memory_mut(&mut self) -> &mut Value {
-        &mut self.memory
+    pub fn history(&self) -> &VecDeque<Value> {
+        &self.history
+    }
+
+    pub fn active(&self) -> bool {
+        self.active
     }
 
     pub fn active_mut(&mut self) -> &mut bool {
@@ -80,3 +597,97 @@ impl From<&Entity> for SpiritState {
         SpiritState::new(Value::from(creature.moan()), creature.active())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scroll::entity::Species;
+
+    #[test]
+    fn detect_deadlock() {
+        let peter = Entity::builder("Peter", Species::Zombie).build();
+        let lisa = Entity::builder("Lisa", Species::Zombie).build();
+        let state = Arc::new(State::from([&peter, &lisa].into_iter()));
+        let (peter, lisa) = (state.id("Peter").unwrap(), state.id("Lisa").unwrap());
+        assert_eq!(state.deadlocked(), None);
+
+        let _peter = state.enter();
+        let _lisa = state.enter();
+        let dormant = state.wait(lisa, false);
+        assert_eq!(state.deadlocked(), None);
+
+        let stuck = state.wait(peter, true);
+        assert_eq!(state.deadlocked(), Some(vec![SmolStr::from("Peter")]));
+
+        // A message on its way may summon a spirit that animates Peter again.
+        state.post();
+        assert_eq!(state.deadlocked(), None);
+        state.handled();
+        assert_eq!(state.deadlocked(), Some(vec![SmolStr::from("Peter")]));
+
+        drop(dormant);
+        assert_eq!(state.deadlocked(), None);
+        drop(stuck);
+        let _dormant = state.wait(lisa, false);
+        let _dormant = state.wait(peter, false);
+        assert_eq!(state.deadlocked(), None);
+    }
+
+    #[test]
+    fn forsaken_when_only_inactive_spirits_live() {
+        let peter = Entity::builder("Peter", Species::Zombie).build();
+        let lisa = Entity::builder("Lisa", Species::Ghost).build();
+        let state = Arc::new(State::from([&peter, &lisa].into_iter()));
+        let (peter, lisa) = (state.id("Peter").unwrap(), state.id("Lisa").unwrap());
+        assert!(!state.forsaken());
+
+        let first = state.light(peter);
+        let second = state.light(peter);
+        let _lisa = state.light(lisa);
+        assert_eq!(*state.alive.get(&peter).unwrap(), 2);
+        assert!(!state.forsaken());
+
+        *state.spirit(peter).active_mut() = false;
+        assert!(!state.forsaken());
+        *state.spirit(lisa).active_mut() = false;
+        assert!(state.forsaken());
+
+        *state.spirit(peter).active_mut() = true;
+        assert!(!state.forsaken());
+        drop(first);
+        assert_eq!(*state.alive.get(&peter).unwrap(), 1);
+        assert!(!state.forsaken());
+        drop(second);
+        assert!(!state.alive.contains_key(&peter));
+        assert!(state.forsaken());
+    }
+
+    #[test]
+    fn settled_when_only_listening_wraiths_live() {
+        let echo = Entity::builder("Echo", Species::Wraith).build();
+        let peter = Entity::builder("Peter", Species::Zombie).build();
+        let state = Arc::new(State::from([&echo, &peter].into_iter()));
+        let (echo, peter) = (state.id("Echo").unwrap(), state.id("Peter").unwrap());
+        assert!(!state.settled());
+
+        let _echo = state.light(echo);
+        let listening = state.listen(echo);
+        assert!(state.settled());
+
+        // A waiting zombie may still be woken up by someone else.
+        let zombie = state.light(peter);
+        let waiting = state.wait(peter, false);
+        assert!(state.idle());
+        assert!(!state.settled());
+        drop(waiting);
+        drop(zombie);
+        assert!(state.settled());
+
+        state.disturb(echo);
+        assert!(!state.settled());
+        assert!(state.next_disturbance(echo));
+        assert!(state.settled());
+        drop(listening);
+        assert!(!state.settled());
+    }
+}
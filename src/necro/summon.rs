@@ -1,14 +1,27 @@
+use std::env;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
 use async_recursion::async_recursion;
-use log::{debug, error};
 use smol_str::SmolStr;
-use tokio::sync::mpsc::UnboundedSender;
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::oneshot;
 use tokio::time;
+use tracing::{debug, debug_span, error, trace, warn, Instrument};
 
-use super::state::State;
-use super::Message;
+use super::config::{Engine, Overflow, RitualConfig};
+use super::core::{self, World};
+use super::name::NameId;
+#[cfg(feature = "ouija")]
+use super::ouija::Ouija;
+use super::state::{self, SpiritHandle, SpiritState, State};
+use super::{Message, Necromancer, RitualReport, RuntimeError, Warning};
 use crate::scroll::entity::{Entity, Species};
 use crate::scroll::expression::Expr;
 use crate::scroll::statement::Stmt;
@@ -17,22 +30,96 @@ use crate::value::Value;
 
 // static DEMON_RESAMPLE_COUNT_RNG_DISTRIBUTION: Lazy<Uniform<u64>> = Lazy::new(|| Uniform::from(0..=5));
 
-pub type Candle = Arc<SmolStr>;
+/// How deeply tasks may perform each other before the spirit fails.
+const MAX_CALL_DEPTH: usize = 32;
+
+/// How deeply rituals may be performed within each other before the spirit fails.
+const MAX_NESTING_DEPTH: usize = 8;
+
+/// How often a spirit gives way to spirits of higher rank after each statement, at most.
+const MAX_RANK_YIELDS: u32 = 16;
+
+/// How often a spirit gives way to others after each statement at most, as decided by the seed
+/// of a deterministic ritual.
+const MAX_SEEDED_YIELDS: usize = 3;
+
+/// How long a revenant pauses before its first retry of a task at most, in milliseconds. The
+/// pause doubles with every further retry, up to [`MAX_RETRY_PAUSE`].
+const RETRY_PAUSE: u64 = 100;
+
+/// How long a revenant pauses before retrying a task at most, in milliseconds.
+const MAX_RETRY_PAUSE: u64 = 10_000;
 
 // Represents a summoned creature. Fields are read-only.
-pub struct Spirit<'a> {
+pub struct Spirit {
+    /// The id of the entity the spirit was summoned from.
+    id: NameId,
+    /// The state of that entity, to check cheaply whether it is active before every statement.
+    own: SpiritHandle,
     name: SmolStr,
-    creature: &'a Entity,
-    sender: UnboundedSender<Message>,
+    creature: Arc<Entity>,
+    sender: Sender<Message>,
+    config: Arc<RitualConfig>,
+    /// Whether the spirit performed any statement so far.
+    awake: AtomicBool,
+    /// Whether the spirit failed with an error. A failed spirit performs nothing anymore.
+    failed: AtomicBool,
+    /// The only task to perform and its argument, if the spirit was invoked to perform a task.
+    call: Option<(SmolStr, Value)>,
+}
+
+/// The entity whose memory bare `moan`, `remembering` and `reminisce` refer to. Unknown entities
+/// are kept by name, so that every reference to them can be warned about.
+#[derive(Debug, Clone, Copy)]
+enum Context<'a> {
+    Known(NameId),
+    Unknown(&'a str),
+}
+
+/// How a block of statements ended, for the shamble around it to act on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flow {
+    /// Continue with the next statement.
+    Next,
+    /// Leave the innermost shamble.
+    Flee,
+    /// Skip to the next round of the innermost shamble.
+    Lurch,
 }
 
 struct RunningTask {
+    name: SmolStr,
+    /// The parameter of the task and the value it is bound to.
+    binding: Option<(SmolStr, Value)>,
+    /// How many tasks perform this one, nested into each other.
+    depth: usize,
     active: bool,
+    /// Whether the task ended with `stumble`.
+    stumbled: bool,
+    /// Statements executed since the last pause.
+    executed: usize,
 }
 
 impl RunningTask {
-    fn new() -> RunningTask {
-        RunningTask { active: true }
+    fn new(task: &Task, argument: Value, depth: usize) -> RunningTask {
+        RunningTask {
+            name: task.name(),
+            binding: task
+                .parameter()
+                .map(|parameter| (parameter.clone(), argument)),
+            depth,
+            active: true,
+            stumbled: false,
+            executed: 0,
+        }
+    }
+
+    /// Return the value of the parameter of the given name, if the task has one.
+    fn argument(&self, name: &str) -> Option<&Value> {
+        self.binding
+            .as_ref()
+            .filter(|(parameter, _)| parameter == name)
+            .map(|(_, value)| value)
     }
 
     fn active(&self) -> bool {
@@ -44,49 +131,120 @@ impl RunningTask {
     }
 }
 
-impl<'a: 'static> Spirit<'a> {
+impl Spirit {
     pub fn summon(
+        id: NameId,
+        own: SpiritHandle,
         name: SmolStr,
-        creature: &'a Entity,
-        sender: UnboundedSender<Message>,
-    ) -> Arc<Spirit<'a>> {
+        creature: Arc<Entity>,
+        sender: Sender<Message>,
+        config: Arc<RitualConfig>,
+        call: Option<(SmolStr, Value)>,
+    ) -> Arc<Spirit> {
         Arc::new(Spirit {
+            id,
+            own,
             name,
             creature,
             sender,
+            config,
+            awake: AtomicBool::new(false),
+            failed: AtomicBool::new(false),
+            call,
         })
     }
 
-    pub async fn unleash(self: Arc<Self>, state: Arc<State>, _candle: Candle) {
+    pub async fn unleash(self: Arc<Self>, state: Arc<State>) {
+        if let Some((task, argument)) = &self.call {
+            match self.creature.tasks().get_full(task) {
+                Some((index, _, _)) => {
+                    let argument = argument.clone();
+                    Arc::clone(&self)
+                        .perform(Arc::clone(&state), index, argument)
+                        .await;
+                }
+                None => self.unknown_task(&state, &self.creature.name(), task),
+            }
+            return;
+        }
+
+        // Tasks with a parameter are only performed when called.
+        let scheduled: Vec<usize> = self
+            .creature
+            .tasks()
+            .values()
+            .enumerate()
+            .filter(|(_, task)| task.parameter().is_none())
+            .map(|(index, _)| index)
+            .collect();
         match self.creature.species() {
             Species::Zombie => {
-                for task in self.creature.tasks().values() {
-                    if let Err(e) =
-                        tokio::spawn(Arc::clone(&self).perform(Arc::clone(&state), task)).await
-                    {
-                        error!("{}", e);
-                    }
+                for task in scheduled {
+                    Arc::clone(&self)
+                        .perform(Arc::clone(&state), task, Value::Void)
+                        .await;
                 }
             }
             Species::Ghost => {
-                for task in self.creature.tasks().values() {
-                    if let Err(e) =
-                        tokio::spawn(Arc::clone(&self).perform(Arc::clone(&state), task)).await
+                for task in scheduled {
+                    Arc::clone(&self)
+                        .perform(Arc::clone(&state), task, Value::Void)
+                        .await;
+                    let rest = state.rng().u64(500..=10_000);
+                    time::sleep(Duration::from_millis(rest)).await;
+                }
+            }
+            Species::Lich => {
+                let total = scheduled.len();
+                let resumed = state.checkpoint(self.id);
+                if resumed > 0 {
+                    debug!("{} resuming after {} completed task(s)", self.name, resumed);
+                }
+                for (position, task) in scheduled.into_iter().enumerate().skip(resumed) {
+                    Arc::clone(&self)
+                        .perform(Arc::clone(&state), task, Value::Void)
+                        .await;
+                    state.set_checkpoint(self.id, position + 1, total);
+                }
+            }
+            Species::Wraith => {
+                for &task in scheduled.iter().cycle() {
+                    self.disturbed(&state).await;
+                    debug!("{} responding to a disturbance", self.name);
+                    Arc::clone(&self)
+                        .perform(Arc::clone(&state), task, Value::Void)
+                        .await;
+                }
+            }
+            Species::Revenant => {
+                for task in scheduled {
+                    let mut retries = 0;
+                    while Arc::clone(&self)
+                        .perform(Arc::clone(&state), task, Value::Void)
+                        .await
+                        && retries < self.config.retry_limit()
                     {
-                        error!("{}", e);
+                        retries += 1;
+                        state.retry(self.id);
+                        let limit = RETRY_PAUSE
+                            .saturating_mul(1 << (retries - 1).min(32))
+                            .min(MAX_RETRY_PAUSE);
+                        let pause = state.rng().u64(limit / 2..=limit);
+                        debug!(
+                            "{} retrying a task after {} ms (attempt {})",
+                            self.name, pause, retries
+                        );
+                        time::sleep(Duration::from_millis(pause)).await;
                     }
-                    time::sleep(Duration::from_millis(fastrand::u64(500..=10_000))).await;
                 }
             }
             Species::Vampire => {
-                let mut tasks: Vec<&Task> = self.creature.tasks().values().collect();
-                fastrand::shuffle(&mut tasks);
+                let mut tasks = scheduled;
+                state.rng().shuffle(&mut tasks);
                 for task in tasks {
-                    if let Err(e) =
-                        tokio::spawn(Arc::clone(&self).perform(Arc::clone(&state), task)).await
-                    {
-                        error!("{}", e);
-                    }
+                    Arc::clone(&self)
+                        .perform(Arc::clone(&state), task, Value::Void)
+                        .await;
                 }
             }
             Species::Demon => {
@@ -163,30 +321,60 @@ impl<'a: 'static> Spirit<'a> {
         }
     }
 
-    // perform a task asynchronously
-    async fn perform(self: Arc<Self>, state: Arc<State>, task: &'a Task) {
-        debug!("{} performing task {}", self.name, task.name());
-        let mut running_task = RunningTask::new();
-        self.exec_stmts(&state, &mut running_task, task.statements())
-            .await;
+    /// Wait until the wraith is disturbed, and take the disturbance from the queue.
+    async fn disturbed(&self, state: &State) {
+        let _waiting = state.listen(self.id);
+        loop {
+            // Listen before looking, so that no disturbance goes unnoticed in between.
+            let notified = state.notifier().notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if state.next_disturbance(self.id) {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    // perform a task asynchronously, returning whether it ended with stumble
+    async fn perform(self: Arc<Self>, state: Arc<State>, index: usize, argument: Value) -> bool {
+        let (_, task) = self.creature.tasks().get_index(index).unwrap();
+        let span = debug_span!("task", task = %task.name());
+        async {
+            debug!("{} performing task {}", self.name, task.name());
+            let mut running_task = RunningTask::new(task, argument, 0);
+            // Fleeing or lurching outside of any shamble ends the task all the same.
+            self.exec_stmts(&state, &mut running_task, task.statements())
+                .await;
+            running_task.stumbled && !self.failed.load(Ordering::Relaxed)
+        }
+        .instrument(span)
+        .await
     }
 
     // #[async_recursion]
-    async fn exec_stmts(&self, state: &Arc<State>, task: &mut RunningTask, stmts: &'a Vec<Stmt>) {
+    async fn exec_stmts(&self, state: &Arc<State>, task: &mut RunningTask, stmts: &[Stmt]) -> Flow {
         debug!("{} executing statements {:?}", self.name, stmts);
         for stmt in stmts {
+            if self.failed.load(Ordering::Relaxed) {
+                *task.active_mut() = false;
+                return Flow::Next;
+            }
             // wait until entity is active
-            loop {
-                if state.knowledge().get(&self.name).unwrap().active() {
-                    break;
-                } else {
+            if !state::lock(&self.own).active() {
+                let _waiting = state.wait(self.id, self.awake.load(Ordering::Relaxed));
+                loop {
                     // sleep until notified, then check again
                     state.notifier().notified().await;
+                    if state::lock(&self.own).active() {
+                        break;
+                    }
                 }
             }
             // execute one statement at a time
             // let other tasks perform and check for being active again before next statement
-            self.exec_stmt(state, task, stmt).await;
+            let flow = self.exec_stmt(state, task, stmt).await;
+            self.awake.store(true, Ordering::Relaxed);
 
             // check if task is still active
             if !task.active() {
@@ -194,12 +382,69 @@ impl<'a: 'static> Spirit<'a> {
                 break;
             }
 
-            tokio::task::yield_now().await;
+            // give way to spirits of higher rank
+            for _ in 0..state.rank_gap(self.creature.rank()).min(MAX_RANK_YIELDS) {
+                tokio::task::yield_now().await;
+            }
+
+            // let the seed decide who goes next, if that is all that decides
+            if self.config.runtime_engine() == Engine::CurrentThreadDeterministic {
+                let yields = state.rng().usize(..=MAX_SEEDED_YIELDS);
+                for _ in 0..yields {
+                    tokio::task::yield_now().await;
+                }
+            }
+
+            match self.config.statement_budget() {
+                Some((statements, pause)) => {
+                    task.executed += 1;
+                    if task.executed >= statements {
+                        trace!("{} pausing for {:?}", self.name, pause);
+                        task.executed = 0;
+                        time::sleep(pause).await;
+                    } else {
+                        tokio::task::yield_now().await;
+                    }
+                }
+                None => tokio::task::yield_now().await,
+            }
+
+            // leave the block after yielding, so that shambling on doesn't starve anyone
+            if flow != Flow::Next {
+                return flow;
+            }
         }
+        Flow::Next
     }
 
     #[async_recursion]
-    async fn exec_stmt(&self, state: &Arc<State>, task: &mut RunningTask, stmt: &'a Stmt) {
+    async fn exec_stmt(&self, state: &Arc<State>, task: &mut RunningTask, stmt: &Stmt) -> Flow {
+        for hook in self.config.hooks() {
+            hook.before_stmt(&self.name, &task.name, stmt);
+        }
+        // Statements aimed at a coven act on each of its members in turn. None of them are
+        // blocks, so all of them continue with the next statement.
+        let flow = match stmt.target().and_then(|name| state.coven(name)) {
+            Some(members) => {
+                debug!("{} acting on the coven {:?}", self.name, members);
+                for member in members {
+                    self.perform_stmt(state, task, &stmt.retarget(member.clone()))
+                        .await;
+                }
+                Flow::Next
+            }
+            None => self.perform_stmt(state, task, stmt).await,
+        };
+        if let Some(every) = self.config.snapshot_interval() {
+            state.observe(every);
+        }
+        for hook in self.config.hooks() {
+            hook.after_stmt(&self.name, &task.name, stmt);
+        }
+        flow
+    }
+
+    async fn perform_stmt(&self, state: &Arc<State>, task: &mut RunningTask, stmt: &Stmt) -> Flow {
         match stmt {
             Stmt::Animate(None) => {
                 debug!(
@@ -207,19 +452,60 @@ impl<'a: 'static> Spirit<'a> {
                     self.name,
                     self.creature.species(),
                 );
-                self.send_message(Message::Animate(self.name.clone()));
+                match self.creature.species() {
+                    species if species.responds_like(Species::Zombie) => {
+                        self.send_message(state, Message::Animate(self.id)).await
+                    }
+                    species => self.warn(
+                        state,
+                        Warning::AnimateOnNonZombie {
+                            name: self.name.clone(),
+                            species,
+                        },
+                    ),
+                }
             }
             Stmt::Animate(Some(other_name)) => {
                 debug!("{} tries to animate {}", self.name, other_name);
-                self.send_message(Message::Animate(other_name.clone()));
+                let Some(other) = self.knows(state, other_name) else {
+                    return Flow::Next;
+                };
+                self.send_message(state, Message::Animate(other)).await;
+            }
+            Stmt::AnimateAll => {
+                debug!("{} animating all zombies", self.name);
+                self.send_message(state, Message::Broadcast(Species::Zombie))
+                    .await;
             }
             Stmt::Banish(None) => {
                 debug!("{} banishing itself", self.name);
-                set_active(&state, self.name.as_str(), false);
+                self.set_active(state, self.id, false);
+                state.cancel(self.id);
             }
             Stmt::Banish(Some(other_name)) => {
                 debug!("{} banishing {}", self.name, other_name);
-                set_active(&state, other_name, false);
+                if let Some(other) = self.knows(state, other_name) {
+                    self.set_active(state, other, false);
+                    state.cancel(other);
+                }
+            }
+            Stmt::BanishAll => {
+                debug!("{} banishing everyone", self.name);
+                // Banishing itself cancels the spirit, so that comes last.
+                for other in state.creature_ids().filter(|&other| other != self.id) {
+                    self.set_active(state, other, false);
+                    state.cancel(other);
+                }
+                self.set_active(state, self.id, false);
+                state.cancel(self.id);
+            }
+            Stmt::Channel(port) => {
+                debug!("{} listening on channel {}", self.name, port);
+                if self.config.net_allowed() {
+                    self.channel(state, *port).await;
+                } else {
+                    warn!("{} may not open channel {}", self.name, port);
+                }
             }
             Stmt::Disturb(None) => {
                 debug!(
@@ -227,48 +513,246 @@ impl<'a: 'static> Spirit<'a> {
                     self.name,
                     self.creature.species(),
                 );
-                self.send_message(Message::Disturb(self.name.clone()));
+                match self.creature.species() {
+                    Species::Ghost => self.send_message(state, Message::Disturb(self.id)).await,
+                    Species::Wraith => state.disturb(self.id),
+                    species => self.warn(
+                        state,
+                        Warning::DisturbOnNonGhost {
+                            name: self.name.clone(),
+                            species,
+                        },
+                    ),
+                }
             }
             Stmt::Disturb(Some(other_name)) => {
                 debug!("{} tries to disturb {}", self.name, other_name);
-                self.send_message(Message::Disturb(other_name.clone()));
+                let Some(other) = self.knows(state, other_name) else {
+                    return Flow::Next;
+                };
+                // Wraiths respond to their queue of disturbances instead of being summoned.
+                if state
+                    .creature(other)
+                    .is_some_and(|creature| creature.species() == Species::Wraith)
+                {
+                    state.disturb(other);
+                    return Flow::Next;
+                }
+                self.send_message(state, Message::Disturb(other)).await;
+            }
+            Stmt::DisturbAll => {
+                debug!("{} disturbing all ghosts", self.name);
+                self.send_message(state, Message::Broadcast(Species::Ghost))
+                    .await;
+            }
+            Stmt::Entomb(path, exprs) => {
+                let value = self.eval_exprs(state, task, exprs);
+                debug!("{} entombing {} in {}", self.name, value, path);
+                let Some(file) = self.config.sandboxed(path) else {
+                    warn!("{} may not entomb anything in {}", self.name, path);
+                    return Flow::Next;
+                };
+                let result = async {
+                    OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(&file)
+                        .await?
+                        .write_all(value.to_string().as_bytes())
+                        .await
+                }
+                .await;
+                if let Err(e) = result {
+                    error!("{} failed to entomb {}: {}", self.name, path, e);
+                }
+            }
+            Stmt::Exhume(path) => {
+                debug!("{} exhuming {}", self.name, path);
+                let value = match self.config.sandboxed(path) {
+                    Some(file) => match fs::read_to_string(&file).await {
+                        Ok(contents) => Value::from(contents),
+                        Err(e) => {
+                            error!("{} failed to exhume {}: {}", self.name, path, e);
+                            Value::Void
+                        }
+                    },
+                    None => {
+                        warn!("{} may not exhume {}", self.name, path);
+                        Value::Void
+                    }
+                };
+                self.set_value(state, self.id, value)
+            }
+            Stmt::SummonWithin(other_name, path) => {
+                debug!("{} summoning {} within {}", self.name, other_name, path);
+                if let Some(value) = self.summon_within(state, other_name, path).await {
+                    self.set_value(state, self.id, value)
+                }
             }
             Stmt::Forget(None) => {
                 debug!("{} forgets its value", self.name);
-                set_value(&state, self.name.as_str(), Value::default())
+                self.set_value(state, self.id, Value::default())
             }
             Stmt::Forget(Some(other_name)) => {
                 debug!("{} makes {} forget its value", self.name, other_name);
-                set_value(&state, other_name, Value::default())
+                self.set_value_of(state, other_name, Value::default())
             }
             Stmt::Invoke(None) => {
                 debug!("{} invoking a new copy of itself", self.name);
-                self.send_message(Message::Invoke(self.name.clone()));
+                self.send_message(state, Message::Invoke(self.id, None))
+                    .await;
             }
             Stmt::Invoke(Some(other_name)) => {
-                debug!("{} invoking a new copy of {}", self.name, other_name);
-                self.send_message(Message::Invoke(other_name.clone()));
+                let Some(other) = self.knows(state, other_name) else {
+                    return Flow::Next;
+                };
+                match state.bound(other) {
+                    Some(spirit) => {
+                        debug!("{} invoking bound spirit {}", self.name, other_name);
+                        let value = spirit.call(state.spirit(other).memory().clone());
+                        self.set_value(state, other, value);
+                    }
+                    None => {
+                        debug!("{} invoking a new copy of {}", self.name, other_name);
+                        self.send_message(state, Message::Invoke(other, None)).await;
+                    }
+                }
+            }
+            Stmt::InvokeTask(other_name, task_name, exprs) => {
+                let argument = self.eval_exprs(state, task, exprs);
+                debug!(
+                    "{} invoking a new copy of {} to perform {} with {}",
+                    self.name, other_name, task_name, argument
+                );
+                let Some(other) = self.knows(state, other_name) else {
+                    return Flow::Next;
+                };
+                self.send_message(state, Message::Call(other, task_name.clone(), argument))
+                    .await;
+            }
+            Stmt::Perform(name, task_name, exprs) => {
+                let argument = self.eval_exprs(state, task, exprs);
+                let other = match name {
+                    Some(other_name) => match self.knows(state, other_name) {
+                        Some(other) => Some(other),
+                        None => return Flow::Next,
+                    },
+                    None => None,
+                };
+                let creature = match other {
+                    Some(other) if other != self.id => {
+                        let other_name = state.name(other);
+                        debug!(
+                            "{} performing task {} of {} with {}",
+                            self.name, task_name, other_name, argument
+                        );
+                        // Bound spirits have no tasks to perform.
+                        match state.creature(other) {
+                            Some(creature) => creature,
+                            None => {
+                                self.unknown_task(state, other_name, task_name);
+                                return Flow::Next;
+                            }
+                        }
+                    }
+                    _ => {
+                        debug!(
+                            "{} performing task {} with {}",
+                            self.name, task_name, argument
+                        );
+                        &self.creature
+                    }
+                };
+                let Some(called) = creature.tasks().get(task_name) else {
+                    self.unknown_task(state, &creature.name(), task_name);
+                    return Flow::Next;
+                };
+                if task.depth >= MAX_CALL_DEPTH {
+                    self.fail(
+                        state,
+                        RuntimeError::RecursionLimit {
+                            spirit: self.name.clone(),
+                            task: task_name.clone(),
+                        },
+                    );
+                    return Flow::Next;
+                }
+                let mut running_task = RunningTask::new(called, argument, task.depth + 1);
+                // The shambles of the caller are out of reach of the performed task.
+                self.exec_stmts(state, &mut running_task, called.statements())
+                    .await;
+            }
+            Stmt::Harvest(name) => {
+                let other_name = name.as_ref().unwrap_or(&self.name);
+                let other = match name {
+                    Some(other_name) => self.knows(state, other_name),
+                    None => Some(self.id),
+                };
+                let value = match other.map(|other| (other, state.bound(other))) {
+                    Some((other, Some(spirit))) => {
+                        debug!("{} harvesting bound spirit {}", self.name, other_name);
+                        let value = spirit.call(state.spirit(other).memory().clone());
+                        self.set_value(state, other, value.clone());
+                        Some(value)
+                    }
+                    Some((other, None)) => {
+                        debug!(
+                            "{} invoking a new copy of {} to harvest",
+                            self.name, other_name
+                        );
+                        let (tx, rx) = oneshot::channel();
+                        self.send_message(state, Message::Invoke(other, Some(tx)))
+                            .await;
+                        rx.await.ok()
+                    }
+                    None => None,
+                };
+                match value {
+                    Some(value) => {
+                        debug!("{} harvested {} from {}", self.name, value, other_name);
+                        self.set_value(state, self.id, value)
+                    }
+                    None => warn!("{} could not harvest {}", self.name, other_name),
+                }
             }
             Stmt::Remember(None, exprs) => {
-                let value = self.eval_exprs(&state, exprs);
+                let value = self.eval_exprs(state, task, exprs);
                 debug!("{} remembering {} (self)", self.name, value);
-                set_value(&state, self.name.as_str(), value)
+                self.set_value(state, self.id, value)
             }
             Stmt::Remember(Some(other_name), exprs) => {
-                let value = self.eval_exprs(&state, exprs);
+                let value = self.eval_exprs(state, task, exprs);
                 debug!("{} remembering {} (from {})", other_name, value, self.name);
-                set_value(&state, other_name, value)
+                self.set_value_of(state, other_name, value)
+            }
+            Stmt::Say(None, exprs) => {
+                let value = self.eval_exprs(state, task, exprs);
+                debug!("{} saying {:?} (is {})", self.name, exprs, value);
+                self.say(value);
+            }
+            Stmt::Say(Some(other_name), exprs) => {
+                let context = match state.id(other_name) {
+                    Some(other) => Context::Known(other),
+                    None => Context::Unknown(other_name),
+                };
+                let value = self.eval_exprs_as(state, task, context, exprs);
+                debug!(
+                    "{} saying {:?} (is {}, for {})",
+                    other_name, exprs, value, self.name
+                );
+                self.say(value);
             }
-            Stmt::Say(name, exprs) => {
-                let value = self.eval_exprs(&state, exprs);
-                match name {
-                    None => debug!("{} saying {:?} (is {})", self.name, exprs, value),
-                    Some(other_name) => debug!("{} saying {:?} (is {})", other_name, exprs, value),
+            Stmt::Whisper(address, exprs) => {
+                let value = self.eval_exprs(state, task, exprs);
+                debug!("{} whispering {} beyond {}", self.name, value, address);
+                if self.config.net_allowed() {
+                    self.whisper(address, &value).await;
+                } else {
+                    warn!("{} may not whisper beyond {}", self.name, address);
                 }
-                self.send_message(Message::Say(value));
             }
             Stmt::ShambleUntil(expr, stmts) => loop {
-                let cond = self.eval_standalone_expr(&state, expr);
+                let cond = self.eval_standalone_expr(state, task, expr);
                 debug!(
                     "{} shambling until {:?} is true (currently {})",
                     self.name, expr, cond
@@ -278,115 +762,493 @@ impl<'a: 'static> Spirit<'a> {
                         break;
                     }
                     Value::Boolean(false) => {
-                        self.exec_stmts(&state, task, stmts).await;
+                        let flow = self.exec_stmts(state, task, stmts).await;
+                        if !task.active() || flow == Flow::Flee {
+                            break;
+                        }
                     }
                     value => panic!("Not a boolean: {}", value),
                 }
             },
+            Stmt::ShambleTimes(expr, stmts) => {
+                let count = self.eval_standalone_expr(state, task, expr);
+                debug!("{} shambling {:?} times (is {})", self.name, expr, count);
+                let rounds = match count {
+                    Value::Integer(count) if count < 0 => 0,
+                    Value::Integer(count) => u64::try_from(&count).unwrap_or(u64::MAX),
+                    Value::Void => 0,
+                    value => {
+                        let warning = Warning::InvalidShambleCount {
+                            spirit: self.name.clone(),
+                            count: value.to_string(),
+                        };
+                        self.warn(state, warning);
+                        0
+                    }
+                };
+                for _ in 0..rounds {
+                    let flow = self.exec_stmts(state, task, stmts).await;
+                    if !task.active() || flow == Flow::Flee {
+                        break;
+                    }
+                }
+            }
             Stmt::ShambleAround(stmts) => loop {
                 debug!("{} shambling around", self.name);
-                self.exec_stmts(&state, task, stmts).await;
+                let flow = self.exec_stmts(state, task, stmts).await;
+                if !task.active() || flow == Flow::Flee {
+                    break;
+                }
             },
             Stmt::Stumble => {
                 debug!("{} stumbling", self.name);
                 *task.active_mut() = false;
+                task.stumbled = true;
+            }
+            Stmt::Flee => {
+                debug!("{} fleeing", self.name);
+                return Flow::Flee;
+            }
+            Stmt::Lurch => {
+                debug!("{} lurching", self.name);
+                return Flow::Lurch;
             }
             Stmt::Taste(expr, stmts1, stmts2) => {
-                let cond = self.eval_standalone_expr(&state, expr);
+                let cond = self.eval_standalone_expr(state, task, expr);
                 debug!("{} tasting {:?} (tastes like {})...", self.name, expr, cond);
                 match cond {
                     Value::Boolean(true) => {
                         debug!("...{} likes the taste", self.name);
-                        self.exec_stmts(&state, task, stmts1).await;
+                        return self.exec_stmts(state, task, stmts1).await;
                     }
                     Value::Boolean(false) => {
                         debug!("...{} hates the taste", self.name);
-                        self.exec_stmts(&state, task, stmts2).await;
+                        return self.exec_stmts(state, task, stmts2).await;
                     }
                     value => panic!("Not a boolean: {}", value),
                 }
             }
+            Stmt::Consult(expr, cases, lest) => {
+                let value = self.eval_standalone_expr(state, task, expr);
+                debug!("{} consulting {:?} (is {})...", self.name, expr, value);
+                match cases.iter().find(|(upon, _)| value == upon) {
+                    Some((upon, stmts)) => {
+                        debug!("...{} settles upon {}", self.name, upon);
+                        return self.exec_stmts(state, task, stmts).await;
+                    }
+                    None => {
+                        debug!("...{} settles upon nothing", self.name);
+                        return self.exec_stmts(state, task, lest).await;
+                    }
+                }
+            }
         }
+        Flow::Next
     }
 
-    fn eval_exprs(&self, state: &Arc<State>, exprs: &Vec<Expr>) -> Value {
-        debug!("{} evaluating expressions {:?}", self.name, exprs);
-        let mut stack = vec![Value::default()];
-        for index in (0..exprs.len()).rev() {
-            let expr = exprs.get(index).unwrap();
-            self.eval_expr(state, expr, &mut stack);
-            debug!(
-                "{} evaluating expression {:?} (Stack {:?})",
-                self.name, expr, stack
+    /// Perform the scroll at the path as a ritual of its own and return the final memory of the
+    /// named entity in it. Returns `None` if the spirit failed.
+    async fn summon_within(&self, state: &State, name: &SmolStr, path: &str) -> Option<Value> {
+        if self.config.depth() >= MAX_NESTING_DEPTH {
+            self.fail(
+                state,
+                RuntimeError::NestingLimit {
+                    spirit: self.name.clone(),
+                    path: String::from(path),
+                },
             );
+            return None;
         }
-        stack.pop().unwrap()
+        let Some(file) = self.config.sandboxed(path) else {
+            warn!("{} may not summon {}", self.name, path);
+            return Some(Value::Void);
+        };
+        let code = match fs::read_to_string(&file).await {
+            Ok(code) => code,
+            Err(e) => {
+                error!("{} failed to summon {}: {}", self.name, path, e);
+                return Some(Value::Void);
+            }
+        };
+        let scroll = match crate::parse::parse(&code) {
+            Ok(scroll) => scroll,
+            Err(e) => {
+                error!(
+                    "{} failed to summon {}: cannot parse the scroll ({})",
+                    self.name,
+                    path,
+                    e.code.description()
+                );
+                return Some(Value::Void);
+            }
+        };
+
+        // The inner ritual is aborted when this spirit is, since its future is dropped then.
+        let ritual: Pin<Box<dyn Future<Output = RitualReport> + Send>> = Box::pin(
+            Necromancer::unroll(scroll)
+                .with_config(self.config.within())
+                .unfold(),
+        );
+        let report = ritual.await;
+        if let Some(error) = report.error() {
+            self.fail(
+                state,
+                RuntimeError::Within {
+                    spirit: self.name.clone(),
+                    path: String::from(path),
+                    error: Box::new(error.clone()),
+                },
+            );
+            return None;
+        }
+        match report.memory(name) {
+            Some(memory) => Some(memory.clone()),
+            None => {
+                self.warn(
+                    state,
+                    Warning::UnknownEntityReference {
+                        spirit: self.name.clone(),
+                        name: name.clone(),
+                    },
+                );
+                Some(Value::Void)
+            }
+        }
+    }
+
+    #[cfg(feature = "ouija")]
+    async fn channel(&self, state: &State, port: u16) {
+        match state.ouija().channel(port).await {
+            Ok(value) => self.set_value(state, self.id, value),
+            Err(e) => error!("{} failed to listen on channel {}: {}", self.name, port, e),
+        }
+    }
+
+    #[cfg(not(feature = "ouija"))]
+    async fn channel(&self, _state: &State, port: u16) {
+        warn!(
+            "{} cannot open channel {}: networking is not supported by this build",
+            self.name, port
+        );
+    }
+
+    #[cfg(feature = "ouija")]
+    async fn whisper(&self, address: &str, value: &Value) {
+        if let Err(e) = Ouija::whisper(address, value).await {
+            error!("{} failed to whisper beyond {}: {}", self.name, address, e);
+        }
+    }
+
+    #[cfg(not(feature = "ouija"))]
+    async fn whisper(&self, address: &str, _value: &Value) {
+        warn!(
+            "{} cannot whisper beyond {}: networking is not supported by this build",
+            self.name, address
+        );
     }
 
-    fn eval_standalone_expr(&self, state: &Arc<State>, expr: &Expr) -> Value {
+    fn eval_exprs(&self, state: &Arc<State>, task: &RunningTask, exprs: &[Expr]) -> Value {
+        self.eval_exprs_as(state, task, Context::Known(self.id), exprs)
+    }
+
+    /// Evaluate the expressions in the context of the given entity.
+    ///
+    /// Bare `moan` and `remembering` refer to the memory of that entity instead of the memory of
+    /// the executing one. Expressions naming an entity always refer to the named entity.
+    fn eval_exprs_as(
+        &self,
+        state: &Arc<State>,
+        task: &RunningTask,
+        context: Context<'_>,
+        exprs: &[Expr],
+    ) -> Value {
+        debug!(
+            "{} evaluating expressions {:?} (as {:?})",
+            self.name, exprs, context
+        );
+        core::eval_exprs(&self.scope(state, task, context), exprs)
+    }
+
+    fn eval_standalone_expr(&self, state: &Arc<State>, task: &RunningTask, expr: &Expr) -> Value {
         let mut stack = vec![Value::default()];
-        self.eval_expr(state, expr, &mut stack);
+        let scope = self.scope(state, task, Context::Known(self.id));
+        core::eval_expr(&scope, expr, &mut stack);
         debug!(
             "{} evaluating standalone expression {:?} to {}",
             self.name,
             expr,
             stack.last().unwrap()
         );
-        let value = stack.pop().unwrap();
-        value
+
+        stack.pop().unwrap()
     }
 
-    /// Evaluate the expression. The stack is modified accordingly. The returned value is put on top of the stack as well.
-    fn eval_expr(&self, state: &Arc<State>, expr: &Expr, stack: &mut Vec<Value>) {
-        match expr {
-            Expr::Moan(None) => {
-                *stack.last_mut().unwrap() =
-                    get_value(state, self.name.as_str()) + stack.last().unwrap();
-            }
-            Expr::Moan(Some(other_name)) => {
-                *stack.last_mut().unwrap() = get_value(state, other_name) + stack.last().unwrap();
-            }
-            Expr::Remembering(None, value) => stack.push(Value::Boolean(
-                value == get_value(state, self.name.as_str()),
-            )),
-            Expr::Remembering(Some(other_name), value) => {
-                stack.push(Value::Boolean(value == get_value(state, other_name)))
-            }
-            Expr::Rend => {
-                let top = &stack.pop().unwrap();
-                *stack.last_mut().unwrap() = stack.last().unwrap() / top;
+    /// The spirit performing the task, as seen by the expressions it evaluates.
+    fn scope<'a>(
+        &'a self,
+        state: &'a State,
+        task: &'a RunningTask,
+        context: Context<'a>,
+    ) -> Scope<'a> {
+        Scope {
+            spirit: self,
+            state,
+            task,
+            context,
+        }
+    }
+
+    /// Report that the described operation corrupted a value. Fails if that is not tolerated.
+    fn corrupted(&self, state: &State, operation: String) {
+        if self.config.fails_on_infernal() {
+            self.fail(
+                state,
+                RuntimeError::Corruption {
+                    spirit: self.name.clone(),
+                    operation,
+                },
+            );
+        } else {
+            self.warn(
+                state,
+                Warning::CorruptedValueCreated {
+                    spirit: self.name.clone(),
+                    operation,
+                },
+            );
+        }
+    }
+
+    /// Emit the warning, or fail with it if warnings are treated as errors.
+    fn warn(&self, state: &State, warning: Warning) {
+        warn!("{}", warning);
+        for hook in self.config.hooks() {
+            hook.on_warning(&warning);
+        }
+        state.warn(warning.clone());
+        if self.config.treats_warnings_as_errors() {
+            self.fail(state, RuntimeError::Warning(warning));
+        }
+    }
+
+    /// Return the id of the entity that the name or alias refers to, if it exists. Warns about
+    /// the reference otherwise.
+    ///
+    /// Statements that act on entities never refer to tasks, even if the spirit has a task of
+    /// that name and no entity is called so. Such references are pointed out separately.
+    fn knows(&self, state: &State, name: &str) -> Option<NameId> {
+        let known = state.id(name);
+        if known.is_none() {
+            let name = SmolStr::from(name);
+            let warning = if self.creature.tasks().contains_key(&name) {
+                Warning::TaskReferencedAsEntity {
+                    spirit: self.name.clone(),
+                    task: name,
+                }
+            } else {
+                Warning::UnknownEntityReference {
+                    spirit: self.name.clone(),
+                    name,
+                }
+            };
+            self.warn(state, warning);
+        }
+        known
+    }
+
+    /// Warn that the named entity has no task of the given name.
+    fn unknown_task(&self, state: &State, entity: &SmolStr, task: &SmolStr) {
+        self.warn(
+            state,
+            Warning::UnknownTaskReference {
+                spirit: self.name.clone(),
+                entity: entity.clone(),
+                task: task.clone(),
+            },
+        );
+    }
+
+    /// Return the value the named entity remembers. Unknown entities remember the void.
+    fn memory_of(&self, state: &State, name: &str) -> Value {
+        match self.knows(state, name) {
+            Some(id) => state.spirit(id).memory().clone(),
+            None => Value::Void,
+        }
+    }
+
+    /// Return the value the entity of the context remembers, like [`Spirit::memory_of`].
+    fn memory_in(&self, state: &State, context: Context<'_>) -> Value {
+        match self.context_id(state, context) {
+            Some(id) => state.spirit(id).memory().clone(),
+            None => Value::Void,
+        }
+    }
+
+    /// Return the id of the entity of the context. Warns about unknown entities.
+    fn context_id(&self, state: &State, context: Context<'_>) -> Option<NameId> {
+        match context {
+            Context::Known(id) => Some(id),
+            Context::Unknown(name) => self.knows(state, name),
+        }
+    }
+
+    /// Stop performing and end the ritual with the error.
+    fn fail(&self, state: &State, error: RuntimeError) {
+        if !self.failed.swap(true, Ordering::Relaxed) {
+            error!("Spirit failed! Aborting: {}", error);
+            state.fail(error);
+            // Failures must not be dropped, and this can't wait for room in the queue.
+            state.post();
+            if let Err(TrySendError::Full(message)) = self.sender.try_send(Message::Fail) {
+                let sender = self.sender.clone();
+                tokio::spawn(async move { sender.send(message).await });
             }
-            Expr::Turn => {
-                *stack.last_mut().unwrap() = -stack.last().unwrap();
+        }
+    }
+
+    /// Send the message to the ritual. Depending on the [overflow policy](Overflow), waits for
+    /// room in the queue or drops the message if too many messages wait already.
+    async fn send_message(&self, state: &State, message: Message) {
+        state.post();
+        let result = match self.config.overflow_policy() {
+            Overflow::Wait => self.sender.send(message).await.map_err(|e| e.0),
+            Overflow::Drop => match self.sender.try_send(message) {
+                Err(TrySendError::Full(message)) => {
+                    state.handled();
+                    let message = message.describe(state);
+                    let spirit = self.name.clone();
+                    self.warn(state, Warning::MessageDropped { spirit, message });
+                    return;
+                }
+                Err(TrySendError::Closed(message)) => Err(message),
+                Ok(()) => Ok(()),
+            },
+        };
+        result.expect("Message receiver dropped before task could finish!");
+    }
+
+    fn say(&self, value: Value) {
+        if self.failed.load(Ordering::Relaxed) {
+            return;
+        }
+        for hook in self.config.hooks() {
+            hook.on_say(&self.name, &value);
+        }
+        // Write right away instead of sending a message, so that nothing said gets lost or
+        // reordered on the way.
+        let result = match value {
+            Value::Integer(_) if self.config.groups_digits() => {
+                self.config.sink().say(&value.grouped())
             }
-            Expr::Value(value) => stack.push(value.clone()),
+            _ if self.config.cursed() => self.config.sink().say(&value),
+            _ => self.config.sink().say(&value.uncursed()),
+        };
+        if let Err(e) = result {
+            error!("{} failed to say {}: {}", self.name, value, e);
         }
     }
 
-    fn send_message(&self, message: Message) {
-        self.sender
-            .send(message)
-            .expect("Message receiver dropped before task could finish!");
+    fn set_active(&self, state: &State, id: NameId, active: bool) {
+        {
+            let mut spirit = state.spirit(id);
+            *spirit.active_mut() = active;
+            self.state_changed(state.name(id), &spirit);
+        }
+        if active {
+            state.notifier().notify_waiters();
+        }
     }
-}
 
-fn set_active(state: &State, name: &str, active: bool) {
-    state.knowledge().alter(name, |_, mut spirit| {
-        *spirit.active_mut() = active;
-        spirit
-    });
-    if active {
-        state.notifier().notify_waiters();
+    fn set_value(&self, state: &State, id: NameId, value: Value) {
+        if self.failed.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut spirit = state.spirit(id);
+        spirit.remember(value, state.history());
+        trace!("{} recalls {:?}", state.name(id), spirit.history());
+        self.state_changed(state.name(id), &spirit);
+    }
+
+    /// Let the named entity remember the value. Warns about unknown entities, unless the spirit
+    /// failed already.
+    fn set_value_of(&self, state: &State, name: &str, value: Value) {
+        if self.failed.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Some(id) = self.knows(state, name) {
+            self.set_value(state, id, value);
+        }
+    }
+
+    fn state_changed(&self, name: &str, spirit: &SpiritState) {
+        for hook in self.config.hooks() {
+            hook.on_state_change(name, spirit.memory(), spirit.active());
+        }
     }
 }
 
-fn get_value(state: &State, name: &str) -> Value {
-    state.knowledge().get(name).unwrap().memory().clone()
+/// A spirit performing a task in the context of an entity. Expressions are evaluated in it.
+struct Scope<'a> {
+    spirit: &'a Spirit,
+    state: &'a State,
+    task: &'a RunningTask,
+    context: Context<'a>,
 }
 
-fn set_value(state: &State, name: &str, value: Value) {
-    state.knowledge().alter(name, |_, mut spirit| {
-        *spirit.memory_mut() = value;
-        spirit
-    });
+impl World for Scope<'_> {
+    fn moan(&self, name: Option<&str>) -> Value {
+        let Some(name) = name else {
+            return self.spirit.memory_in(self.state, self.context);
+        };
+        if let Some(argument) = self.task.argument(name) {
+            return argument.clone();
+        }
+        match self.spirit.knows(self.state, name) {
+            Some(other) => {
+                let memory = self.state.spirit(other).memory().clone();
+                match self.state.bound(other) {
+                    Some(spirit) => spirit.call(memory),
+                    None => memory,
+                }
+            }
+            None => Value::Void,
+        }
+    }
+
+    fn memory(&self, name: Option<&str>) -> Value {
+        match name {
+            Some(name) => match self.task.argument(name) {
+                Some(argument) => argument.clone(),
+                None => self.spirit.memory_of(self.state, name),
+            },
+            None => self.spirit.memory_in(self.state, self.context),
+        }
+    }
+
+    fn recall(&self, name: Option<&str>, n: usize) -> Value {
+        let id = match name {
+            Some(name) => self.spirit.knows(self.state, name),
+            None => self.spirit.context_id(self.state, self.context),
+        };
+        match id {
+            Some(id) => self.state.spirit(id).recall(n).cloned().unwrap_or_default(),
+            None => Value::Void,
+        }
+    }
+
+    fn divine(&self, var: &str) -> Value {
+        if self.spirit.config.env_allowed() {
+            env::var(var).map_or(Value::Void, Value::from)
+        } else {
+            warn!(
+                "{} tried to divine {}, but environment access is not allowed",
+                self.spirit.name, var
+            );
+            Value::Void
+        }
+    }
+
+    fn corrupted(&self, operation: String) {
+        self.spirit.corrupted(self.state, operation);
+    }
 }
@@ -0,0 +1,501 @@
+//! Recording of a ritual over time, and its export as an SVG timeline.
+//!
+//! Every entity gets a row. Bars show when its spirits performed which task, and dots mark when
+//! its memory or its activity changed; hovering over them tells more. Rows are shaded while their
+//! entity is active. Performing the same scroll twice shows how ghosts, vampires and demons take
+//! their time and order differently.
+//!
+//! The tasks are reported by a [hook](StatementHook), and the changes of the entities by the
+//! [events](RitualEvent) of the ritual. Spirits of the same entity share its row.
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use indexmap::IndexMap;
+use smol_str::SmolStr;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+
+use super::{Necromancer, RitualConfig, RitualEvent, RitualReport, StatementHook};
+use crate::scroll::entity::Species;
+use crate::scroll::statement::Stmt;
+use crate::scroll::Scroll;
+use crate::value::Value;
+
+/// The width of the SVG, in pixels.
+const WIDTH: u32 = 960;
+/// The width of the column with the names of the entities.
+const LABELS: u32 = 180;
+/// The space around the timeline.
+const MARGIN: u32 = 20;
+/// The height of the title above the rows.
+const HEADER: u32 = 40;
+/// The height of a row.
+const ROW: u32 = 40;
+/// The height of the time axis below the rows.
+const AXIS: u32 = 30;
+
+/// The colours of the bars, one per task of an entity, in order.
+const PALETTE: [&str; 8] = [
+    "#4e79a7", "#f28e2b", "#59a14f", "#e15759", "#76b7b2", "#edc948", "#b07aa1", "#9c755f",
+];
+
+/// What happened to the entities of a ritual over time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timeline {
+    title: Option<String>,
+    rows: IndexMap<SmolStr, Row>,
+    /// How long the ritual lasted.
+    length: Duration,
+}
+
+/// What happened to an entity.
+#[derive(Debug, Clone, PartialEq)]
+struct Row {
+    species: Species,
+    /// Whether the entity was active before the ritual.
+    active: bool,
+    /// The last statement of each task, which ends the bar of the task once performed.
+    tasks: IndexMap<SmolStr, Option<Stmt>>,
+    bars: Vec<Bar>,
+    changes: Vec<Change>,
+}
+
+/// A task being performed, from its first statement to its last one.
+#[derive(Debug, Clone, PartialEq)]
+struct Bar {
+    task: SmolStr,
+    start: Duration,
+    /// When the task ended, unless it was still performed when the ritual ended.
+    end: Option<Duration>,
+}
+
+/// A new memory or activity of an entity.
+#[derive(Debug, Clone, PartialEq)]
+struct Change {
+    at: Duration,
+    memory: Value,
+    active: bool,
+}
+
+/// Perform the ritual while recording its timeline.
+pub fn record(scroll: Scroll, config: RitualConfig) -> (RitualReport, Timeline) {
+    let recording = Arc::new(Mutex::new(Recording {
+        start: Instant::now(),
+        timeline: Timeline::new(&scroll),
+    }));
+    let config = config.hook(TimelineHook(recording.clone()));
+    let mut necromancer = Necromancer::unroll(scroll).with_config(config);
+    let listener = listen(necromancer.subscribe(), recording.clone());
+
+    Recording::lock(&recording).start = Instant::now();
+    let report = necromancer.initiate();
+    let _ = listener.join();
+    let mut recording = Recording::lock(&recording);
+    recording.timeline.length = recording.start.elapsed();
+    (report, recording.timeline.clone())
+}
+
+/// Record the changes of the entities until the ritual ends.
+fn listen(mut events: Receiver<RitualEvent>, recording: Arc<Mutex<Recording>>) -> JoinHandle<()> {
+    thread::spawn(move || loop {
+        let event = match events.blocking_recv() {
+            Ok(event) => event,
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => break,
+        };
+        let mut recording = Recording::lock(&recording);
+        let now = recording.start.elapsed();
+        match event {
+            RitualEvent::StateChanged {
+                name,
+                memory,
+                active,
+            } => {
+                if let Some(row) = recording.timeline.rows.get_mut(&name) {
+                    row.changes.push(Change {
+                        at: now,
+                        memory,
+                        active,
+                    });
+                }
+            }
+            // A spirit may be banished in the middle of its task.
+            RitualEvent::SpiritFinished(name) => {
+                if let Some(row) = recording.timeline.rows.get_mut(&name) {
+                    row.close(now);
+                }
+            }
+            _ => {}
+        }
+    })
+}
+
+/// The timeline of a ritual while it is performed.
+struct Recording {
+    start: Instant,
+    timeline: Timeline,
+}
+
+impl Recording {
+    fn lock(recording: &Mutex<Recording>) -> MutexGuard<'_, Recording> {
+        recording.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Reports the tasks that the spirits perform to the recording.
+struct TimelineHook(Arc<Mutex<Recording>>);
+
+impl StatementHook for TimelineHook {
+    fn before_stmt(&self, spirit: &str, task: &str, _stmt: &Stmt) {
+        let mut recording = Recording::lock(&self.0);
+        let now = recording.start.elapsed();
+        let Some(row) = recording.timeline.rows.get_mut(spirit) else {
+            return;
+        };
+        match row.bars.last() {
+            Some(bar) if bar.end.is_none() && bar.task == task => {}
+            _ => {
+                row.close(now);
+                row.bars.push(Bar {
+                    task: SmolStr::from(task),
+                    start: now,
+                    end: None,
+                });
+            }
+        }
+    }
+
+    fn after_stmt(&self, spirit: &str, task: &str, stmt: &Stmt) {
+        let mut recording = Recording::lock(&self.0);
+        let now = recording.start.elapsed();
+        let Some(row) = recording.timeline.rows.get_mut(spirit) else {
+            return;
+        };
+        if row
+            .tasks
+            .get(task)
+            .is_some_and(|last| last.as_ref() == Some(stmt))
+        {
+            row.close(now);
+        }
+    }
+}
+
+impl Row {
+    /// End the task that is being performed, if any.
+    fn close(&mut self, now: Duration) {
+        if let Some(bar) = self.bars.last_mut().filter(|bar| bar.end.is_none()) {
+            bar.end = Some(now);
+        }
+    }
+}
+
+impl Timeline {
+    /// An empty timeline of the entities of the scroll.
+    fn new(scroll: &Scroll) -> Timeline {
+        let rows = scroll
+            .creatures_ordered()
+            .map(|entity| {
+                let tasks = entity
+                    .tasks()
+                    .iter()
+                    .map(|(name, task)| (name.clone(), task.statements().last().cloned()))
+                    .collect();
+                let row = Row {
+                    species: entity.species(),
+                    active: entity.active(),
+                    tasks,
+                    bars: Vec::new(),
+                    changes: Vec::new(),
+                };
+                (entity.name(), row)
+            })
+            .collect();
+        Timeline {
+            title: scroll.meta().and_then(|meta| meta.title.clone()),
+            rows,
+            length: Duration::ZERO,
+        }
+    }
+
+    /// How long the ritual lasted.
+    pub fn length(&self) -> Duration {
+        self.length
+    }
+
+    /// The tasks that the spirits of the named entity performed, in order, with when they
+    /// started and ended.
+    pub fn tasks(&self, name: &str) -> Vec<(&str, Duration, Duration)> {
+        self.rows.get(name).map_or_else(Vec::new, |row| {
+            row.bars
+                .iter()
+                .map(|bar| (bar.task.as_str(), bar.start, bar.end.unwrap_or(self.length)))
+                .collect()
+        })
+    }
+
+    /// Draw the timeline as an SVG image.
+    pub fn svg(&self) -> String {
+        let rows = self.rows.len() as u32;
+        let height = HEADER + rows * ROW + AXIS + MARGIN;
+        let span = (WIDTH - LABELS - MARGIN) as f64;
+        // Even an instant ritual gets a timeline.
+        let length = self.length.max(Duration::from_millis(1));
+        let x = |at: Duration| {
+            LABELS as f64 + at.min(length).as_secs_f64() / length.as_secs_f64() * span
+        };
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+             viewBox=\"0 0 {} {}\" font-family=\"sans-serif\" font-size=\"12\">",
+            WIDTH, height, WIDTH, height
+        );
+        let _ = writeln!(
+            out,
+            "<rect width=\"{}\" height=\"{}\" fill=\"white\"/>",
+            WIDTH, height
+        );
+        let _ = writeln!(
+            out,
+            "<text x=\"{}\" y=\"{}\" font-size=\"16\" font-weight=\"bold\">{} ({})</text>",
+            MARGIN,
+            HEADER / 2 + 6,
+            escape(self.title.as_deref().unwrap_or("Ritual")),
+            time(self.length)
+        );
+
+        // The time axis and its grid go first, so that the rows are drawn over them.
+        let axis = HEADER + rows * ROW;
+        let _ = writeln!(
+            out,
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"#777\"/>",
+            LABELS,
+            axis,
+            WIDTH - MARGIN,
+            axis
+        );
+        let step = step(length);
+        let mut tick = Duration::ZERO;
+        while tick <= length {
+            let _ = writeln!(
+                out,
+                "<line x1=\"{:.1}\" y1=\"{}\" x2=\"{:.1}\" y2=\"{}\" stroke=\"#ddd\"/>\
+                 <text x=\"{:.1}\" y=\"{}\" fill=\"#777\" font-size=\"10\" \
+                 text-anchor=\"middle\">{}</text>",
+                x(tick),
+                HEADER,
+                x(tick),
+                axis + 4,
+                x(tick),
+                axis + 16,
+                time(tick)
+            );
+            tick += step;
+        }
+
+        for (index, (name, row)) in self.rows.iter().enumerate() {
+            let top = HEADER + index as u32 * ROW;
+            let middle = top + ROW / 2;
+            let _ = writeln!(out, "<g>");
+            // Shade the row while the entity is active.
+            let mut active = row.active.then_some(Duration::ZERO);
+            for change in &row.changes {
+                match (active, change.active) {
+                    (None, true) => active = Some(change.at),
+                    (Some(since), false) => {
+                        shade(&mut out, x(since), x(change.at), top);
+                        active = None;
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(since) = active {
+                shade(&mut out, x(since), x(length), top);
+            }
+            let _ = writeln!(
+                out,
+                "<text x=\"{}\" y=\"{}\"><tspan font-weight=\"bold\">{}</tspan> \
+                 <tspan fill=\"#777\">{}</tspan></text>",
+                MARGIN,
+                middle + 4,
+                escape(name),
+                row.species
+            );
+
+            for bar in &row.bars {
+                let end = bar.end.unwrap_or(self.length);
+                let (left, right) = (x(bar.start), x(end));
+                // Keep statements that take no time visible.
+                let width = (right - left).max(2.0);
+                let colour =
+                    PALETTE[row.tasks.get_index_of(&bar.task).unwrap_or(0) % PALETTE.len()];
+                let _ = writeln!(
+                    out,
+                    "<rect x=\"{:.1}\" y=\"{}\" width=\"{:.1}\" height=\"18\" rx=\"3\" fill=\"{}\">\
+                     <title>{}: {} to {}</title></rect>",
+                    left,
+                    top + 8,
+                    width,
+                    colour,
+                    escape(&bar.task),
+                    time(bar.start),
+                    time(end)
+                );
+                // Roughly the width of the name in pixels.
+                if width > 7.0 * bar.task.chars().count() as f64 + 8.0 {
+                    let _ = writeln!(
+                        out,
+                        "<text x=\"{:.1}\" y=\"{}\" fill=\"white\" font-size=\"11\">{}</text>",
+                        left + 4.0,
+                        top + 21,
+                        escape(&bar.task)
+                    );
+                }
+            }
+
+            for change in &row.changes {
+                let _ = writeln!(
+                    out,
+                    "<circle cx=\"{:.1}\" cy=\"{}\" r=\"3\" fill=\"#444\">\
+                     <title>{}: remembers {}, {} at {}</title></circle>",
+                    x(change.at),
+                    top + ROW - 6,
+                    escape(name),
+                    escape(&change.memory.uncursed().to_string()),
+                    if change.active { "active" } else { "inactive" },
+                    time(change.at)
+                );
+            }
+            let _ = writeln!(out, "</g>");
+        }
+
+        out.push_str("</svg>\n");
+        out
+    }
+}
+
+/// Shade a row between the given positions.
+fn shade(out: &mut String, left: f64, right: f64, top: u32) {
+    let _ = writeln!(
+        out,
+        "<rect x=\"{:.1}\" y=\"{}\" width=\"{:.1}\" height=\"{}\" fill=\"#59a14f\" fill-opacity=\"0.12\"/>",
+        left,
+        top + 2,
+        right - left,
+        ROW - 4
+    );
+}
+
+/// The distance between the ticks of the time axis: 1, 2 or 5 times a power of ten
+/// milliseconds, so that there are at most ten ticks.
+fn step(length: Duration) -> Duration {
+    let mut power = 1;
+    loop {
+        for factor in [1, 2, 5] {
+            let step = Duration::from_millis(factor * power);
+            if length <= step * 10 {
+                return step;
+            }
+        }
+        power *= 10;
+    }
+}
+
+/// A point in time on the timeline, in milliseconds or in seconds from one second on.
+fn time(at: Duration) -> String {
+    if at < Duration::from_secs(1) {
+        format!("{} ms", at.as_millis())
+    } else {
+        format!("{:.1} s", at.as_secs_f64())
+    }
+}
+
+/// Escape the text for XML.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::necro::OutputBuffer;
+    use crate::parse::parse;
+
+    #[test]
+    fn record_timeline() {
+        let scroll = parse(
+            "\
+scroll \"Timeline\"
+
+Peter is a zombie
+summon
+    task Greet
+        say \"Hello\"
+    animate
+    task Count
+        remember 1
+        say moan
+    animate
+animate
+
+Lisa is a zombie
+summon
+    task Love
+        remember \"<3\"
+    animate
+animate
+",
+        )
+        .unwrap();
+        let output = OutputBuffer::new();
+        let (report, timeline) = record(scroll, RitualConfig::default().output(output.clone()));
+        assert!(report.error().is_none());
+        assert_eq!(output.contents(), "Hello\n1\n");
+
+        let tasks: Vec<&str> = timeline
+            .tasks("Peter")
+            .into_iter()
+            .map(|(task, start, end)| {
+                assert!(start <= end && end <= timeline.length());
+                task
+            })
+            .collect();
+        assert_eq!(tasks, ["Greet", "Count"]);
+        assert_eq!(timeline.tasks("Lisa")[0].0, "Love");
+
+        let svg = timeline.svg();
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>\n"));
+        assert!(svg.contains("Timeline ("));
+        assert!(svg.contains("<title>Greet: "));
+        assert!(svg.contains("<title>Peter: remembers 1, active at "));
+        assert!(svg.contains("<title>Lisa: remembers &lt;3, active at "));
+        assert_eq!(svg.matches("<g>").count(), 2);
+    }
+
+    #[test]
+    fn ticks() {
+        assert_eq!(step(Duration::ZERO), Duration::from_millis(1));
+        assert_eq!(step(Duration::from_millis(15)), Duration::from_millis(2));
+        assert_eq!(
+            step(Duration::from_millis(4200)),
+            Duration::from_millis(500)
+        );
+        assert_eq!(time(Duration::from_millis(42)), "42 ms");
+        assert_eq!(time(Duration::from_millis(4300)), "4.3 s");
+    }
+}
@@ -0,0 +1,56 @@
+use smol_str::SmolStr;
+
+use crate::scroll::entity::Species;
+
+/// Something suspicious that happened during a ritual without ending it.
+///
+/// Warnings are passed to [`StatementHook::on_warning`](super::StatementHook::on_warning) and
+/// collected in the [`RitualReport`](super::RitualReport). With
+/// [`RitualConfig::warnings_as_errors`](super::RitualConfig::warnings_as_errors), the first
+/// warning ends the ritual instead.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// Only zombies can be animated. Animating anything else does nothing.
+    #[error("cannot animate {name}, which is a {species} and not a zombie")]
+    AnimateOnNonZombie { name: SmolStr, species: Species },
+    /// Only ghosts can be disturbed. Disturbing anything else does nothing.
+    #[error("cannot disturb {name}, which is a {species} and not a ghost")]
+    DisturbOnNonGhost { name: SmolStr, species: Species },
+    /// An active entity was animated or disturbed again. Depending on the
+    /// [reanimation policy](super::RitualConfig::reanimation), another spirit of it was summoned
+    /// or nothing happened.
+    #[error(
+        "{name} is active already, {}",
+        if *.spawned { "summoning another spirit of it" } else { "ignoring the reanimation" }
+    )]
+    ReanimatedActive { name: SmolStr, spawned: bool },
+    /// A spirit referred to an entity that is neither part of the scroll nor bound.
+    /// It is treated as remembering the void, and statements acting on it do nothing.
+    #[error("{spirit} refers to the unknown entity {name}")]
+    UnknownEntityReference { spirit: SmolStr, name: SmolStr },
+    /// A spirit referred to one of its own tasks where an entity is expected, e.g. with
+    /// `invoke <task>`. Such statements only ever act on entities, so it does nothing.
+    #[error("{spirit} refers to its task {task} as if it were an entity")]
+    TaskReferencedAsEntity { spirit: SmolStr, task: SmolStr },
+    /// A spirit tried to perform a task that the named entity does not have.
+    #[error("{spirit} refers to the unknown task {task} of {entity}")]
+    UnknownTaskReference {
+        spirit: SmolStr,
+        entity: SmolStr,
+        task: SmolStr,
+    },
+    /// A spirit sent a message to the ritual while too many messages waited already, and the
+    /// [overflow policy](super::RitualConfig::overflow) dropped it.
+    #[error("too many messages for the ritual, dropping {message} from {spirit}")]
+    MessageDropped { spirit: SmolStr, message: String },
+    /// A spirit was to shamble a number of times that is not an integer. It shambles not at all.
+    #[error("{spirit} cannot shamble {count} times, which is not a number")]
+    InvalidShambleCount { spirit: SmolStr, count: String },
+    /// An expression corrupted a value out of uncorrupted operands.
+    #[error("{spirit} corrupted a value: {operation}")]
+    CorruptedValueCreated {
+        spirit: SmolStr,
+        /// The operation that corrupted the value, with its operands.
+        operation: String,
+    },
+}
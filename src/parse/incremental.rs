@@ -0,0 +1,277 @@
+//! Parsing of scrolls that are edited over time, like in an editor.
+//!
+//! A [`Session`] splits the scroll into entities and coven declarations the same way as
+//! [`parse_reader`](super::parse_reader): each of them begins with its header and ends where the
+//! next one begins. After an edit, only those that the edit touched are parsed again.
+use std::collections::HashMap;
+use std::ops::Range;
+
+use nom::character::complete::multispace0;
+use nom::combinator::eof;
+use nom::error::{Error, ErrorKind};
+use nom::sequence::{pair, terminated};
+use nom::Finish;
+use smol_str::SmolStr;
+
+use super::{
+    assemble, configured, parse_chunk, parse_prologue, read_error, starts_with_item, Item, Parse,
+    ParseConfig, ParseError,
+};
+use crate::scroll::Scroll;
+
+/// A scroll being edited, with the parsed entities and covens of its current code.
+#[derive(Debug, Clone)]
+pub struct Session {
+    config: ParseConfig,
+    code: String,
+    /// Where the first entity or coven begins, or the end of the code if there is none. Anything
+    /// before is the prologue.
+    prologue: usize,
+    chunks: Vec<Chunk>,
+}
+
+/// The code of an entity or a coven declaration, and what it was parsed into.
+#[derive(Debug, Clone)]
+struct Chunk {
+    range: Range<usize>,
+    item: Result<Item, ParseError>,
+}
+
+impl Session {
+    /// Parse the code of the scroll with the given settings.
+    pub fn new(code: impl Into<String>, config: ParseConfig) -> Session {
+        let mut session = Session {
+            config,
+            code: code.into(),
+            prologue: 0,
+            chunks: Vec::new(),
+        };
+        session.reparse(None);
+        session
+    }
+
+    /// The current code of the scroll.
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    /// Replace the given byte range of the code with the text, and parse what changed.
+    /// Returns the names of the entities and covens that were added, changed or removed by the
+    /// edit, in order of their appearance, with removed ones last. An entity that can't be
+    /// parsed anymore counts as removed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds or doesn't lie on character boundaries.
+    pub fn edit(&mut self, range: Range<usize>, text: &str) -> Vec<SmolStr> {
+        let before: Vec<Item> = self
+            .chunks
+            .iter()
+            .filter_map(|chunk| chunk.item.clone().ok())
+            .collect();
+        self.code.replace_range(range.clone(), text);
+        self.reparse(Some((range, text.len())));
+
+        let mut old: HashMap<SmolStr, Item> =
+            before.into_iter().map(|item| (name(&item), item)).collect();
+        let mut changed = Vec::new();
+        for item in self
+            .chunks
+            .iter()
+            .filter_map(|chunk| chunk.item.as_ref().ok())
+        {
+            let name = name(item);
+            if old.remove(&name).as_ref() != Some(item) {
+                changed.push(name);
+            }
+        }
+        let mut removed: Vec<SmolStr> = old.into_keys().collect();
+        removed.sort();
+        changed.extend(removed);
+        changed
+    }
+
+    /// Assemble the scroll from the parsed entities and covens. Fails with the first error in
+    /// the code, if any.
+    pub fn scroll(&self) -> Result<Scroll, ParseError> {
+        configured(self.config, || {
+            let meta = parse_prologue(&self.code[..self.prologue], 1)?;
+            if self.chunks.is_empty() {
+                // Let the parser explain what is wrong with the scroll.
+                let error = Finish::finish(terminated(Scroll::parse, pair(multispace0, eof))(
+                    self.code.as_str(),
+                ))
+                .err()
+                .unwrap_or(Error::new(&self.code, ErrorKind::Many1));
+                return Err(read_error(&self.code, 1, error));
+            }
+            let items = self
+                .chunks
+                .iter()
+                .map(|chunk| chunk.item.clone())
+                .collect::<Result<Vec<Item>, ParseError>>()?;
+            let mut scroll = assemble(items);
+            *scroll.meta_mut() = meta;
+            Ok(scroll)
+        })
+    }
+
+    /// Split the code into chunks and parse them. Given the replaced range of the last edit and
+    /// the length of its replacement, chunks that the edit didn't touch keep what they were
+    /// parsed into. Chunks that failed to parse are parsed again anyway, since the lines of
+    /// their errors may have moved.
+    fn reparse(&mut self, edit: Option<(Range<usize>, usize)>) {
+        let mut kept: HashMap<Range<usize>, Item> = HashMap::new();
+        if let Some((range, length)) = edit {
+            for chunk in self.chunks.drain(..) {
+                let Ok(item) = chunk.item else {
+                    continue;
+                };
+                // Touching the edit counts, since the edit may extend the chunk.
+                if chunk.range.end < range.start {
+                    kept.insert(chunk.range, item);
+                } else if chunk.range.start > range.end {
+                    let shift = |offset: usize| offset - range.len() + length;
+                    kept.insert(shift(chunk.range.start)..shift(chunk.range.end), item);
+                }
+            }
+        }
+
+        configured(self.config, || {
+            let starts = item_starts(&self.code);
+            self.prologue = starts.first().copied().unwrap_or(self.code.len());
+            let ends = starts.iter().skip(1).copied().chain([self.code.len()]);
+            self.chunks = starts
+                .iter()
+                .zip(ends)
+                .map(|(&start, end)| {
+                    let range = start..end;
+                    let item = match kept.remove(&range) {
+                        Some(item) => Ok(item),
+                        None => {
+                            let line = self.code[..start].matches('\n').count() + 1;
+                            parse_chunk(&self.code[range.clone()], line)
+                        }
+                    };
+                    Chunk { range, item }
+                })
+                .collect();
+        });
+    }
+}
+
+/// The name of the entity or coven.
+fn name(item: &Item) -> SmolStr {
+    match item {
+        Item::Entity(entity) => entity.name(),
+        Item::Coven(name, _) => name.clone(),
+    }
+}
+
+/// The byte offsets of the lines that begin an entity or a coven declaration.
+fn item_starts(code: &str) -> Vec<usize> {
+    let mut starts = Vec::new();
+    let mut start = 0;
+    for line in code.split_inclusive('\n') {
+        let rest = code[start..].trim_start();
+        if !line.trim().is_empty() && starts_with_item(rest) == Some(true) {
+            starts.push(start);
+        }
+        start += line.len();
+    }
+    starts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    const CODE: &str = "\
+scroll \"Edits\" by \"Peter\"
+
+Peter is a zombie
+summon
+    task Greet
+        say \"Hello\"
+    animate
+animate
+
+Lisa is a ghost
+summon
+    remember 1
+disturb
+
+coven Pair containing Peter, Lisa
+";
+
+    fn listing(scroll: &Scroll) -> String {
+        crate::scroll::listing::listing(scroll)
+    }
+
+    #[test]
+    fn parse_session() {
+        let session = Session::new(CODE, ParseConfig::default());
+        let scroll = session.scroll().unwrap();
+        let expected = parse(CODE).unwrap();
+        assert_eq!(listing(&scroll), listing(&expected));
+        assert_eq!(scroll.meta(), expected.meta());
+        assert_eq!(scroll.coven("Pair"), expected.coven("Pair"));
+    }
+
+    #[test]
+    fn edit_entities() {
+        let mut session = Session::new(CODE, ParseConfig::default());
+
+        let hello = session.code().find("Hello").unwrap();
+        assert_eq!(session.edit(hello..hello + 5, "Howdy"), ["Peter"]);
+        assert_eq!(
+            session.scroll().unwrap().creatures()["Peter"].tasks()["Greet"]
+                .statements()
+                .len(),
+            1
+        );
+
+        // Whitespace changes nothing, but still moves the entities after it.
+        let blank = session.code().find("\n\nLisa").unwrap();
+        assert!(session.edit(blank..blank, "\n\n").is_empty());
+        assert!(session.code().contains("\n\n\n\nLisa"));
+
+        let one = session.code().find("remember 1").unwrap() + "remember ".len();
+        assert_eq!(session.edit(one..one + 1, "2"), ["Lisa"]);
+
+        // Breaking an entity removes it, and adding one reports it.
+        let disturb = session.code().find("disturb").unwrap();
+        assert_eq!(session.edit(disturb..disturb + 7, "wander"), ["Lisa"]);
+        assert!(session.scroll().is_err());
+        let end = session.code().len();
+        assert_eq!(
+            session.edit(end..end, "\nJay is a zombie\nsummon\nbind\n"),
+            ["Jay"]
+        );
+        let wander = session.code().find("wander").unwrap();
+        assert_eq!(session.edit(wander..wander + 6, "disturb"), ["Lisa"]);
+
+        let scroll = session.scroll().unwrap();
+        assert_eq!(listing(&scroll), listing(&parse(session.code()).unwrap()));
+        assert_eq!(scroll.creatures().len(), 3);
+    }
+
+    #[test]
+    fn report_errors() {
+        let code = "Peter is a zombie\nsummon\n    remember 12abc\nanimate\n";
+        let mut session = Session::new(code, ParseConfig::default());
+        let error = session.scroll().unwrap_err();
+        assert_eq!((error.line(), error.column()), (3, 14));
+
+        // Errors move along with the code.
+        assert!(session.edit(0..0, "\n").is_empty());
+        assert_eq!(session.scroll().unwrap_err().line(), 4);
+
+        let abc = session.code().find("abc").unwrap();
+        assert_eq!(session.edit(abc..abc + 3, ""), ["Peter"]);
+        assert!(session.scroll().is_ok());
+
+        assert!(Session::new("", ParseConfig::default()).scroll().is_err());
+    }
+}
@@ -1,31 +1,39 @@
-use either::Either;
-use log::{debug, trace};
-use malachite::num::conversion::traits::FromSciString;
+use std::cell::Cell;
+use std::io::{self, BufRead};
+use std::time::Duration;
+
+use malachite::num::conversion::traits::{FromSciString, FromStringBase};
 use malachite::Integer;
 use nom::branch::alt;
-use nom::bytes::complete::{tag, take_till, take_until};
-use nom::character::complete::{
-    alpha1, alphanumeric0, anychar, char, digit1, multispace0, multispace1,
-};
+use nom::bytes::complete::{tag, tag_no_case, take_till, take_while, take_while1};
+use nom::character::complete::{anychar, char, digit1, multispace0, multispace1, one_of, satisfy};
 use nom::combinator::{
-    all_consuming, complete, consumed, cut, eof, into, map, map_opt, map_parser, not, peek,
-    recognize, rest_len, value,
+    all_consuming, complete, consumed, cut, eof, map, map_res, not, opt, peek, recognize, rest_len,
+    value, verify,
 };
-use nom::error::Error;
+use nom::error::{Error, ErrorKind};
 use nom::multi::{many0, many1, many_till, separated_list1};
 use nom::sequence::{delimited, pair, preceded, separated_pair, terminated, tuple};
 use nom::{Finish, IResult};
+use smol_str::SmolStr;
+use tracing::{debug, trace, trace_span};
+use unicode_ident::{is_xid_continue, is_xid_start};
 
-use crate::scroll::entity::{Entity, Species, TaskList};
+use crate::diag::expected;
+use crate::scroll::entity::{Entity, Species};
 use crate::scroll::expression::Expr;
 use crate::scroll::statement::Stmt;
 use crate::scroll::task::Task;
-use crate::scroll::Scroll;
-use crate::value::Value;
+use crate::scroll::{Scroll, ScrollMeta};
+use crate::value::{Radix, Value, MAX_POWER_BITS};
 
+pub mod incremental;
+mod suggest;
 #[cfg(test)]
 mod tests;
 
+pub use suggest::{fix, suggest, Suggestion};
+
 trait Parse<'a> {
     fn parse(code: &'a str) -> IResult<&'a str, Self>
     where
@@ -36,10 +44,60 @@ impl<'a> Parse<'a> for Scroll {
     fn parse(code: &'a str) -> IResult<&'a str, Scroll> {
         trace!("Code (syntax tree): {}", code);
         multispace0(code)?;
-        into(complete(many1(terminated(
-            Entity::parse,
-            alt((recognize(pair(multispace0, eof)), recognize(multispace1))),
-        ))))(code)
+        let (code, meta) = opt(terminated(ScrollMeta::parse, multispace1))(code)?;
+        let (code, mut scroll) = map(
+            complete(many1(terminated(
+                parse_item,
+                alt((recognize(pair(multispace0, eof)), recognize(multispace1))),
+            ))),
+            assemble,
+        )(code)?;
+        *scroll.meta_mut() = meta;
+        Ok((code, scroll))
+    }
+}
+
+impl<'a> Parse<'a> for ScrollMeta {
+    fn parse(code: &'a str) -> IResult<&'a str, ScrollMeta> {
+        trace!("Code (prologue): {}", code);
+        let language = || {
+            preceded(
+                tuple((
+                    keyword_tag("scroll"),
+                    multispace1,
+                    keyword_tag("speaks"),
+                    multispace1,
+                    keyword_tag("zombie"),
+                    multispace1,
+                )),
+                map_res(digit1, str::parse),
+            )
+        };
+        alt((
+            map(
+                tuple((
+                    preceded(pair(keyword_tag("scroll"), multispace1), parse_string),
+                    opt(preceded(
+                        tuple((multispace1, keyword_tag("by"), multispace1)),
+                        parse_string,
+                    )),
+                    opt(preceded(
+                        tuple((multispace1, keyword_tag("version"), multispace1)),
+                        parse_string,
+                    )),
+                    opt(preceded(multispace1, language())),
+                )),
+                |(title, author, version, language)| ScrollMeta {
+                    title: Some(String::from(title)),
+                    author: author.map(String::from),
+                    version: version.map(String::from),
+                    language,
+                },
+            ),
+            map(language(), |language| {
+                ScrollMeta::default().speaks(language)
+            }),
+        ))(code)
     }
 }
 
@@ -47,7 +105,8 @@ impl<'a> Parse<'a> for Entity {
     fn parse(code: &'a str) -> IResult<&'a str, Entity> {
         // Leave any whitespace after the entity definition in the input.
         trace!("Code (entity): {}", code);
-        let (code, (name, species)) = parse_entity_header(code)?;
+        let (code, (name, species, rank, aliases)) = parse_entity_header(code)?;
+        let _span = trace_span!("entity", name).entered();
 
         // Find the end of the entity definition and collect any code in between. Expect EOF or a new entity definition after this one.
         // End of entity definition is still in input after this.
@@ -55,10 +114,15 @@ impl<'a> Parse<'a> for Entity {
             anychar,
             peek(tuple((
                 multispace1,
-                alt((tag("animate"), tag("bind"), tag("disturb"))),
+                alt((
+                    keyword_tag("animate"),
+                    keyword_tag("bind"),
+                    keyword_tag("disturb"),
+                )),
                 alt((
                     recognize(pair(multispace0, eof)),
                     recognize(pair(multispace1, parse_entity_header)),
+                    recognize(pair(multispace1, parse_coven)),
                 )),
             ))),
         ))(code)?;
@@ -66,104 +130,251 @@ impl<'a> Parse<'a> for Entity {
         // Now actually parse the end of the entity definition.
         let (code, spell) = preceded(
             multispace1,
-            alt((tag("animate"), tag("bind"), tag("disturb"))),
+            alt((
+                keyword_tag("animate"),
+                keyword_tag("bind"),
+                keyword_tag("disturb"),
+            )),
         )(code)?;
 
         trace!("Code (entity): content is {}", contents);
 
-        // Parse the contents of the entity definition.
-        let (_, statements) = many0(preceded(
-            multispace1,
-            alt((
-                map(Task::parse, Either::Left),
-                map(
-                    preceded(pair(tag("remember"), multispace1), Value::parse),
-                    Either::Right,
-                ),
+        // Parse the contents of the entity definition. All of them, so that nothing is dropped.
+        let (_, definitions) = cut(terminated(
+            many0(preceded(
+                multispace1,
+                alt((
+                    map(Task::parse, Definition::Task),
+                    map(
+                        preceded(pair(keyword_tag("remember"), multispace1), Value::parse),
+                        Definition::Memory,
+                    ),
+                    map(
+                        preceded(
+                            tuple((
+                                keyword_tag("haunt"),
+                                multispace1,
+                                keyword_tag("every"),
+                                multispace1,
+                            )),
+                            map_res(digit1, str::parse),
+                        ),
+                        |millis| Definition::Haunt(Duration::from_millis(millis)),
+                    ),
+                )),
             )),
+            pair(multispace0, eof),
         ))(contents)?;
 
         let active = matches!(
-            (species, spell),
+            (species, spell.to_ascii_lowercase().as_str()),
             (Species::Zombie, "animate")
                 | (Species::Ghost, "disturb")
                 | (Species::Vampire, "bind")
                 | (Species::Demon, "bind")
                 | (Species::Djinn, "bind")
+                | (Species::Lich, "animate")
+                | (Species::Wraith, "disturb")
+                | (Species::Revenant, "animate")
         );
 
-        // Separate values and tasks into different Vecs.
-        let statements = statements
-            .into_iter()
-            .partition::<Vec<Either<Task, Value>>, _>(Either::is_left);
-        let tasks = statements
-            .0
-            .into_iter()
-            .map(Either::unwrap_left)
-            .map(|task| (task.name(), task))
-            .collect::<TaskList>();
-        let memory = statements
-            .1
-            .into_iter()
-            .next()
-            .map(Either::unwrap_right)
-            .unwrap_or(Value::Void);
+        // The first remembered value counts, and the last haunting period.
+        let mut entity = Entity::builder(name, species).active(active).rank(rank);
+        for alias in aliases {
+            entity = entity.alias(alias);
+        }
+        let mut remembered = false;
+        for definition in definitions {
+            match definition {
+                Definition::Task(task) => entity = entity.task(task),
+                Definition::Memory(memory) if !remembered => {
+                    entity = entity.remember(memory);
+                    remembered = true;
+                }
+                Definition::Memory(_) => {}
+                Definition::Haunt(period) => entity = entity.haunt(period),
+            }
+        }
+        let entity = entity.build();
 
         debug!(
             "Summoning creature {} of species {:?} with {} tasks, using {}.",
             name,
             species,
-            tasks.len(),
+            entity.tasks().len(),
             spell
         );
 
-        Ok((code, Entity::summon(name, species, active, memory, tasks)))
+        Ok((code, entity))
     }
 }
 
-fn parse_entity_header(code: &str) -> IResult<&str, (&str, Species)> {
-    trace!("Code (entity header): {}", code);
-    terminated(
+/// A declaration at the top level of a scroll.
+#[derive(Debug, Clone, PartialEq)]
+enum Item {
+    Entity(Entity),
+    Coven(SmolStr, Vec<SmolStr>),
+}
+
+/// Parse an entity or a coven declaration.
+fn parse_item(code: &str) -> IResult<&str, Item> {
+    alt((
+        map(Entity::parse, Item::Entity),
+        map(parse_coven, |(name, members)| {
+            Item::Coven(
+                SmolStr::from(name),
+                members.into_iter().map(SmolStr::from).collect(),
+            )
+        }),
+    ))(code)
+}
+
+/// Parse the name and the members of a coven, e.g. `coven Nightshift containing Peter, Jay`.
+fn parse_coven(code: &str) -> IResult<&str, (&str, Vec<&str>)> {
+    trace!("Code (coven): {}", code);
+    preceded(
+        pair(keyword_tag("coven"), multispace1),
         separated_pair(
             parse_identifier,
-            tuple((multispace1, tag("is"), multispace1)),
-            Species::parse,
+            tuple((multispace1, keyword_tag("containing"), multispace1)),
+            separated_list1(
+                tuple((multispace0, char(','), multispace0)),
+                parse_identifier,
+            ),
         ),
-        pair(multispace1, tag("summon")),
     )(code)
 }
 
+/// Make a scroll of the entities and covens, in the order of their declaration.
+fn assemble(items: Vec<Item>) -> Scroll {
+    items
+        .into_iter()
+        .fold(Scroll::builder(), |scroll, item| match item {
+            Item::Entity(entity) => scroll.entity(entity),
+            Item::Coven(name, members) => scroll.coven(name, members),
+        })
+        .build()
+}
+
+/// A part of the definition of an entity, between `summon` and the spell at the end.
+enum Definition {
+    Task(Task),
+    Memory(Value),
+    Haunt(Duration),
+}
+
+/// Parse the header of an entity, which declares it before `summon`.
+fn parse_entity_header(code: &str) -> IResult<&str, (&str, Species, u32, Vec<&str>)> {
+    trace!("Code (entity header): {}", code);
+    terminated(
+        parse_entity_declaration,
+        pair(multispace1, keyword_tag("summon")),
+    )(code)
+}
+
+/// Parse the name, the species, the rank and the aliases of an entity. The rank is 0 unless given
+/// with `of rank <n>`, and aliases follow `also known as`, separated by commas.
+fn parse_entity_declaration(code: &str) -> IResult<&str, (&str, Species, u32, Vec<&str>)> {
+    tuple((
+        parse_identifier,
+        preceded(
+            tuple((multispace1, keyword_tag("is"), multispace1)),
+            Species::parse,
+        ),
+        map(
+            opt(preceded(
+                tuple((
+                    multispace1,
+                    keyword_tag("of"),
+                    multispace1,
+                    keyword_tag("rank"),
+                    multispace1,
+                )),
+                map_res(digit1, str::parse),
+            )),
+            Option::unwrap_or_default,
+        ),
+        map(
+            opt(preceded(
+                tuple((
+                    multispace1,
+                    keyword_tag("also"),
+                    multispace1,
+                    keyword_tag("known"),
+                    multispace1,
+                    keyword_tag("as"),
+                    multispace1,
+                )),
+                separated_list1(
+                    tuple((multispace0, char(','), multispace0)),
+                    parse_identifier,
+                ),
+            )),
+            Option::unwrap_or_default,
+        ),
+    ))(code)
+}
+
 impl<'a> Parse<'a> for Species {
     fn parse(code: &'a str) -> IResult<&'a str, Species> {
         trace!("Code (species): {}", code);
         alt((
-            map(tuple((tag("a"), multispace1, tag("zombie"))), |_| {
-                Species::Zombie
-            }),
             map(
-                tuple((tag("an"), multispace1, tag("enslaved undead"))),
+                tuple((keyword_tag("a"), multispace1, keyword_tag("zombie"))),
                 |_| Species::Zombie,
             ),
-            map(tuple((tag("a"), multispace1, tag("ghost"))), |_| {
-                Species::Ghost
-            }),
             map(
-                tuple((tag("a"), multispace1, tag("restless undead"))),
+                tuple((
+                    keyword_tag("an"),
+                    multispace1,
+                    keyword_tag("enslaved undead"),
+                )),
+                |_| Species::Zombie,
+            ),
+            map(
+                tuple((keyword_tag("a"), multispace1, keyword_tag("ghost"))),
+                |_| Species::Ghost,
+            ),
+            map(
+                tuple((
+                    keyword_tag("a"),
+                    multispace1,
+                    keyword_tag("restless undead"),
+                )),
                 |_| Species::Ghost,
             ),
-            map(tuple((tag("a"), multispace1, tag("vampire"))), |_| {
-                Species::Vampire
-            }),
             map(
-                tuple((tag("a"), multispace1, tag("free-willed undead"))),
+                tuple((keyword_tag("a"), multispace1, keyword_tag("vampire"))),
                 |_| Species::Vampire,
             ),
-            map(tuple((tag("a"), multispace1, tag("demon"))), |_| {
-                Species::Demon
-            }),
-            map(tuple((tag("a"), multispace1, tag("djinn"))), |_| {
-                Species::Djinn
-            }),
+            map(
+                tuple((
+                    keyword_tag("a"),
+                    multispace1,
+                    keyword_tag("free-willed undead"),
+                )),
+                |_| Species::Vampire,
+            ),
+            map(
+                tuple((keyword_tag("a"), multispace1, keyword_tag("demon"))),
+                |_| Species::Demon,
+            ),
+            map(
+                tuple((keyword_tag("a"), multispace1, keyword_tag("djinn"))),
+                |_| Species::Djinn,
+            ),
+            map(
+                tuple((keyword_tag("a"), multispace1, keyword_tag("lich"))),
+                |_| Species::Lich,
+            ),
+            map(
+                tuple((keyword_tag("a"), multispace1, keyword_tag("wraith"))),
+                |_| Species::Wraith,
+            ),
+            map(
+                tuple((keyword_tag("a"), multispace1, keyword_tag("revenant"))),
+                |_| Species::Revenant,
+            ),
         ))(code)
     }
 }
@@ -173,7 +384,8 @@ impl<'a> Parse<'a> for Task {
         // Parse anything until the next task defintion. Take the last animate or bind as the end of the task.
         trace!("Code (task): {}", code);
 
-        let (code, name) = parse_task_header(code)?;
+        let (code, (name, parameter)) = parse_task_header(code)?;
+        let _span = trace_span!("task", name).entered();
 
         // Find the beginning of the next task definition or the end of the input.
         // May include some remembers after the end of the task though.
@@ -188,138 +400,399 @@ impl<'a> Parse<'a> for Task {
         // Now find the last animate or bind in the contents. Everything after that is remember statements outside the task.
         let (remembers, contents) = cut(recognize(many1(many_till(
             anychar,
-            alt((tag("animate"), tag("bind"))),
+            alt((keyword_tag("animate"), keyword_tag("bind"))),
         ))))(contents)?;
 
         // Remove the animate or bind at the end of the task.
         let (_, (contents, (_, active))) = cut(consumed(many_till(
             many_till(anychar, multispace1),
             peek(terminated(
-                alt((value(true, tag("animate")), value(false, tag("bind")))),
+                alt((
+                    value(true, keyword_tag("animate")),
+                    value(false, keyword_tag("bind")),
+                )),
                 pair(multispace0, eof),
             )),
         )))(contents)?;
         trace!("Code (task): content is {}", contents);
 
-        // Parse statements in the task.
-        let (_, stmts) = many0(preceded(multispace1, Stmt::parse))(contents)?;
+        // Parse statements in the task. All of them, so that a broken statement is not dropped.
+        let (_, stmts) = cut(terminated(
+            many0(preceded(multispace1, Stmt::parse)),
+            pair(multispace0, eof),
+        ))(contents)?;
 
         let rest = &code[rest_len(code)?.1 - next.len() - remembers.len()..];
-        Ok((rest, Task::new(name, active, stmts)))
+        let mut task = Task::builder(name).active(active).statements(stmts);
+        if let Some(parameter) = parameter {
+            task = task.parameter(parameter);
+        }
+        Ok((rest, task.build()))
     }
 }
 
-/// Parse the header of a task definition and return the task's name.
+/// Parse the header of a task definition and return the task's name and parameter.
 ///
-/// A task header is defined as the keyword `task` followed by a single identifier.
-fn parse_task_header(code: &str) -> IResult<&str, &str> {
+/// A task header is defined as the keyword `task` followed by a single identifier, optionally
+/// followed by `of` and the identifier of the parameter.
+fn parse_task_header(code: &str) -> IResult<&str, (&str, Option<&str>)> {
     trace!("Code (task header): {}", code);
-    preceded(pair(tag("task"), multispace1), parse_identifier)(code)
+    preceded(
+        pair(keyword_tag("task"), multispace1),
+        pair(
+            parse_identifier,
+            opt(preceded(
+                tuple((multispace1, keyword_tag("of"), multispace1)),
+                parse_identifier,
+            )),
+        ),
+    )(code)
+}
+
+/// Parse the argument of a task call, i.e. `with` followed by the statement stack.
+/// Without an argument, the call evaluates an empty statement stack.
+fn parse_argument(code: &str) -> IResult<&str, Vec<Expr>> {
+    map(
+        opt(preceded(
+            tuple((multispace1, keyword_tag("with"), multispace1)),
+            Vec::<Expr>::parse,
+        )),
+        Option::unwrap_or_default,
+    )(code)
+}
+
+/// Parse the name of a task that is followed by an optional argument. Makes sure that `with` is
+/// not mistaken for the name, so that `perform Task with ...` calls a task of the entity itself.
+fn parse_task_name(code: &str) -> IResult<&str, &str> {
+    verify(parse_identifier, |name: &str| {
+        if RELAXED.get() {
+            !name.eq_ignore_ascii_case("with")
+        } else {
+            name != "with"
+        }
+    })(code)
 }
 
 impl<'a> Parse<'a> for Stmt {
     fn parse(code: &'a str) -> IResult<&'a str, Stmt> {
         trace!("Code (statement): {}", code);
         alt((
-            map(
-                separated_pair(tag("animate"), multispace1, parse_identifier),
-                |(_, name)| Stmt::Animate(Some(name.into())),
-            ),
-            map(tag("animate"), |_| Stmt::Animate(None)),
-            map(
-                separated_pair(tag("banish"), multispace1, parse_identifier),
-                |(_, name)| Stmt::Banish(Some(name.into())),
-            ),
-            map(tag("banish"), |_| Stmt::Banish(None)),
-            map(
-                separated_pair(tag("disturb"), multispace1, parse_identifier),
-                |(_, name)| Stmt::Disturb(Some(name.into())),
-            ),
-            map(tag("disturb"), |_| Stmt::Disturb(None)),
-            map(
-                separated_pair(tag("forget"), multispace1, parse_identifier),
-                |(_, name)| Stmt::Forget(Some(name.into())),
-            ),
-            map(tag("forget"), |_| Stmt::Forget(None)),
-            map(
-                separated_pair(tag("invoke"), multispace1, parse_identifier),
-                |(_, name)| Stmt::Invoke(Some(name.into())),
-            ),
-            map(tag("invoke"), |_| Stmt::Invoke(None)),
-            map(
-                separated_pair(tag("remember"), multispace1, Vec::<Expr>::parse),
-                |(_, exprs)| Stmt::Remember(None, exprs),
-            ),
-            map(
-                tuple((
-                    tag("remember"),
-                    multispace1,
-                    parse_identifier,
-                    multispace1,
-                    Vec::<Expr>::parse,
-                )),
-                |(_, _, name, _, exprs)| Stmt::Remember(Some(name.into()), exprs),
-            ),
-            map(
-                separated_pair(tag("say"), multispace1, Vec::<Expr>::parse),
-                |(_, exprs)| Stmt::Say(None, exprs),
-            ),
-            map(
-                tuple((
-                    tag("say"),
-                    multispace1,
-                    parse_identifier,
-                    multispace1,
-                    Vec::<Expr>::parse,
-                )),
-                |(_, _, name, _, exprs)| Stmt::Say(Some(name.into()), exprs),
-            ),
-            map(
-                delimited(
-                    pair(tag("shamble"), multispace1),
-                    map_parser(
-                        take_until("around"),
-                        all_consuming(many0(terminated(Stmt::parse, multispace1))),
+            // Broadcasts come first, so that `all` is not taken for the name of an entity.
+            alt((
+                map(
+                    tuple((
+                        keyword_tag("animate"),
+                        multispace1,
+                        keyword_tag("all"),
+                        multispace1,
+                        keyword_tag("zombies"),
+                    )),
+                    |_| Stmt::AnimateAll,
+                ),
+                map(
+                    separated_pair(keyword_tag("banish"), multispace1, keyword_tag("all")),
+                    |_| Stmt::BanishAll,
+                ),
+                map(
+                    tuple((
+                        keyword_tag("disturb"),
+                        multispace1,
+                        keyword_tag("all"),
+                        multispace1,
+                        keyword_tag("ghosts"),
+                    )),
+                    |_| Stmt::DisturbAll,
+                ),
+            )),
+            alt((
+                map(
+                    separated_pair(keyword_tag("animate"), multispace1, parse_identifier),
+                    |(_, name)| Stmt::Animate(Some(name.into())),
+                ),
+                map(keyword_tag("animate"), |_| Stmt::Animate(None)),
+                map(
+                    separated_pair(keyword_tag("banish"), multispace1, parse_identifier),
+                    |(_, name)| Stmt::Banish(Some(name.into())),
+                ),
+                map(keyword_tag("banish"), |_| Stmt::Banish(None)),
+                map(
+                    separated_pair(
+                        keyword_tag("channel"),
+                        multispace1,
+                        map_res(digit1, str::parse),
                     ),
-                    tag("around"),
+                    |(_, port)| Stmt::Channel(port),
+                ),
+                map(
+                    separated_pair(keyword_tag("disturb"), multispace1, parse_identifier),
+                    |(_, name)| Stmt::Disturb(Some(name.into())),
+                ),
+                map(keyword_tag("disturb"), |_| Stmt::Disturb(None)),
+                map(
+                    tuple((
+                        keyword_tag("entomb"),
+                        multispace1,
+                        parse_string,
+                        multispace1,
+                        Vec::<Expr>::parse,
+                    )),
+                    |(_, _, path, _, exprs)| Stmt::Entomb(String::from(path), exprs),
+                ),
+                map(
+                    separated_pair(keyword_tag("exhume"), multispace1, parse_string),
+                    |(_, path)| Stmt::Exhume(String::from(path)),
+                ),
+                map(
+                    separated_pair(keyword_tag("forget"), multispace1, parse_identifier),
+                    |(_, name)| Stmt::Forget(Some(name.into())),
+                ),
+                map(keyword_tag("forget"), |_| Stmt::Forget(None)),
+                map(
+                    tuple((
+                        keyword_tag("invoke"),
+                        multispace1,
+                        parse_identifier,
+                        multispace1,
+                        keyword_tag("harvest"),
+                    )),
+                    |(_, _, name, _, _)| Stmt::Harvest(Some(name.into())),
                 ),
-                Stmt::ShambleAround,
+                map(
+                    separated_pair(keyword_tag("invoke"), multispace1, keyword_tag("harvest")),
+                    |_| Stmt::Harvest(None),
+                ),
+                map(
+                    tuple((
+                        keyword_tag("invoke"),
+                        multispace1,
+                        parse_identifier,
+                        multispace1,
+                        parse_identifier,
+                        parse_argument,
+                    )),
+                    |(_, _, name, _, task, argument)| {
+                        Stmt::InvokeTask(name.into(), task.into(), argument)
+                    },
+                ),
+                map(
+                    separated_pair(keyword_tag("invoke"), multispace1, parse_identifier),
+                    |(_, name)| Stmt::Invoke(Some(name.into())),
+                ),
+                map(keyword_tag("invoke"), |_| Stmt::Invoke(None)),
+                map(
+                    separated_pair(keyword_tag("remember"), multispace1, Vec::<Expr>::parse),
+                    |(_, exprs)| Stmt::Remember(None, exprs),
+                ),
+                map(
+                    tuple((
+                        keyword_tag("remember"),
+                        multispace1,
+                        parse_identifier,
+                        multispace1,
+                        Vec::<Expr>::parse,
+                    )),
+                    |(_, _, name, _, exprs)| Stmt::Remember(Some(name.into()), exprs),
+                ),
+                map(
+                    separated_pair(keyword_tag("remember"), multispace1, parse_identifier),
+                    |(_, name)| Stmt::Remember(Some(name.into()), vec![]),
+                ),
+                map(keyword_tag("remember"), |_| Stmt::Remember(None, vec![])),
+            )),
+            alt((
+                map(
+                    tuple((
+                        keyword_tag("perform"),
+                        multispace1,
+                        parse_identifier,
+                        multispace1,
+                        parse_task_name,
+                        parse_argument,
+                    )),
+                    |(_, _, name, _, task, argument)| {
+                        Stmt::Perform(Some(name.into()), task.into(), argument)
+                    },
+                ),
+                map(
+                    tuple((
+                        keyword_tag("perform"),
+                        multispace1,
+                        parse_identifier,
+                        parse_argument,
+                    )),
+                    |(_, _, task, argument)| Stmt::Perform(None, task.into(), argument),
+                ),
+                map(
+                    separated_pair(keyword_tag("say"), multispace1, Vec::<Expr>::parse),
+                    |(_, exprs)| Stmt::Say(None, exprs),
+                ),
+                map(
+                    tuple((
+                        keyword_tag("summon"),
+                        multispace1,
+                        parse_identifier,
+                        tuple((multispace1, keyword_tag("within"), multispace1)),
+                        parse_string,
+                    )),
+                    |(_, _, name, _, path)| Stmt::SummonWithin(name.into(), String::from(path)),
+                ),
+                map(
+                    tuple((
+                        keyword_tag("say"),
+                        multispace1,
+                        parse_identifier,
+                        multispace1,
+                        Vec::<Expr>::parse,
+                    )),
+                    |(_, _, name, _, exprs)| Stmt::Say(Some(name.into()), exprs),
+                ),
+                map(
+                    tuple((
+                        tuple((
+                            keyword_tag("whisper"),
+                            multispace1,
+                            keyword_tag("beyond"),
+                            multispace1,
+                        )),
+                        parse_string,
+                        multispace1,
+                        Vec::<Expr>::parse,
+                    )),
+                    |(_, address, _, exprs)| Stmt::Whisper(String::from(address), exprs),
+                ),
+                parse_shamble,
+                map(keyword_tag("stumble"), |_| Stmt::Stumble),
+                map(keyword_tag("flee"), |_| Stmt::Flee),
+                map(keyword_tag("lurch"), |_| Stmt::Lurch),
+                parse_taste,
+                parse_consult,
+            )),
+        ))(code)
+    }
+}
+
+/// Parse a shamble, either `around`, `until` a condition, or a number of `times`.
+///
+/// The body is parsed statement by statement, so that it may contain shambles of its own.
+fn parse_shamble(code: &str) -> IResult<&str, Stmt> {
+    let body = || {
+        map(
+            many_till(
+                terminated(Stmt::parse, multispace1),
+                peek(alt((keyword_tag("around"), keyword_tag("until")))),
             ),
-            map(
-                tuple((
-                    pair(tag("shamble"), multispace1),
-                    map_parser(
-                        take_until("until"),
-                        all_consuming(many0(terminated(Stmt::parse, multispace1))),
+            |(stmts, _)| stmts,
+        )
+    };
+    alt((
+        map(
+            tuple((
+                preceded(pair(keyword_tag("shamble"), multispace1), Expr::parse),
+                delimited(
+                    tuple((multispace1, keyword_tag("times"), multispace1)),
+                    body(),
+                    keyword_tag("around"),
+                ),
+            )),
+            |(count, stmts)| Stmt::ShambleTimes(count, stmts),
+        ),
+        map(
+            pair(
+                preceded(pair(keyword_tag("shamble"), multispace1), body()),
+                alt((
+                    map(keyword_tag("around"), |_| None),
+                    map(
+                        preceded(pair(keyword_tag("until"), multispace1), Expr::parse),
+                        Some,
                     ),
-                    preceded(pair(tag("until"), multispace1), Expr::parse),
                 )),
-                |(_, statements, expr)| Stmt::ShambleUntil(expr, statements),
             ),
-            map(tag("stumble"), |_| Stmt::Stumble),
-            map(
-                tuple((
-                    preceded(pair(tag("taste"), multispace1), Expr::parse),
+            |(stmts, until)| match until {
+                Some(expr) => Stmt::ShambleUntil(expr, stmts),
+                None => Stmt::ShambleAround(stmts),
+            },
+        ),
+    ))(code)
+}
+
+/// Parse a taste, including any tastes chained to it with `otherwise`.
+///
+/// `taste A good ... otherwise taste B good ... bad ... spit` is the same as a taste of `B`
+/// nested in the bad branch of the taste of `A`. The branches are parsed statement by statement,
+/// so that they may contain tastes of their own, with or without a bad branch.
+fn parse_taste(code: &str) -> IResult<&str, Stmt> {
+    map(
+        tuple((
+            preceded(pair(keyword_tag("taste"), multispace1), Expr::parse),
+            preceded(
+                tuple((multispace1, keyword_tag("good"), multispace1)),
+                many_till(
+                    terminated(Stmt::parse, multispace1),
+                    peek(alt((
+                        keyword_tag("otherwise"),
+                        keyword_tag("bad"),
+                        keyword_tag("spit"),
+                    ))),
+                ),
+            ),
+            alt((
+                map(
+                    preceded(pair(keyword_tag("otherwise"), multispace1), parse_taste),
+                    |taste| vec![taste],
+                ),
+                map(
                     preceded(
-                        tuple((multispace1, tag("good"), multispace1)),
-                        map_parser(
-                            take_until("bad"),
-                            all_consuming(many0(terminated(Stmt::parse, multispace1))),
-                        ),
+                        pair(keyword_tag("bad"), multispace1),
+                        many_till(terminated(Stmt::parse, multispace1), keyword_tag("spit")),
                     ),
-                    delimited(
-                        pair(tag("bad"), multispace1),
-                        map_parser(
-                            take_until("spit"),
-                            all_consuming(many0(terminated(Stmt::parse, multispace1))),
+                    |(bad, _)| bad,
+                ),
+                map(keyword_tag("spit"), |_| Vec::new()),
+            )),
+        )),
+        |(condition, (good, _), bad)| Stmt::Taste(condition, good, bad),
+    )(code)
+}
+
+/// Parse a consult, with any number of cases and an optional `lest` branch.
+fn parse_consult(code: &str) -> IResult<&str, Stmt> {
+    let block_end = || {
+        peek(alt((
+            keyword_tag("upon"),
+            keyword_tag("lest"),
+            keyword_tag("settle"),
+        )))
+    };
+    map(
+        tuple((
+            preceded(pair(keyword_tag("consult"), multispace1), Expr::parse),
+            preceded(
+                multispace1,
+                many1(preceded(
+                    pair(keyword_tag("upon"), multispace1),
+                    separated_pair(
+                        Value::parse,
+                        multispace1,
+                        map(
+                            many_till(terminated(Stmt::parse, multispace1), block_end()),
+                            |(stmts, _)| stmts,
                         ),
-                        tag("spit"),
                     ),
                 )),
-                |(condition, good, bad)| Stmt::Taste(condition, good, bad),
             ),
-        ))(code)
-    }
+            alt((
+                map(
+                    preceded(
+                        pair(keyword_tag("lest"), multispace1),
+                        many_till(terminated(Stmt::parse, multispace1), keyword_tag("settle")),
+                    ),
+                    |(lest, _)| lest,
+                ),
+                map(keyword_tag("settle"), |_| Vec::new()),
+            )),
+        )),
+        |(expr, cases, lest)| Stmt::Consult(expr, cases, lest),
+    )(code)
 }
 
 impl<'a> Parse<'a> for Vec<Expr> {
@@ -334,13 +807,21 @@ impl<'a> Parse<'a> for Expr {
         trace!("Code (expression): {}", code);
         alt((
             map(
-                separated_pair(tag("moan"), multispace1, parse_identifier),
+                delimited(
+                    pair(char('('), multispace0),
+                    Vec::<Expr>::parse,
+                    pair(multispace0, char(')')),
+                ),
+                Expr::Group,
+            ),
+            map(
+                separated_pair(keyword_tag("moan"), multispace1, parse_identifier),
                 |(_, name)| Expr::Moan(Some(name.into())),
             ),
-            map(tag("moan"), |_| Expr::Moan(None)),
+            map(keyword_tag("moan"), |_| Expr::Moan(None)),
             map(
                 tuple((
-                    tag("remembering"),
+                    keyword_tag("remembering"),
                     multispace1,
                     parse_identifier,
                     multispace1,
@@ -349,11 +830,58 @@ impl<'a> Parse<'a> for Expr {
                 |(_, _, name, _, value)| Expr::Remembering(Some(name.into()), value),
             ),
             map(
-                separated_pair(tag("remembering"), multispace1, Value::parse),
+                separated_pair(keyword_tag("remembering"), multispace1, Value::parse),
                 |(_, value)| Expr::Remembering(None, value),
             ),
-            map(tag("rend"), |_| Expr::Rend),
-            map(tag("turn"), |_| Expr::Turn),
+            map(
+                tuple((
+                    keyword_tag("reminisce"),
+                    multispace1,
+                    parse_identifier,
+                    multispace1,
+                    map_res(digit1, str::parse),
+                )),
+                |(_, _, name, _, n)| Expr::Reminisce(Some(name.into()), n),
+            ),
+            map(
+                separated_pair(
+                    keyword_tag("reminisce"),
+                    multispace1,
+                    map_res(digit1, str::parse),
+                ),
+                |(_, n)| Expr::Reminisce(None, n),
+            ),
+            map(keyword_tag("rend"), |_| Expr::Rend),
+            map(keyword_tag("turn"), |_| Expr::Turn),
+            map(
+                separated_pair(
+                    keyword_tag("fester"),
+                    multispace1,
+                    map_res(recognize(pair(opt(one_of("+-")), digit1)), str::parse),
+                ),
+                |(_, exponent)| Expr::Fester(exponent),
+            ),
+            map(
+                separated_pair(keyword_tag("divine"), multispace1, parse_string),
+                |(_, var)| Expr::Divine(String::from(var)),
+            ),
+            map(
+                separated_pair(keyword_tag("compose"), multispace1, parse_string),
+                |(_, format)| Expr::Compose(String::from(format)),
+            ),
+            map(
+                tuple((
+                    keyword_tag("transcribe"),
+                    multispace1,
+                    keyword_tag("as"),
+                    multispace1,
+                    alt((
+                        value(Radix::Binary, keyword_tag("binary")),
+                        value(Radix::Hex, keyword_tag("hex")),
+                    )),
+                )),
+                |(_, _, _, _, radix)| Expr::Transcribe(radix),
+            ),
             map(Value::parse, Expr::Value),
         ))(code)
     }
@@ -364,20 +892,68 @@ impl<'a> Parse<'a> for Value {
         trace!("Code (value): {}", code);
         alt((
             map(parse_integer, Value::Integer),
-            map(parse_string, |s| Value::String(String::from(s))),
+            map(parse_string, |s| Value::String(SmolStr::from(s))),
         ))(code)
     }
 }
 
 /// Parse an integer.
 ///
-/// Supports positive and negative integers.
+/// Supports an optional sign (`+5`, `-5`). Digits may be separated by underscores (`1_000_000`).
+/// Integers are either hexadecimal (`0x1F`) or decimal with an optional exponent (`1e6`).
+///
+/// Fails without backtracking if the number is directly followed by letters or digits that don't
+/// belong to it (`12abc`, `0xFG`), or if its exponent makes it too large to be remembered
+/// (`1e300000000`), so that the error points at the invalid number literal.
 fn parse_integer(code: &str) -> IResult<&str, Integer> {
     trace!("Code (int): {}", code);
-    map_opt(
-        alt((digit1, recognize(pair(char('-'), digit1)))),
-        FromSciString::from_sci_string,
-    )(code)
+    let literal = code;
+    let (code, sign) = opt(one_of("+-"))(code)?;
+    let (code, integer) = alt((
+        map(
+            preceded(tag_no_case("0x"), separated(|c| c.is_ascii_hexdigit())),
+            |digits| Integer::from_string_base(16, &digits.replace('_', "")),
+        ),
+        map(
+            recognize(pair(
+                separated(|c| c.is_ascii_digit()),
+                opt(pair(one_of("eE"), digit1)),
+            )),
+            |digits| decimal(&digits.replace('_', "")),
+        ),
+    ))(code)?;
+    match integer {
+        Some(integer) if !code.starts_with(|c: char| c.is_alphanumeric() || c == '_') => {
+            Ok((code, if sign == Some('-') { -integer } else { integer }))
+        }
+        _ => Err(nom::Err::Failure(Error::new(literal, ErrorKind::Digit))),
+    }
+}
+
+/// Convert decimal digits with an optional exponent to an integer, unless it has more bits than
+/// any power may have.
+fn decimal(digits: &str) -> Option<Integer> {
+    let (mantissa, exponent) = match digits.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<u64>().ok()?),
+        None => (digits, 0),
+    };
+    // A decimal digit takes less than 10/3 bits.
+    let bits = (mantissa.len() as u64)
+        .saturating_add(exponent)
+        .saturating_mul(10)
+        / 3;
+    if bits > MAX_POWER_BITS {
+        return None;
+    }
+    Integer::from_sci_string(digits)
+}
+
+/// Recognize a sequence of digits, which may be separated by underscores after the first one.
+fn separated<'a>(digit: fn(char) -> bool) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+    recognize(pair(
+        take_while1(digit),
+        take_while(move |c| digit(c) || c == '_'),
+    ))
 }
 
 /// Parse a string.
@@ -390,11 +966,13 @@ fn parse_string(code: &str) -> IResult<&str, &str> {
 
 /// Parse an identifier.
 ///
-/// An identifier is a string of alphanumeric characters starting with a letter. Keywords are not allowed as identifiers.
+/// An identifier follows the Unicode rules for identifiers: it starts with a character of class
+/// XID_Start, such as a letter, followed by any number of characters of class XID_Continue, such
+/// as letters, digits, underscores and combining marks. Keywords are not allowed as identifiers.
 fn parse_identifier(code: &str) -> IResult<&str, &str> {
     trace!("Code (identifier): {}", code);
     peek(not(keyword))(code)?;
-    recognize(pair(alpha1, alphanumeric0))(code)
+    recognize(pair(satisfy(is_xid_start), take_while(is_xid_continue)))(code)
 }
 
 /// Recognize a keyword.
@@ -403,44 +981,396 @@ fn parse_identifier(code: &str) -> IResult<&str, &str> {
 fn keyword(code: &str) -> IResult<&str, &str> {
     recognize(alt((
         alt((
-            tag("zombie"),
-            tag("enslaved undead"),
-            tag("ghost"),
-            tag("restless undead"),
-            tag("vampire"),
-            tag("free-willed undead"),
-            tag("demon"),
-            tag("djin"),
-            tag("summon"),
-            tag("animate"),
-            tag("disturb"),
-            tag("bind"),
-            tag("task"),
-            tag("remember"),
-            tag("moan"),
-            tag("banish"),
-            tag("forget"),
-            tag("invoke"),
-            tag("say"),
-            tag("shamble"),
-            tag("until"),
+            keyword_tag("zombie"),
+            keyword_tag("enslaved undead"),
+            keyword_tag("ghost"),
+            keyword_tag("restless undead"),
+            keyword_tag("vampire"),
+            keyword_tag("free-willed undead"),
+            keyword_tag("demon"),
+            keyword_tag("djinn"),
+            keyword_tag("summon"),
+            keyword_tag("animate"),
+            keyword_tag("disturb"),
+            keyword_tag("bind"),
+            keyword_tag("task"),
+            keyword_tag("remember"),
+            keyword_tag("moan"),
+            keyword_tag("banish"),
+            keyword_tag("forget"),
+            keyword_tag("invoke"),
+            keyword_tag("say"),
+            keyword_tag("shamble"),
+            keyword_tag("until"),
+        )),
+        alt((
+            keyword_tag("around"),
+            keyword_tag("stumble"),
+            keyword_tag("taste"),
+            keyword_tag("good"),
+            keyword_tag("spit"),
+            keyword_tag("remembering"),
+            keyword_tag("reminisce"),
+            keyword_tag("rend"),
+            keyword_tag("turn"),
+            keyword_tag("fester"),
+            keyword_tag("divine"),
+            keyword_tag("entomb"),
+            keyword_tag("exhume"),
+            keyword_tag("channel"),
+            keyword_tag("whisper"),
+            keyword_tag("beyond"),
+            keyword_tag("harvest"),
+            keyword_tag("perform"),
+            keyword_tag("lich"),
+            keyword_tag("wraith"),
+            keyword_tag("revenant"),
         )),
         alt((
-            tag("around"),
-            tag("stumble"),
-            tag("taste"),
-            tag("good"),
-            tag("spit"),
-            tag("remembering"),
-            tag("rend"),
-            tag("turn"),
+            keyword_tag("coven"),
+            keyword_tag("containing"),
+            keyword_tag("bad"),
+            keyword_tag("otherwise"),
+            keyword_tag("consult"),
+            keyword_tag("upon"),
+            keyword_tag("lest"),
+            keyword_tag("settle"),
+            keyword_tag("flee"),
+            keyword_tag("lurch"),
+            keyword_tag("times"),
+            keyword_tag("compose"),
+            keyword_tag("transcribe"),
         )),
     )))(code)
 }
 
-pub fn parse(code: &str) -> Result<Scroll, Error<&str>> {
-    match Finish::finish(terminated(Scroll::parse, pair(multispace0, eof))(code)) {
+/// Recognize the keyword as a whole word, i.e. unless it is only the beginning of a name like
+/// `Sayid`. Ignores case in relaxed mode.
+fn keyword_tag<'a>(keyword: &'static str) -> impl Fn(&'a str) -> IResult<&'a str, &'a str> {
+    move |code| {
+        let (rest, word) = if RELAXED.get() {
+            tag_no_case(keyword)(code)?
+        } else {
+            tag(keyword)(code)?
+        };
+        not(satisfy(is_xid_continue))(rest)?;
+        Ok((rest, word))
+    }
+}
+
+thread_local! {
+    /// Whether keywords are matched case-insensitively by the parse currently running on this
+    /// thread.
+    static RELAXED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Settings of the parser.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParseConfig {
+    relaxed: bool,
+}
+
+impl ParseConfig {
+    /// Accept keywords regardless of case, like `Summon` or `ANIMATE`. Names of entities and
+    /// tasks stay case-sensitive, but may not be a keyword in any case. Disabled by
+    /// default.
+    pub fn relaxed(mut self, relaxed: bool) -> ParseConfig {
+        self.relaxed = relaxed;
+        self
+    }
+
+    /// Whether keywords are accepted regardless of case.
+    pub fn is_relaxed(&self) -> bool {
+        self.relaxed
+    }
+}
+
+/// An error that occurred while parsing a scroll. Points at the remaining source code.
+///
+/// See [`ParseError`] for an owned error that only knows the position in the scroll.
+pub type SyntaxError<'a> = Error<&'a str>;
+
+/// Parse the scroll with the default settings.
+pub fn parse(code: &str) -> Result<Scroll, SyntaxError<'_>> {
+    parse_with(code, ParseConfig::default())
+}
+
+/// Parse the scroll with the given settings.
+pub fn parse_with(code: &str, config: ParseConfig) -> Result<Scroll, SyntaxError<'_>> {
+    let result = configured(config, || {
+        Finish::finish(terminated(Scroll::parse, pair(multispace0, eof))(code))
+    });
+    match result {
         Ok((_, tree)) => Ok(tree),
         Err(error) => Err(error),
     }
 }
+
+/// Parse the statement at the start of the code, like `say moan Peter`, and return it with the
+/// code following it.
+pub fn parse_statement(code: &str) -> Result<(Stmt, &str), SyntaxError<'_>> {
+    Finish::finish(preceded(multispace0, Stmt::parse)(code)).map(|(rest, stmt)| (stmt, rest))
+}
+
+/// Parse the expression at the start of the code, like `moan Peter`, and return it with the code
+/// following it. Only the first expression of a statement stack is parsed.
+pub fn parse_expression(code: &str) -> Result<(Expr, &str), SyntaxError<'_>> {
+    Finish::finish(preceded(multispace0, Expr::parse)(code)).map(|(rest, expr)| (expr, rest))
+}
+
+/// Parse the scroll with the given settings, without stopping at the first error.
+///
+/// When an entity can't be parsed, the parser records the error and continues with the next
+/// entity header or coven declaration. A broken entity whose header can be read is replaced by an inactive placeholder
+/// without tasks, so that references to it stay valid. The errors are returned in order of their
+/// appearance, together with the scroll made from everything that could be parsed.
+pub fn parse_recovering(code: &str, config: ParseConfig) -> (Scroll, Vec<SyntaxError<'_>>) {
+    configured(config, || {
+        let mut errors = Vec::new();
+        let (mut rest, meta) = match opt(preceded(multispace0, ScrollMeta::parse))(code) {
+            Ok(result) => result,
+            Err(nom::Err::Error(e) | nom::Err::Failure(e)) => {
+                errors.push(e);
+                (skip_to_item(code), None)
+            }
+            Err(nom::Err::Incomplete(_)) => (code, None),
+        };
+
+        let mut items = Vec::new();
+        loop {
+            let (code, _) = multispace0::<_, Error<_>>(rest).unwrap_or((rest, ""));
+            if code.is_empty() {
+                break;
+            }
+            let result = terminated(
+                parse_item,
+                alt((recognize(pair(multispace0, eof)), recognize(multispace1))),
+            )(code);
+            match result {
+                Ok((code, item)) => {
+                    items.push(item);
+                    rest = code;
+                }
+                Err(nom::Err::Error(e) | nom::Err::Failure(e)) => {
+                    errors.push(e);
+                    if let Ok((_, (name, species, rank, aliases))) = parse_entity_header(code) {
+                        debug!("Replacing broken creature {} with a placeholder.", name);
+                        let mut entity = Entity::builder(name, species).active(false).rank(rank);
+                        for alias in aliases {
+                            entity = entity.alias(alias);
+                        }
+                        items.push(Item::Entity(entity.build()));
+                    }
+                    rest = skip_to_item(code);
+                }
+                Err(nom::Err::Incomplete(_)) => break,
+            }
+        }
+
+        let mut scroll = assemble(items);
+        *scroll.meta_mut() = meta;
+        (scroll, errors)
+    })
+}
+
+/// An error that occurred while parsing a scroll from a reader.
+#[derive(thiserror::Error, Debug)]
+pub enum ReadError {
+    /// The scroll could not be read.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// The scroll could not be parsed.
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+}
+
+/// The position in the scroll where parsing failed, and what the parser expected there.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("cannot parse the scroll at line {line}, column {column}: expected {expected}")]
+pub struct ParseError {
+    line: usize,
+    column: usize,
+    expected: String,
+}
+
+impl ParseError {
+    /// The line where parsing failed, starting at 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// The column where parsing failed, starting at 1.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// What the parser expected, e.g. `a keyword`.
+    pub fn expected(&self) -> &str {
+        &self.expected
+    }
+}
+
+/// Parse the scroll from the reader with the given settings, one entity at a time.
+///
+/// Only the code of the entity currently being parsed is kept in memory, instead of the code of
+/// the whole scroll. Entities end where the header of the next entity or a coven declaration
+/// begins.
+pub fn parse_reader<R: BufRead>(mut reader: R, config: ParseConfig) -> Result<Scroll, ReadError> {
+    configured(config, || {
+        let mut chunk = String::new();
+        // Line of the scroll that the chunk starts at.
+        let mut line = 1;
+        // Where the header of the current entity or coven starts in the chunk, once it has been
+        // found.
+        let mut header: Option<usize> = None;
+        // Starts of non-blank lines in the chunk that might still turn out to be headers.
+        let mut candidates: Vec<usize> = Vec::new();
+        let mut meta = None;
+        let mut items = Vec::new();
+
+        loop {
+            let start = chunk.len();
+            let finished = reader.read_line(&mut chunk)? == 0;
+            if !chunk[start..].trim().is_empty() {
+                candidates.push(start);
+            }
+
+            while let Some(&candidate) = candidates.first() {
+                let rest = &chunk[candidate..];
+                let is_header = match starts_with_item(rest.trim_start()) {
+                    Some(is_header) => is_header,
+                    None if !finished => break,
+                    None => false,
+                };
+                candidates.remove(0);
+                if !is_header {
+                    continue;
+                }
+                match header {
+                    None => header = Some(candidate),
+                    Some(header_start) => {
+                        let code = &chunk[..candidate];
+                        if items.is_empty() {
+                            meta = parse_prologue(&code[..header_start], line)?;
+                        }
+                        let header_line = line + code[..header_start].matches('\n').count();
+                        items.push(parse_chunk(&code[header_start..], header_line)?);
+                        line += code.matches('\n').count();
+                        chunk.drain(..candidate);
+                        candidates.iter_mut().for_each(|start| *start -= candidate);
+                        header = Some(0);
+                    }
+                }
+            }
+
+            if finished {
+                break;
+            }
+        }
+
+        match header {
+            Some(header_start) => {
+                if items.is_empty() {
+                    meta = parse_prologue(&chunk[..header_start], line)?;
+                }
+                let header_line = line + chunk[..header_start].matches('\n').count();
+                items.push(parse_chunk(&chunk[header_start..], header_line)?);
+            }
+            // Let the parser explain what is wrong with the scroll.
+            None => {
+                let error =
+                    Finish::finish(terminated(Scroll::parse, pair(multispace0, eof))(&chunk))
+                        .err()
+                        .unwrap_or(Error::new(&chunk, ErrorKind::Many1));
+                return Err(read_error(&chunk, line, error).into());
+            }
+        }
+
+        let mut scroll = assemble(items);
+        *scroll.meta_mut() = meta;
+        Ok(scroll)
+    })
+}
+
+/// Parse the optional prologue in front of the first entity.
+fn parse_prologue(code: &str, line: usize) -> Result<Option<ScrollMeta>, ParseError> {
+    Finish::finish(all_consuming(delimited(
+        multispace0,
+        opt(ScrollMeta::parse),
+        multispace0,
+    ))(code))
+    .map(|(_, meta)| meta)
+    .map_err(|e| read_error(code, line, e))
+}
+
+/// Parse the code of a single entity or coven, which starts at the given line.
+fn parse_chunk(code: &str, line: usize) -> Result<Item, ParseError> {
+    Finish::finish(delimited(multispace0, parse_item, pair(multispace0, eof))(
+        code,
+    ))
+    .map(|(_, item)| item)
+    .map_err(|e| read_error(code, line, e))
+}
+
+/// Locate the error in the scroll, given the line that the code starts at.
+fn read_error(code: &str, line: usize, error: Error<&str>) -> ParseError {
+    let position = (error.input.as_ptr() as usize)
+        .saturating_sub(code.as_ptr() as usize)
+        .min(code.len());
+    let before = &code[..position];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    ParseError {
+        line: line + before.matches('\n').count(),
+        column: before[line_start..].chars().count() + 1,
+        expected: expected(error.code),
+    }
+}
+
+/// Whether the code begins with an entity header or a coven declaration, or `None` if that
+/// depends on code that has not been read yet, because the code ends in the middle of what might
+/// be one.
+fn starts_with_item(code: &str) -> Option<bool> {
+    match (
+        starts_with(parse_entity_header, code),
+        starts_with(parse_coven, code),
+    ) {
+        (Some(true), _) | (_, Some(true)) => Some(true),
+        (None, _) | (_, None) => None,
+        _ => Some(false),
+    }
+}
+
+/// Whether the parser accepts the start of the code, or `None` if the code ends before the
+/// parser could decide.
+fn starts_with<'a, O>(
+    mut parser: impl FnMut(&'a str) -> IResult<&'a str, O>,
+    code: &'a str,
+) -> Option<bool> {
+    match parser(code) {
+        Ok(_) => Some(true),
+        Err(nom::Err::Error(e) | nom::Err::Failure(e)) if e.input.trim().is_empty() => None,
+        Err(_) => Some(false),
+    }
+}
+
+/// Skip to the start of the next line that begins with an entity header or a coven declaration,
+/// or to the end of the code if there is none. The current line is never a candidate.
+fn skip_to_item(code: &str) -> &str {
+    let mut rest = code;
+    while let Some(newline) = rest.find('\n') {
+        rest = &rest[newline + 1..];
+        let line = rest.trim_start();
+        if parse_entity_header(line).is_ok() || parse_coven(line).is_ok() {
+            return rest;
+        }
+    }
+    &code[code.len()..]
+}
+
+/// Run the parser with the given settings on this thread.
+fn configured<T>(config: ParseConfig, parser: impl FnOnce() -> T) -> T {
+    let relaxed = RELAXED.replace(config.relaxed);
+    let result = parser();
+    RELAXED.set(relaxed);
+    result
+}
@@ -0,0 +1,321 @@
+//! Suggestions for fixing common mistakes that keep a scroll from being parsed: a task or an
+//! entity without `summon`, a task that doesn't end with `animate`, and a `shamble` without
+//! `around`.
+//!
+//! Each mistake is fixed by inserting a line with the missing keyword. Instead of guessing from
+//! the error alone, the candidate lines are inserted near the error one by one, and the scroll is
+//! parsed again. A suggestion is only made if a single candidate gets the parser further than
+//! any other one.
+use std::fmt::{Display, Formatter, Result};
+
+use super::{parse_entity_declaration, parse_with, ParseConfig, SyntaxError};
+
+/// A keyword that may be missing.
+struct Missing {
+    keyword: &'static str,
+    /// Whether the line opens what the keyword belongs to. The keyword takes the indentation of
+    /// that line.
+    opens: fn(&str) -> bool,
+    /// Whether the keyword closes what it belongs to, so that it belongs at the first line that
+    /// is not indented further than the opening line. Otherwise it directly follows that line.
+    closes: bool,
+}
+
+const MISSING: [Missing; 3] = [
+    Missing {
+        keyword: "summon",
+        opens: |line| parse_entity_declaration(line).is_ok(),
+        closes: false,
+    },
+    Missing {
+        keyword: "animate",
+        opens: |line| starts_with_word(line, "task"),
+        closes: true,
+    },
+    Missing {
+        keyword: "around",
+        opens: |line| starts_with_word(line, "shamble"),
+        closes: true,
+    },
+];
+
+/// How many non-blank lines, starting at the error, may receive the missing keyword.
+const WINDOW: usize = 4;
+
+/// A line to insert into the code of a scroll in order to fix an error of the parser.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    keyword: &'static str,
+    /// The line before which to insert, starting at 1.
+    line: usize,
+    /// The byte offset of the start of that line.
+    offset: usize,
+    /// The inserted text, including indentation and line break.
+    text: String,
+}
+
+impl Suggestion {
+    /// The missing keyword, e.g. `animate`.
+    pub fn keyword(&self) -> &str {
+        self.keyword
+    }
+
+    /// The line before which the keyword is inserted, starting at 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Insert the keyword into the code that the suggestion was made for.
+    pub fn apply(&self, code: &str) -> String {
+        let mut fixed = String::with_capacity(code.len() + self.text.len());
+        fixed.push_str(&code[..self.offset]);
+        fixed.push_str(&self.text);
+        fixed.push_str(&code[self.offset..]);
+        fixed
+    }
+}
+
+impl Display for Suggestion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "insert `{}` before line {}", self.keyword, self.line)
+    }
+}
+
+/// Suggest how to fix the first error that the parser finds in the code, unless the code can be
+/// parsed or there is no unambiguous fix.
+pub fn suggest(code: &str, config: ParseConfig) -> Option<Suggestion> {
+    let error = offset(code, &parse_with(code, config).err()?);
+    let lines = line_starts(code);
+
+    // The code before the error was accepted by the parser, so it is left as it is. Candidates
+    // are ranked by how far the parser gets with them, and any that don't get it past the
+    // inserted line are dropped.
+    let mut best: Option<(usize, Suggestion)> = None;
+    let mut tied = false;
+    let candidates = (0..lines.len())
+        .filter(|&line| lines[line] >= error)
+        .filter(|&line| !line_text(code, &lines, line).trim().is_empty())
+        .take(WINDOW);
+    for line in candidates {
+        let offset = lines[line];
+        let text = line_text(code, &lines, line);
+        let depth = indentation(text).len();
+        for Missing {
+            keyword,
+            opens,
+            closes,
+        } in MISSING
+        {
+            let Some(opening) = (0..line)
+                .rev()
+                .find(|&above| opens(line_text(code, &lines, above).trim_start()))
+            else {
+                continue;
+            };
+            let indent = indentation(line_text(code, &lines, opening));
+            let fits = if closes {
+                depth <= indent.len()
+            } else {
+                (opening + 1..line)
+                    .all(|between| line_text(code, &lines, between).trim().is_empty())
+            };
+            if !fits {
+                continue;
+            }
+            let suggestion = Suggestion {
+                keyword,
+                line: line + 1,
+                offset,
+                text: format!("{}{}\n", indent, keyword),
+            };
+            let fixed = suggestion.apply(code);
+            let reached = match parse_with(&fixed, config) {
+                Ok(_) => usize::MAX,
+                Err(e) => self::offset(&fixed, &e),
+            };
+            if reached <= offset + suggestion.text.len() {
+                continue;
+            }
+            match &best {
+                Some((furthest, _)) if reached < *furthest => {}
+                Some((furthest, _)) if reached == *furthest => tied = true,
+                _ => {
+                    best = Some((reached, suggestion));
+                    tied = false;
+                }
+            }
+        }
+    }
+    best.filter(|_| !tied).map(|(_, suggestion)| suggestion)
+}
+
+/// Apply suggestions to the code until it can be parsed or there is no unambiguous fix left.
+/// Returns the fixed code and the suggestions applied to it, in order.
+pub fn fix(code: &str, config: ParseConfig) -> (String, Vec<Suggestion>) {
+    let mut code = String::from(code);
+    let mut applied = Vec::new();
+    // Every fix gets the parser further, but nothing guarantees that it gets anywhere in the end.
+    let limit = code.lines().count();
+    while applied.len() < limit {
+        let Some(suggestion) = suggest(&code, config) else {
+            break;
+        };
+        code = suggestion.apply(&code);
+        applied.push(suggestion);
+    }
+    (code, applied)
+}
+
+/// Whether the line starts with the keyword as a whole word.
+fn starts_with_word(line: &str, keyword: &str) -> bool {
+    line.strip_prefix(keyword)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+}
+
+/// The whitespace at the start of the line.
+fn indentation(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// The byte offset of the error in the code.
+fn offset(code: &str, error: &SyntaxError<'_>) -> usize {
+    (error.input.as_ptr() as usize)
+        .saturating_sub(code.as_ptr() as usize)
+        .min(code.len())
+}
+
+/// The byte offsets at which the lines of the code start.
+fn line_starts(code: &str) -> Vec<usize> {
+    let mut starts = vec![0];
+    starts.extend(code.match_indices('\n').map(|(i, _)| i + 1));
+    if starts.last() == Some(&code.len()) {
+        starts.pop();
+    }
+    starts
+}
+
+/// The text of the line with the given index, without the line break.
+fn line_text<'a>(code: &'a str, lines: &[usize], line: usize) -> &'a str {
+    let end = lines.get(line + 1).map_or(code.len(), |&next| next - 1);
+    code[lines[line]..end].trim_end_matches('\r')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suggest(code: &str) -> Option<String> {
+        super::suggest(code, ParseConfig::default()).map(|suggestion| suggestion.to_string())
+    }
+
+    #[test]
+    fn suggest_missing_animate() {
+        let code = "\
+Peter is a zombie
+summon
+    task Count
+        say 1
+    task Idle
+        say 2
+    animate
+animate
+";
+        assert_eq!(
+            suggest(code).as_deref(),
+            Some("insert `animate` before line 5")
+        );
+    }
+
+    #[test]
+    fn suggest_missing_summon() {
+        let code = "\
+Peter is a zombie
+    remember 1
+bind
+";
+        assert_eq!(
+            suggest(code).as_deref(),
+            Some("insert `summon` before line 2")
+        );
+    }
+
+    #[test]
+    fn suggest_missing_around() {
+        let code = "\
+Peter is a zombie
+summon
+    task Loop
+        shamble
+            say 1
+    animate
+animate
+";
+        assert_eq!(
+            suggest(code).as_deref(),
+            Some("insert `around` before line 6")
+        );
+    }
+
+    #[test]
+    fn no_suggestion() {
+        assert_eq!(suggest("Peter is a zombie\nsummon\nanimate\n"), None);
+        assert_eq!(suggest("Peter is a wombat\nsummon\nanimate\n"), None);
+    }
+
+    #[test]
+    fn fix_several_mistakes() {
+        let code = "\
+Peter is a zombie
+summon
+    task Loop
+        shamble
+            say 1
+    animate
+    task Count
+        say 2
+    task Idle
+        say 3
+    animate
+animate
+
+Lisa is a ghost
+    remember 2
+bind
+";
+        let (fixed, applied) = fix(code, ParseConfig::default());
+        let applied: Vec<String> = applied.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            applied,
+            [
+                "insert `around` before line 6",
+                "insert `animate` before line 10",
+                "insert `summon` before line 17",
+            ]
+        );
+        assert_eq!(
+            fixed,
+            "\
+Peter is a zombie
+summon
+    task Loop
+        shamble
+            say 1
+        around
+    animate
+    task Count
+        say 2
+    animate
+    task Idle
+        say 3
+    animate
+animate
+
+Lisa is a ghost
+summon
+    remember 2
+bind
+"
+        );
+        assert!(crate::parse::parse(&fixed).is_ok());
+    }
+}
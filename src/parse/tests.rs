@@ -1,11 +1,13 @@
+#![allow(clippy::bool_assert_comparison, clippy::get_first)]
+
 use super::*;
 use crate::scroll::expression::Expr;
-use crate::value::Value;
+use crate::value::{Radix, Value};
 
 fn init() {
-    let _ = env_logger::builder()
-        .filter_level(log::LevelFilter::Trace)
-        .is_test(true)
+    let _ = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::TRACE)
+        .with_test_writer()
         .try_init();
 }
 
@@ -207,7 +209,7 @@ animate";
     assert_eq!(recipe.creatures().get("Peter").unwrap().tasks().len(), 0);
     assert_eq!(
         recipe.creatures().get("Peter").unwrap().moan(),
-        Value::Integer(-161)
+        Value::Integer(Integer::from(-161))
     );
 
     assert_eq!(recipe.creatures().get("Jay").unwrap().tasks().len(), 2);
@@ -235,7 +237,7 @@ animate";
     );
     assert_eq!(
         recipe.creatures().get("Jay").unwrap().moan(),
-        Value::Integer(1312)
+        Value::Integer(Integer::from(1312))
     );
 }
 
@@ -253,6 +255,62 @@ fn parse_i64() {
     assert_eq!(num, 0);
 }
 
+#[test]
+fn parse_integer_forms() {
+    init();
+
+    let (_, num) = parse_integer("1_000_000").unwrap();
+    assert_eq!(num, 1_000_000);
+
+    let (_, num) = parse_integer("-1_000").unwrap();
+    assert_eq!(num, -1_000);
+
+    let (_, num) = parse_integer("0x1F").unwrap();
+    assert_eq!(num, 0x1F);
+
+    let (_, num) = parse_integer("-0XfF_fF").unwrap();
+    assert_eq!(num, -0xFFFF);
+
+    let (_, num) = parse_integer("1e6").unwrap();
+    assert_eq!(num, 1_000_000);
+
+    let (_, num) = parse_integer("-2_5E2").unwrap();
+    assert_eq!(num, -2_500);
+
+    let (_, num) = parse_integer("+5").unwrap();
+    assert_eq!(num, 5);
+
+    let (_, num) = parse_integer("+0x10").unwrap();
+    assert_eq!(num, 16);
+
+    let (_, num) = parse_integer("-0").unwrap();
+    assert_eq!(num, 0);
+
+    let (_, num) = parse_integer("+1e3").unwrap();
+    assert_eq!(num, 1_000);
+
+    let (code, num) = parse_integer("7 rend").unwrap();
+    assert_eq!((code, num), (" rend", Integer::from(7)));
+
+    assert!(matches!(parse_integer("_1"), Err(nom::Err::Error(_))));
+    assert!(matches!(parse_integer("-"), Err(nom::Err::Error(_))));
+    assert!(matches!(parse_integer("+-5"), Err(nom::Err::Error(_))));
+    assert!(matches!(parse_integer("12e"), Err(nom::Err::Failure(_))));
+    assert!(matches!(parse_integer("0x"), Err(nom::Err::Failure(_))));
+    assert!(matches!(parse_integer("0x1G"), Err(nom::Err::Failure(_))));
+    assert!(matches!(parse_integer("12abc"), Err(nom::Err::Failure(_))));
+
+    // Literals too large to be remembered are rejected right away instead of being computed.
+    let (_, num) = parse_integer("1e5000").unwrap();
+    assert_eq!(num.to_string().len(), 5001);
+    let error = parse_integer("1e300000000 rend").unwrap_err();
+    assert!(matches!(&error, nom::Err::Failure(e) if e.input == "1e300000000 rend"));
+    assert!(matches!(
+        parse_integer("1e99999999999999999999"),
+        Err(nom::Err::Failure(_))
+    ));
+}
+
 #[test]
 fn parse_str() {
     init();
@@ -272,22 +330,22 @@ fn parse_value() {
     init();
 
     let (_, num) = Value::parse("2341").unwrap();
-    assert_eq!(num, Value::Integer(2341));
+    assert_eq!(num, Value::Integer(Integer::from(2341)));
 
     let (_, num) = Value::parse("-2341").unwrap();
-    assert_eq!(num, Value::Integer(-2341));
+    assert_eq!(num, Value::Integer(Integer::from(-2341)));
 
     let (_, num) = Value::parse("0").unwrap();
-    assert_eq!(num, Value::Integer(0));
+    assert_eq!(num, Value::Integer(Integer::from(0)));
 
     let (_, s) = Value::parse("\"\"").unwrap();
-    assert_eq!(s, Value::String(String::from("")));
+    assert_eq!(s, Value::String(SmolStr::from("")));
 
     let (_, s) = Value::parse("\"foo\"").unwrap();
-    assert_eq!(s, Value::String(String::from("foo")));
+    assert_eq!(s, Value::String(SmolStr::from("foo")));
 
     let (_, s) = Value::parse("\"bar\"  fadf").unwrap();
-    assert_eq!(s, Value::String(String::from("bar")));
+    assert_eq!(s, Value::String(SmolStr::from("bar")));
 }
 
 #[test]
@@ -336,7 +394,7 @@ animate
             .statements()
             .get(0)
             .unwrap(),
-        &Stmt::Say(None, vec![Expr::Value(Value::Integer(-161))])
+        &Stmt::Say(None, vec![Expr::Value(Value::Integer(Integer::from(-161)))])
     );
     assert_eq!(
         recipe
@@ -349,7 +407,7 @@ animate
             .statements()
             .get(1)
             .unwrap(),
-        &Stmt::Say(None, vec![Expr::Value(Value::Integer(1312))])
+        &Stmt::Say(None, vec![Expr::Value(Value::Integer(Integer::from(1312)))])
     );
     assert_eq!(
         recipe
@@ -362,7 +420,10 @@ animate
             .statements()
             .get(2)
             .unwrap(),
-        &Stmt::Say(None, vec![Expr::Value(Value::String(String::from("+161")))])
+        &Stmt::Say(
+            None,
+            vec![Expr::Value(Value::String(SmolStr::from("+161")))]
+        )
     );
     assert_eq!(
         recipe
@@ -377,7 +438,7 @@ animate
             .unwrap(),
         &Stmt::Say(
             None,
-            vec![Expr::Value(Value::String(String::from("Hello World")))]
+            vec![Expr::Value(Value::String(SmolStr::from("Hello World")))]
         )
     );
     assert_eq!(
@@ -393,7 +454,7 @@ animate
             .unwrap(),
         &Stmt::Say(
             Some("Markus".into()),
-            vec![Expr::Value(Value::Integer(-161))]
+            vec![Expr::Value(Value::Integer(Integer::from(-161)))]
         )
     );
     assert_eq!(
@@ -409,7 +470,7 @@ animate
             .unwrap(),
         &Stmt::Say(
             Some("Dorni".into()),
-            vec![Expr::Value(Value::Integer(1312))]
+            vec![Expr::Value(Value::Integer(Integer::from(1312)))]
         )
     );
     assert_eq!(
@@ -425,7 +486,7 @@ animate
             .unwrap(),
         &Stmt::Say(
             Some("Isa".into()),
-            vec![Expr::Value(Value::String(String::from("Hello World")))]
+            vec![Expr::Value(Value::String(SmolStr::from("Hello World")))]
         )
     );
 }
@@ -476,7 +537,7 @@ animate
             .statements()
             .get(0)
             .unwrap(),
-        &Stmt::Remember(None, vec![Expr::Value(Value::Integer(-161))])
+        &Stmt::Remember(None, vec![Expr::Value(Value::Integer(Integer::from(-161)))])
     );
     assert_eq!(
         recipe
@@ -489,7 +550,7 @@ animate
             .statements()
             .get(1)
             .unwrap(),
-        &Stmt::Remember(None, vec![Expr::Value(Value::Integer(1312))])
+        &Stmt::Remember(None, vec![Expr::Value(Value::Integer(Integer::from(1312)))])
     );
     assert_eq!(
         recipe
@@ -502,7 +563,10 @@ animate
             .statements()
             .get(2)
             .unwrap(),
-        &Stmt::Remember(None, vec![Expr::Value(Value::String(String::from("+161")))])
+        &Stmt::Remember(
+            None,
+            vec![Expr::Value(Value::String(SmolStr::from("+161")))]
+        )
     );
     assert_eq!(
         recipe
@@ -517,7 +581,7 @@ animate
             .unwrap(),
         &Stmt::Remember(
             None,
-            vec![Expr::Value(Value::String(String::from("Hello World")))]
+            vec![Expr::Value(Value::String(SmolStr::from("Hello World")))]
         )
     );
     assert_eq!(
@@ -533,7 +597,7 @@ animate
             .unwrap(),
         &Stmt::Remember(
             Some("Markus".into()),
-            vec![Expr::Value(Value::Integer(-161))]
+            vec![Expr::Value(Value::Integer(Integer::from(-161)))]
         )
     );
     assert_eq!(
@@ -549,7 +613,7 @@ animate
             .unwrap(),
         &Stmt::Remember(
             Some("Dorni".into()),
-            vec![Expr::Value(Value::Integer(1312))]
+            vec![Expr::Value(Value::Integer(Integer::from(1312)))]
         )
     );
     assert_eq!(
@@ -565,7 +629,7 @@ animate
             .unwrap(),
         &Stmt::Remember(
             Some("Isa".into()),
-            vec![Expr::Value(Value::String(String::from("Hello World")))]
+            vec![Expr::Value(Value::String(SmolStr::from("Hello World")))]
         )
     );
 }
@@ -621,7 +685,7 @@ animate
             .statements()
             .get(0)
             .unwrap(),
-        &Stmt::Remember(None, vec![Expr::Value(Value::Integer(-161))])
+        &Stmt::Remember(None, vec![Expr::Value(Value::Integer(Integer::from(-161)))])
     );
     assert_eq!(
         recipe
@@ -634,7 +698,7 @@ animate
             .statements()
             .get(1)
             .unwrap(),
-        &Stmt::Remember(None, vec![Expr::Value(Value::Integer(1312))])
+        &Stmt::Remember(None, vec![Expr::Value(Value::Integer(Integer::from(1312)))])
     );
     assert_eq!(
         recipe
@@ -905,14 +969,14 @@ animate";
     assert_eq!(recipe.creatures().get("Zombie1").unwrap().tasks().len(), 0);
     assert_eq!(
         recipe.creatures().get("Zombie1").unwrap().moan(),
-        Value::Integer(1)
+        Value::Integer(Integer::from(1))
     );
 
     assert_eq!(recipe.creatures().get("Zombie2").unwrap().active(), false);
     assert_eq!(recipe.creatures().get("Zombie2").unwrap().tasks().len(), 0);
     assert_eq!(
         recipe.creatures().get("Zombie2").unwrap().moan(),
-        Value::Integer(1)
+        Value::Integer(Integer::from(1))
     );
 
     assert_eq!(recipe.creatures().get("Fibonacci").unwrap().active(), true);
@@ -945,7 +1009,10 @@ animate";
 
     match &statements[0] {
         Stmt::ShambleUntil(expr, statements) => {
-            assert_eq!(expr, &Expr::Remembering(None, Value::Integer(100)));
+            assert_eq!(
+                expr,
+                &Expr::Remembering(None, Value::Integer(Integer::from(100)))
+            );
 
             assert_eq!(statements.len(), 5);
             assert_eq!(
@@ -981,7 +1048,7 @@ animate";
                 Stmt::Remember(None, vec![Expr::Moan(Some("Zombie2".into()))])
             );
         }
-        _ => assert!(false),
+        _ => unreachable!(),
     }
 }
 
@@ -1043,7 +1110,7 @@ animate
             .get(0)
             .unwrap(),
         &Stmt::ShambleAround(vec![
-            Stmt::Say(None, vec![Expr::Value(Value::Integer(1312))]),
+            Stmt::Say(None, vec![Expr::Value(Value::Integer(Integer::from(1312)))]),
             Stmt::Remember(None, vec![Expr::Moan(None)]),
         ])
     );
@@ -1071,7 +1138,7 @@ animate
             .statements()
             .get(2)
             .unwrap(),
-        &Stmt::Remember(None, vec![Expr::Value(Value::String(String::from("foo")))])
+        &Stmt::Remember(None, vec![Expr::Value(Value::String(SmolStr::from("foo")))])
     );
     assert_eq!(
         recipe
@@ -1098,9 +1165,9 @@ animate
             .get(4)
             .unwrap(),
         &Stmt::ShambleUntil(
-            Expr::Remembering(None, Value::Integer(42)),
+            Expr::Remembering(None, Value::Integer(Integer::from(42))),
             vec![
-                Stmt::Say(None, vec![Expr::Value(Value::Integer(1312))]),
+                Stmt::Say(None, vec![Expr::Value(Value::Integer(Integer::from(1312)))]),
                 Stmt::Remember(None, vec![Expr::Moan(None)]),
             ]
         )
@@ -1116,7 +1183,10 @@ animate
             .statements()
             .get(5)
             .unwrap(),
-        &Stmt::ShambleUntil(Expr::Remembering(None, Value::Integer(42)), vec![])
+        &Stmt::ShambleUntil(
+            Expr::Remembering(None, Value::Integer(Integer::from(42))),
+            vec![]
+        )
     );
     assert_eq!(
         recipe
@@ -1132,7 +1202,7 @@ animate
         &Stmt::Taste(
             Expr::Moan(None),
             vec![
-                Stmt::Say(None, vec![Expr::Value(Value::Integer(1312))]),
+                Stmt::Say(None, vec![Expr::Value(Value::Integer(Integer::from(1312)))]),
                 Stmt::Remember(None, vec![Expr::Moan(None)]),
             ],
             vec![Stmt::Stumble]
@@ -1140,6 +1210,219 @@ animate
     );
 }
 
+#[test]
+fn parse_taste_without_bad() {
+    init();
+
+    let int = |i: i64| Value::Integer(Integer::from(i));
+    let say = |i: i64| Stmt::Say(None, vec![Expr::Value(int(i))]);
+
+    let (_, stmt) = Stmt::parse("taste moan good\n    say 1\nspit").unwrap();
+    assert_eq!(stmt, Stmt::Taste(Expr::Moan(None), vec![say(1)], vec![]));
+
+    let (_, stmt) = Stmt::parse("taste moan good spit").unwrap();
+    assert_eq!(stmt, Stmt::Taste(Expr::Moan(None), vec![], vec![]));
+
+    let code = "\
+taste remembering 1 good
+    taste remembering 2 good
+        say 2
+    spit
+bad
+    taste remembering 3 good
+        say 3
+    bad
+        say 4
+    spit
+    taste remembering 5 good
+    spit
+spit";
+    let (_, stmt) = Stmt::parse(code).unwrap();
+    assert_eq!(
+        stmt,
+        Stmt::Taste(
+            Expr::Remembering(None, int(1)),
+            vec![Stmt::Taste(
+                Expr::Remembering(None, int(2)),
+                vec![say(2)],
+                vec![]
+            )],
+            vec![
+                Stmt::Taste(Expr::Remembering(None, int(3)), vec![say(3)], vec![say(4)]),
+                Stmt::Taste(Expr::Remembering(None, int(5)), vec![], vec![]),
+            ]
+        )
+    );
+
+    assert!(Stmt::parse("taste moan good say 1").is_err());
+    assert!(Stmt::parse("taste moan good say 1 otherwise say 2 spit").is_err());
+    assert!(Stmt::parse("taste moan bad say 1 spit").is_err());
+}
+
+#[test]
+fn parse_taste_chain() {
+    init();
+
+    let int = |i: i64| Value::Integer(Integer::from(i));
+    let say = |i: i64| Stmt::Say(None, vec![Expr::Value(int(i))]);
+
+    let chained = "\
+taste remembering 1 good
+    say 1
+otherwise taste remembering 2 good
+    say 2
+otherwise taste remembering 3 good
+bad
+    say 4
+spit";
+    let nested = "\
+taste remembering 1 good
+    say 1
+bad
+    taste remembering 2 good
+        say 2
+    bad
+        taste remembering 3 good
+        bad
+            say 4
+        spit
+    spit
+spit";
+    let (_, stmt) = Stmt::parse(chained).unwrap();
+    assert_eq!(stmt, Stmt::parse(nested).unwrap().1);
+    assert_eq!(
+        stmt,
+        Stmt::Taste(
+            Expr::Remembering(None, int(1)),
+            vec![say(1)],
+            vec![Stmt::Taste(
+                Expr::Remembering(None, int(2)),
+                vec![say(2)],
+                vec![Stmt::Taste(
+                    Expr::Remembering(None, int(3)),
+                    vec![],
+                    vec![say(4)]
+                )]
+            )]
+        )
+    );
+
+    let (_, stmt) = Stmt::parse("taste moan good otherwise taste moan good spit").unwrap();
+    assert_eq!(
+        stmt,
+        Stmt::Taste(
+            Expr::Moan(None),
+            vec![],
+            vec![Stmt::Taste(Expr::Moan(None), vec![], vec![])]
+        )
+    );
+}
+
+#[test]
+fn parse_loop_control() {
+    init();
+
+    let code = "\
+shamble
+    shamble
+        lurch
+    until remembering 1
+    flee
+around";
+    let (_, stmt) = Stmt::parse(code).unwrap();
+    assert_eq!(
+        stmt,
+        Stmt::ShambleAround(vec![
+            Stmt::ShambleUntil(
+                Expr::Remembering(None, Value::Integer(Integer::from(1))),
+                vec![Stmt::Lurch]
+            ),
+            Stmt::Flee,
+        ])
+    );
+}
+
+#[test]
+fn parse_shamble_times() {
+    init();
+
+    let int = |i: i64| Value::Integer(Integer::from(i));
+
+    let (_, stmt) = Stmt::parse("shamble 3 times\n    say 1\naround").unwrap();
+    assert_eq!(
+        stmt,
+        Stmt::ShambleTimes(
+            Expr::Value(int(3)),
+            vec![Stmt::Say(None, vec![Expr::Value(int(1))])]
+        )
+    );
+
+    let (_, stmt) = Stmt::parse("shamble moan times shamble 2 times around around").unwrap();
+    assert_eq!(
+        stmt,
+        Stmt::ShambleTimes(
+            Expr::Moan(None),
+            vec![Stmt::ShambleTimes(Expr::Value(int(2)), vec![])]
+        )
+    );
+
+    assert!(Stmt::parse("shamble 3 times say 1 until remembering 1").is_err());
+    assert!(Stmt::parse("shamble 3 say 1 around").is_err());
+}
+
+#[test]
+fn parse_consult() {
+    init();
+
+    let int = |i: i64| Value::Integer(Integer::from(i));
+    let say = |i: i64| Stmt::Say(None, vec![Expr::Value(int(i))]);
+
+    let code = "\
+consult moan Peter
+upon 1
+    say 1
+upon \"two\"
+upon 3
+    consult moan
+    upon 4
+        say 4
+    settle
+    say 3
+lest
+    say 5
+settle";
+    let (_, stmt) = Stmt::parse(code).unwrap();
+    assert_eq!(
+        stmt,
+        Stmt::Consult(
+            Expr::Moan(Some("Peter".into())),
+            vec![
+                (int(1), vec![say(1)]),
+                (Value::from("two"), vec![]),
+                (
+                    int(3),
+                    vec![
+                        Stmt::Consult(Expr::Moan(None), vec![(int(4), vec![say(4)])], vec![]),
+                        say(3),
+                    ]
+                ),
+            ],
+            vec![say(5)]
+        )
+    );
+
+    let (_, stmt) = Stmt::parse("consult moan upon 1 settle").unwrap();
+    assert_eq!(
+        stmt,
+        Stmt::Consult(Expr::Moan(None), vec![(int(1), vec![])], vec![])
+    );
+
+    assert!(Stmt::parse("consult moan settle").is_err());
+    assert!(Stmt::parse("consult moan lest say 1 settle").is_err());
+    assert!(Stmt::parse("consult moan upon moan say 1 settle").is_err());
+    assert!(Stmt::parse("consult moan upon 1 say 1").is_err());
+}
+
 #[test]
 fn parse_expressions() {
     init();
@@ -1238,7 +1521,7 @@ animate
         &Stmt::Say(
             None,
             vec![
-                Expr::Remembering(None, Value::Integer(69)),
+                Expr::Remembering(None, Value::Integer(Integer::from(69))),
                 Expr::Moan(None),
             ]
         ),
@@ -1258,7 +1541,7 @@ animate
             None,
             vec![
                 Expr::Moan(Some("Y".into())),
-                Expr::Remembering(Some("X".into()), Value::Integer(1312))
+                Expr::Remembering(Some("X".into()), Value::Integer(Integer::from(1312)))
             ]
         ),
     );
@@ -1298,8 +1581,773 @@ animate
             None,
             vec![
                 Expr::Moan(None),
-                Expr::Value(Value::String(String::from("X")))
+                Expr::Value(Value::String(SmolStr::from("X")))
             ]
         ),
     );
 }
+
+#[test]
+fn parse_divine() {
+    init();
+
+    let (_, expr) = Expr::parse("divine \"HOME\"").unwrap();
+    assert_eq!(expr, Expr::Divine(String::from("HOME")));
+
+    let (_, exprs) = Vec::<Expr>::parse("moan divine  \"PATH\" 1").unwrap();
+    assert_eq!(
+        exprs,
+        vec![
+            Expr::Moan(None),
+            Expr::Divine(String::from("PATH")),
+            Expr::Value(Value::Integer(Integer::from(1)))
+        ]
+    );
+
+    assert!(Expr::parse("divine HOME").is_err());
+    assert!(parse_identifier("divine").is_err());
+}
+
+#[test]
+fn parse_compose() {
+    init();
+
+    let (_, exprs) = Vec::<Expr>::parse("compose \"%-6s|%3d\" moan Peter 7").unwrap();
+    assert_eq!(
+        exprs,
+        vec![
+            Expr::Compose(String::from("%-6s|%3d")),
+            Expr::Moan(Some("Peter".into())),
+            Expr::Value(Value::Integer(Integer::from(7)))
+        ]
+    );
+    assert_eq!(exprs[0].to_string(), "compose \"%-6s|%3d\"");
+
+    assert!(Expr::parse("compose %d").is_err());
+    assert!(parse_identifier("compose").is_err());
+}
+
+#[test]
+fn parse_transcribe() {
+    init();
+
+    let (_, exprs) = Vec::<Expr>::parse("transcribe as hex transcribe as binary moan").unwrap();
+    assert_eq!(
+        exprs,
+        vec![
+            Expr::Transcribe(Radix::Hex),
+            Expr::Transcribe(Radix::Binary),
+            Expr::Moan(None)
+        ]
+    );
+    assert_eq!(exprs[0].to_string(), "transcribe as hex");
+    assert_eq!(exprs[1].to_string(), "transcribe as binary");
+
+    assert!(Expr::parse("transcribe as hexagon").is_err());
+    assert!(Expr::parse("transcribe hex").is_err());
+    assert!(parse_identifier("transcribe").is_err());
+}
+
+#[test]
+fn parse_unicode_identifier() {
+    init();
+
+    assert_eq!(parse_identifier("Günther is"), Ok((" is", "Günther")));
+    assert_eq!(parse_identifier("幽霊 is"), Ok((" is", "幽霊")));
+    // "Günther" with a combining diaeresis.
+    assert_eq!(
+        parse_identifier("Gu\u{308}nther is"),
+        Ok((" is", "Gu\u{308}nther"))
+    );
+    assert_eq!(parse_identifier("Lisa_2 is"), Ok((" is", "Lisa_2")));
+
+    assert!(parse_identifier("\u{308}Lisa").is_err());
+    assert!(parse_identifier("_Lisa").is_err());
+    assert!(parse_identifier("2Lisa").is_err());
+    assert!(parse_identifier("summon").is_err());
+
+    let scroll = parse("Günther is a zombie\nsummon\nanimate").unwrap();
+    assert!(scroll.creatures().contains_key("Günther"));
+}
+
+#[test]
+fn parse_file_statements() {
+    init();
+
+    let (_, stmt) = Stmt::parse("exhume \"grave.txt\"").unwrap();
+    assert_eq!(stmt, Stmt::Exhume(String::from("grave.txt")));
+
+    let (_, stmt) = Stmt::parse("summon Peter within \"crypt/inner.z\"").unwrap();
+    assert_eq!(
+        stmt,
+        Stmt::SummonWithin("Peter".into(), String::from("crypt/inner.z"))
+    );
+
+    let (_, stmt) = Stmt::parse("entomb \"grave.txt\" moan Peter").unwrap();
+    assert_eq!(
+        stmt,
+        Stmt::Entomb(
+            String::from("grave.txt"),
+            vec![Expr::Moan(Some("Peter".into()))]
+        )
+    );
+
+    assert!(Stmt::parse("exhume grave").is_err());
+    assert!(Stmt::parse("entomb \"grave.txt\"").is_err());
+}
+
+#[test]
+fn parse_network_statements() {
+    init();
+
+    let (_, stmt) = Stmt::parse("channel 7777").unwrap();
+    assert_eq!(stmt, Stmt::Channel(7777));
+
+    let (_, stmt) = Stmt::parse("whisper beyond \"localhost:7777\" moan 1").unwrap();
+    assert_eq!(
+        stmt,
+        Stmt::Whisper(
+            String::from("localhost:7777"),
+            vec![
+                Expr::Moan(None),
+                Expr::Value(Value::Integer(Integer::from(1)))
+            ]
+        )
+    );
+
+    assert!(Stmt::parse("channel 77777").is_err());
+    assert!(Stmt::parse("channel Peter").is_err());
+    assert!(Stmt::parse("whisper \"localhost:7777\" 1").is_err());
+}
+
+#[test]
+fn parse_empty_remember() {
+    init();
+
+    let (_, stmt) = Stmt::parse("remember").unwrap();
+    assert_eq!(stmt, Stmt::Remember(None, vec![]));
+
+    let (_, stmt) = Stmt::parse("remember Peter").unwrap();
+    assert_eq!(stmt, Stmt::Remember(Some("Peter".into()), vec![]));
+
+    let code = "\
+Peter is a zombie
+summon
+    task Test
+        remember
+        remember Peter
+        say moan
+    animate
+animate
+";
+
+    let scroll = parse(code).unwrap();
+    let stmts = scroll
+        .creatures()
+        .get("Peter")
+        .unwrap()
+        .tasks()
+        .get("Test")
+        .unwrap()
+        .statements();
+    assert_eq!(
+        stmts,
+        &[
+            Stmt::Remember(None, vec![]),
+            Stmt::Remember(Some("Peter".into()), vec![]),
+            Stmt::Say(None, vec![Expr::Moan(None)]),
+        ]
+    );
+}
+
+#[test]
+fn parse_reminisce() {
+    init();
+
+    let (_, expr) = Expr::parse("reminisce 2").unwrap();
+    assert_eq!(expr, Expr::Reminisce(None, 2));
+
+    let (_, expr) = Expr::parse("reminisce Peter 1").unwrap();
+    assert_eq!(expr, Expr::Reminisce(Some("Peter".into()), 1));
+
+    assert!(Expr::parse("reminisce").is_err());
+    assert!(Expr::parse("reminisce -1").is_err());
+}
+
+#[test]
+fn parse_group() {
+    init();
+
+    let (_, exprs) = Vec::<Expr>::parse("rend ( moan Peter 2 ) (turn (1))").unwrap();
+    assert_eq!(
+        exprs,
+        vec![
+            Expr::Rend,
+            Expr::Group(vec![
+                Expr::Moan(Some("Peter".into())),
+                Expr::Value(Value::Integer(Integer::from(2)))
+            ]),
+            Expr::Group(vec![
+                Expr::Turn,
+                Expr::Group(vec![Expr::Value(Value::Integer(Integer::from(1)))])
+            ])
+        ]
+    );
+    let listing: Vec<String> = exprs.iter().map(Expr::to_string).collect();
+    assert_eq!(listing, vec!["rend", "(moan Peter 2)", "(turn (1))"]);
+
+    assert!(Expr::parse("()").is_err());
+    assert!(Expr::parse("(moan 2").is_err());
+}
+
+#[test]
+fn parse_fester() {
+    init();
+
+    let (_, exprs) = Vec::<Expr>::parse("fester 3 fester -2 moan").unwrap();
+    assert_eq!(
+        exprs,
+        vec![Expr::Fester(3), Expr::Fester(-2), Expr::Moan(None)]
+    );
+    assert_eq!(Expr::Fester(-2).to_string(), "fester -2");
+
+    assert!(Expr::parse("fester").is_err());
+    assert!(Expr::parse("fester moan").is_err());
+    assert!(parse_identifier("fester").is_err());
+}
+
+#[test]
+fn parse_harvest() {
+    init();
+
+    let (_, stmt) = Stmt::parse("invoke harvest").unwrap();
+    assert_eq!(stmt, Stmt::Harvest(None));
+
+    let (_, stmt) = Stmt::parse("invoke Peter harvest").unwrap();
+    assert_eq!(stmt, Stmt::Harvest(Some("Peter".into())));
+
+    let (_, stmt) = Stmt::parse("invoke Peter").unwrap();
+    assert_eq!(stmt, Stmt::Invoke(Some("Peter".into())));
+}
+
+#[test]
+fn parse_task_calls() {
+    init();
+
+    let (_, stmt) = Stmt::parse("perform Double with 5").unwrap();
+    assert_eq!(
+        stmt,
+        Stmt::Perform(
+            None,
+            "Double".into(),
+            vec![Expr::Value(Value::Integer(Integer::from(5)))]
+        )
+    );
+
+    let (_, stmt) = Stmt::parse("perform Greet").unwrap();
+    assert_eq!(stmt, Stmt::Perform(None, "Greet".into(), vec![]));
+
+    let (_, stmt) = Stmt::parse("perform Lisa Greet with moan").unwrap();
+    assert_eq!(
+        stmt,
+        Stmt::Perform(Some("Lisa".into()), "Greet".into(), vec![Expr::Moan(None)])
+    );
+
+    let (_, stmt) = Stmt::parse("invoke Peter Double with moan X").unwrap();
+    assert_eq!(
+        stmt,
+        Stmt::InvokeTask(
+            "Peter".into(),
+            "Double".into(),
+            vec![Expr::Moan(Some("X".into()))]
+        )
+    );
+
+    let (_, stmt) = Stmt::parse("invoke Peter Greet").unwrap();
+    assert_eq!(
+        stmt,
+        Stmt::InvokeTask("Peter".into(), "Greet".into(), vec![])
+    );
+
+    let code = "\
+Peter is a zombie
+summon
+    task Double of X
+        say moan X moan X
+    bind
+    task Main
+        perform Double with 21
+    animate
+animate";
+    let scroll = parse(code).unwrap();
+    let tasks = scroll.creatures()["Peter"].tasks();
+    assert_eq!(tasks["Double"].parameter(), Some(&"X".into()));
+    assert!(!tasks["Double"].active());
+    assert_eq!(tasks["Main"].parameter(), None);
+}
+
+#[test]
+fn parse_haunt() {
+    init();
+
+    let code = "\
+Heart is a ghost
+summon
+    haunt every 500
+    task Beat
+        say \"beat\"
+    animate
+disturb
+
+Peter is a zombie
+summon
+animate";
+    let scroll = parse(code).unwrap();
+    let heart = &scroll.creatures()["Heart"];
+    assert_eq!(heart.haunt(), Some(Duration::from_millis(500)));
+    assert_eq!(heart.tasks().len(), 1);
+    assert_eq!(scroll.creatures()["Peter"].haunt(), None);
+}
+
+#[test]
+fn parse_rank() {
+    init();
+
+    let code = "\
+Peter is a zombie of rank 3
+summon
+    task Hurry
+        say \"first\"
+    animate
+animate
+
+Lisa is a zombie
+summon
+animate";
+    let scroll = parse(code).unwrap();
+    assert_eq!(scroll.creatures()["Peter"].rank(), 3);
+    assert_eq!(scroll.creatures()["Peter"].tasks().len(), 1);
+    assert_eq!(scroll.creatures()["Lisa"].rank(), 0);
+
+    assert!(parse("Peter is a zombie of rank high\nsummon\nanimate").is_err());
+}
+
+#[test]
+fn parse_lich() {
+    init();
+
+    let scroll = parse("Vecna is a lich\nsummon\nanimate\n\nKas is a lich\nsummon\nbind").unwrap();
+    assert_eq!(scroll.creatures()["Vecna"].species(), Species::Lich);
+    assert!(scroll.creatures()["Vecna"].active());
+    assert!(!scroll.creatures()["Kas"].active());
+}
+
+#[test]
+fn parse_aliases() {
+    init();
+
+    let code = "\
+Peter is a zombie of rank 2 also known as Pete, Petey
+summon
+animate
+
+Lisa is a ghost also known as Lis
+summon
+disturb";
+    let scroll = parse(code).unwrap();
+    assert_eq!(scroll.creatures()["Peter"].rank(), 2);
+    assert_eq!(scroll.creatures()["Peter"].aliases(), ["Pete", "Petey"]);
+    assert_eq!(scroll.creatures()["Lisa"].aliases(), ["Lis"]);
+    assert_eq!(scroll.resolve("Petey").unwrap().name(), "Peter");
+    assert_eq!(scroll.resolve("Lisa").unwrap().name(), "Lisa");
+    assert!(scroll.resolve("Pet").is_none());
+
+    // The header spans more than a few words, so the reader has to look further ahead.
+    let streamed = parse_reader(code.as_bytes(), ParseConfig::default()).unwrap();
+    assert_eq!(streamed.creatures().len(), 2);
+    assert_eq!(streamed.creatures()["Lisa"].aliases(), ["Lis"]);
+
+    assert!(parse("Peter is a zombie also known as\nsummon\nanimate").is_err());
+}
+
+#[test]
+fn parse_covens() {
+    init();
+
+    let code = "\
+coven Nightshift containing Peter, Jay
+
+Peter is a zombie
+summon
+animate
+
+coven Everyone containing Peter,
+    Jay, Sarah
+
+Jay is a zombie
+summon
+animate";
+    let scroll = parse(code).unwrap();
+    assert_eq!(scroll.creatures().len(), 2);
+    assert_eq!(scroll.coven("Nightshift").unwrap(), ["Peter", "Jay"]);
+    assert_eq!(scroll.coven("Everyone").unwrap(), ["Peter", "Jay", "Sarah"]);
+    assert!(scroll.coven("Peter").is_none());
+
+    let streamed = parse_reader(code.as_bytes(), ParseConfig::default()).unwrap();
+    assert_eq!(streamed.creatures().len(), 2);
+    assert_eq!(streamed.covens(), scroll.covens());
+
+    assert!(parse("coven Nightshift containing\n\nPeter is a zombie\nsummon\nanimate").is_err());
+}
+
+#[test]
+fn parse_broadcasts() {
+    init();
+
+    let code = "\
+Peter is a zombie
+summon
+    task Call
+        animate all zombies
+        disturb all ghosts
+        banish all
+        banish allies
+        animate all
+    animate
+animate";
+    let scroll = parse(code).unwrap();
+    assert_eq!(
+        *scroll.creatures()["Peter"].tasks()["Call"].statements(),
+        [
+            Stmt::AnimateAll,
+            Stmt::DisturbAll,
+            Stmt::BanishAll,
+            Stmt::Banish(Some("allies".into())),
+            Stmt::Animate(Some("all".into())),
+        ]
+    );
+}
+
+#[test]
+fn parse_prologue() {
+    init();
+
+    let code = "\
+scroll \"Greeting\" by \"Peter\" version \"1.0\"
+
+Peter is a zombie
+summon
+    task Greet
+        say \"Hello World!\"
+    animate
+animate
+";
+    let scroll = parse(code).unwrap();
+    assert_eq!(
+        scroll.meta(),
+        Some(&ScrollMeta::new("Greeting").author("Peter").version("1.0"))
+    );
+    assert_eq!(scroll.creatures().len(), 1);
+
+    let (_, meta) = ScrollMeta::parse("scroll \"Greeting\" version \"2\"").unwrap();
+    assert_eq!(meta, ScrollMeta::new("Greeting").version("2"));
+    assert_eq!(meta.to_string(), "scroll \"Greeting\" version \"2\"");
+
+    let scroll = parse(&code[code.find("Peter is").unwrap()..]).unwrap();
+    assert_eq!(scroll.meta(), None);
+}
+
+#[test]
+fn parse_language_pragma() {
+    init();
+
+    let code = "\
+scroll \"Greeting\" by \"Peter\"
+scroll speaks zombie 1
+
+Peter is a zombie
+summon
+    task Greet
+        say \"Hello World!\"
+    animate
+animate
+";
+    let scroll = parse(code).unwrap();
+    let meta = ScrollMeta::new("Greeting").author("Peter").speaks(1);
+    assert_eq!(scroll.meta(), Some(&meta));
+    assert_eq!(scroll.language(), 1);
+    assert_eq!(
+        meta.to_string(),
+        "scroll \"Greeting\" by \"Peter\"\nscroll speaks zombie 1"
+    );
+
+    let scroll = parse(&code[code.find("scroll speaks").unwrap()..]).unwrap();
+    assert_eq!(scroll.meta(), Some(&ScrollMeta::default().speaks(1)));
+    assert_eq!(
+        ScrollMeta::default().speaks(2).to_string(),
+        "scroll speaks zombie 2"
+    );
+
+    let scroll = parse(&code[code.find("Peter is").unwrap()..]).unwrap();
+    assert_eq!(scroll.language(), crate::scroll::LANGUAGE_VERSION);
+
+    assert!(ScrollMeta::parse("scroll speaks zombie two").is_err());
+}
+
+#[test]
+fn parse_relaxed() {
+    init();
+
+    let code = "\
+Peter IS A Zombie
+Summon
+    Task Count
+        SHAMBLE
+            Remember Peter MOAN 1
+        Until Remembering 3
+        Taste remembering 3 Good
+            say \"three\"
+        BAD
+            say \"not three\"
+        spit
+    ANIMATE
+Animate";
+
+    assert!(parse(code).is_err());
+
+    let scroll = parse_with(code, ParseConfig::default().relaxed(true)).unwrap();
+    let peter = scroll.creatures().get("Peter").unwrap();
+    assert!(peter.active());
+    assert!(peter.tasks().contains_key("Count"));
+
+    // Names stay case-sensitive.
+    let scroll = parse_with(
+        "peter is a zombie\nsummon\nanimate",
+        ParseConfig::default().relaxed(true),
+    )
+    .unwrap();
+    assert!(scroll.creatures().contains_key("peter"));
+    assert!(!scroll.creatures().contains_key("Peter"));
+
+    // Names may start with a keyword in any case, but may not be one.
+    let names = "\
+Lestat is a vampire
+summon
+    task Turner
+        say moan Sayid
+    animate
+bind
+
+Sayid is a zombie
+summon
+    remember 1
+bind";
+    for config in [ParseConfig::default(), ParseConfig::default().relaxed(true)] {
+        let scroll = parse_with(names, config).unwrap();
+        assert!(scroll.creatures()["Lestat"].tasks().contains_key("Turner"));
+        assert!(scroll.creatures().contains_key("Sayid"));
+        assert!(parse_with("bind is a zombie\nsummon\nbind", config).is_err());
+    }
+    let relaxed = ParseConfig::default().relaxed(true);
+    assert!(parse_with("Bind is a zombie\nsummon\nbind", relaxed).is_err());
+
+    // The default is restored afterwards.
+    assert!(parse(code).is_err());
+}
+
+#[test]
+fn parse_recovering_entities() {
+    init();
+
+    let code = "\
+Peter is a zombie
+summon
+    remember 1
+animate
+
+Lisa is a ghost of rank 2 also known as Lis
+summon
+    task Broken
+        say 12abc
+    animate
+disturb
+
+Walter is a zombie
+summon
+animate
+
+Bob is a zombie
+summon
+    task Broken
+        remember 0xZZ
+    animate
+animate";
+
+    let (scroll, errors) = parse_recovering(code, ParseConfig::default());
+    assert_eq!(errors.len(), 2);
+    assert!(errors[0].input.starts_with("12abc"));
+    assert!(errors[1].input.starts_with("0xZZ"));
+
+    let creatures = scroll.creatures();
+    assert_eq!(creatures.len(), 4);
+    assert_eq!(creatures["Peter"].moan(), &Value::Integer(Integer::from(1)));
+    assert!(creatures["Walter"].active());
+    // Lisa is replaced by a placeholder.
+    assert!(!creatures["Lisa"].active());
+    assert!(creatures["Lisa"].tasks().is_empty());
+    assert_eq!(creatures["Lisa"].rank(), 2);
+    assert_eq!(creatures["Lisa"].aliases(), ["Lis"]);
+
+    // Without a readable header, there is no placeholder.
+    let (scroll, errors) = parse_recovering(
+        "Bob is a wombat\nsummon\nanimate\n\nPeter is a zombie\nsummon\nanimate",
+        ParseConfig::default(),
+    );
+    assert_eq!(errors.len(), 1);
+    assert_eq!(scroll.creatures().len(), 1);
+    assert!(scroll.creatures().contains_key("Peter"));
+
+    let (scroll, errors) =
+        parse_recovering("Peter is a zombie\nsummon\nanimate", ParseConfig::default());
+    assert!(errors.is_empty());
+    assert_eq!(scroll.creatures().len(), 1);
+}
+
+#[test]
+fn parse_from_reader() {
+    init();
+
+    let lines = |scroll: &Scroll| {
+        let mut lines = crate::scroll::listing::listing(scroll)
+            .lines()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        lines.sort();
+        lines
+    };
+
+    for code in [
+        include_str!("../../examples/Fibonacci.z"),
+        include_str!("../../examples/Hello World.z"),
+        include_str!("../../examples/Hello Underworld.z"),
+    ] {
+        let expected = parse(code).unwrap();
+        let scroll = parse_reader(code.as_bytes(), ParseConfig::default()).unwrap();
+        assert_eq!(lines(&scroll), lines(&expected));
+        assert_eq!(scroll.creatures().len(), expected.creatures().len());
+    }
+
+    let code = "\
+scroll \"Stream\" by \"Peter\"
+
+Peter
+is a zombie
+summon
+    remember 1
+animate
+
+Lisa is a ghost summon
+    task Broken
+        say 12abc
+    animate
+disturb";
+
+    let ReadError::Parse(error) =
+        parse_reader(code.as_bytes(), ParseConfig::default()).unwrap_err()
+    else {
+        panic!("expected a parse error");
+    };
+    assert_eq!((error.line(), error.column()), (11, 13));
+    assert_eq!(error.expected(), "a number");
+    assert_eq!(
+        error.to_string(),
+        "cannot parse the scroll at line 11, column 13: expected a number"
+    );
+
+    let code = code.replace("12abc", "12");
+    let scroll = parse_reader(code.as_bytes(), ParseConfig::default()).unwrap();
+    assert_eq!(scroll.meta().unwrap().title.as_deref(), Some("Stream"));
+    assert_eq!(scroll.creatures().len(), 2);
+    assert!(scroll.creatures()["Lisa"].tasks().contains_key("Broken"));
+
+    assert!(parse_reader("".as_bytes(), ParseConfig::default()).is_err());
+}
+
+#[test]
+fn parse_fragments() {
+    init();
+
+    let (stmt, rest) = parse_statement("  say moan Peter\nforget").unwrap();
+    assert_eq!(
+        stmt,
+        Stmt::Say(None, vec![Expr::Moan(Some(SmolStr::from("Peter")))])
+    );
+    assert_eq!(rest, "\nforget");
+
+    let (expr, rest) = parse_expression("rend (moan Peter 2) 4").unwrap();
+    assert_eq!(expr, Expr::Rend);
+    assert_eq!(rest, " (moan Peter 2) 4");
+    let (expr, rest) = parse_expression(rest).unwrap();
+    assert_eq!(
+        expr,
+        Expr::Group(vec![
+            Expr::Moan(Some(SmolStr::from("Peter"))),
+            Expr::Value(Value::Integer(Integer::from(2))),
+        ])
+    );
+    assert_eq!(rest, " 4");
+
+    assert!(parse_statement("wander off").is_err());
+    assert!(parse_expression("").is_err());
+}
+
+#[test]
+fn reject_leftover_code() {
+    init();
+
+    // A shamble without its end used to swallow the rest of the task.
+    let code = "\
+Peter is a zombie
+summon
+    task Loop
+        shamble
+            say 1
+    animate
+animate";
+    let error = parse(code).unwrap_err();
+    assert!(error.input.starts_with("shamble"));
+
+    // An entity without summon used to be swallowed by the one before it.
+    let code = "\
+Peter is a zombie
+summon
+    task Greet
+        say 1
+    animate
+animate
+
+Lisa is a ghost
+    remember 2
+bind";
+    let error = parse(code).unwrap_err();
+    assert!(error.input.trim_start().starts_with("Lisa"));
+}
+
+#[test]
+fn parse_keeps_entity_order() {
+    init();
+
+    let names = ["Zora", "Peter", "Anna", "Lisa", "Bob"];
+    let code = names
+        .iter()
+        .map(|name| format!("{} is a zombie\nsummon\nanimate", name))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let scroll = parse(&code).unwrap();
+    let order = scroll
+        .creatures_ordered()
+        .map(|entity| entity.name())
+        .collect::<Vec<_>>();
+    assert_eq!(order, names);
+}
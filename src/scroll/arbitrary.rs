@@ -0,0 +1,402 @@
+//! Strategies for generating scrolls with [proptest], and the source code to go with them.
+//!
+//! [`Value`], [`Expr`], [`Stmt`], [`Task`] and [`Entity`] implement [`Arbitrary`], so that
+//! `any::<Stmt>()` generates statements that can be written as code and parsed back.
+//! [`scroll_source`] generates whole scrolls along with their code. The code is written from the
+//! scroll, so a failing case shrinks the scroll, and the code follows along.
+//!
+//! Names are made up and rarely refer to entities or tasks of the scroll, so generated scrolls
+//! parse, but usually don't pass [validation](crate::validate).
+use std::fmt::Write;
+use std::time::Duration;
+
+use malachite::Integer;
+use proptest::collection::vec;
+use proptest::option;
+use proptest::prelude::*;
+use smol_str::SmolStr;
+
+use super::entity::{Entity, Species};
+use super::expression::Expr;
+use super::listing::{join, Argument, Target};
+use super::statement::Stmt;
+use super::task::Task;
+use super::Scroll;
+use crate::value::{Radix, Value};
+
+/// How deep expressions and statements are nested at most.
+const DEPTH: u32 = 3;
+
+/// Generate a name of an entity, a task or a parameter.
+fn name() -> impl Strategy<Value = SmolStr> {
+    // Keywords are lowercase, so capitalized names never start with one.
+    "[A-Z][a-z0-9_]{0,7}".prop_map(SmolStr::from)
+}
+
+/// Generate the text of a string literal.
+fn text() -> impl Strategy<Value = String> {
+    // Strings can't contain quotes. They don't contain whitespace either, since the parser looks
+    // for the ends of entities and tasks before it parses any strings.
+    "[A-Za-z0-9_.,:!?%-]{0,12}"
+}
+
+/// Generate a statement stack with at least the given number of expressions.
+fn exprs(min: usize) -> impl Strategy<Value = Vec<Expr>> {
+    vec(any::<Expr>(), min..4)
+}
+
+impl Arbitrary for Value {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Value>;
+
+    /// Generate integers of any size and strings, the values that can be written as literals.
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        prop_oneof![
+            any::<i64>().prop_map(|i| Value::Integer(Integer::from(i))),
+            "-?[1-9][0-9]{19,40}".prop_map(|digits| Value::Integer(digits.parse().unwrap())),
+            text().prop_map(|s| Value::String(SmolStr::from(s))),
+        ]
+        .boxed()
+    }
+}
+
+impl Arbitrary for Expr {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Expr>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        let leaf = prop_oneof![
+            option::of(name()).prop_map(Expr::Moan),
+            (option::of(name()), any::<Value>())
+                .prop_map(|(name, value)| Expr::Remembering(name, value)),
+            (option::of(name()), any::<usize>()).prop_map(|(name, n)| Expr::Reminisce(name, n)),
+            Just(Expr::Rend),
+            Just(Expr::Turn),
+            any::<i64>().prop_map(Expr::Fester),
+            text().prop_map(Expr::Divine),
+            text().prop_map(Expr::Compose),
+            prop_oneof![Just(Radix::Binary), Just(Radix::Hex)].prop_map(Expr::Transcribe),
+            any::<Value>().prop_map(Expr::Value),
+        ];
+        leaf.prop_recursive(DEPTH, 16, 4, |inner| vec(inner, 1..4).prop_map(Expr::Group))
+            .boxed()
+    }
+}
+
+impl Arbitrary for Stmt {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Stmt>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        let leaf = prop_oneof![
+            option::of(name()).prop_map(Stmt::Animate),
+            Just(Stmt::AnimateAll),
+            option::of(name()).prop_map(Stmt::Banish),
+            Just(Stmt::BanishAll),
+            any::<u16>().prop_map(Stmt::Channel),
+            option::of(name()).prop_map(Stmt::Disturb),
+            Just(Stmt::DisturbAll),
+            (text(), exprs(1)).prop_map(|(path, exprs)| Stmt::Entomb(path, exprs)),
+            text().prop_map(Stmt::Exhume),
+            option::of(name()).prop_map(Stmt::Forget),
+            option::of(name()).prop_map(Stmt::Invoke),
+            (name(), name(), exprs(0))
+                .prop_map(|(name, task, exprs)| Stmt::InvokeTask(name, task, exprs)),
+            option::of(name()).prop_map(Stmt::Harvest),
+            (option::of(name()), name(), exprs(0))
+                .prop_map(|(name, task, exprs)| Stmt::Perform(name, task, exprs)),
+            (option::of(name()), exprs(0)).prop_map(|(name, exprs)| Stmt::Remember(name, exprs)),
+            (option::of(name()), exprs(1)).prop_map(|(name, exprs)| Stmt::Say(name, exprs)),
+            (name(), text()).prop_map(|(name, path)| Stmt::SummonWithin(name, path)),
+            (text(), exprs(1)).prop_map(|(address, exprs)| Stmt::Whisper(address, exprs)),
+            Just(Stmt::Stumble),
+            Just(Stmt::Flee),
+            Just(Stmt::Lurch),
+        ];
+        leaf.prop_recursive(DEPTH, 32, 4, |inner| {
+            let block = vec(inner, 0..4);
+            prop_oneof![
+                (any::<Expr>(), block.clone())
+                    .prop_map(|(expr, stmts)| Stmt::ShambleUntil(expr, stmts)),
+                block.clone().prop_map(Stmt::ShambleAround),
+                (any::<Expr>(), block.clone())
+                    .prop_map(|(expr, stmts)| Stmt::ShambleTimes(expr, stmts)),
+                (any::<Expr>(), block.clone(), block.clone())
+                    .prop_map(|(expr, good, bad)| Stmt::Taste(expr, good, bad)),
+                (
+                    any::<Expr>(),
+                    vec((any::<Value>(), block.clone()), 1..3),
+                    block
+                )
+                    .prop_map(|(expr, cases, lest)| Stmt::Consult(expr, cases, lest)),
+            ]
+        })
+        .boxed()
+    }
+}
+
+impl Arbitrary for Task {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Task>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (
+            name(),
+            option::of(name()),
+            any::<bool>(),
+            vec(any::<Stmt>(), 0..6),
+        )
+            .prop_map(|(name, parameter, active, stmts)| {
+                let mut task = Task::builder(&name).active(active).statements(stmts);
+                if let Some(parameter) = parameter {
+                    task = task.parameter(&parameter);
+                }
+                task.build()
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for Entity {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Entity>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        let species = prop_oneof![
+            Just(Species::Zombie),
+            Just(Species::Ghost),
+            Just(Species::Vampire),
+            Just(Species::Demon),
+            Just(Species::Djinn),
+            Just(Species::Lich),
+            Just(Species::Wraith),
+            Just(Species::Revenant),
+        ];
+        (
+            (name(), species, any::<bool>()),
+            option::of(any::<Value>()),
+            vec(any::<Task>(), 0..4),
+            option::of(any::<u64>()),
+            prop_oneof![Just(0), any::<u32>()],
+            vec(name(), 0..3),
+        )
+            .prop_map(
+                |((name, species, active), memory, tasks, haunt, rank, aliases)| {
+                    let mut entity = Entity::builder(&name, species)
+                        .active(active)
+                        .tasks(tasks)
+                        .rank(rank);
+                    if let Some(memory) = memory {
+                        entity = entity.remember(memory);
+                    }
+                    if let Some(millis) = haunt {
+                        entity = entity.haunt(Duration::from_millis(millis));
+                    }
+                    for alias in aliases {
+                        entity = entity.alias(&alias);
+                    }
+                    entity.build()
+                },
+            )
+            .boxed()
+    }
+}
+
+/// Generate a scroll of a few entities, along with its source code.
+pub fn scroll_source() -> impl Strategy<Value = (Scroll, String)> {
+    vec(any::<Entity>(), 1..4).prop_map(|entities| {
+        let scroll = Scroll::builder().entities(entities).build();
+        let code = source(&scroll);
+        (scroll, code)
+    })
+}
+
+/// Write the source code of the scroll, with blocks indented by four spaces.
+///
+/// Strings are written as they are, so the code only parses into the same scroll if none of
+/// them contain quotes.
+pub fn source(scroll: &Scroll) -> String {
+    let mut code = String::new();
+    if let Some(meta) = scroll.meta() {
+        let _ = writeln!(code, "{}\n", meta);
+    }
+    for entity in scroll.creatures().values() {
+        write_entity(&mut code, entity);
+        code.push('\n');
+    }
+    for (name, members) in scroll.covens() {
+        let _ = writeln!(code, "coven {} containing {}", name, members.join(", "));
+    }
+    code
+}
+
+fn write_entity(code: &mut String, entity: &Entity) {
+    let species = entity.species().to_string().to_lowercase();
+    let _ = write!(code, "{} is a {}", entity.name(), species);
+    if entity.rank() > 0 {
+        let _ = write!(code, " of rank {}", entity.rank());
+    }
+    if !entity.aliases().is_empty() {
+        let _ = write!(code, " also known as {}", entity.aliases().join(", "));
+    }
+    code.push_str("\nsummon\n");
+    if !matches!(entity.moan(), Value::Void) {
+        let _ = writeln!(code, "    remember {}", Expr::Value(entity.moan().clone()));
+    }
+    if let Some(period) = entity.haunt() {
+        let _ = writeln!(code, "    haunt every {}", period.as_millis());
+    }
+    for task in entity.tasks().values() {
+        let _ = write!(code, "    task {}", task.name());
+        if let Some(parameter) = task.parameter() {
+            let _ = write!(code, " of {}", parameter);
+        }
+        code.push('\n');
+        write_block(code, task.statements(), 2);
+        let spell = if task.active() { "animate" } else { "bind" };
+        let _ = writeln!(code, "    {}", spell);
+    }
+    let _ = writeln!(code, "{}", spell(entity));
+}
+
+/// The spell at the end of the entity definition, which decides whether the entity is active.
+fn spell(entity: &Entity) -> &'static str {
+    let awaken = match entity.species() {
+        Species::Zombie | Species::Lich | Species::Revenant => "animate",
+        Species::Ghost | Species::Wraith => "disturb",
+        Species::Vampire | Species::Demon | Species::Djinn => "bind",
+    };
+    match (entity.active(), awaken) {
+        (true, spell) => spell,
+        (false, "bind") => "animate",
+        (false, _) => "bind",
+    }
+}
+
+fn write_block(code: &mut String, stmts: &[Stmt], depth: usize) {
+    for stmt in stmts {
+        write_stmt(code, stmt, depth);
+    }
+}
+
+fn write_stmt(code: &mut String, stmt: &Stmt, depth: usize) {
+    let indent = "    ".repeat(depth);
+    let _ = match stmt {
+        Stmt::Animate(name) => writeln!(code, "{}animate{}", indent, Target(name)),
+        Stmt::AnimateAll => writeln!(code, "{}animate all zombies", indent),
+        Stmt::Banish(name) => writeln!(code, "{}banish{}", indent, Target(name)),
+        Stmt::BanishAll => writeln!(code, "{}banish all", indent),
+        Stmt::Channel(port) => writeln!(code, "{}channel {}", indent, port),
+        Stmt::Disturb(name) => writeln!(code, "{}disturb{}", indent, Target(name)),
+        Stmt::DisturbAll => writeln!(code, "{}disturb all ghosts", indent),
+        Stmt::Entomb(path, exprs) => {
+            writeln!(code, "{}entomb \"{}\"{}", indent, path, join(exprs))
+        }
+        Stmt::Exhume(path) => writeln!(code, "{}exhume \"{}\"", indent, path),
+        Stmt::Forget(name) => writeln!(code, "{}forget{}", indent, Target(name)),
+        Stmt::Invoke(name) => writeln!(code, "{}invoke{}", indent, Target(name)),
+        Stmt::InvokeTask(name, task, exprs) => {
+            writeln!(
+                code,
+                "{}invoke {} {}{}",
+                indent,
+                name,
+                task,
+                Argument(exprs)
+            )
+        }
+        Stmt::Harvest(name) => writeln!(code, "{}invoke{} harvest", indent, Target(name)),
+        Stmt::Perform(name, task, exprs) => writeln!(
+            code,
+            "{}perform{} {}{}",
+            indent,
+            Target(name),
+            task,
+            Argument(exprs)
+        ),
+        Stmt::Remember(name, exprs) => {
+            writeln!(code, "{}remember{}{}", indent, Target(name), join(exprs))
+        }
+        Stmt::Say(name, exprs) => writeln!(code, "{}say{}{}", indent, Target(name), join(exprs)),
+        Stmt::SummonWithin(name, path) => {
+            writeln!(code, "{}summon {} within \"{}\"", indent, name, path)
+        }
+        Stmt::Whisper(address, exprs) => writeln!(
+            code,
+            "{}whisper beyond \"{}\"{}",
+            indent,
+            address,
+            join(exprs)
+        ),
+        Stmt::ShambleUntil(expr, stmts) => {
+            let _ = writeln!(code, "{}shamble", indent);
+            write_block(code, stmts, depth + 1);
+            writeln!(code, "{}until {}", indent, expr)
+        }
+        Stmt::ShambleAround(stmts) => {
+            let _ = writeln!(code, "{}shamble", indent);
+            write_block(code, stmts, depth + 1);
+            writeln!(code, "{}around", indent)
+        }
+        Stmt::ShambleTimes(expr, stmts) => {
+            let _ = writeln!(code, "{}shamble {} times", indent, expr);
+            write_block(code, stmts, depth + 1);
+            writeln!(code, "{}around", indent)
+        }
+        Stmt::Stumble => writeln!(code, "{}stumble", indent),
+        Stmt::Flee => writeln!(code, "{}flee", indent),
+        Stmt::Lurch => writeln!(code, "{}lurch", indent),
+        Stmt::Taste(expr, good, bad) => {
+            let _ = writeln!(code, "{}taste {} good", indent, expr);
+            write_block(code, good, depth + 1);
+            if !bad.is_empty() {
+                let _ = writeln!(code, "{}bad", indent);
+                write_block(code, bad, depth + 1);
+            }
+            writeln!(code, "{}spit", indent)
+        }
+        Stmt::Consult(expr, cases, lest) => {
+            let _ = writeln!(code, "{}consult {}", indent, expr);
+            for (value, stmts) in cases {
+                let _ = writeln!(code, "{}upon {}", indent, Expr::Value(value.clone()));
+                write_block(code, stmts, depth + 1);
+            }
+            if !lest.is_empty() {
+                let _ = writeln!(code, "{}lest", indent);
+                write_block(code, lest, depth + 1);
+            }
+            writeln!(code, "{}settle", indent)
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::{parse, parse_expression, parse_statement};
+
+    proptest! {
+        #[test]
+        fn parse_expressions(expr in any::<Expr>()) {
+            let code = expr.to_string();
+            prop_assert_eq!(parse_expression(&code).unwrap(), (expr, ""));
+        }
+
+        #[test]
+        fn parse_statements(stmt in any::<Stmt>()) {
+            let mut code = String::new();
+            write_stmt(&mut code, &stmt, 0);
+            prop_assert_eq!(parse_statement(&code).unwrap(), (stmt, "\n"));
+        }
+    }
+
+    proptest! {
+        // Whole scrolls take a while to parse in debug builds.
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn parse_scrolls((scroll, code) in scroll_source()) {
+            prop_assert_eq!(parse(&code).unwrap(), scroll);
+        }
+    }
+}
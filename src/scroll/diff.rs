@@ -0,0 +1,255 @@
+//! Composing scrolls from several others, and comparing two versions of a scroll.
+//!
+//! A diff lists the entities that were added, removed or changed, one per line. Changed entities
+//! are followed by the tasks that were added, removed or changed:
+//!
+//! ```text
+//! + Lisa
+//! - Bob
+//! ~ Peter
+//!     ~ definition
+//!     + task Greet
+//!     ~ task Count
+//! ```
+use std::fmt::{Display, Formatter, Result};
+
+use smol_str::SmolStr;
+
+use super::entity::Entity;
+use super::Scroll;
+
+/// The scrolls to be merged define different entities with the same name.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("conflicting definitions of {}", names.join(", "))]
+pub struct ConflictError {
+    /// The names of the conflicting entities.
+    pub names: Vec<SmolStr>,
+}
+
+/// The differences between two versions of a scroll. Create one with [`Scroll::diff`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScrollDiff {
+    /// Entities only present in the new version.
+    pub added: Vec<SmolStr>,
+    /// Entities only present in the old version.
+    pub removed: Vec<SmolStr>,
+    /// Entities present in both versions, but defined differently.
+    pub changed: Vec<EntityDiff>,
+}
+
+impl ScrollDiff {
+    /// Whether both versions define the same entities.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// The differences between two versions of an entity.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntityDiff {
+    pub name: SmolStr,
+    /// Whether the species, the activity or the remembered value of the entity changed.
+    pub definition: bool,
+    /// Tasks only present in the new version.
+    pub added_tasks: Vec<SmolStr>,
+    /// Tasks only present in the old version.
+    pub removed_tasks: Vec<SmolStr>,
+    /// Tasks present in both versions, but defined differently.
+    pub changed_tasks: Vec<SmolStr>,
+}
+
+impl Scroll {
+    /// Add the entities of the other scroll to this one.
+    ///
+    /// Entities defined identically in both scrolls are kept once. The prologue and the covens of
+    /// this scroll take precedence over those of the other scroll.
+    pub fn merge(mut self, other: Scroll) -> std::result::Result<Scroll, ConflictError> {
+        let names: Vec<SmolStr> = other
+            .entities
+            .iter()
+            .filter(|(name, entity)| self.entities.get(*name).is_some_and(|own| own != *entity))
+            .map(|(name, _)| name.clone())
+            .collect();
+        if !names.is_empty() {
+            return Err(ConflictError { names });
+        }
+        for (name, entity) in other.entities {
+            self.entities.entry(name).or_insert(entity);
+        }
+        for (name, members) in other.covens {
+            self.covens.entry(name).or_insert(members);
+        }
+        if self.meta.is_none() {
+            self.meta = other.meta;
+        }
+        Ok(self)
+    }
+
+    /// Describe how the other, newer version of the scroll differs from this one.
+    pub fn diff(&self, other: &Scroll) -> ScrollDiff {
+        let mut diff = ScrollDiff::default();
+        for (name, old) in &self.entities {
+            match other.entities.get(name) {
+                None => diff.removed.push(name.clone()),
+                Some(new) if new != old => diff.changed.push(diff_entity(old, new)),
+                Some(_) => {}
+            }
+        }
+        diff.added = other
+            .entities
+            .keys()
+            .filter(|name| !self.entities.contains_key(*name))
+            .cloned()
+            .collect();
+        diff
+    }
+}
+
+fn diff_entity(old: &Entity, new: &Entity) -> EntityDiff {
+    let mut diff = EntityDiff {
+        name: new.name(),
+        definition: old.species() != new.species()
+            || old.active() != new.active()
+            || old.moan() != new.moan()
+            || old.haunt() != new.haunt()
+            || old.rank() != new.rank()
+            || old.aliases() != new.aliases(),
+        ..EntityDiff::default()
+    };
+    for (name, task) in old.tasks() {
+        match new.tasks().get(name) {
+            None => diff.removed_tasks.push(name.clone()),
+            Some(new_task) if new_task != task => diff.changed_tasks.push(name.clone()),
+            Some(_) => {}
+        }
+    }
+    diff.added_tasks = new
+        .tasks()
+        .keys()
+        .filter(|name| !old.tasks().contains_key(*name))
+        .cloned()
+        .collect();
+    diff
+}
+
+impl Display for ScrollDiff {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        for name in &self.added {
+            writeln!(fmt, "+ {}", name)?;
+        }
+        for name in &self.removed {
+            writeln!(fmt, "- {}", name)?;
+        }
+        for entity in &self.changed {
+            write!(fmt, "{}", entity)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for EntityDiff {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        writeln!(fmt, "~ {}", self.name)?;
+        if self.definition {
+            writeln!(fmt, "    ~ definition")?;
+        }
+        for name in &self.added_tasks {
+            writeln!(fmt, "    + task {}", name)?;
+        }
+        for name in &self.removed_tasks {
+            writeln!(fmt, "    - task {}", name)?;
+        }
+        for name in &self.changed_tasks {
+            writeln!(fmt, "    ~ task {}", name)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    const OLD: &str = "\
+Peter is a zombie
+summon
+    task Count
+        remember 1
+    animate
+    task Old
+        remember 2
+    animate
+animate
+
+Bob is a zombie
+summon
+animate";
+
+    const NEW: &str = "\
+Peter is a zombie
+summon
+    task Count
+        remember 3
+    animate
+    task Greet
+        say \"Hello\"
+    animate
+bind
+
+Lisa is a ghost
+summon
+disturb";
+
+    #[test]
+    fn diff_scrolls() {
+        let old = parse(OLD).unwrap();
+        let new = parse(NEW).unwrap();
+
+        assert!(old.diff(&old).is_empty());
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.added, vec![SmolStr::from("Lisa")]);
+        assert_eq!(diff.removed, vec![SmolStr::from("Bob")]);
+        assert_eq!(
+            diff.changed,
+            vec![EntityDiff {
+                name: SmolStr::from("Peter"),
+                definition: true,
+                added_tasks: vec![SmolStr::from("Greet")],
+                removed_tasks: vec![SmolStr::from("Old")],
+                changed_tasks: vec![SmolStr::from("Count")],
+            }]
+        );
+        assert_eq!(
+            diff.to_string(),
+            "\
++ Lisa
+- Bob
+~ Peter
+    ~ definition
+    + task Greet
+    - task Old
+    ~ task Count
+"
+        );
+    }
+
+    #[test]
+    fn merge_scrolls() {
+        let old = parse(OLD).unwrap();
+        let lisa = parse("Lisa is a ghost\nsummon\ndisturb").unwrap();
+
+        let merged = old.clone().merge(lisa).unwrap();
+        let names: Vec<SmolStr> = merged.creatures_ordered().map(Entity::name).collect();
+        assert_eq!(names, vec!["Peter", "Bob", "Lisa"]);
+
+        // Identical definitions are fine.
+        let merged = merged.merge(old.clone()).unwrap();
+        assert_eq!(merged.creatures().len(), 3);
+
+        let error = old.merge(parse(NEW).unwrap()).unwrap_err();
+        assert_eq!(error.names, vec![SmolStr::from("Peter")]);
+        assert_eq!(error.to_string(), "conflicting definitions of Peter");
+    }
+}
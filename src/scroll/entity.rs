@@ -1,4 +1,5 @@
 use std::fmt::{Display, Formatter, Result};
+use std::time::Duration;
 
 use indexmap::IndexMap;
 use smol_str::SmolStr;
@@ -8,16 +9,22 @@ use crate::value::Value;
 
 pub type TaskList = IndexMap<SmolStr, Task>;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Entity {
     name: SmolStr,
     species: Species,
     active: bool,
     memory: Value,
     tasks: TaskList,
+    haunt: Option<Duration>,
+    rank: u32,
+    aliases: Vec<SmolStr>,
 }
 
 impl Entity {
+    /// Create a new entity from all of its parts. See [`Entity::builder`] for a more
+    /// convenient way.
     pub fn summon(
         name: &str,
         species: Species,
@@ -31,6 +38,25 @@ impl Entity {
             active,
             memory,
             tasks,
+            haunt: None,
+            rank: 0,
+            aliases: Vec::new(),
+        }
+    }
+
+    /// Start creating a new entity with the given name and species.
+    ///
+    /// The entity is active, remembers nothing and has no tasks unless specified otherwise.
+    pub fn builder(name: &str, species: Species) -> EntityBuilder {
+        EntityBuilder {
+            name: SmolStr::from(name),
+            species,
+            active: true,
+            memory: Value::Void,
+            tasks: TaskList::new(),
+            haunt: None,
+            rank: 0,
+            aliases: Vec::new(),
         }
     }
 
@@ -53,10 +79,112 @@ impl Entity {
     pub fn tasks(&self) -> &TaskList {
         &self.tasks
     }
+
+    /// The period after which the entity haunts the ritual again, given with
+    /// `haunt every <millis>`.
+    ///
+    /// Once summoned, a new spirit of a haunting entity is summoned every period for as long
+    /// as the entity stays active. Banishing it ends the haunting. This is meant for ghosts
+    /// that keep a heartbeat or monitor something.
+    pub fn haunt(&self) -> Option<Duration> {
+        self.haunt
+    }
+
+    /// The priority of the entity, given with `of rank <n>`. Higher ranks come first.
+    ///
+    /// Spirits of higher rank are summoned first, and spirits of lower rank give way to them
+    /// more often while performing their tasks. The rank is 0 by default.
+    pub fn rank(&self) -> u32 {
+        self.rank
+    }
+
+    /// Other names of the entity, given with `also known as <name>, ...`.
+    ///
+    /// Statements may refer to the entity by any of its aliases instead of its name.
+    pub fn aliases(&self) -> &[SmolStr] {
+        &self.aliases
+    }
+
+    pub(crate) fn tasks_mut(&mut self) -> &mut TaskList {
+        &mut self.tasks
+    }
+}
+
+/// Builder for an [`Entity`]. Create one with [`Entity::builder`].
+#[derive(Debug, Clone)]
+pub struct EntityBuilder {
+    name: SmolStr,
+    species: Species,
+    active: bool,
+    memory: Value,
+    tasks: TaskList,
+    haunt: Option<Duration>,
+    rank: u32,
+    aliases: Vec<SmolStr>,
+}
+
+impl EntityBuilder {
+    /// Set whether the entity is active when the ritual begins.
+    pub fn active(mut self, active: bool) -> EntityBuilder {
+        self.active = active;
+        self
+    }
+
+    /// Set the value that the entity remembers when the ritual begins.
+    pub fn remember(mut self, memory: impl Into<Value>) -> EntityBuilder {
+        self.memory = memory.into();
+        self
+    }
+
+    /// Add a task to the entity. A task with the same name replaces any previous one.
+    pub fn task(mut self, task: Task) -> EntityBuilder {
+        self.tasks.insert(task.name(), task);
+        self
+    }
+
+    /// Add several tasks to the entity.
+    pub fn tasks(mut self, tasks: impl IntoIterator<Item = Task>) -> EntityBuilder {
+        self.tasks
+            .extend(tasks.into_iter().map(|task| (task.name(), task)));
+        self
+    }
+
+    /// Let the entity haunt the ritual again every period. See [`Entity::haunt`].
+    pub fn haunt(mut self, period: Duration) -> EntityBuilder {
+        self.haunt = Some(period);
+        self
+    }
+
+    /// Set the priority of the entity. See [`Entity::rank`].
+    pub fn rank(mut self, rank: u32) -> EntityBuilder {
+        self.rank = rank;
+        self
+    }
+
+    /// Add another name for the entity. See [`Entity::aliases`].
+    pub fn alias(mut self, alias: &str) -> EntityBuilder {
+        self.aliases.push(SmolStr::from(alias));
+        self
+    }
+
+    /// Finish the entity.
+    pub fn build(self) -> Entity {
+        Entity {
+            name: self.name,
+            species: self.species,
+            active: self.active,
+            memory: self.memory,
+            tasks: self.tasks,
+            haunt: self.haunt,
+            rank: self.rank,
+            aliases: self.aliases,
+        }
+    }
 }
 
 /// The different kinds of species that a [`Creature`] can belong to.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Species {
     /// Zombies process their active tasks in sequence, beginning from the first task defined,
     /// as quickly as they can. They perform each task exactly once.
@@ -77,6 +205,34 @@ pub enum Species {
     /// to perform each task multiple times, or not at all, before becoming inactive.
     /// They may perform multiple tasks at the same time.
     Djinn,
+    /// Liches process their active tasks in sequence like zombies, but they keep track of the
+    /// tasks they completed. A lich that is banished and animated again resumes with the task it
+    /// was interrupted in, which it performs from the beginning. Once done with all tasks, it
+    /// starts over with the first one the next time it is animated.
+    Lich,
+    /// Wraiths don't perform any tasks on their own, but respond to being disturbed. Every
+    /// disturbance makes them perform one task, taking turns with the tasks in the order of
+    /// their definition. They linger for as long as anyone could still disturb them.
+    Wraith,
+    /// Revenants process their active tasks in sequence like zombies, but they don't give up
+    /// easily. A task that ends with `stumble` is performed again, up to a
+    /// [configurable](crate::necro::RitualConfig::revenant_retries) number of times, after a
+    /// random pause that doubles with every attempt.
+    Revenant,
+}
+
+impl Species {
+    /// Whether creatures of the species respond to the statement that wakes up the given species,
+    /// i.e. `animate` for zombies and `disturb` for ghosts. Liches and revenants respond to
+    /// `animate` just like zombies, and wraiths to `disturb` like ghosts. This holds for naming a
+    /// creature as well as for the broadcasts `animate all zombies` and `disturb all ghosts`.
+    pub fn responds_like(self, species: Species) -> bool {
+        match species {
+            Species::Zombie => matches!(self, Species::Zombie | Species::Lich | Species::Revenant),
+            Species::Ghost => matches!(self, Species::Ghost | Species::Wraith),
+            species => self == species,
+        }
+    }
 }
 
 impl Display for Species {
@@ -87,6 +243,9 @@ impl Display for Species {
             Species::Vampire => write!(fmt, "Vampire"),
             Species::Demon => write!(fmt, "Demon"),
             Species::Djinn => write!(fmt, "Djinn"),
+            Species::Lich => write!(fmt, "Lich"),
+            Species::Wraith => write!(fmt, "Wraith"),
+            Species::Revenant => write!(fmt, "Revenant"),
         }
     }
 }
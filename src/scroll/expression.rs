@@ -1,10 +1,13 @@
+use std::fmt::{Display, Formatter, Result};
+
 use smol_str::SmolStr;
 
-use crate::value::Value;
+use crate::value::{Radix, Value};
 
 /// An expression in the ZOMBIE language. Expressions occur in [`Statement`]s
 /// and are distinct from them in that they evaluate to a value.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expr {
     /// Instructs the named entity to moan its remembered
     /// data value, and to keep remembering it.
@@ -13,14 +16,87 @@ pub enum Expr {
     /// is currently remembering a data value equal to the given
     /// variable, false otherwise.
     Remembering(Option<SmolStr>, Value),
+    /// Instructs the named entity to moan the n-th value it remembered
+    /// before its current one. Evaluates to the void if the entity
+    /// does not recall that far back.
+    Reminisce(Option<SmolStr>, usize),
     /// This operator pops the top two value off the statement
     /// stack, divides the second value by the top value, and
-    /// puts the result back on the statement stack.
+    /// puts the result back on the statement stack. If the stack
+    /// holds only one value, it is replaced by a corrupted value.
     Rend,
     /// This operator replaces the top value of the statement
     /// stack with its negative.
     Turn,
+    /// This operator raises the top value of the statement stack
+    /// to the given power. Negative powers are truncated to integers.
+    Fester(i64),
+    /// Reads the environment variable of the given name. Evaluates to the void
+    /// if the variable is not set or access to the environment is not allowed.
+    Divine(String),
+    /// This operator replaces the top values of the statement stack with the string composed
+    /// from the given format, taking one value per specifier from the top down. So the values
+    /// appear in the same order as in the source code: `compose "%d-%d" 1 2` evaluates to
+    /// `"1-2"`. See [`Value::compose`] for the specifiers.
+    Compose(String),
+    /// This operator replaces the top value of the statement stack with a string of its digits
+    /// in the given radix, like `0xff` for `transcribe as hex 255`.
+    /// See [`Value::transcribe`].
+    Transcribe(Radix),
     /// This is not associated with a keyword from the ZOMBIE language.
     /// It represents any concrete value occuring in the code.
     Value(Value),
+    /// Expressions in parentheses. They are evaluated on a stack of their own, like the
+    /// expressions of a statement, and the result is put on the statement stack as one value.
+    ///
+    /// Expressions are evaluated from right to left, so without parentheses an operator like
+    /// `rend` takes whatever values happen to be adjacent on the stack. A group always counts as
+    /// a single operand, namely the top of its own stack: `rend 1 2 8` divides 2 by 1, while
+    /// `rend (1 2) 8` divides 8 by 1.
+    Group(Vec<Expr>),
+}
+
+impl Display for Expr {
+    /// Write the expression the way it appears in the source code.
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        match self {
+            Expr::Moan(None) => write!(fmt, "moan"),
+            Expr::Moan(Some(name)) => write!(fmt, "moan {}", name),
+            Expr::Remembering(None, value) => write!(fmt, "remembering {}", Literal(value)),
+            Expr::Remembering(Some(name), value) => {
+                write!(fmt, "remembering {} {}", name, Literal(value))
+            }
+            Expr::Reminisce(None, n) => write!(fmt, "reminisce {}", n),
+            Expr::Reminisce(Some(name), n) => write!(fmt, "reminisce {} {}", name, n),
+            Expr::Rend => write!(fmt, "rend"),
+            Expr::Turn => write!(fmt, "turn"),
+            Expr::Fester(exponent) => write!(fmt, "fester {}", exponent),
+            Expr::Divine(var) => write!(fmt, "divine \"{}\"", var),
+            Expr::Compose(format) => write!(fmt, "compose \"{}\"", format),
+            Expr::Transcribe(radix) => write!(fmt, "transcribe as {}", radix),
+            Expr::Value(value) => write!(fmt, "{}", Literal(value)),
+            Expr::Group(exprs) => {
+                write!(fmt, "(")?;
+                for (index, expr) in exprs.iter().enumerate() {
+                    if index > 0 {
+                        write!(fmt, " ")?;
+                    }
+                    write!(fmt, "{}", expr)?;
+                }
+                write!(fmt, ")")
+            }
+        }
+    }
+}
+
+/// Displays a value as a literal in the source code.
+struct Literal<'a>(&'a Value);
+
+impl Display for Literal<'_> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        match self.0 {
+            Value::String(s) => write!(fmt, "\"{}\"", s),
+            value => write!(fmt, "{}", value),
+        }
+    }
 }
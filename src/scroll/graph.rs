@@ -0,0 +1,199 @@
+//! Export of the dependencies between entities as a Graphviz DOT graph.
+//!
+//! Every entity is a node. An edge is drawn from an entity to another one for every statement or
+//! expression in its tasks that refers to the other entity by name. Aliases are resolved to the
+//! entity they stand for, and covens to an edge for each of their members.
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use smol_str::SmolStr;
+
+use super::entity::Entity;
+use super::expression::Expr;
+use super::statement::Stmt;
+use super::task::Task;
+use super::visit::{walk_entity, walk_expr, walk_stmt, walk_task, Visitor};
+use super::Scroll;
+
+/// Create the DOT graph of the scroll.
+pub fn graph(scroll: &Scroll) -> String {
+    let mut grapher = Grapher::new(scroll);
+    grapher.visit_scroll(scroll);
+
+    let mut entities: Vec<&Entity> = scroll.creatures().values().collect();
+    entities.sort_by_key(|entity| entity.name());
+
+    let mut out = String::from("digraph scroll {\n");
+    for entity in entities {
+        let _ = writeln!(
+            out,
+            "    \"{}\" [label=\"{}\\n({})\"];",
+            entity.name(),
+            entity.name(),
+            entity.species()
+        );
+    }
+    for (from, to, label) in grapher.edges {
+        let _ = writeln!(out, "    \"{}\" -> \"{}\" [label=\"{}\"];", from, to, label);
+    }
+    out.push_str("}\n");
+    out
+}
+
+struct Grapher<'a> {
+    scroll: &'a Scroll,
+    entity: SmolStr,
+    /// The parameter of the current task, which shadows any entity of the same name.
+    parameter: Option<SmolStr>,
+    edges: BTreeSet<(SmolStr, SmolStr, &'static str)>,
+}
+
+impl<'a> Grapher<'a> {
+    fn new(scroll: &'a Scroll) -> Self {
+        Self {
+            scroll,
+            entity: SmolStr::default(),
+            parameter: None,
+            edges: BTreeSet::new(),
+        }
+    }
+
+    /// Draw an edge to the named entity, like the ritual would resolve the name.
+    fn edge(&mut self, to: &SmolStr, label: &'static str) {
+        let scroll = self.scroll;
+        if scroll.resolve(to).is_none() {
+            if let Some(members) = scroll.coven(to) {
+                for member in members {
+                    self.edge_to(member, label);
+                }
+                return;
+            }
+        }
+        self.edge_to(to, label);
+    }
+
+    fn edge_to(&mut self, to: &SmolStr, label: &'static str) {
+        let to = self
+            .scroll
+            .resolve(to)
+            .map_or_else(|| to.clone(), |entity| entity.name());
+        self.edges.insert((self.entity.clone(), to, label));
+    }
+}
+
+impl<'ast> Visitor<'ast> for Grapher<'_> {
+    fn visit_entity(&mut self, entity: &'ast Entity) {
+        self.entity = entity.name();
+        walk_entity(self, entity);
+    }
+
+    fn visit_task(&mut self, task: &'ast Task) {
+        self.parameter = task.parameter().cloned();
+        walk_task(self, task);
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        match stmt {
+            Stmt::Animate(Some(name)) => self.edge(name, "animate"),
+            Stmt::Banish(Some(name)) => self.edge(name, "banish"),
+            Stmt::Disturb(Some(name)) => self.edge(name, "disturb"),
+            Stmt::Invoke(Some(name)) | Stmt::InvokeTask(name, _, _) => self.edge(name, "invoke"),
+            Stmt::Harvest(Some(name)) => self.edge(name, "harvest"),
+            Stmt::Perform(Some(name), _, _) => self.edge(name, "perform"),
+            Stmt::Remember(Some(name), _) => self.edge(name, "remember"),
+            _ => {}
+        }
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        if let Expr::Moan(Some(name)) = expr {
+            if self.parameter.as_ref() != Some(name) {
+                self.edge(name, "moan");
+            }
+        }
+        walk_expr(self, expr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    #[test]
+    fn graph_references() {
+        let code = "\
+Zombie1 is a zombie
+summon
+    remember 1
+bind
+
+Fibonacci is a ghost
+summon
+    task SayFibonaccis
+        shamble
+            say moan Zombie1
+            remember Zombie1 moan Zombie1 moan
+            banish Zombie1
+        until remembering 1000
+        animate Zombie1
+        invoke
+    animate
+disturb";
+
+        let scroll = parse(code).unwrap();
+        assert_eq!(
+            graph(&scroll),
+            "\
+digraph scroll {
+    \"Fibonacci\" [label=\"Fibonacci\\n(Ghost)\"];
+    \"Zombie1\" [label=\"Zombie1\\n(Zombie)\"];
+    \"Fibonacci\" -> \"Zombie1\" [label=\"animate\"];
+    \"Fibonacci\" -> \"Zombie1\" [label=\"banish\"];
+    \"Fibonacci\" -> \"Zombie1\" [label=\"moan\"];
+    \"Fibonacci\" -> \"Zombie1\" [label=\"remember\"];
+}
+"
+        );
+    }
+
+    #[test]
+    fn graph_aliases_and_covens() {
+        let code = "\
+Peter is a zombie also known as Pete
+summon
+    remember 1
+bind
+
+Jay is a zombie
+summon
+    remember 2
+bind
+
+coven Nightshift containing Pete, Jay
+
+Lisa is a ghost
+summon
+    task Wake
+        animate Pete
+        banish Nightshift
+    animate
+disturb";
+
+        let scroll = parse(code).unwrap();
+        assert_eq!(
+            graph(&scroll),
+            "\
+digraph scroll {
+    \"Jay\" [label=\"Jay\\n(Zombie)\"];
+    \"Lisa\" [label=\"Lisa\\n(Ghost)\"];
+    \"Peter\" [label=\"Peter\\n(Zombie)\"];
+    \"Lisa\" -> \"Jay\" [label=\"banish\"];
+    \"Lisa\" -> \"Peter\" [label=\"animate\"];
+    \"Lisa\" -> \"Peter\" [label=\"banish\"];
+}
+"
+        );
+    }
+}
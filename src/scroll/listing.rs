@@ -0,0 +1,227 @@
+//! A compact "disassembly" of a scroll, printing one line per statement.
+//!
+//! Every line is qualified with the entity and task the statement belongs to and the index of
+//! the statement in its block, e.g. `Fibonacci.SayFibonaccis[0.3] remember Zombie2 moan Zombie1`.
+//! Indices of nested statements are joined by dots. The branches of `taste` are marked with
+//! `good` and `bad`, those of `consult` with `upon` and the index of the case, or `lest`.
+use std::fmt::{Display, Formatter, Result, Write};
+
+use smol_str::SmolStr;
+
+use super::entity::Entity;
+use super::expression::Expr;
+use super::statement::Stmt;
+use super::task::Task;
+use super::visit::{walk_entity, walk_task, Visitor};
+use super::Scroll;
+
+/// Create the listing of the scroll.
+pub fn listing(scroll: &Scroll) -> String {
+    let mut lister = Lister::default();
+    lister.visit_scroll(scroll);
+    lister.out
+}
+
+#[derive(Default)]
+struct Lister {
+    entity: SmolStr,
+    task: SmolStr,
+    path: Vec<SmolStr>,
+    out: String,
+}
+
+impl Lister {
+    fn visit_labelled_block(&mut self, label: &str, stmts: &[Stmt]) {
+        self.path.push(SmolStr::from(label));
+        self.visit_block(stmts);
+        self.path.pop();
+    }
+}
+
+impl<'ast> Visitor<'ast> for Lister {
+    fn visit_entity(&mut self, entity: &'ast Entity) {
+        self.entity = entity.name();
+        walk_entity(self, entity);
+    }
+
+    fn visit_task(&mut self, task: &'ast Task) {
+        self.task = task.name();
+        walk_task(self, task);
+    }
+
+    fn visit_block(&mut self, stmts: &'ast [Stmt]) {
+        for (index, stmt) in stmts.iter().enumerate() {
+            self.path.push(SmolStr::from(index.to_string()));
+            self.visit_stmt(stmt);
+            self.path.pop();
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        let _ = writeln!(
+            self.out,
+            "{}.{}[{}] {}",
+            self.entity,
+            self.task,
+            self.path.join("."),
+            line(stmt)
+        );
+        match stmt {
+            Stmt::ShambleUntil(_, stmts)
+            | Stmt::ShambleAround(stmts)
+            | Stmt::ShambleTimes(_, stmts) => self.visit_block(stmts),
+            Stmt::Taste(_, good, bad) => {
+                self.visit_labelled_block("good", good);
+                self.visit_labelled_block("bad", bad);
+            }
+            Stmt::Consult(_, cases, lest) => {
+                for (index, (_, stmts)) in cases.iter().enumerate() {
+                    self.visit_labelled_block(&format!("upon{}", index), stmts);
+                }
+                self.visit_labelled_block("lest", lest);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The line of the statement in the listing, without its position, e.g.
+/// `shamble ... until remembering 1000`. Nested statements are left out.
+pub fn line(stmt: &Stmt) -> String {
+    match stmt {
+        Stmt::Animate(name) => format!("animate{}", Target(name)),
+        Stmt::AnimateAll => String::from("animate all zombies"),
+        Stmt::Banish(name) => format!("banish{}", Target(name)),
+        Stmt::BanishAll => String::from("banish all"),
+        Stmt::Channel(port) => format!("channel {}", port),
+        Stmt::Disturb(name) => format!("disturb{}", Target(name)),
+        Stmt::DisturbAll => String::from("disturb all ghosts"),
+        Stmt::Entomb(path, exprs) => format!("entomb \"{}\"{}", path, join(exprs)),
+        Stmt::Exhume(path) => format!("exhume \"{}\"", path),
+        Stmt::SummonWithin(name, path) => format!("summon {} within \"{}\"", name, path),
+        Stmt::Forget(name) => format!("forget{}", Target(name)),
+        Stmt::Invoke(name) => format!("invoke{}", Target(name)),
+        Stmt::InvokeTask(name, task, exprs) => {
+            format!("invoke {} {}{}", name, task, Argument(exprs))
+        }
+        Stmt::Harvest(name) => format!("invoke{} harvest", Target(name)),
+        Stmt::Perform(name, task, exprs) => {
+            format!("perform{} {}{}", Target(name), task, Argument(exprs))
+        }
+        Stmt::Remember(name, exprs) => format!("remember{}{}", Target(name), join(exprs)),
+        Stmt::Say(name, exprs) => format!("say{}{}", Target(name), join(exprs)),
+        Stmt::Whisper(address, exprs) => format!("whisper beyond \"{}\"{}", address, join(exprs)),
+        Stmt::ShambleUntil(expr, _) => format!("shamble ... until {}", expr),
+        Stmt::ShambleAround(_) => String::from("shamble ... around"),
+        Stmt::ShambleTimes(expr, _) => format!("shamble {} times ... around", expr),
+        Stmt::Stumble => String::from("stumble"),
+        Stmt::Flee => String::from("flee"),
+        Stmt::Lurch => String::from("lurch"),
+        Stmt::Taste(expr, _, _) => format!("taste {} good ... bad ... spit", expr),
+        Stmt::Consult(expr, cases, _) => {
+            let cases: String = cases
+                .iter()
+                .map(|(value, _)| format!(" upon {} ...", Expr::Value(value.clone())))
+                .collect();
+            format!("consult {}{} lest ... settle", expr, cases)
+        }
+    }
+}
+
+/// Joins the expressions, each preceded by a space.
+pub(super) fn join(exprs: &[Expr]) -> String {
+    exprs.iter().map(|expr| format!(" {}", expr)).collect()
+}
+
+/// Displays the optional name of the entity a statement refers to.
+pub(super) struct Target<'a>(pub(super) &'a Option<SmolStr>);
+
+impl Display for Target<'_> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        match self.0 {
+            Some(name) => write!(fmt, " {}", name),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Displays the argument of a task call, if any.
+pub(super) struct Argument<'a>(pub(super) &'a [Expr]);
+
+impl Display for Argument<'_> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            write!(fmt, " with{}", join(self.0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    #[test]
+    fn list_statements() {
+        let code = "\
+Fibonacci is a zombie
+summon
+    remember 0
+    task SayFibonaccis
+        shamble
+            say moan Zombie1
+            remember Zombie2 moan Zombie1 moan Zombie2
+            taste remembering \"x\" good
+                stumble
+            bad
+                say \"y\" 1 rend
+            spit
+        until remembering 1000
+        forget
+    animate
+animate";
+
+        let scroll = parse(code).unwrap();
+        assert_eq!(
+            listing(&scroll),
+            "\
+Fibonacci.SayFibonaccis[0] shamble ... until remembering 1000
+Fibonacci.SayFibonaccis[0.0] say moan Zombie1
+Fibonacci.SayFibonaccis[0.1] remember Zombie2 moan Zombie1 moan Zombie2
+Fibonacci.SayFibonaccis[0.2] taste remembering \"x\" good ... bad ... spit
+Fibonacci.SayFibonaccis[0.2.good.0] stumble
+Fibonacci.SayFibonaccis[0.2.bad.0] say \"y\" 1 rend
+Fibonacci.SayFibonaccis[1] forget
+"
+        );
+    }
+
+    #[test]
+    fn list_consult() {
+        let code = "\
+Oracle is a zombie
+summon
+    task Answer
+        consult moan
+        upon 1
+            say \"one\"
+        upon \"two\"
+        lest
+            stumble
+        settle
+    animate
+animate";
+
+        let scroll = parse(code).unwrap();
+        assert_eq!(
+            listing(&scroll),
+            "\
+Oracle.Answer[0] consult moan upon 1 ... upon \"two\" ... lest ... settle
+Oracle.Answer[0.upon0.0] say \"one\"
+Oracle.Answer[0.lest.0] stumble
+"
+        );
+    }
+}
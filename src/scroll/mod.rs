@@ -1,35 +1,201 @@
 //! Scrolls are the internal representation of ZOMBIE source code. This module and its submodules contain the data type definitions for recipes.
-use std::collections::HashMap;
+use std::fmt::{Display, Formatter, Result};
 
 use entity::Entity;
+use indexmap::IndexMap;
 use smol_str::SmolStr;
+use summary::ScrollStats;
 
+#[cfg(feature = "arbitrary")]
+pub mod arbitrary;
+pub mod diff;
 pub mod entity;
 pub mod expression;
+pub mod graph;
+pub mod listing;
 pub mod statement;
+pub mod summary;
 pub mod task;
+pub mod visit;
 
-pub type EntityList = HashMap<SmolStr, Entity>;
+/// The creatures of a scroll by name, in the order of their definition.
+pub type EntityList = IndexMap<SmolStr, Entity>;
+
+/// The latest version of the ZOMBIE language. Version 1 is the language of the original spec, and
+/// version 2 adds the species, statements and expressions that this interpreter brought along.
+pub const LANGUAGE_VERSION: u32 = 2;
+
+/// The members of every coven of a scroll by the name of the coven, in the order of definition.
+pub type CovenList = IndexMap<SmolStr, Vec<SmolStr>>;
 
 /// A mysterious scroll with instructions for necromancers and their summoning rituals.
 ///
 /// Contains a list of creatures to summon.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Scroll {
-    // use hash map to store values on heap.
     entities: EntityList,
+    covens: CovenList,
+    meta: Option<ScrollMeta>,
 }
 
 impl Scroll {
     /// Create a new recipe from a set of creatures.
     fn new(entities: EntityList) -> Scroll {
-        Scroll { entities }
+        Scroll {
+            entities,
+            covens: CovenList::new(),
+            meta: None,
+        }
+    }
+
+    /// Start writing a new scroll in Rust code instead of parsing it from text.
+    ///
+    /// ```
+    /// use necromancer::scroll::entity::{Entity, Species};
+    /// use necromancer::scroll::expression::Expr;
+    /// use necromancer::scroll::statement::Stmt;
+    /// use necromancer::scroll::task::Task;
+    /// use necromancer::scroll::Scroll;
+    ///
+    /// let scroll = Scroll::builder()
+    ///     .entity(
+    ///         Entity::builder("Peter", Species::Zombie)
+    ///             .task(
+    ///                 Task::builder("Greet")
+    ///                     .statement(Stmt::Say(None, vec![Expr::Value("Hello World!".into())]))
+    ///                     .build(),
+    ///             )
+    ///             .build(),
+    ///     )
+    ///     .build();
+    ///
+    /// assert_eq!(scroll.creatures()["Peter"].tasks().len(), 1);
+    /// ```
+    pub fn builder() -> ScrollBuilder {
+        ScrollBuilder::default()
     }
 
     /// Return the creatures listed in the recipe.
     pub fn creatures(&self) -> &EntityList {
         &self.entities
     }
+
+    /// Return the creature that the name or alias refers to. Names take precedence over aliases.
+    pub fn resolve(&self, name: &str) -> Option<&Entity> {
+        self.entities.get(name).or_else(|| {
+            self.entities
+                .values()
+                .find(|entity| entity.aliases().iter().any(|alias| alias == name))
+        })
+    }
+
+    /// Return the covens of the scroll, declared with `coven <name> containing <member>, ...`.
+    ///
+    /// Statements that act on a named entity, like `banish` or `animate`, act on every member of
+    /// a coven in turn when given its name. Entities and their aliases take precedence over covens.
+    pub fn covens(&self) -> &CovenList {
+        &self.covens
+    }
+
+    /// Return the members of the named coven.
+    pub fn coven(&self, name: &str) -> Option<&[SmolStr]> {
+        self.covens.get(name).map(Vec::as_slice)
+    }
+
+    /// Return the creatures listed in the recipe, in the order of their definition.
+    pub fn creatures_ordered(&self) -> impl Iterator<Item = &Entity> {
+        self.entities.values()
+    }
+
+    /// Return the creatures listed in the recipe for modification.
+    pub(crate) fn creatures_mut(&mut self) -> &mut EntityList {
+        &mut self.entities
+    }
+
+    /// Return the title, author and version of the scroll, if it has a prologue.
+    pub fn meta(&self) -> Option<&ScrollMeta> {
+        self.meta.as_ref()
+    }
+
+    /// Return the version of the ZOMBIE language that the scroll speaks. Scrolls that don't
+    /// declare it speak the latest version, [`LANGUAGE_VERSION`].
+    pub fn language(&self) -> u32 {
+        self.meta
+            .as_ref()
+            .and_then(|meta| meta.language)
+            .unwrap_or(LANGUAGE_VERSION)
+    }
+
+    pub(crate) fn meta_mut(&mut self) -> &mut Option<ScrollMeta> {
+        &mut self.meta
+    }
+
+    /// Count the entities, tasks and statements of the scroll, and find names that don't belong
+    /// to any of its entities.
+    pub fn stats(&self) -> ScrollStats {
+        summary::stats(self)
+    }
+}
+
+/// The prologue of a scroll, e.g. `scroll "Fibonacci" by "Peter" version "1.0"`, followed by the
+/// version of the ZOMBIE language that the scroll speaks, e.g. `scroll speaks zombie 1`.
+///
+/// Either line may be left out. Author and version require a title.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScrollMeta {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub version: Option<String>,
+    /// The version of the ZOMBIE language. See [`Scroll::language`].
+    pub language: Option<u32>,
+}
+
+impl ScrollMeta {
+    pub fn new(title: impl Into<String>) -> ScrollMeta {
+        ScrollMeta {
+            title: Some(title.into()),
+            ..ScrollMeta::default()
+        }
+    }
+
+    pub fn author(mut self, author: impl Into<String>) -> ScrollMeta {
+        self.author = Some(author.into());
+        self
+    }
+
+    pub fn version(mut self, version: impl Into<String>) -> ScrollMeta {
+        self.version = Some(version.into());
+        self
+    }
+
+    pub fn speaks(mut self, language: u32) -> ScrollMeta {
+        self.language = Some(language);
+        self
+    }
+}
+
+impl Display for ScrollMeta {
+    /// Write the prologue the way it appears in the source code.
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        if let Some(title) = &self.title {
+            write!(fmt, "scroll \"{}\"", title)?;
+            if let Some(author) = &self.author {
+                write!(fmt, " by \"{}\"", author)?;
+            }
+            if let Some(version) = &self.version {
+                write!(fmt, " version \"{}\"", version)?;
+            }
+            if self.language.is_some() {
+                writeln!(fmt)?;
+            }
+        }
+        if let Some(language) = self.language {
+            write!(fmt, "scroll speaks zombie {}", language)?;
+        }
+        Ok(())
+    }
 }
 
 impl From<Vec<Entity>> for Scroll {
@@ -37,3 +203,52 @@ impl From<Vec<Entity>> for Scroll {
         Scroll::new(creatures.into_iter().map(|c| (c.name(), c)).collect())
     }
 }
+
+/// Builder for a [`Scroll`]. Create one with [`Scroll::builder`].
+#[derive(Debug, Clone, Default)]
+pub struct ScrollBuilder {
+    entities: Vec<Entity>,
+    covens: CovenList,
+    meta: Option<ScrollMeta>,
+}
+
+impl ScrollBuilder {
+    /// Add a creature to the scroll. A creature with the same name replaces any previous one, but
+    /// keeps its position.
+    pub fn entity(mut self, entity: Entity) -> ScrollBuilder {
+        self.entities.push(entity);
+        self
+    }
+
+    /// Add several creatures to the scroll.
+    pub fn entities(mut self, entities: impl IntoIterator<Item = Entity>) -> ScrollBuilder {
+        self.entities.extend(entities);
+        self
+    }
+
+    /// Add a coven of the named entities to the scroll. A coven with the same name replaces any
+    /// previous one.
+    pub fn coven<S: Into<SmolStr>>(
+        mut self,
+        name: impl Into<SmolStr>,
+        members: impl IntoIterator<Item = S>,
+    ) -> ScrollBuilder {
+        self.covens
+            .insert(name.into(), members.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Give the scroll a title, and optionally an author and a version.
+    pub fn meta(mut self, meta: ScrollMeta) -> ScrollBuilder {
+        self.meta = Some(meta);
+        self
+    }
+
+    /// Finish the scroll.
+    pub fn build(self) -> Scroll {
+        let mut scroll = Scroll::from(self.entities);
+        scroll.covens = self.covens;
+        scroll.meta = self.meta;
+        scroll
+    }
+}
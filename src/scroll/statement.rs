@@ -1,34 +1,153 @@
 use smol_str::SmolStr;
 
 use super::expression::Expr;
+use crate::value::Value;
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Stmt {
     /// Activates a new copy of the named entity, if it is an inactive zombie.
     Animate(Option<SmolStr>),
+    /// Animates every zombie of the ritual, as if each of them was named in turn with `animate`.
+    /// Liches and revenants count as zombies. Written `animate all zombies`.
+    AnimateAll,
     /// Immediately deactivates the entity.
     Banish(Option<SmolStr>),
+    /// Immediately deactivates every entity of the ritual, including the one performing the task.
+    /// Written `banish all`, so an entity named `all` can't be banished by name.
+    BanishAll,
+    /// Waits for a message on the given TCP port and remembers it.
+    Channel(u16),
     /// Activates a new copy of the named entity, if it is an inactive ghost.
     Disturb(Option<SmolStr>),
+    /// Disturbs every ghost of the ritual, as if each of them was named in turn with `disturb`.
+    /// Wraiths count as ghosts. Written `disturb all ghosts`.
+    DisturbAll,
+    /// Appends the sum of the values in the statement stack to the file at the given path.
+    Entomb(String, Vec<Expr>),
+    /// Instructs the entity to remember the contents of the file at the given path as a string.
+    Exhume(String),
     /// Instructs the entity to forget its remembered data value.
     Forget(Option<SmolStr>),
     /// Invokes a new copy of the named entity.
     Invoke(Option<SmolStr>),
+    /// Invokes a new copy of the named entity that performs only the named task, with the
+    /// parameter of the task bound to the sum of the values in the statement stack.
+    InvokeTask(SmolStr, SmolStr, Vec<Expr>),
+    /// Invokes a new copy of the named entity and waits for it to finish all its tasks.
+    /// The entity then remembers the value that the copy remembered at the end.
+    Harvest(Option<SmolStr>),
+    /// Performs the named task right away, like a subroutine, with the parameter of the task
+    /// bound to the sum of the values in the statement stack. Continues with the next statement
+    /// once the task is done.
+    /// If an entity is named, performs the task of that entity instead of one of its own. The
+    /// statements of the task still act on the entity performing it.
+    Perform(Option<SmolStr>, SmolStr, Vec<Expr>),
     /// Instructs the entity to remember the sum of the values in the statement stack.
     /// Since a zombie can only remember one thing at a time, this causes it
     /// to forget any previously remembered value.
+    /// Without any expressions, the entity remembers nothing, just like after `forget`.
     Remember(Option<SmolStr>, Vec<Expr>),
     /// Print the text to the standard output.
-    /// (It doesn't matter what entity does this, as the result is the same.)
+    /// If an entity is named, bare `moan` and `remembering` in the statement stack
+    /// refer to that entity instead of the one performing the task.
+    ///
+    /// The text is written and flushed before the statement completes, so the lines of a
+    /// single task appear in the order of their statements, and nothing said is lost when the
+    /// ritual ends. Lines of different tasks interleave in the order they were said. Hence all
+    /// lines of a zombie, a ghost or a vampire, which perform one task at a time, appear in the
+    /// order they were said, while the tasks of demons and djinn may interleave.
     Say(Option<SmolStr>, Vec<Expr>),
+    /// Performs the scroll at the given path as a ritual of its own and waits for it to end.
+    /// The entities of both scrolls are kept apart, so that their names never collide. Once the
+    /// inner ritual ended, the entity remembers the final memory of the named entity of the
+    /// inner scroll.
+    SummonWithin(SmolStr, String),
+
+    /// Sends the sum of the values in the statement stack to the given TCP address.
+    Whisper(String, Vec<Expr>),
 
     // Control flow
     /// Causes the entity to repeat the statements between shamble and until until the variable evaluates to true.
     ShambleUntil(Expr, Vec<Stmt>),
     /// Causes the entity to repeat the statements between shamble and around in an infinite loop.
     ShambleAround(Vec<Stmt>),
+    /// Causes the entity to repeat the statements between times and around as many times as
+    /// the expression evaluates to, which is evaluated once before the first round. Counts below
+    /// one and the void repeat nothing.
+    ShambleTimes(Expr, Vec<Stmt>),
     /// Causes the current task to become inactive immediately.
     Stumble,
+    /// Leaves the innermost shamble right away, continuing with the statement after it.
+    /// Outside of any shamble, ends the task, though unlike `stumble` not as a failure.
+    Flee,
+    /// Skips the rest of the innermost shamble, continuing with its next round, which checks
+    /// the condition of `shamble ... until` first. Outside of any shamble, ends the task like
+    /// `flee`.
+    Lurch,
     /// If the variable evaluates to true, causes the entity to perform the statements between good and bad, otherwise perform the statements between bad and spit.
     Taste(Expr, Vec<Stmt>, Vec<Stmt>),
+    /// Evaluates the expression once and performs the statements following the first `upon`
+    /// whose value equals it, or else those between `lest` and `settle`. Written
+    /// `consult <expr> upon <value> ... upon <value> ... lest ... settle`, where `lest` may be
+    /// left out.
+    Consult(Expr, Vec<(Value, Vec<Stmt>)>, Vec<Stmt>),
+}
+
+impl Stmt {
+    /// The entity that the statement acts on by name, if it is a statement that acts on every
+    /// member of a coven in turn when given its name.
+    pub fn target(&self) -> Option<&SmolStr> {
+        match self {
+            Stmt::Animate(name)
+            | Stmt::Banish(name)
+            | Stmt::Disturb(name)
+            | Stmt::Forget(name)
+            | Stmt::Invoke(name)
+            | Stmt::Remember(name, _) => name.as_ref(),
+            Stmt::InvokeTask(name, ..) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Return a copy of the statement that acts on the named entity instead of its
+    /// [target](Stmt::target). Statements without a target are copied as they are.
+    pub fn retarget(&self, name: SmolStr) -> Stmt {
+        match self {
+            Stmt::Animate(Some(_)) => Stmt::Animate(Some(name)),
+            Stmt::Banish(Some(_)) => Stmt::Banish(Some(name)),
+            Stmt::Disturb(Some(_)) => Stmt::Disturb(Some(name)),
+            Stmt::Forget(Some(_)) => Stmt::Forget(Some(name)),
+            Stmt::Invoke(Some(_)) => Stmt::Invoke(Some(name)),
+            Stmt::Remember(Some(_), exprs) => Stmt::Remember(Some(name), exprs.clone()),
+            Stmt::InvokeTask(_, task, exprs) => Stmt::InvokeTask(name, task.clone(), exprs.clone()),
+            stmt => stmt.clone(),
+        }
+    }
+
+    /// The keyword the statement starts with, or `harvest` for `invoke ... harvest`.
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Stmt::Animate(_) | Stmt::AnimateAll => "animate",
+            Stmt::Banish(_) | Stmt::BanishAll => "banish",
+            Stmt::Channel(_) => "channel",
+            Stmt::Disturb(_) | Stmt::DisturbAll => "disturb",
+            Stmt::Entomb(..) => "entomb",
+            Stmt::Exhume(_) => "exhume",
+            Stmt::Forget(_) => "forget",
+            Stmt::Invoke(_) | Stmt::InvokeTask(..) => "invoke",
+            Stmt::Harvest(_) => "harvest",
+            Stmt::Perform(..) => "perform",
+            Stmt::Remember(..) => "remember",
+            Stmt::Say(..) => "say",
+            Stmt::SummonWithin(..) => "summon",
+            Stmt::Whisper(..) => "whisper",
+            Stmt::ShambleUntil(..) | Stmt::ShambleAround(_) | Stmt::ShambleTimes(..) => "shamble",
+            Stmt::Stumble => "stumble",
+            Stmt::Flee => "flee",
+            Stmt::Lurch => "lurch",
+            Stmt::Taste(..) => "taste",
+            Stmt::Consult(..) => "consult",
+        }
+    }
 }
@@ -0,0 +1,359 @@
+//! A summary of the size of a scroll, as tables with one column or row per entity.
+//!
+//! The first table counts the tasks, statements and expressions of every entity, how deeply its
+//! statements are nested, how many expressions its longest statement stack holds, and which other
+//! entities it refers to by name. The second table counts the statements of every kind. Names
+//! that don't belong to any entity of the scroll are listed last.
+//!
+//! The same counts are available to tools as [`ScrollStats`].
+//!
+//! ```text
+//! Entity     Species  Tasks  Statements  Expressions  Nesting  Stack  References
+//! Fibonacci  Ghost    1      6           4            2        2      Zombie1
+//! Zombie1    Zombie   0      0           0            0        0      -
+//!
+//! Statements  Fibonacci  Zombie1
+//! animate     1          0
+//! banish      1          0
+//! ```
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use smol_str::SmolStr;
+
+use super::entity::{Entity, Species};
+use super::expression::Expr;
+use super::statement::Stmt;
+use super::task::Task;
+use super::visit::{walk_block, walk_entity, walk_expr, walk_stmt, walk_task, Visitor};
+use super::Scroll;
+
+/// Statistics about a scroll, for linters and other tools. See [`Scroll::stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScrollStats {
+    /// The number of entities of every species. Species without entities are left out.
+    pub species: BTreeMap<Species, usize>,
+    /// The number of tasks of all entities.
+    pub tasks: usize,
+    /// The number of statements of every kind by keyword, including nested statements.
+    pub statements: BTreeMap<&'static str, usize>,
+    /// The deepest nesting of blocks in any task, where the body of a task is at depth 1.
+    pub nesting: usize,
+    /// Names referred to without an entity of that name in the scroll. Spirits bound by the host
+    /// may still answer to them during the ritual.
+    pub undefined: BTreeSet<SmolStr>,
+}
+
+/// Collect the statistics of the scroll.
+pub fn stats(scroll: &Scroll) -> ScrollStats {
+    let mut counter = Counter::default();
+    counter.visit_scroll(scroll);
+
+    let mut stats = ScrollStats::default();
+    for entity in scroll.creatures().values() {
+        *stats.species.entry(entity.species()).or_default() += 1;
+    }
+    for count in counter.counts.values() {
+        stats.tasks += count.tasks;
+        stats.nesting = stats.nesting.max(count.nesting);
+        for (kind, n) in &count.kinds {
+            *stats.statements.entry(kind).or_default() += n;
+        }
+        stats.undefined.extend(
+            count
+                .references
+                .iter()
+                .filter(|name| scroll.resolve(name).is_none() && scroll.coven(name).is_none())
+                .cloned(),
+        );
+    }
+    stats
+}
+
+/// Create the summary of the scroll.
+pub fn summary(scroll: &Scroll) -> String {
+    let mut counter = Counter::default();
+    counter.visit_scroll(scroll);
+
+    let mut sizes = vec![row([
+        "Entity",
+        "Species",
+        "Tasks",
+        "Statements",
+        "Expressions",
+        "Nesting",
+        "Stack",
+        "References",
+    ])];
+    let mut kinds = BTreeSet::new();
+    for (entity, count) in &counter.counts {
+        kinds.extend(count.kinds.keys().copied());
+        let references = if count.references.is_empty() {
+            String::from("-")
+        } else {
+            count
+                .references
+                .iter()
+                .map(SmolStr::as_str)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        sizes.push(vec![
+            entity.to_string(),
+            count.species.clone(),
+            count.tasks.to_string(),
+            count.statements.to_string(),
+            count.expressions.to_string(),
+            count.nesting.to_string(),
+            count.stack.to_string(),
+            references,
+        ]);
+    }
+
+    let mut statements = vec![std::iter::once(String::from("Statements"))
+        .chain(counter.counts.keys().map(SmolStr::to_string))
+        .collect()];
+    for kind in kinds {
+        statements.push(
+            std::iter::once(String::from(kind))
+                .chain(
+                    counter
+                        .counts
+                        .values()
+                        .map(|count| count.kinds.get(kind).copied().unwrap_or(0).to_string()),
+                )
+                .collect(),
+        );
+    }
+
+    let mut out = table(&sizes);
+    out.push('\n');
+    out.push_str(&table(&statements));
+
+    let undefined = stats(scroll).undefined;
+    if !undefined.is_empty() {
+        let names: Vec<&str> = undefined.iter().map(SmolStr::as_str).collect();
+        let _ = write!(out, "\nUndefined  {}\n", names.join(", "));
+    }
+    out
+}
+
+fn row<const N: usize>(cells: [&str; N]) -> Vec<String> {
+    cells.into_iter().map(String::from).collect()
+}
+
+/// Lay out the rows in columns separated by two spaces.
+fn table(rows: &[Vec<String>]) -> String {
+    let mut widths = Vec::new();
+    for row in rows {
+        widths.resize(widths.len().max(row.len()), 0);
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut out = String::new();
+    for row in rows {
+        let mut line = String::new();
+        for (cell, width) in row.iter().zip(&widths) {
+            let _ = write!(line, "{:width$}  ", cell, width = width);
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// What is counted for a single entity.
+#[derive(Default)]
+struct Count {
+    species: String,
+    tasks: usize,
+    statements: usize,
+    expressions: usize,
+    /// The deepest nesting of blocks, where the body of a task is at depth 1.
+    nesting: usize,
+    /// The most expressions in the stack of a single statement.
+    stack: usize,
+    kinds: BTreeMap<&'static str, usize>,
+    references: BTreeSet<SmolStr>,
+}
+
+#[derive(Default)]
+struct Counter {
+    entity: SmolStr,
+    /// The parameter of the current task, which shadows any entity of the same name.
+    parameter: Option<SmolStr>,
+    depth: usize,
+    counts: BTreeMap<SmolStr, Count>,
+}
+
+impl Counter {
+    fn count(&mut self) -> &mut Count {
+        self.counts.entry(self.entity.clone()).or_default()
+    }
+
+    fn refer(&mut self, name: &SmolStr) {
+        if *name != self.entity && self.parameter.as_ref() != Some(name) {
+            self.count().references.insert(name.clone());
+        }
+    }
+
+    fn stack(&mut self, exprs: &[Expr]) {
+        let count = self.count();
+        count.stack = count.stack.max(exprs.len());
+    }
+}
+
+impl<'ast> Visitor<'ast> for Counter {
+    fn visit_entity(&mut self, entity: &'ast Entity) {
+        self.entity = entity.name();
+        self.count().species = entity.species().to_string();
+        walk_entity(self, entity);
+    }
+
+    fn visit_task(&mut self, task: &'ast Task) {
+        self.parameter = task.parameter().cloned();
+        self.count().tasks += 1;
+        walk_task(self, task);
+    }
+
+    fn visit_block(&mut self, stmts: &'ast [Stmt]) {
+        self.depth += 1;
+        let depth = self.depth;
+        let count = self.count();
+        count.nesting = count.nesting.max(depth);
+        walk_block(self, stmts);
+        self.depth -= 1;
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        let count = self.count();
+        count.statements += 1;
+        *count.kinds.entry(stmt.keyword()).or_default() += 1;
+        match stmt {
+            Stmt::Animate(Some(name))
+            | Stmt::Banish(Some(name))
+            | Stmt::Disturb(Some(name))
+            | Stmt::Forget(Some(name))
+            | Stmt::Invoke(Some(name))
+            | Stmt::Harvest(Some(name)) => self.refer(name),
+            Stmt::InvokeTask(name, _, exprs) | Stmt::Perform(Some(name), _, exprs) => {
+                self.refer(name);
+                self.stack(exprs);
+            }
+            Stmt::Remember(name, exprs) | Stmt::Say(name, exprs) => {
+                if let Some(name) = name {
+                    self.refer(name);
+                }
+                self.stack(exprs);
+            }
+            Stmt::Entomb(_, exprs) | Stmt::Perform(None, _, exprs) | Stmt::Whisper(_, exprs) => {
+                self.stack(exprs)
+            }
+            _ => {}
+        }
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        self.count().expressions += 1;
+        if let Expr::Moan(Some(name))
+        | Expr::Remembering(Some(name), _)
+        | Expr::Reminisce(Some(name), _) = expr
+        {
+            self.refer(name);
+        }
+        if let Expr::Group(exprs) = expr {
+            self.stack(exprs);
+        }
+        walk_expr(self, expr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    #[test]
+    fn summarize_scroll() {
+        let code = "\
+Zombie1 is a zombie
+summon
+    remember 1
+bind
+
+Fibonacci is a ghost
+summon
+    task SayFibonaccis
+        shamble
+            say moan Zombie1
+            remember Zombie1 moan Zombie1 moan
+            banish Zombie1
+        until remembering 1000
+        animate Zombie1
+        invoke
+    animate
+disturb";
+
+        let scroll = parse(code).unwrap();
+        assert_eq!(
+            summary(&scroll),
+            "\
+Entity     Species  Tasks  Statements  Expressions  Nesting  Stack  References
+Fibonacci  Ghost    1      6           4            2        2      Zombie1
+Zombie1    Zombie   0      0           0            0        0      -
+
+Statements  Fibonacci  Zombie1
+animate     1          0
+banish      1          0
+invoke      1          0
+remember    1          0
+say         1          0
+shamble     1          0
+"
+        );
+    }
+
+    #[test]
+    fn collect_stats() {
+        let code = "\
+Peter is a zombie
+summon
+    task Haunt
+        taste remembering Lisa 1 good
+            shamble
+                say moan Gustav
+            until remembering 3
+        bad
+            animate Clock
+        spit
+    animate
+    task Count of Clock
+        say moan Clock
+    animate
+animate
+
+Lisa is a ghost
+summon
+bind";
+
+        let scroll = parse(code).unwrap();
+        let stats = scroll.stats();
+        assert_eq!(
+            stats.species,
+            BTreeMap::from([(Species::Zombie, 1), (Species::Ghost, 1)])
+        );
+        assert_eq!(stats.tasks, 2);
+        assert_eq!(
+            stats.statements,
+            BTreeMap::from([("animate", 1), ("say", 2), ("shamble", 1), ("taste", 1)])
+        );
+        assert_eq!(stats.nesting, 3);
+        assert_eq!(
+            stats.undefined,
+            BTreeSet::from([SmolStr::from("Clock"), SmolStr::from("Gustav")])
+        );
+        assert!(summary(&scroll).ends_with("\nUndefined  Clock, Gustav\n"));
+    }
+}
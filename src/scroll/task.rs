@@ -2,26 +2,52 @@ use smol_str::SmolStr;
 
 use super::statement::Stmt;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Task {
     name: SmolStr,
+    parameter: Option<SmolStr>,
     active: bool,
     stmts: Vec<Stmt>,
 }
 
 impl Task {
+    /// Create a new task from all of its parts. See [`Task::builder`] for a more
+    /// convenient way.
     pub fn new(name: &str, active: bool, stmts: Vec<Stmt>) -> Task {
         Task {
             name: SmolStr::from(name),
+            parameter: None,
             active,
             stmts,
         }
     }
 
+    /// Start creating a new task with the given name.
+    ///
+    /// The task is active and has no statements unless specified otherwise.
+    pub fn builder(name: &str) -> TaskBuilder {
+        TaskBuilder {
+            name: SmolStr::from(name),
+            parameter: None,
+            active: true,
+            stmts: Vec::new(),
+        }
+    }
+
     pub fn name(&self) -> SmolStr {
         self.name.clone()
     }
 
+    /// The name of the parameter of the task, as in `task Double of X`.
+    ///
+    /// Tasks with a parameter are subroutines: they are only performed when called with
+    /// `perform` or `invoke ... with`, which binds the parameter to a value. Within the task,
+    /// `moan X` and `remembering X` refer to that value instead of an entity named `X`.
+    pub fn parameter(&self) -> Option<&SmolStr> {
+        self.parameter.as_ref()
+    }
+
     pub fn active(&self) -> bool {
         self.active
     }
@@ -29,4 +55,53 @@ impl Task {
     pub fn statements(&self) -> &Vec<Stmt> {
         &self.stmts
     }
+
+    pub(crate) fn statements_mut(&mut self) -> &mut Vec<Stmt> {
+        &mut self.stmts
+    }
+}
+
+/// Builder for a [`Task`]. Create one with [`Task::builder`].
+#[derive(Debug, Clone)]
+pub struct TaskBuilder {
+    name: SmolStr,
+    parameter: Option<SmolStr>,
+    active: bool,
+    stmts: Vec<Stmt>,
+}
+
+impl TaskBuilder {
+    /// Give the task a parameter of the given name. See [`Task::parameter`].
+    pub fn parameter(mut self, name: &str) -> TaskBuilder {
+        self.parameter = Some(SmolStr::from(name));
+        self
+    }
+
+    /// Set whether the task is active.
+    pub fn active(mut self, active: bool) -> TaskBuilder {
+        self.active = active;
+        self
+    }
+
+    /// Append a statement to the task.
+    pub fn statement(mut self, stmt: Stmt) -> TaskBuilder {
+        self.stmts.push(stmt);
+        self
+    }
+
+    /// Append several statements to the task.
+    pub fn statements(mut self, stmts: impl IntoIterator<Item = Stmt>) -> TaskBuilder {
+        self.stmts.extend(stmts);
+        self
+    }
+
+    /// Finish the task.
+    pub fn build(self) -> Task {
+        Task {
+            name: self.name,
+            parameter: self.parameter,
+            active: self.active,
+            stmts: self.stmts,
+        }
+    }
 }
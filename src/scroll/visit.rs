@@ -0,0 +1,119 @@
+//! Traversal of the syntax tree of a [`Scroll`].
+//!
+//! Implement [`Visitor`] and override the methods for the nodes of interest. The default
+//! implementations descend into the children of a node by calling the matching `walk_*` function,
+//! so an overriding method should call it as well if it wants to keep descending.
+use super::entity::Entity;
+use super::expression::Expr;
+use super::statement::Stmt;
+use super::task::Task;
+use super::Scroll;
+
+/// A visitor walking over the syntax tree of a [`Scroll`].
+pub trait Visitor<'ast> {
+    fn visit_scroll(&mut self, scroll: &'ast Scroll) {
+        walk_scroll(self, scroll)
+    }
+
+    fn visit_entity(&mut self, entity: &'ast Entity) {
+        walk_entity(self, entity)
+    }
+
+    fn visit_task(&mut self, task: &'ast Task) {
+        walk_task(self, task)
+    }
+
+    /// Visit a sequence of statements, i.e. the body of a task or of a control flow statement.
+    fn visit_block(&mut self, stmts: &'ast [Stmt]) {
+        walk_block(self, stmts)
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        walk_stmt(self, stmt)
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        walk_expr(self, expr)
+    }
+}
+
+pub fn walk_scroll<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, scroll: &'ast Scroll) {
+    for entity in scroll.creatures().values() {
+        visitor.visit_entity(entity);
+    }
+}
+
+pub fn walk_entity<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, entity: &'ast Entity) {
+    for task in entity.tasks().values() {
+        visitor.visit_task(task);
+    }
+}
+
+pub fn walk_task<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, task: &'ast Task) {
+    visitor.visit_block(task.statements());
+}
+
+pub fn walk_block<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, stmts: &'ast [Stmt]) {
+    for stmt in stmts {
+        visitor.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_stmt<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, stmt: &'ast Stmt) {
+    match stmt {
+        Stmt::Animate(_)
+        | Stmt::AnimateAll
+        | Stmt::Banish(_)
+        | Stmt::BanishAll
+        | Stmt::Channel(_)
+        | Stmt::Disturb(_)
+        | Stmt::DisturbAll
+        | Stmt::Exhume(_)
+        | Stmt::SummonWithin(..)
+        | Stmt::Forget(_)
+        | Stmt::Harvest(_)
+        | Stmt::Invoke(_)
+        | Stmt::Stumble
+        | Stmt::Flee
+        | Stmt::Lurch => {}
+        Stmt::Entomb(_, exprs)
+        | Stmt::InvokeTask(_, _, exprs)
+        | Stmt::Perform(_, _, exprs)
+        | Stmt::Remember(_, exprs)
+        | Stmt::Say(_, exprs)
+        | Stmt::Whisper(_, exprs) => {
+            for expr in exprs {
+                visitor.visit_expr(expr);
+            }
+        }
+        Stmt::ShambleUntil(expr, stmts) => {
+            visitor.visit_block(stmts);
+            visitor.visit_expr(expr);
+        }
+        Stmt::ShambleAround(stmts) => visitor.visit_block(stmts),
+        Stmt::ShambleTimes(expr, stmts) => {
+            visitor.visit_expr(expr);
+            visitor.visit_block(stmts);
+        }
+        Stmt::Taste(expr, good, bad) => {
+            visitor.visit_expr(expr);
+            visitor.visit_block(good);
+            visitor.visit_block(bad);
+        }
+        Stmt::Consult(expr, cases, lest) => {
+            visitor.visit_expr(expr);
+            for (_, stmts) in cases {
+                visitor.visit_block(stmts);
+            }
+            visitor.visit_block(lest);
+        }
+    }
+}
+
+pub fn walk_expr<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, expr: &'ast Expr) {
+    if let Expr::Group(exprs) = expr {
+        for expr in exprs {
+            visitor.visit_expr(expr);
+        }
+    }
+}
@@ -0,0 +1,331 @@
+//! A dashboard that shows a ritual in the terminal while it is performed, like a visual debugger.
+//!
+//! Every entity gets a pane with the value it remembers, whether it is active, how many of its
+//! spirits perform, and the statement that one of them performs right now. Below the panes, a log
+//! collects everything said and any warnings. The dashboard follows the [events](RitualEvent) of
+//! the ritual, and a [hook](StatementHook) reports the statements.
+use std::collections::VecDeque;
+use std::io::{self, IsTerminal};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use indexmap::IndexMap;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use smol_str::SmolStr;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+
+use crate::necro::{
+    Interrupt, Necromancer, RitualConfig, RitualEvent, RitualReport, StatementHook,
+};
+use crate::scroll::entity::Species;
+use crate::scroll::listing::line;
+use crate::scroll::statement::Stmt;
+use crate::scroll::Scroll;
+use crate::value::Value;
+
+/// How often the dashboard is drawn again.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How many lines the log keeps. Older ones are dropped.
+const LOG_CAPACITY: usize = 1000;
+
+/// How many panes are placed side by side.
+const COLUMNS: usize = 3;
+
+/// Perform the ritual while showing it on a dashboard in the terminal, and return its report once
+/// the dashboard is closed.
+///
+/// The ritual can be ended early with `q`, `Esc` or `Ctrl+C`. Once it ended on its own, the
+/// dashboard shows the final state until closed with the same keys. The arrow keys scroll the log.
+///
+/// The dashboard takes over the standard output, so the output of the ritual should go
+/// [elsewhere](RitualConfig::output). Fails if the standard output is not a terminal.
+pub fn dashboard(scroll: Scroll, config: RitualConfig) -> io::Result<RitualReport> {
+    if !io::stdout().is_terminal() {
+        return Err(io::Error::other("the dashboard needs a terminal"));
+    }
+    let board = Arc::new(Mutex::new(Board::new(&scroll)));
+    let interrupt = Interrupt::new();
+    let config = config
+        .hook(BoardHook(board.clone()))
+        .interrupt(interrupt.clone());
+    let mut necromancer = Necromancer::unroll(scroll).with_config(config);
+    let listener = listen(necromancer.subscribe(), board.clone());
+
+    let mut terminal = ratatui::try_init()?;
+    let ritual = thread::spawn(move || necromancer.initiate());
+    let shown = show(&mut terminal, &board, &ritual);
+    interrupt.trigger();
+    ratatui::try_restore()?;
+    let report = ritual
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+    let _ = listener.join();
+    shown.map(|_| report)
+}
+
+/// Draw the dashboard until it is closed. The ritual may still be going on by then.
+fn show(
+    terminal: &mut DefaultTerminal,
+    board: &Mutex<Board>,
+    ritual: &JoinHandle<RitualReport>,
+) -> io::Result<()> {
+    let start = Instant::now();
+    let mut end = None;
+    loop {
+        if end.is_none() && ritual.is_finished() {
+            end = Some(start.elapsed());
+        }
+        let elapsed = end.unwrap_or_else(|| start.elapsed());
+        terminal.draw(|frame| Board::lock(board).draw(frame, elapsed, end.is_some()))?;
+
+        if !event::poll(REFRESH_INTERVAL)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        let mut board = Board::lock(board);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+            KeyCode::Up => board.scroll += 1,
+            KeyCode::Down => board.scroll = board.scroll.saturating_sub(1),
+            KeyCode::PageUp => board.scroll += 10,
+            KeyCode::PageDown => board.scroll = board.scroll.saturating_sub(10),
+            KeyCode::End => board.scroll = 0,
+            _ => {}
+        }
+    }
+}
+
+/// Update the board with the events of the ritual until it ends.
+fn listen(mut events: Receiver<RitualEvent>, board: Arc<Mutex<Board>>) -> JoinHandle<()> {
+    thread::spawn(move || loop {
+        let event = match events.blocking_recv() {
+            Ok(event) => event,
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => break,
+        };
+        let mut board = Board::lock(&board);
+        match event {
+            RitualEvent::SpiritSummoned(name) => {
+                if let Some(pane) = board.panes.get_mut(&name) {
+                    pane.spirits += 1;
+                }
+            }
+            RitualEvent::SpiritFinished(name) => {
+                if let Some(pane) = board.panes.get_mut(&name) {
+                    pane.spirits = pane.spirits.saturating_sub(1);
+                    if pane.spirits == 0 {
+                        pane.statement = None;
+                    }
+                }
+            }
+            RitualEvent::Said { spirit, value } => {
+                board.log(Entry::Said(spirit, value.uncursed().to_string()))
+            }
+            RitualEvent::StateChanged {
+                name,
+                memory,
+                active,
+            } => {
+                if let Some(pane) = board.panes.get_mut(&name) {
+                    pane.memory = memory;
+                    pane.active = active;
+                }
+            }
+            RitualEvent::Warned(warning) => board.log(Entry::Warned(warning.to_string())),
+        }
+    })
+}
+
+/// Everything the dashboard shows.
+struct Board {
+    panes: IndexMap<SmolStr, Pane>,
+    log: VecDeque<Entry>,
+    /// How many lines the log is scrolled back from its end.
+    scroll: usize,
+}
+
+/// The state of an entity.
+struct Pane {
+    species: Species,
+    memory: Value,
+    active: bool,
+    /// How many spirits of the entity perform right now.
+    spirits: usize,
+    /// The task and the statement that a spirit of the entity performs right now.
+    statement: Option<String>,
+}
+
+/// A line of the log.
+enum Entry {
+    Said(SmolStr, String),
+    Warned(String),
+}
+
+impl Board {
+    /// Show the entities of the scroll as they are before the ritual.
+    fn new(scroll: &Scroll) -> Board {
+        let panes = scroll
+            .creatures()
+            .values()
+            .map(|entity| {
+                let pane = Pane {
+                    species: entity.species(),
+                    memory: entity.moan().clone(),
+                    active: entity.active(),
+                    spirits: 0,
+                    statement: None,
+                };
+                (entity.name(), pane)
+            })
+            .collect();
+        Board {
+            panes,
+            log: VecDeque::new(),
+            scroll: 0,
+        }
+    }
+
+    fn lock(board: &Mutex<Board>) -> MutexGuard<'_, Board> {
+        board.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn log(&mut self, entry: Entry) {
+        if self.log.len() == LOG_CAPACITY {
+            self.log.pop_front();
+        }
+        self.log.push_back(entry);
+        // Keep the log in place while it is scrolled back.
+        if self.scroll > 0 {
+            self.scroll += 1;
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame, elapsed: Duration, ended: bool) {
+        let [header, panes, log, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(6),
+            Constraint::Percentage(35),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let spirits: usize = self.panes.values().map(|pane| pane.spirits).sum();
+        let state = if ended {
+            "ended".red()
+        } else {
+            "performing".green()
+        };
+        let header_line = Line::from(vec![
+            " Ritual ".bold(),
+            state,
+            format!(" | {:.1}s | spirits: {}", elapsed.as_secs_f64(), spirits).into(),
+        ]);
+        frame.render_widget(header_line, header);
+
+        self.draw_panes(frame, panes);
+        self.draw_log(frame, log);
+
+        let keys = if ended {
+            " q: close | ↑↓: scroll the log | End: follow the log"
+        } else {
+            " q: end the ritual | ↑↓: scroll the log | End: follow the log"
+        };
+        frame.render_widget(Line::from(keys).dark_gray(), footer);
+    }
+
+    fn draw_panes(&self, frame: &mut Frame, area: Rect) {
+        let columns = self.panes.len().clamp(1, COLUMNS);
+        let rows = self.panes.len().div_ceil(columns).max(1);
+        let row_areas = Layout::vertical(vec![Constraint::Ratio(1, rows as u32); rows]).split(area);
+        let names: Vec<&SmolStr> = self.panes.keys().collect();
+        for (row, names) in row_areas.iter().zip(names.chunks(columns)) {
+            let cells =
+                Layout::horizontal(vec![Constraint::Ratio(1, columns as u32); columns]).split(*row);
+            for (cell, name) in cells.iter().zip(names) {
+                let pane = &self.panes[*name];
+                let title = format!(" {} ({}) ", name, pane.species);
+                let title_style = if pane.active {
+                    Style::new().bold().fg(Color::Green)
+                } else {
+                    Style::new().fg(Color::DarkGray)
+                };
+                let memory = match &pane.memory {
+                    Value::Void => Span::from("nothing").dark_gray(),
+                    memory => Span::from(memory.uncursed().to_string()),
+                };
+                let lines = vec![
+                    Line::from(vec!["remembers ".dark_gray(), memory]),
+                    Line::from(vec![
+                        "active ".dark_gray(),
+                        if pane.active { "yes" } else { "no" }.into(),
+                    ]),
+                    Line::from(vec![
+                        "spirits ".dark_gray(),
+                        pane.spirits.to_string().into(),
+                    ]),
+                    Line::from(vec![
+                        "performs ".dark_gray(),
+                        match &pane.statement {
+                            Some(statement) => Span::from(statement.as_str()).cyan(),
+                            None => Span::from("nothing").dark_gray(),
+                        },
+                    ]),
+                ];
+                let block = Block::bordered().title(Span::styled(title, title_style));
+                frame.render_widget(Paragraph::new(lines).block(block), *cell);
+            }
+        }
+    }
+
+    fn draw_log(&mut self, frame: &mut Frame, area: Rect) {
+        let height = usize::from(area.height.saturating_sub(2));
+        self.scroll = self.scroll.min(self.log.len().saturating_sub(height));
+        let end = self.log.len() - self.scroll;
+        let lines: Vec<Line> = self
+            .log
+            .range(end.saturating_sub(height)..end)
+            .map(|entry| match entry {
+                Entry::Said(spirit, text) => Line::from(vec![
+                    format!("{}: ", spirit).dark_gray(),
+                    text.as_str().into(),
+                ]),
+                Entry::Warned(warning) => Line::from(format!("warning: {}", warning)).yellow(),
+            })
+            .collect();
+        let title = if self.scroll > 0 {
+            format!(" Said ({} more below) ", self.scroll)
+        } else {
+            String::from(" Said ")
+        };
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(title)),
+            area,
+        );
+    }
+}
+
+/// Reports the statements that the spirits perform to the board.
+struct BoardHook(Arc<Mutex<Board>>);
+
+impl StatementHook for BoardHook {
+    fn before_stmt(&self, spirit: &str, task: &str, stmt: &Stmt) {
+        let statement = format!("{}: {}", task, line(stmt));
+        if let Some(pane) = Board::lock(&self.0).panes.get_mut(spirit) {
+            pane.statement = Some(statement);
+        }
+    }
+}
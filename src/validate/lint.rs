@@ -0,0 +1,619 @@
+//! Lints for scrolls that perform fine, but are written in a questionable way. Unlike the
+//! findings of [`validate`](super::validate), each of them can be turned off or made an error with
+//! a [`LintConfig`], usually read from a `.necrolint.toml`:
+//!
+//! ```toml
+//! [rules]
+//! unused-entity = "deny"
+//! magic-number = "warn"
+//!
+//! [magic-number]
+//! allowed = [0, 1, 60]
+//! ```
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use malachite::Integer;
+use smol_str::SmolStr;
+
+use super::reachable;
+use crate::scroll::entity::{Entity, Species};
+use crate::scroll::expression::Expr;
+use crate::scroll::statement::Stmt;
+use crate::scroll::task::Task;
+use crate::scroll::visit::{walk_entity, walk_expr, walk_stmt, walk_task, Visitor};
+use crate::scroll::Scroll;
+use crate::value::Value;
+
+/// A finding of the linter.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum Lint {
+    /// No other entity refers to the entity, and it does nothing on its own since it has no tasks
+    /// or is never awakened.
+    #[error("{entity} is never referred to by another entity and does nothing on its own")]
+    UnusedEntity { entity: SmolStr },
+    /// The entity remembers values, but no entity ever moans them.
+    #[error("{entity} remembers values, but they are never moaned")]
+    WriteOnlyEntity { entity: SmolStr },
+    /// The task has no statements.
+    #[error("task {task} of {entity} has no statements")]
+    EmptyTask { entity: SmolStr, task: SmolStr },
+    /// The condition of a `shamble ... until` or a `taste` doesn't refer to any entity, so it
+    /// always evaluates to the same value.
+    #[error("the condition of {keyword} in task {task} of {entity} is always the same")]
+    ConstantCondition {
+        entity: SmolStr,
+        task: SmolStr,
+        keyword: &'static str,
+    },
+    /// The task uses an integer literal that isn't explained by the name of an entity
+    /// remembering it.
+    #[error("task {task} of {entity} uses the magic number {number}")]
+    MagicNumber {
+        entity: SmolStr,
+        task: SmolStr,
+        number: Integer,
+    },
+}
+
+impl Lint {
+    pub fn rule(&self) -> Rule {
+        match self {
+            Lint::UnusedEntity { .. } => Rule::UnusedEntity,
+            Lint::WriteOnlyEntity { .. } => Rule::WriteOnlyEntity,
+            Lint::EmptyTask { .. } => Rule::EmptyTask,
+            Lint::ConstantCondition { .. } => Rule::ConstantCondition,
+            Lint::MagicNumber { .. } => Rule::MagicNumber,
+        }
+    }
+}
+
+/// A rule of the linter, which finds one kind of [`Lint`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Rule {
+    UnusedEntity,
+    WriteOnlyEntity,
+    EmptyTask,
+    ConstantCondition,
+    MagicNumber,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 5] = [
+        Rule::UnusedEntity,
+        Rule::WriteOnlyEntity,
+        Rule::EmptyTask,
+        Rule::ConstantCondition,
+        Rule::MagicNumber,
+    ];
+
+    /// The name of the rule in the configuration, like `unused-entity`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::UnusedEntity => "unused-entity",
+            Rule::WriteOnlyEntity => "write-only-entity",
+            Rule::EmptyTask => "empty-task",
+            Rule::ConstantCondition => "constant-condition",
+            Rule::MagicNumber => "magic-number",
+        }
+    }
+
+    /// The code of the lints found by the rule in rendered diagnostics.
+    pub fn code(self) -> &'static str {
+        match self {
+            Rule::UnusedEntity => "L0001",
+            Rule::WriteOnlyEntity => "L0002",
+            Rule::EmptyTask => "L0003",
+            Rule::ConstantCondition => "L0004",
+            Rule::MagicNumber => "L0005",
+        }
+    }
+
+    /// The level of the rule unless configured otherwise. Magic numbers are common enough in
+    /// scrolls to be allowed.
+    pub fn default_level(self) -> Level {
+        match self {
+            Rule::MagicNumber => Level::Allow,
+            _ => Level::Warn,
+        }
+    }
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Rule {
+    type Err = LintConfigError;
+
+    fn from_str(s: &str) -> Result<Rule, LintConfigError> {
+        Rule::ALL
+            .into_iter()
+            .find(|rule| rule.name() == s)
+            .ok_or_else(|| LintConfigError::UnknownRule(String::from(s)))
+    }
+}
+
+/// What to do about the lints of a rule.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Level {
+    /// Don't look for them.
+    Allow,
+    /// Report them as warnings.
+    Warn,
+    /// Report them as errors.
+    Deny,
+}
+
+/// The error type for reading a [`LintConfig`].
+#[derive(thiserror::Error, Debug)]
+pub enum LintConfigError {
+    #[error(transparent)]
+    Toml(#[from] toml::de::Error),
+    #[error("unknown lint rule {0}")]
+    UnknownRule(String),
+    #[error("invalid level for {rule}, expected \"allow\", \"warn\" or \"deny\"")]
+    InvalidLevel { rule: Rule },
+    #[error("invalid setting {0}")]
+    InvalidSetting(String),
+}
+
+/// Settings for the linter: the level of each rule and the numbers that aren't magic.
+#[derive(Debug, Clone)]
+pub struct LintConfig {
+    levels: HashMap<Rule, Level>,
+    numbers: Vec<Integer>,
+}
+
+impl Default for LintConfig {
+    /// Apply every rule at its default level, and allow the numbers -1, 0, 1 and 2.
+    fn default() -> LintConfig {
+        LintConfig {
+            levels: HashMap::new(),
+            numbers: [-1, 0, 1, 2].into_iter().map(Integer::from).collect(),
+        }
+    }
+}
+
+impl LintConfig {
+    /// Read the settings from the contents of a `.necrolint.toml`. Anything not mentioned keeps
+    /// its default.
+    pub fn from_toml(toml: &str) -> Result<LintConfig, LintConfigError> {
+        let table: toml::Table = toml.parse()?;
+        let mut config = LintConfig::default();
+        for (key, value) in table {
+            match (key.as_str(), value) {
+                ("rules", toml::Value::Table(rules)) => {
+                    for (rule, level) in rules {
+                        let rule = rule.parse()?;
+                        let level = match level.as_str() {
+                            Some("allow") => Level::Allow,
+                            Some("warn") => Level::Warn,
+                            Some("deny") => Level::Deny,
+                            _ => return Err(LintConfigError::InvalidLevel { rule }),
+                        };
+                        config = config.level(rule, level);
+                    }
+                }
+                ("magic-number", toml::Value::Table(settings)) => {
+                    for (key, value) in settings {
+                        let numbers = match (key.as_str(), value) {
+                            ("allowed", toml::Value::Array(numbers)) => numbers
+                                .iter()
+                                .map(toml::Value::as_integer)
+                                .collect::<Option<Vec<i64>>>(),
+                            _ => None,
+                        };
+                        let numbers = numbers.ok_or_else(|| {
+                            LintConfigError::InvalidSetting(format!("magic-number.{}", key))
+                        })?;
+                        config = config.allow_numbers(numbers);
+                    }
+                }
+                (key, _) => return Err(LintConfigError::InvalidSetting(String::from(key))),
+            }
+        }
+        Ok(config)
+    }
+
+    /// Set the level of the rule.
+    pub fn level(mut self, rule: Rule, level: Level) -> LintConfig {
+        self.levels.insert(rule, level);
+        self
+    }
+
+    /// Replace the numbers that are not considered magic.
+    pub fn allow_numbers(mut self, numbers: impl IntoIterator<Item = i64>) -> LintConfig {
+        self.numbers = numbers.into_iter().map(Integer::from).collect();
+        self
+    }
+
+    pub fn level_of(&self, rule: Rule) -> Level {
+        self.levels
+            .get(&rule)
+            .copied()
+            .unwrap_or_else(|| rule.default_level())
+    }
+
+    fn applies(&self, rule: Rule) -> bool {
+        self.level_of(rule) != Level::Allow
+    }
+}
+
+/// Look for questionable code in the scroll, with the rules that the settings don't allow.
+pub fn lint(scroll: &Scroll, config: &LintConfig) -> Vec<Lint> {
+    let mut usage = Usage {
+        scroll,
+        entity: SmolStr::default(),
+        parameter: None,
+        context: None,
+        referenced: HashSet::new(),
+        written: HashSet::new(),
+        read: HashSet::new(),
+    };
+    usage.visit_scroll(scroll);
+    let awakened = reachable(scroll).awakened;
+
+    let mut lints = Vec::new();
+    for entity in scroll.creatures().values() {
+        let name = entity.name();
+        let unused = !usage.referenced.contains(&name)
+            && (entity.tasks().is_empty() || !awakened.contains(&name));
+        if unused && config.applies(Rule::UnusedEntity) {
+            lints.push(Lint::UnusedEntity {
+                entity: name.clone(),
+            });
+        }
+        // An unused entity is written to by itself at most, which is reported above already.
+        if !unused
+            && usage.written.contains(&name)
+            && !usage.read.contains(&name)
+            && config.applies(Rule::WriteOnlyEntity)
+        {
+            lints.push(Lint::WriteOnlyEntity {
+                entity: name.clone(),
+            });
+        }
+        for task in entity.tasks().values() {
+            if task.statements().is_empty() && config.applies(Rule::EmptyTask) {
+                lints.push(Lint::EmptyTask {
+                    entity: name.clone(),
+                    task: task.name(),
+                });
+            }
+            let mut linter = TaskLinter {
+                config,
+                entity: name.clone(),
+                task: task.name(),
+                numbers: HashSet::new(),
+                lints: &mut lints,
+            };
+            linter.visit_task(task);
+        }
+    }
+    lints
+}
+
+/// Collects which entities are referred to by others, remember values and have their values
+/// moaned.
+struct Usage<'s> {
+    scroll: &'s Scroll,
+    entity: SmolStr,
+    /// The parameter of the current task, which shadows any entity of the same name.
+    parameter: Option<SmolStr>,
+    /// The entity that bare expressions refer to inside of `say <name>`.
+    context: Option<SmolStr>,
+    referenced: HashSet<SmolStr>,
+    written: HashSet<SmolStr>,
+    read: HashSet<SmolStr>,
+}
+
+impl Usage<'_> {
+    /// The entities of the scroll that the name stands for: the entity of that name or alias, or
+    /// the members of the coven of that name.
+    fn targets(&self, name: &SmolStr) -> Vec<SmolStr> {
+        match (self.scroll.resolve(name), self.scroll.coven(name)) {
+            (Some(entity), _) => vec![entity.name()],
+            (None, Some(members)) => members
+                .iter()
+                .filter_map(|member| self.scroll.resolve(member))
+                .map(Entity::name)
+                .collect(),
+            (None, None) => Vec::new(),
+        }
+    }
+
+    fn refer(&mut self, name: &SmolStr) {
+        for target in self.targets(name) {
+            if target != self.entity {
+                self.referenced.insert(target);
+            }
+        }
+    }
+
+    fn refer_all(&mut self, species: Species) {
+        for entity in self.scroll.creatures().values() {
+            if entity.species() == species && entity.name() != self.entity {
+                self.referenced.insert(entity.name());
+            }
+        }
+    }
+
+    /// Note that the named entity, or the one performing the task, remembers a value.
+    fn write(&mut self, name: Option<&SmolStr>) {
+        let name = name.unwrap_or(&self.entity).clone();
+        let targets = self.targets(&name);
+        self.written.extend(targets);
+    }
+
+    /// Note that the value of the named entity, or the one of the context, is moaned.
+    fn read(&mut self, name: Option<&SmolStr>) {
+        let name = name
+            .or(self.context.as_ref())
+            .unwrap_or(&self.entity)
+            .clone();
+        let targets = self.targets(&name);
+        self.read.extend(targets);
+    }
+}
+
+impl<'ast> Visitor<'ast> for Usage<'_> {
+    fn visit_entity(&mut self, entity: &'ast Entity) {
+        self.entity = entity.name();
+        if *entity.moan() != Value::Void {
+            self.written.insert(entity.name());
+        }
+        walk_entity(self, entity);
+    }
+
+    fn visit_task(&mut self, task: &'ast Task) {
+        self.parameter = task.parameter().cloned();
+        walk_task(self, task);
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        if let Some(name) = stmt.target() {
+            self.refer(name);
+        }
+        match stmt {
+            Stmt::AnimateAll => self.refer_all(Species::Zombie),
+            Stmt::DisturbAll => self.refer_all(Species::Ghost),
+            Stmt::Remember(name, exprs) if !exprs.is_empty() => self.write(name.as_ref()),
+            Stmt::Channel(_) | Stmt::Exhume(_) | Stmt::SummonWithin(..) => self.write(None),
+            Stmt::Harvest(name) => {
+                if let Some(name) = name {
+                    self.refer(name);
+                }
+                self.read(name.as_ref());
+                self.write(None);
+            }
+            Stmt::Perform(Some(name), _, _) => self.refer(name),
+            Stmt::Say(Some(name), exprs) => {
+                self.refer(name);
+                let outer = self.context.replace(name.clone());
+                for expr in exprs {
+                    self.visit_expr(expr);
+                }
+                self.context = outer;
+                return;
+            }
+            _ => {}
+        }
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        if let Expr::Moan(name) | Expr::Remembering(name, _) | Expr::Reminisce(name, _) = expr {
+            if name.is_none() || name.as_ref() != self.parameter.as_ref() {
+                if let Some(name) = name {
+                    self.refer(name);
+                }
+                self.read(name.as_ref());
+            }
+        }
+        walk_expr(self, expr);
+    }
+}
+
+/// Finds constant conditions and magic numbers in a task.
+struct TaskLinter<'s> {
+    config: &'s LintConfig,
+    entity: SmolStr,
+    task: SmolStr,
+    /// The magic numbers found so far, which are reported once per task.
+    numbers: HashSet<Integer>,
+    lints: &'s mut Vec<Lint>,
+}
+
+impl<'ast> Visitor<'ast> for TaskLinter<'_> {
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        let keyword = match stmt {
+            Stmt::ShambleUntil(condition, _) if constant(condition) => Some("shamble"),
+            Stmt::Taste(condition, _, _) if constant(condition) => Some("taste"),
+            _ => None,
+        };
+        if let Some(keyword) = keyword.filter(|_| self.config.applies(Rule::ConstantCondition)) {
+            self.lints.push(Lint::ConstantCondition {
+                entity: self.entity.clone(),
+                task: self.task.clone(),
+                keyword,
+            });
+        }
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        if let Expr::Value(Value::Integer(number)) = expr {
+            if self.config.applies(Rule::MagicNumber)
+                && !self.config.numbers.contains(number)
+                && self.numbers.insert(number.clone())
+            {
+                self.lints.push(Lint::MagicNumber {
+                    entity: self.entity.clone(),
+                    task: self.task.clone(),
+                    number: number.clone(),
+                });
+            }
+        }
+        walk_expr(self, expr);
+    }
+}
+
+/// Whether the expression evaluates to the same value every time, since it reads neither
+/// entities nor the environment.
+fn constant(expr: &Expr) -> bool {
+    match expr {
+        Expr::Moan(_) | Expr::Remembering(..) | Expr::Reminisce(..) | Expr::Divine(_) => false,
+        Expr::Group(exprs) => exprs.iter().all(constant),
+        Expr::Rend
+        | Expr::Turn
+        | Expr::Fester(_)
+        | Expr::Compose(_)
+        | Expr::Transcribe(_)
+        | Expr::Value(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    #[test]
+    fn lint_entities() {
+        let code = "\
+Peter is a zombie
+summon
+    task Count
+        remember Counter moan Counter 1
+        say moan Counter
+        remember Diary moan Counter
+        invoke Lonely
+    animate
+animate
+
+Counter is a zombie
+summon
+    remember 0
+bind
+
+Diary is a zombie
+summon
+bind
+
+Lonely is a zombie
+summon
+    task Idle
+    animate
+bind
+
+Forgotten is a zombie
+summon
+    remember 42
+bind
+";
+        let scroll = parse(code).unwrap();
+        assert_eq!(
+            lint(&scroll, &LintConfig::default()),
+            [
+                Lint::WriteOnlyEntity {
+                    entity: SmolStr::from("Diary")
+                },
+                Lint::EmptyTask {
+                    entity: SmolStr::from("Lonely"),
+                    task: SmolStr::from("Idle")
+                },
+                Lint::UnusedEntity {
+                    entity: SmolStr::from("Forgotten")
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn lint_tasks() {
+        let code = "\
+Peter is a zombie
+summon
+    remember 3
+    task Loop
+        shamble
+            taste (rend 4 2) good
+                say 100
+            bad
+                say 100 moan
+            spit
+        until remembering 10
+        shamble
+            remember moan 7
+        until 1
+    animate
+animate
+";
+        let scroll = parse(code).unwrap();
+        let config = LintConfig::default().level(Rule::MagicNumber, Level::Warn);
+        let lints = lint(&scroll, &config);
+        let rules: Vec<Rule> = lints.iter().map(Lint::rule).collect();
+        assert_eq!(
+            rules,
+            [
+                Rule::ConstantCondition,
+                Rule::MagicNumber,
+                Rule::MagicNumber,
+                Rule::ConstantCondition,
+                Rule::MagicNumber,
+            ]
+        );
+        let numbers: Vec<String> = lints
+            .iter()
+            .filter_map(|lint| match lint {
+                Lint::MagicNumber { number, .. } => Some(number.to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(numbers, ["4", "100", "7"]);
+
+        let config = config.allow_numbers([1, 2, 4, 7, 100]);
+        assert_eq!(lint(&scroll, &config).len(), 2);
+    }
+
+    #[test]
+    fn read_config() {
+        let config = LintConfig::from_toml(
+            "\
+[rules]
+unused-entity = \"deny\"
+empty-task = \"allow\"
+magic-number = \"warn\"
+
+[magic-number]
+allowed = [0, 60]
+",
+        )
+        .unwrap();
+        assert_eq!(config.level_of(Rule::UnusedEntity), Level::Deny);
+        assert_eq!(config.level_of(Rule::EmptyTask), Level::Allow);
+        assert_eq!(config.level_of(Rule::MagicNumber), Level::Warn);
+        assert_eq!(config.level_of(Rule::WriteOnlyEntity), Level::Warn);
+        assert_eq!(config.numbers, [Integer::from(0), Integer::from(60)]);
+
+        assert!(matches!(
+            LintConfig::from_toml("[rules]\nunused = \"deny\""),
+            Err(LintConfigError::UnknownRule(_))
+        ));
+        assert!(matches!(
+            LintConfig::from_toml("[rules]\nempty-task = \"forbid\""),
+            Err(LintConfigError::InvalidLevel {
+                rule: Rule::EmptyTask
+            })
+        ));
+        assert!(matches!(
+            LintConfig::from_toml("[magic-number]\nallowed = [\"one\"]"),
+            Err(LintConfigError::InvalidSetting(_))
+        ));
+        assert!(LintConfig::from_toml("rules = [").is_err());
+    }
+}
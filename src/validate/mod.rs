@@ -0,0 +1,498 @@
+//! Static analysis of scrolls. Runs after parsing and before the ritual begins.
+use std::collections::HashSet;
+
+use smol_str::SmolStr;
+use tracing::debug;
+
+use crate::scroll::entity::{Entity, Species};
+use crate::scroll::expression::Expr;
+use crate::scroll::statement::Stmt;
+use crate::scroll::task::Task;
+use crate::scroll::visit::{walk_expr, walk_stmt, Visitor};
+use crate::scroll::{Scroll, LANGUAGE_VERSION};
+
+pub mod lint;
+#[cfg(test)]
+mod tests;
+
+/// A finding of the validation pass.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum Diagnostic {
+    /// The task belongs to an entity that is inactive and never awakened by anyone, and nobody
+    /// performs it.
+    #[error("task {task} of {entity} can never run, since {entity} is never awakened")]
+    DeadTask { entity: SmolStr, task: SmolStr },
+    /// The task shares its name with an entity, which statements like `invoke <name>` refer to.
+    #[error("task {task} of {entity} shares its name with an entity, which takes precedence")]
+    ShadowedTask { entity: SmolStr, task: SmolStr },
+    /// The task animates an entity that is not a zombie, which does nothing.
+    #[error("task {task} of {entity} animates {target}, which is a {species} and not a zombie")]
+    AnimateOnNonZombie {
+        entity: SmolStr,
+        task: SmolStr,
+        target: SmolStr,
+        species: Species,
+    },
+    /// The task disturbs an entity that is not a ghost, which does nothing.
+    #[error("task {task} of {entity} disturbs {target}, which is a {species} and not a ghost")]
+    DisturbOnNonGhost {
+        entity: SmolStr,
+        task: SmolStr,
+        target: SmolStr,
+        species: Species,
+    },
+    /// Statements following a `stumble`, `flee`, `lurch` or an endless `shamble ... around` in
+    /// the same block.
+    #[error("{count} statement(s) in task {task} of {entity} can never be reached")]
+    UnreachableStmts {
+        entity: SmolStr,
+        task: SmolStr,
+        count: usize,
+    },
+    /// The entity uses a species, statement or expression of a later version of the language
+    /// than the one that the scroll speaks.
+    #[error(
+        "{entity} uses {construct}, which needs zombie {since}, but the scroll speaks zombie {speaks}"
+    )]
+    UnspokenConstruct {
+        entity: SmolStr,
+        construct: SmolStr,
+        since: u32,
+        speaks: u32,
+    },
+    /// The scroll speaks a version of the language that doesn't exist.
+    #[error(
+        "the scroll speaks zombie {version}, but only zombie 1 to {} exist",
+        LANGUAGE_VERSION
+    )]
+    UnknownLanguage { version: u32 },
+}
+
+impl Diagnostic {
+    /// Whether the finding breaks a rule of the spec about which species respond to which
+    /// statements. Such scrolls are refused in strict mode.
+    pub fn breaks_species_rules(&self) -> bool {
+        matches!(
+            self,
+            Diagnostic::AnimateOnNonZombie { .. } | Diagnostic::DisturbOnNonGhost { .. }
+        )
+    }
+
+    /// Whether the finding is about something the scroll cannot say, so it may not be performed
+    /// at all.
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            Diagnostic::UnspokenConstruct { .. } | Diagnostic::UnknownLanguage { .. }
+        )
+    }
+}
+
+/// Analyse the scroll and report anything suspicious.
+pub fn validate(scroll: &Scroll) -> Vec<Diagnostic> {
+    let reachable = reachable(scroll);
+    let mut diagnostics = Vec::new();
+
+    let speaks = scroll.language();
+    if !(1..=LANGUAGE_VERSION).contains(&speaks) {
+        diagnostics.push(Diagnostic::UnknownLanguage { version: speaks });
+    }
+
+    for entity in scroll.creatures().values() {
+        if (1..LANGUAGE_VERSION).contains(&speaks) {
+            let mut dialect = Dialect::default();
+            dialect.visit_entity(entity);
+            if let Some(since) = species_since(entity.species()) {
+                dialect
+                    .constructs
+                    .insert(0, (since, format!("the species {}", entity.species())));
+            }
+            for (since, construct) in dialect.constructs {
+                if since > speaks {
+                    diagnostics.push(Diagnostic::UnspokenConstruct {
+                        entity: entity.name(),
+                        construct: SmolStr::from(construct),
+                        since,
+                        speaks,
+                    });
+                }
+            }
+        }
+        for task in entity.tasks().values() {
+            if scroll.resolve(&task.name()).is_some() {
+                diagnostics.push(Diagnostic::ShadowedTask {
+                    entity: entity.name(),
+                    task: task.name(),
+                });
+            }
+            let mut misdirection = Misdirection {
+                scroll,
+                entity,
+                task: task.name(),
+                diagnostics: &mut diagnostics,
+            };
+            misdirection.visit_task(task);
+            if !reachable.runs(entity, task) {
+                diagnostics.push(Diagnostic::DeadTask {
+                    entity: entity.name(),
+                    task: task.name(),
+                });
+                continue;
+            }
+            let count = count_unreachable(task.statements());
+            if count > 0 {
+                diagnostics.push(Diagnostic::UnreachableStmts {
+                    entity: entity.name(),
+                    task: task.name(),
+                    count,
+                });
+            }
+        }
+    }
+
+    diagnostics
+}
+
+/// Strip any tasks and statements from the scroll that can never be executed.
+pub fn optimize(scroll: &mut Scroll) {
+    let reachable = reachable(scroll);
+
+    for entity in scroll.creatures_mut().values_mut() {
+        if !reachable.awakened.contains(&entity.name()) {
+            debug!(
+                "Stripping all tasks of {} that nobody performs",
+                entity.name()
+            );
+            let name = entity.name();
+            entity
+                .tasks_mut()
+                .retain(|task, _| reachable.performed.contains(&(name.clone(), task.clone())));
+        }
+        for task in entity.tasks_mut().values_mut() {
+            strip_unreachable(task.statements_mut());
+        }
+    }
+}
+
+/// The entities and tasks of a scroll that may ever run.
+struct Reachable {
+    /// The entities that are either active from the beginning or awakened by another entity that
+    /// is awakened itself.
+    awakened: HashSet<SmolStr>,
+    /// The tasks, by entity and task name, that are performed by an entity that runs. Tasks of
+    /// entities that are never awakened run this way too.
+    performed: HashSet<(SmolStr, SmolStr)>,
+}
+
+impl Reachable {
+    /// Whether the task of the entity may ever run.
+    fn runs(&self, entity: &Entity, task: &Task) -> bool {
+        self.awakened.contains(&entity.name())
+            || self.performed.contains(&(entity.name(), task.name()))
+    }
+}
+
+/// Find the entities and tasks of the scroll that may ever run.
+fn reachable(scroll: &Scroll) -> Reachable {
+    let awakened: HashSet<SmolStr> = scroll
+        .creatures()
+        .values()
+        .filter(|entity| entity.active())
+        .map(|entity| entity.name())
+        .collect();
+    let mut pending: Vec<(SmolStr, Option<SmolStr>)> =
+        awakened.iter().map(|name| (name.clone(), None)).collect();
+    let mut reachable = Reachable {
+        awakened,
+        performed: HashSet::new(),
+    };
+
+    while let Some((name, task)) = pending.pop() {
+        let mut awakening = Awakening {
+            scroll,
+            summoner: name.clone(),
+            awakened: Vec::new(),
+            performed: Vec::new(),
+        };
+        let entity = &scroll.creatures()[&name];
+        match task {
+            // A performed task runs in the spirit that performs it, which is awakened already.
+            Some(task) => awakening.visit_task(&entity.tasks()[&task]),
+            None => awakening.visit_entity(entity),
+        }
+        for other in awakening.awakened {
+            if reachable.awakened.insert(other.clone()) {
+                pending.push((other, None));
+            }
+        }
+        for (other, task) in awakening.performed {
+            if reachable.performed.insert((other.clone(), task.clone())) {
+                pending.push((other, Some(task)));
+            }
+        }
+    }
+
+    reachable
+}
+
+/// Collects the names of the entities that a creature tries to wake up, and the tasks of other
+/// entities that it performs.
+struct Awakening<'s> {
+    scroll: &'s Scroll,
+    summoner: SmolStr,
+    awakened: Vec<SmolStr>,
+    performed: Vec<(SmolStr, SmolStr)>,
+}
+
+impl Awakening<'_> {
+    /// Wake up every creature of the species, for the broadcasts `animate all zombies` and
+    /// `disturb all ghosts`.
+    fn awaken_all(&mut self, species: Species) {
+        self.awakened.extend(
+            self.scroll
+                .creatures_ordered()
+                .filter(|entity| entity.species().responds_like(species))
+                .map(Entity::name),
+        );
+    }
+}
+
+impl<'ast> Visitor<'ast> for Awakening<'_> {
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        let (name, species) = match stmt {
+            Stmt::AnimateAll => return self.awaken_all(Species::Zombie),
+            Stmt::DisturbAll => return self.awaken_all(Species::Ghost),
+            Stmt::Animate(name) => (name, Some(Species::Zombie)),
+            Stmt::Disturb(name) => (name, Some(Species::Ghost)),
+            Stmt::Invoke(name) | Stmt::Harvest(name) => (name, None),
+            Stmt::InvokeTask(name, _, _) => (&Some(name.clone()), None),
+            Stmt::Perform(Some(name), task, _) => {
+                if let Some(entity) = self.scroll.resolve(name) {
+                    if entity.tasks().contains_key(task) {
+                        self.performed.push((entity.name(), task.clone()));
+                    }
+                }
+                return walk_stmt(self, stmt);
+            }
+            _ => return walk_stmt(self, stmt),
+        };
+        let name = name.clone().unwrap_or_else(|| self.summoner.clone());
+        // Statements aimed at a coven awaken each of its members.
+        let targets = match (self.scroll.resolve(&name), self.scroll.coven(&name)) {
+            (Some(entity), _) => vec![entity],
+            (None, Some(members)) => members
+                .iter()
+                .filter_map(|member| self.scroll.resolve(member))
+                .collect(),
+            (None, None) => Vec::new(),
+        };
+        for entity in targets {
+            if species.is_none_or(|species| entity.species().responds_like(species)) {
+                self.awakened.push(entity.name());
+            }
+        }
+    }
+}
+
+/// Finds statements of a task that can't have an effect because of the species of their target.
+struct Misdirection<'s> {
+    scroll: &'s Scroll,
+    entity: &'s Entity,
+    task: SmolStr,
+    diagnostics: &'s mut Vec<Diagnostic>,
+}
+
+impl<'ast> Visitor<'ast> for Misdirection<'_> {
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        let (name, expected) = match stmt {
+            Stmt::Animate(name) => (name, Species::Zombie),
+            Stmt::Disturb(name) => (name, Species::Ghost),
+            _ => return walk_stmt(self, stmt),
+        };
+        // Bound spirits are unknown before the ritual, so only creatures of the scroll count.
+        let target = match name {
+            Some(name) => match self.scroll.resolve(name) {
+                Some(target) => target,
+                None => return,
+            },
+            None => self.entity,
+        };
+        if target.species().responds_like(expected) {
+            return;
+        }
+        let (entity, task, species) = (self.entity.name(), self.task.clone(), target.species());
+        let target = name.clone().unwrap_or_else(|| target.name());
+        self.diagnostics.push(match expected {
+            Species::Zombie => Diagnostic::AnimateOnNonZombie {
+                entity,
+                task,
+                target,
+                species,
+            },
+            _ => Diagnostic::DisturbOnNonGhost {
+                entity,
+                task,
+                target,
+                species,
+            },
+        });
+    }
+}
+
+/// Collects the statements and expressions of an entity that are newer than the first version of
+/// the language, each once, with the version that introduced them.
+#[derive(Default)]
+struct Dialect {
+    constructs: Vec<(u32, String)>,
+}
+
+impl Dialect {
+    fn found(&mut self, since: u32, construct: &str) {
+        if !self.constructs.iter().any(|(_, known)| known == construct) {
+            self.constructs.push((since, String::from(construct)));
+        }
+    }
+}
+
+impl<'ast> Visitor<'ast> for Dialect {
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        let construct = match stmt {
+            Stmt::AnimateAll => Some("animate all zombies"),
+            Stmt::BanishAll => Some("banish all"),
+            Stmt::DisturbAll => Some("disturb all ghosts"),
+            Stmt::Channel(_) => Some("channel"),
+            Stmt::Entomb(..) => Some("entomb"),
+            Stmt::Exhume(_) => Some("exhume"),
+            Stmt::SummonWithin(..) => Some("summon within"),
+            Stmt::InvokeTask(..) => Some("invoke with a task"),
+            Stmt::Harvest(_) => Some("harvest"),
+            Stmt::Perform(..) => Some("perform"),
+            Stmt::Whisper(..) => Some("whisper"),
+            Stmt::ShambleTimes(..) => Some("shamble times"),
+            Stmt::Flee => Some("flee"),
+            Stmt::Lurch => Some("lurch"),
+            Stmt::Consult(..) => Some("consult"),
+            Stmt::Animate(_)
+            | Stmt::Banish(_)
+            | Stmt::Disturb(_)
+            | Stmt::Forget(_)
+            | Stmt::Invoke(_)
+            | Stmt::Remember(..)
+            | Stmt::Say(..)
+            | Stmt::ShambleUntil(..)
+            | Stmt::ShambleAround(_)
+            | Stmt::Stumble
+            | Stmt::Taste(..) => None,
+        };
+        if let Some(construct) = construct {
+            self.found(2, construct);
+        }
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        let construct = match expr {
+            Expr::Reminisce(..) => Some("reminisce"),
+            Expr::Fester(_) => Some("fester"),
+            Expr::Divine(_) => Some("divine"),
+            Expr::Compose(_) => Some("compose"),
+            Expr::Transcribe(_) => Some("transcribe"),
+            Expr::Group(_) => Some("parentheses"),
+            Expr::Moan(_) | Expr::Remembering(..) | Expr::Rend | Expr::Turn | Expr::Value(_) => {
+                None
+            }
+        };
+        if let Some(construct) = construct {
+            self.found(2, construct);
+        }
+        walk_expr(self, expr);
+    }
+}
+
+/// The version of the language that introduced the species, unless it is part of the first one.
+fn species_since(species: Species) -> Option<u32> {
+    match species {
+        Species::Lich | Species::Wraith | Species::Revenant => Some(2),
+        Species::Zombie | Species::Ghost | Species::Vampire | Species::Demon | Species::Djinn => {
+            None
+        }
+    }
+}
+
+/// Whether executing the statement never continues with the next statement of the block.
+fn diverges(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Stumble | Stmt::Flee | Stmt::Lurch => true,
+        Stmt::ShambleAround(stmts) => !flees(stmts),
+        Stmt::Taste(_, good, bad) => block_diverges(good) && block_diverges(bad),
+        Stmt::Consult(_, cases, lest) => {
+            cases.iter().all(|(_, stmts)| block_diverges(stmts)) && block_diverges(lest)
+        }
+        _ => false,
+    }
+}
+
+fn block_diverges(stmts: &[Stmt]) -> bool {
+    stmts.iter().any(diverges)
+}
+
+/// Whether the block of a shamble may leave it with `flee`, not counting nested shambles.
+fn flees(stmts: &[Stmt]) -> bool {
+    stmts.iter().any(|stmt| match stmt {
+        Stmt::Flee => true,
+        Stmt::Taste(_, good, bad) => flees(good) || flees(bad),
+        Stmt::Consult(_, cases, lest) => cases.iter().any(|(_, stmts)| flees(stmts)) || flees(lest),
+        _ => false,
+    })
+}
+
+/// Count the statements that can never be reached, including those in nested blocks.
+fn count_unreachable(stmts: &[Stmt]) -> usize {
+    let reachable = stmts
+        .iter()
+        .position(diverges)
+        .map_or(stmts.len(), |index| index + 1);
+    let nested: usize = stmts[..reachable]
+        .iter()
+        .map(|stmt| match stmt {
+            Stmt::ShambleUntil(_, stmts)
+            | Stmt::ShambleAround(stmts)
+            | Stmt::ShambleTimes(_, stmts) => count_unreachable(stmts),
+            Stmt::Taste(_, good, bad) => count_unreachable(good) + count_unreachable(bad),
+            Stmt::Consult(_, cases, lest) => {
+                let cases: usize = cases
+                    .iter()
+                    .map(|(_, stmts)| count_unreachable(stmts))
+                    .sum();
+                cases + count_unreachable(lest)
+            }
+            _ => 0,
+        })
+        .sum();
+    stmts.len() - reachable + nested
+}
+
+/// Remove the statements that can never be reached, including those in nested blocks.
+fn strip_unreachable(stmts: &mut Vec<Stmt>) {
+    if let Some(index) = stmts.iter().position(diverges) {
+        stmts.truncate(index + 1);
+    }
+    for stmt in stmts {
+        match stmt {
+            Stmt::ShambleUntil(_, stmts)
+            | Stmt::ShambleAround(stmts)
+            | Stmt::ShambleTimes(_, stmts) => strip_unreachable(stmts),
+            Stmt::Taste(_, good, bad) => {
+                strip_unreachable(good);
+                strip_unreachable(bad);
+            }
+            Stmt::Consult(_, cases, lest) => {
+                for (_, stmts) in cases {
+                    strip_unreachable(stmts);
+                }
+                strip_unreachable(lest);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,552 @@
+use super::*;
+use crate::parse::parse;
+
+fn init() {
+    let _ = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::TRACE)
+        .with_test_writer()
+        .try_init();
+}
+
+#[test]
+fn dead_tasks() {
+    init();
+
+    let code = "\
+Peter is a zombie
+summon
+    task Wake
+        animate Jay
+        invoke Max
+    animate
+animate
+
+Jay is a zombie
+summon
+    task Sleep
+        say 1
+    animate
+bind
+
+Sarah is a zombie
+summon
+    task Sleep
+        say 2
+    animate
+bind
+
+Max is a ghost
+summon
+    task Haunt
+        disturb Anna
+        animate Isa
+    animate
+disturb
+
+Anna is a ghost
+summon
+    task Haunt
+        say 3
+    animate
+bind
+
+Isa is a ghost
+summon
+    task Haunt
+        say 4
+    animate
+bind";
+
+    let scroll = parse(code).unwrap();
+    let mut diagnostics = validate(&scroll);
+    diagnostics.sort_by_key(|diagnostic| diagnostic.to_string());
+
+    assert_eq!(
+        diagnostics,
+        vec![
+            Diagnostic::DeadTask {
+                entity: "Isa".into(),
+                task: "Haunt".into()
+            },
+            Diagnostic::AnimateOnNonZombie {
+                entity: "Max".into(),
+                task: "Haunt".into(),
+                target: "Isa".into(),
+                species: Species::Ghost,
+            },
+            Diagnostic::DeadTask {
+                entity: "Sarah".into(),
+                task: "Sleep".into()
+            },
+        ]
+    );
+}
+
+#[test]
+fn unreachable_statements() {
+    init();
+
+    let code = "\
+Peter is a zombie
+summon
+    task Test1
+        say 1
+        stumble
+        say 2
+        say 3
+    animate
+    task Test2
+        taste remembering 1 good
+            stumble
+        bad
+            shamble
+                say 4
+            around
+            say 5
+        spit
+        say 6
+    animate
+    task Test3
+        shamble
+            say 7
+            stumble
+            say 8
+        until remembering 1
+        say 9
+    animate
+animate";
+
+    let mut scroll = parse(code).unwrap();
+    let mut diagnostics = validate(&scroll);
+    diagnostics.sort_by_key(|diagnostic| diagnostic.to_string());
+
+    assert_eq!(
+        diagnostics,
+        vec![
+            Diagnostic::UnreachableStmts {
+                entity: "Peter".into(),
+                task: "Test3".into(),
+                count: 1
+            },
+            Diagnostic::UnreachableStmts {
+                entity: "Peter".into(),
+                task: "Test1".into(),
+                count: 2
+            },
+            Diagnostic::UnreachableStmts {
+                entity: "Peter".into(),
+                task: "Test2".into(),
+                count: 2
+            },
+        ]
+    );
+
+    optimize(&mut scroll);
+    assert!(validate(&scroll).is_empty());
+
+    let tasks = scroll.creatures()["Peter"].tasks();
+    assert_eq!(tasks["Test1"].statements().len(), 2);
+    assert_eq!(tasks["Test2"].statements().len(), 1);
+    assert_eq!(tasks["Test3"].statements().len(), 2);
+}
+
+#[test]
+fn unreachable_after_flee() {
+    init();
+
+    let code = "\
+Peter is a zombie
+summon
+    task Test1
+        shamble
+            taste remembering 1 good
+                flee
+            spit
+            lurch
+            say 1
+        around
+        say 2
+    animate
+    task Test2
+        shamble
+            shamble
+                flee
+            around
+        around
+        say 3
+    animate
+animate";
+
+    let scroll = parse(code).unwrap();
+    assert_eq!(
+        validate(&scroll),
+        vec![
+            Diagnostic::UnreachableStmts {
+                entity: "Peter".into(),
+                task: "Test1".into(),
+                count: 1
+            },
+            Diagnostic::UnreachableStmts {
+                entity: "Peter".into(),
+                task: "Test2".into(),
+                count: 1
+            },
+        ]
+    );
+}
+
+#[test]
+fn optimize_dead_tasks() {
+    init();
+
+    let code = "\
+Peter is a zombie
+summon
+    task Test1
+        say 1
+    animate
+bind
+
+Jay is a zombie
+summon
+    task Test1
+        animate
+    animate
+animate";
+
+    let mut scroll = parse(code).unwrap();
+    optimize(&mut scroll);
+
+    assert!(scroll.creatures()["Peter"].tasks().is_empty());
+    assert_eq!(scroll.creatures()["Jay"].tasks().len(), 1);
+}
+
+#[test]
+fn performed_tasks_of_inactive_entities() {
+    init();
+
+    let code = "\
+Main is a zombie
+summon
+    task Start
+        perform Lib Greet
+    animate
+animate
+
+Lib is a zombie
+summon
+    task Greet
+        say \"hello\"
+        animate Jay
+    bind
+    task Unused
+        say \"unused\"
+    bind
+bind
+
+Jay is a zombie
+summon
+    task Wake
+        say \"awake\"
+    animate
+bind";
+
+    let mut scroll = parse(code).unwrap();
+    assert_eq!(
+        validate(&scroll),
+        vec![Diagnostic::DeadTask {
+            entity: "Lib".into(),
+            task: "Unused".into()
+        }]
+    );
+
+    optimize(&mut scroll);
+    let tasks: Vec<&SmolStr> = scroll.creatures()["Lib"].tasks().keys().collect();
+    assert_eq!(tasks, ["Greet"]);
+    assert_eq!(scroll.creatures()["Jay"].tasks().len(), 1);
+}
+
+#[test]
+fn shadowed_tasks() {
+    init();
+
+    let code = "\
+Peter is a zombie
+summon
+    task Jay
+        invoke Jay
+    animate
+    task Speak
+        say 1
+    animate
+animate
+
+Jay is a zombie also known as Speaker
+summon
+    task Speaker
+        say 2
+    animate
+bind";
+
+    let scroll = parse(code).unwrap();
+    let mut diagnostics = validate(&scroll);
+    diagnostics.sort_by_key(|diagnostic| diagnostic.to_string());
+
+    assert_eq!(
+        diagnostics,
+        vec![
+            Diagnostic::ShadowedTask {
+                entity: "Peter".into(),
+                task: "Jay".into()
+            },
+            Diagnostic::ShadowedTask {
+                entity: "Jay".into(),
+                task: "Speaker".into()
+            },
+        ]
+    );
+}
+
+#[test]
+fn species_rules() {
+    init();
+
+    let code = "\
+Peter is a zombie
+summon
+    task Wake
+        animate Lisa
+        shamble
+            disturb Peter
+        until remembering 1
+        animate Nobody
+        animate
+    animate
+animate
+
+Lisa is a ghost
+summon
+    task Haunt
+        disturb
+        animate
+    animate
+disturb";
+
+    let scroll = parse(code).unwrap();
+    let diagnostics = validate(&scroll);
+    assert!(diagnostics
+        .iter()
+        .all(|diagnostic| diagnostic.breaks_species_rules()));
+    assert_eq!(
+        diagnostics,
+        vec![
+            Diagnostic::AnimateOnNonZombie {
+                entity: "Peter".into(),
+                task: "Wake".into(),
+                target: "Lisa".into(),
+                species: Species::Ghost,
+            },
+            Diagnostic::DisturbOnNonGhost {
+                entity: "Peter".into(),
+                task: "Wake".into(),
+                target: "Peter".into(),
+                species: Species::Zombie,
+            },
+            Diagnostic::AnimateOnNonZombie {
+                entity: "Lisa".into(),
+                task: "Haunt".into(),
+                target: "Lisa".into(),
+                species: Species::Ghost,
+            },
+        ]
+    );
+}
+
+#[test]
+fn liches_awakened_by_animate() {
+    init();
+
+    let code = "\
+Peter is a zombie
+summon
+    task Raise
+        animate Lich
+    animate
+animate
+
+Lich is a lich
+summon
+    task Rise
+        say 1
+    animate
+bind";
+
+    let scroll = parse(code).unwrap();
+    assert_eq!(validate(&scroll), vec![]);
+}
+
+#[test]
+fn wraiths_awakened_by_disturb() {
+    init();
+
+    let code = "\
+Peter is a zombie
+summon
+    task Haunt
+        disturb Wraith
+    animate
+animate
+
+Wraith is a wraith
+summon
+    task Respond
+        say 1
+    animate
+bind";
+
+    let scroll = parse(code).unwrap();
+    assert_eq!(validate(&scroll), vec![]);
+}
+
+#[test]
+fn revenants_awakened_by_animate() {
+    init();
+
+    let code = "\
+Peter is a zombie
+summon
+    task Raise
+        animate Revenant
+    animate
+animate
+
+Revenant is a revenant
+summon
+    task Return
+        say 1
+    animate
+bind";
+
+    let scroll = parse(code).unwrap();
+    assert_eq!(validate(&scroll), vec![]);
+}
+
+#[test]
+fn broadcasts_awaken_species_that_respond_alike() {
+    init();
+
+    let creatures = "\
+Lich is a lich
+summon
+    task Rise
+        say 1
+    animate
+bind
+
+Revenant is a revenant
+summon
+    task Return
+        say 2
+    animate
+bind
+
+Wraith is a wraith
+summon
+    task Respond
+        say 3
+    animate
+bind";
+
+    let caller = "\
+Peter is a zombie
+summon
+    task Call
+        animate all zombies
+    animate
+animate
+";
+    let scroll = parse(&format!("{}\n{}", caller, creatures)).unwrap();
+    assert_eq!(
+        validate(&scroll),
+        vec![Diagnostic::DeadTask {
+            entity: "Wraith".into(),
+            task: "Respond".into()
+        }]
+    );
+
+    let caller = "\
+Peter is a zombie
+summon
+    task Call
+        disturb all ghosts
+    animate
+animate
+";
+    let scroll = parse(&format!("{}\n{}", caller, creatures)).unwrap();
+    assert_eq!(
+        validate(&scroll),
+        vec![
+            Diagnostic::DeadTask {
+                entity: "Lich".into(),
+                task: "Rise".into()
+            },
+            Diagnostic::DeadTask {
+                entity: "Revenant".into(),
+                task: "Return".into()
+            },
+        ]
+    );
+}
+
+#[test]
+fn constructs_above_language_version() {
+    init();
+
+    let body = "
+Peter is a lich
+summon
+    remember 3
+    task Count
+        shamble 2 times
+            say fester 2 moan
+            flee
+        around
+        taste remembering 3 good
+            say reminisce 1
+        bad
+            say fester 3 moan
+        spit
+    animate
+animate";
+
+    let scroll = parse(&format!("scroll speaks zombie 1\n{}", body)).unwrap();
+    let diagnostics = validate(&scroll);
+    assert!(diagnostics.iter().all(Diagnostic::is_error));
+    let unspoken = |construct: &str| Diagnostic::UnspokenConstruct {
+        entity: "Peter".into(),
+        construct: construct.into(),
+        since: 2,
+        speaks: 1,
+    };
+    assert_eq!(
+        diagnostics,
+        vec![
+            unspoken("the species Lich"),
+            unspoken("shamble times"),
+            unspoken("fester"),
+            unspoken("flee"),
+            unspoken("reminisce"),
+        ]
+    );
+
+    let scroll = parse(&format!("scroll speaks zombie 2\n{}", body)).unwrap();
+    assert_eq!(validate(&scroll), vec![]);
+    let scroll = parse(body.trim_start()).unwrap();
+    assert_eq!(validate(&scroll), vec![]);
+
+    let scroll = parse(&format!("scroll speaks zombie 3\n{}", body)).unwrap();
+    assert_eq!(
+        validate(&scroll),
+        vec![Diagnostic::UnknownLanguage { version: 3 }]
+    );
+}
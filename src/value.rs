@@ -1,16 +1,46 @@
+use std::cmp::Ordering;
 use std::fmt::{Display, Formatter, Result};
-use std::iter::repeat_with;
+use std::iter::{repeat_n, repeat_with};
 use std::ops::{Add, Div, Neg};
 
-use malachite::num::arithmetic::traits::CheckedDiv;
+use malachite::num::arithmetic::traits::{CheckedDiv, Pow};
+use malachite::num::logic::traits::SignificantBits;
 use malachite::Integer;
+use smol_str::{format_smolstr, SmolStr};
 use zalgo::{Generator, GeneratorArgs, ZalgoSize};
 
-/// A value that an entity can remember.
-#[derive(Clone, Debug, Default, PartialEq)]
+/// Powers with more bits than this are too large to be remembered and corrupt instead. Number
+/// literals that large are rejected by the parser.
+pub(crate) const MAX_POWER_BITS: u64 = 1 << 24;
+
+/// Composed values padded wider than this are too large to be remembered and corrupt instead.
+const MAX_COMPOSE_WIDTH: usize = 1 << 12;
+
+/// A value that an entity can remember. Cloning it is cheap, except for integers.
+///
+/// Values of any type can be combined, following these rules:
+///
+/// | Operation | Result |
+/// |-----------|--------|
+/// | integer `+` integer | the sum |
+/// | string `+` string | the concatenation |
+/// | string `+` integer or boolean, and vice versa | the concatenation as text |
+/// | void `+` value, value `+` void | the value |
+/// | corrupted `+` value, value `+` corrupted | corrupted, with the text of both operands |
+/// | integer `/` integer | the quotient, truncated towards zero, or corrupted for a divisor of 0 |
+/// | void `/` value, value `/` void | the value |
+/// | `-` integer | the negation |
+/// | `-` void | the void |
+/// | anything else | a newly corrupted value |
+///
+/// Values only equal values of the same type, and are only ordered among them. Corrupted values
+/// are not even equal to themselves.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Value {
-    Integer(Integer),
-    String(String),
+    Integer(#[cfg_attr(feature = "serde", serde(with = "integer"))] Integer),
+    /// Short strings are stored inline and longer ones shared, so copies don't allocate.
+    String(SmolStr),
     Boolean(bool),
     Infernal(String),
     #[default]
@@ -19,13 +49,126 @@ pub enum Value {
 
 impl Value {
     /// Generate a corrupted value.
-    fn corrupted() -> Value {
+    pub(crate) fn corrupted() -> Value {
         let text: String = repeat_with(fastrand::alphanumeric)
             .take(fastrand::usize(7..=13))
             .collect();
         Value::Infernal(text)
     }
 
+    /// Raise the value to the given power.
+    ///
+    /// Performs type inference on a best-effort basis. Negative powers of integers are truncated
+    /// like a division, e.g. to 0 for everything but 1 and -1. Returns some™ value if the power
+    /// cannot be computed, e.g. for strings, negative powers of 0, or results that are too large.
+    pub fn pow(&self, exponent: i64) -> Value {
+        match self {
+            Value::Integer(i) if exponent >= 0 => {
+                let exponent = exponent.unsigned_abs();
+                if i.significant_bits().saturating_mul(exponent) > MAX_POWER_BITS {
+                    Value::corrupted()
+                } else {
+                    Value::Integer(i.pow(exponent))
+                }
+            }
+            Value::Integer(i) if *i == 1 => Value::Integer(Integer::from(1)),
+            Value::Integer(i) if *i == -1 => {
+                Value::Integer(Integer::from(if exponent % 2 == 0 { 1 } else { -1 }))
+            }
+            Value::Integer(i) if *i == 0 => Value::corrupted(),
+            Value::Integer(_) => Value::Integer(Integer::from(0)),
+            Value::Void => Value::Void,
+            _ => Value::corrupted(),
+        }
+    }
+
+    /// Compose a string from the format and the arguments, in the manner of `printf`.
+    ///
+    /// `%d` is replaced by an integer and `%s` by any value, in the order of the arguments. A
+    /// width like `%5d` pads the text with spaces on the left, `%-5s` on the right, and `%05d`
+    /// pads integers with zeros after the sign. `%%` is a percent sign. Returns a corrupted value
+    /// if an argument does not match its specifier or is missing, if an argument is corrupted
+    /// already, or for anything else after a percent sign.
+    pub fn compose(format: &str, arguments: &[Value]) -> Value {
+        let mut text = String::new();
+        let mut arguments = arguments.iter();
+        let mut chars = format.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                text.push(c);
+                continue;
+            }
+            if chars.next_if_eq(&'%').is_some() {
+                text.push('%');
+                continue;
+            }
+            let left = chars.next_if_eq(&'-').is_some();
+            let zeros = chars.next_if_eq(&'0').is_some();
+            let mut width = 0usize;
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                width = width * 10 + digit.to_digit(10).unwrap() as usize;
+                if width > MAX_COMPOSE_WIDTH {
+                    return Value::corrupted();
+                }
+            }
+            let (argument, zeros) = match (chars.next(), arguments.next()) {
+                (Some('d'), Some(Value::Integer(i))) => (i.to_string(), zeros && !left),
+                (Some('s'), Some(value)) if !matches!(value, Value::Infernal(_)) => {
+                    (value.to_string(), false)
+                }
+                _ => return Value::corrupted(),
+            };
+            let padding = width.saturating_sub(argument.chars().count());
+            if zeros {
+                let digits = argument.trim_start_matches('-');
+                text.push_str(&argument[..argument.len() - digits.len()]);
+                text.extend(repeat_n('0', padding));
+                text.push_str(digits);
+            } else if left {
+                text.push_str(&argument);
+                text.extend(repeat_n(' ', padding));
+            } else {
+                text.extend(repeat_n(' ', padding));
+                text.push_str(&argument);
+            }
+        }
+        Value::from(text)
+    }
+
+    /// The number of arguments that the format of [`Value::compose`] takes.
+    pub fn arity(format: &str) -> usize {
+        let mut chars = format.chars();
+        let mut arity = 0;
+        while let Some(c) = chars.next() {
+            if c == '%' && chars.next() != Some('%') {
+                arity += 1;
+            }
+        }
+        arity
+    }
+
+    /// Transcribe an integer to a string of its digits in the given radix, with a prefix like
+    /// `0x` that tells the radix. The void stays void, and anything else is corrupted.
+    pub fn transcribe(&self, radix: Radix) -> Value {
+        match (self, radix) {
+            (Value::Integer(i), Radix::Binary) => Value::String(format_smolstr!("{:#b}", i)),
+            (Value::Integer(i), Radix::Hex) => Value::String(format_smolstr!("{:#x}", i)),
+            (Value::Void, _) => Value::Void,
+            _ => Value::corrupted(),
+        }
+    }
+
+    /// Display the value without cursing it, i.e. corrupted values as `<infernal:text>`.
+    pub fn uncursed(&self) -> Uncursed<'_> {
+        Uncursed(self)
+    }
+
+    /// Display the value with the digits of integers grouped in threes by underscores, like
+    /// `1_000_000`. Integer literals may contain underscores, so the output can be read back in.
+    pub fn grouped(&self) -> Grouped<'_> {
+        Grouped(self)
+    }
+
     /// Curse the text with zalgo.
     #[inline]
     fn curse(text: &str) -> String {
@@ -37,27 +180,48 @@ impl Value {
     }
 }
 
-impl PartialEq<&Value> for Value {
-    fn eq(&self, other: &&Value) -> bool {
+/// Values equal values of the same type with the same content. Corrupted values equal nothing,
+/// not even themselves.
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
         match (self, other) {
-            (Value::Infernal(_), _) => false,
-            (_, Value::Infernal(_)) => false,
-            _ => self == *other,
+            (Value::Integer(i1), Value::Integer(i2)) => i1 == i2,
+            (Value::String(s1), Value::String(s2)) => s1 == s2,
+            (Value::Boolean(b1), Value::Boolean(b2)) => b1 == b2,
+            (Value::Void, Value::Void) => true,
+            _ => false,
         }
     }
 }
 
+impl PartialEq<&Value> for Value {
+    fn eq(&self, other: &&Value) -> bool {
+        *self == **other
+    }
+}
+
 impl PartialEq<Value> for &Value {
     fn eq(&self, other: &Value) -> bool {
+        **self == *other
+    }
+}
+
+/// Values of the same type are ordered: integers numerically, strings lexicographically and
+/// `false` before `true`. The void equals itself. Corrupted values are unordered, just like
+/// values of different types.
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
         match (self, other) {
-            (Value::Infernal(_), _) => false,
-            (_, Value::Infernal(_)) => false,
-            _ => *self == other,
+            (Value::Integer(i1), Value::Integer(i2)) => i1.partial_cmp(i2),
+            (Value::String(s1), Value::String(s2)) => s1.partial_cmp(s2),
+            (Value::Boolean(b1), Value::Boolean(b2)) => b1.partial_cmp(b2),
+            (Value::Void, Value::Void) => Some(Ordering::Equal),
+            _ => None,
         }
     }
 }
 
-impl<'a> Add<&'a Value> for Value {
+impl Add<&Value> for Value {
     type Output = Value;
 
     /// The `+` operator for the `Value` type.
@@ -67,13 +231,16 @@ impl<'a> Add<&'a Value> for Value {
     fn add(self, other: &Value) -> Value {
         match (self, other) {
             (Value::Integer(i1), Value::Integer(i2)) => Value::Integer(i1 + i2),
-            (Value::String(s1), Value::String(s2)) => Value::String(s1 + s2),
-            (Value::String(s), Value::Integer(i)) => Value::String(format!("{}{}", s, i)),
-            (Value::String(s), Value::Boolean(b)) => Value::String(format!("{}{}", s, b)),
-            (Value::Integer(i), Value::String(s)) => Value::String(format!("{}{}", i, s)),
-            (Value::Boolean(b), Value::String(s)) => Value::String(format!("{}{}", b, s)),
+            (Value::String(s1), Value::String(s2)) => {
+                Value::String(format_smolstr!("{}{}", s1, s2))
+            }
+            (Value::String(s), Value::Integer(i)) => Value::String(format_smolstr!("{}{}", s, i)),
+            (Value::String(s), Value::Boolean(b)) => Value::String(format_smolstr!("{}{}", s, b)),
+            (Value::Integer(i), Value::String(s)) => Value::String(format_smolstr!("{}{}", i, s)),
+            (Value::Boolean(b), Value::String(s)) => Value::String(format_smolstr!("{}{}", b, s)),
+            (Value::Infernal(e1), Value::Infernal(e2)) => Value::Infernal(e1 + e2),
             (Value::Infernal(e), v) => Value::Infernal(format!("{}{}", e, v)),
-            (v, Value::Infernal(e)) => Value::Infernal(format!("{}{}", e, v)),
+            (v, Value::Infernal(e)) => Value::Infernal(format!("{}{}", v, e)),
             (Value::Void, v) => Value::from(v),
             (v, Value::Void) => v,
             _ => Value::corrupted(),
@@ -81,7 +248,7 @@ impl<'a> Add<&'a Value> for Value {
     }
 }
 
-impl<'a, 'b> Div<&'b Value> for &'a Value {
+impl Div<&Value> for &Value {
     type Output = Value;
 
     /// The `/` operator for the `Value` type.
@@ -104,7 +271,7 @@ impl<'a, 'b> Div<&'b Value> for &'a Value {
     }
 }
 
-impl<'a> Neg for &'a Value {
+impl Neg for &Value {
     type Output = Value;
 
     /// The unary `-` operator for the `Value` type.
@@ -124,7 +291,7 @@ impl From<&Value> for Value {
     fn from(value: &Value) -> Self {
         match value {
             Value::Integer(i) => Value::Integer(i.clone()),
-            Value::String(s) => Value::String(String::from(s)),
+            Value::String(s) => Value::String(s.clone()),
             Value::Boolean(b) => Value::Boolean(*b),
             Value::Infernal(e) => Value::Infernal(String::from(e)),
             Value::Void => Value::Void,
@@ -132,15 +299,21 @@ impl From<&Value> for Value {
     }
 }
 
+impl From<SmolStr> for Value {
+    fn from(value: SmolStr) -> Self {
+        Value::String(value)
+    }
+}
+
 impl From<String> for Value {
     fn from(value: String) -> Self {
-        Value::String(value)
+        Value::String(SmolStr::from(value))
     }
 }
 
 impl From<&str> for Value {
     fn from(value: &str) -> Self {
-        Value::String(String::from(value))
+        Value::String(SmolStr::from(value))
     }
 }
 
@@ -167,3 +340,350 @@ impl Display for Value {
         }
     }
 }
+
+/// Displays a value like [`Display`] does, but corrupted values in plain text.
+/// See [`Value::uncursed`].
+#[derive(Debug, Clone, Copy)]
+pub struct Uncursed<'a>(&'a Value);
+
+impl Display for Uncursed<'_> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        match self.0 {
+            Value::Infernal(text) => write!(fmt, "<infernal:{}>", text),
+            value => write!(fmt, "{}", value),
+        }
+    }
+}
+
+/// Displays a value like [`Display`] does, but integers with grouped digits.
+/// See [`Value::grouped`].
+#[derive(Debug, Clone, Copy)]
+pub struct Grouped<'a>(&'a Value);
+
+impl Display for Grouped<'_> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        match self.0 {
+            Value::Integer(i) => {
+                let digits = i.unsigned_abs_ref().to_string();
+                if *i < 0 {
+                    write!(fmt, "-")?;
+                }
+                for (index, digit) in digits.chars().enumerate() {
+                    if index > 0 && (digits.len() - index) % 3 == 0 {
+                        write!(fmt, "_")?;
+                    }
+                    write!(fmt, "{}", digit)?;
+                }
+                Ok(())
+            }
+            value => write!(fmt, "{}", value),
+        }
+    }
+}
+
+/// A radix that integers can be transcribed to. See [`Value::transcribe`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Radix {
+    Binary,
+    Hex,
+}
+
+impl Display for Radix {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        match self {
+            Radix::Binary => write!(fmt, "binary"),
+            Radix::Hex => write!(fmt, "hex"),
+        }
+    }
+}
+
+/// Integers are serialized as decimal strings, since they may have any number of digits.
+#[cfg(feature = "serde")]
+mod integer {
+    use std::str::FromStr;
+
+    use malachite::Integer;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(integer: &Integer, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(integer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Integer, D::Error> {
+        let digits = String::deserialize(deserializer)?;
+        Integer::from_str(&digits)
+            .map_err(|()| D::Error::custom(format!("invalid integer: {}", digits)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn order_values() {
+        let int = |i: i64| Value::Integer(Integer::from(i));
+        assert!(int(-3) < int(2));
+        assert!(int(10) > int(9));
+        assert!(Value::from("abc") < Value::from("abd"));
+        assert!(Value::from("Z") < Value::from("a"));
+        assert!(Value::from(false) < Value::from(true));
+        assert_eq!(Value::Void.partial_cmp(&Value::Void), Some(Ordering::Equal));
+
+        assert_eq!(int(1).partial_cmp(&Value::from("1")), None);
+        assert_eq!(Value::from(true).partial_cmp(&int(1)), None);
+        assert_eq!(Value::Void.partial_cmp(&int(0)), None);
+
+        let corrupted = Value::corrupted();
+        assert_eq!(corrupted.partial_cmp(&corrupted), None);
+        assert_eq!(corrupted.partial_cmp(&Value::corrupted()), None);
+        assert_eq!(corrupted.partial_cmp(&int(0)), None);
+    }
+
+    /// One value of every type, with a corrupted value last.
+    fn samples() -> [Value; 5] {
+        [
+            Value::Integer(Integer::from(2)),
+            Value::from("2"),
+            Value::from(true),
+            Value::Void,
+            Value::Infernal(String::from("x")),
+        ]
+    }
+
+    /// The text of a corrupted value, or `None` for any other value.
+    fn infernal(value: &Value) -> Option<&str> {
+        match value {
+            Value::Infernal(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn compare_values() {
+        for (i, left) in samples().iter().enumerate() {
+            for (j, right) in samples().iter().enumerate() {
+                let equal = i == j && infernal(left).is_none();
+                assert_eq!(left == right, equal, "{:?} == {:?}", left, right);
+                assert_eq!(left.clone() == right, equal, "{:?} == &{:?}", left, right);
+                assert_eq!(left == right.clone(), equal, "&{:?} == {:?}", left, right);
+                let ordered = equal.then_some(Ordering::Equal);
+                assert_eq!(
+                    left.partial_cmp(right),
+                    ordered,
+                    "{:?} <> {:?}",
+                    left,
+                    right
+                );
+            }
+        }
+        let int = |i: i64| Value::Integer(Integer::from(i));
+        assert_ne!(int(2), int(3));
+        assert_ne!(Value::from("a"), Value::from("b"));
+        assert_ne!(Value::from(true), Value::from(false));
+    }
+
+    #[test]
+    fn add_values() {
+        let int = |i: i64| Value::Integer(Integer::from(i));
+        let string = Value::from;
+        let boolean = Value::Boolean;
+        // The sums of the samples without the corrupted one, with `None` for corrupted sums.
+        let sums = [
+            [Some(int(4)), Some(string("22")), None, Some(int(2))],
+            [
+                Some(string("22")),
+                Some(string("22")),
+                Some(string("2true")),
+                Some(string("2")),
+            ],
+            [None, Some(string("true2")), None, Some(boolean(true))],
+            [
+                Some(int(2)),
+                Some(string("2")),
+                Some(boolean(true)),
+                Some(Value::Void),
+            ],
+        ];
+        let samples = samples();
+        for (left, sums) in samples.iter().zip(sums) {
+            for (right, sum) in samples.iter().zip(sums) {
+                let result = left.clone() + right;
+                match sum {
+                    Some(sum) => assert_eq!(result, sum, "{:?} + {:?}", left, right),
+                    None => assert!(infernal(&result).is_some(), "{:?} + {:?}", left, right),
+                }
+            }
+        }
+
+        let corrupted = Value::Infernal(String::from("x"));
+        let sums = ["x2", "x2", "xtrue", "x", "xx"];
+        for (value, sum) in samples.iter().zip(sums) {
+            assert_eq!(
+                infernal(&(corrupted.clone() + value)),
+                Some(sum),
+                "x + {:?}",
+                value
+            );
+        }
+        let sums = ["2x", "2x", "truex", "x", "xx"];
+        for (value, sum) in samples.iter().zip(sums) {
+            assert_eq!(
+                infernal(&(value.clone() + &corrupted)),
+                Some(sum),
+                "{:?} + x",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn divide_values() {
+        let int = |i: i64| Value::Integer(Integer::from(i));
+        assert_eq!(&int(7) / &int(2), int(3));
+        assert_eq!(&int(-7) / &int(2), int(-3));
+        assert!(infernal(&(&int(7) / &int(0))).is_some());
+
+        let samples = samples();
+        for left in &samples {
+            for right in &samples {
+                let quotient = left / right;
+                match (left, right) {
+                    (Value::Integer(_), Value::Integer(_)) => assert_eq!(quotient, int(1)),
+                    // The void keeps the corrupted value, but anything else corrupts anew.
+                    (Value::Void, value) | (value, Value::Void) if infernal(value).is_some() => {
+                        assert!(infernal(&quotient).is_some())
+                    }
+                    (Value::Void, value) | (value, Value::Void) => {
+                        assert_eq!(quotient, *value, "{:?} / {:?}", left, right)
+                    }
+                    _ => assert!(infernal(&quotient).is_some(), "{:?} / {:?}", left, right),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn negate_values() {
+        let [int, string, boolean, void, corrupted] = samples();
+        assert_eq!(-&int, Value::Integer(Integer::from(-2)));
+        assert_eq!(-&void, Value::Void);
+        for value in [string, boolean, corrupted] {
+            assert!(infernal(&-&value).is_some(), "-{:?}", value);
+        }
+    }
+
+    #[test]
+    fn raise_to_power() {
+        let int = |i: i64| Value::Integer(Integer::from(i));
+        assert_eq!(int(2).pow(10), int(1024));
+        assert_eq!(int(-3).pow(3), int(-27));
+        assert_eq!(int(0).pow(0), int(1));
+        assert_eq!(int(2).pow(-1), int(0));
+        assert_eq!(int(1).pow(-5), int(1));
+        assert_eq!(int(-1).pow(-3), int(-1));
+        assert_eq!(int(-1).pow(-4), int(1));
+        assert_eq!(Value::Void.pow(2), Value::Void);
+
+        assert!(matches!(int(0).pow(-1), Value::Infernal(_)));
+        assert!(matches!(int(10).pow(i64::MAX), Value::Infernal(_)));
+        assert!(matches!(Value::from("abc").pow(2), Value::Infernal(_)));
+        assert!(matches!(Value::from(true).pow(2), Value::Infernal(_)));
+    }
+
+    #[test]
+    fn compose_values() {
+        let int = |i: i64| Value::Integer(Integer::from(i));
+        let compose = |format: &str, arguments: &[Value]| {
+            assert_eq!(Value::arity(format), arguments.len(), "{}", format);
+            Value::compose(format, arguments)
+        };
+        assert_eq!(
+            compose("%d: %s", &[int(7), Value::from("seven")]),
+            Value::from("7: seven")
+        );
+        assert_eq!(compose("[%4d]", &[int(-7)]), Value::from("[  -7]"));
+        assert_eq!(compose("[%-4d]", &[int(7)]), Value::from("[7   ]"));
+        assert_eq!(compose("[%04d]", &[int(-7)]), Value::from("[-007]"));
+        assert_eq!(compose("[%-04d]", &[int(7)]), Value::from("[7   ]"));
+        assert_eq!(compose("[%2d]", &[int(1234)]), Value::from("[1234]"));
+        assert_eq!(
+            compose("[%6s]", &[Value::from("äöü")]),
+            Value::from("[   äöü]")
+        );
+        assert_eq!(
+            compose("[%s%s]", &[Value::from(true), Value::Void]),
+            Value::from("[true]")
+        );
+        assert_eq!(compose("100%%", &[]), Value::from("100%"));
+
+        let corrupted = Value::Infernal(String::from("x"));
+        assert!(matches!(
+            compose("%d", &[Value::from("7")]),
+            Value::Infernal(_)
+        ));
+        assert!(matches!(compose("%s", &[corrupted]), Value::Infernal(_)));
+        assert!(matches!(compose("%x", &[int(7)]), Value::Infernal(_)));
+        assert!(matches!(compose("%", &[int(7)]), Value::Infernal(_)));
+        assert!(matches!(compose("%99999d", &[int(7)]), Value::Infernal(_)));
+        assert!(matches!(
+            Value::compose("%d %d", &[int(7)]),
+            Value::Infernal(_)
+        ));
+    }
+
+    #[test]
+    fn transcribe_values() {
+        let int = |i: i64| Value::Integer(Integer::from(i));
+        assert_eq!(int(255).transcribe(Radix::Hex), Value::from("0xff"));
+        assert_eq!(int(-26).transcribe(Radix::Hex), Value::from("-0x1a"));
+        assert_eq!(int(5).transcribe(Radix::Binary), Value::from("0b101"));
+        assert_eq!(int(0).transcribe(Radix::Binary), Value::from("0b0"));
+        assert_eq!(Value::Void.transcribe(Radix::Hex), Value::Void);
+        assert!(matches!(
+            Value::from("ff").transcribe(Radix::Hex),
+            Value::Infernal(_)
+        ));
+        assert!(matches!(
+            Value::from(true).transcribe(Radix::Binary),
+            Value::Infernal(_)
+        ));
+    }
+
+    #[test]
+    fn display_grouped() {
+        let int = |i: i64| Value::Integer(Integer::from(i));
+        assert_eq!(int(0).grouped().to_string(), "0");
+        assert_eq!(int(999).grouped().to_string(), "999");
+        assert_eq!(int(1000).grouped().to_string(), "1_000");
+        assert_eq!(int(-1234567).grouped().to_string(), "-1_234_567");
+        assert_eq!(int(123456).grouped().to_string(), "123_456");
+        assert_eq!(Value::from("12345").grouped().to_string(), "12345");
+    }
+
+    #[test]
+    fn display_uncursed() {
+        let corrupted = Value::Infernal(String::from("abc"));
+        assert_eq!(corrupted.uncursed().to_string(), "<infernal:abc>");
+        assert_ne!(corrupted.to_string(), "abc");
+        assert_eq!(Value::from("abc").uncursed().to_string(), "abc");
+        assert_eq!(Value::Void.uncursed().to_string(), "");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_values() {
+        let large = Value::Integer(Integer::from(7).pow(100));
+        let json = serde_json::to_string(&large).unwrap();
+        assert_eq!(json, format!("{{\"Integer\":\"{}\"}}", large));
+        assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), large);
+
+        for value in [Value::from("abc"), Value::from(true), Value::Void] {
+            let json = serde_json::to_string(&value).unwrap();
+            assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), value);
+        }
+        assert!(serde_json::from_str::<Value>("{\"Integer\":\"12abc\"}").is_err());
+    }
+}
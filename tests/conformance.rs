@@ -0,0 +1,131 @@
+//! Runs every scroll in `tests/conformance` and compares the ritual with the expectations next
+//! to it, in a JSON file of the same name. Expectations that are left out are not checked:
+//!
+//! - `say`: the lines said, with corrupted values in plain text
+//! - `memories`: what the named entities remember in the end, with `null` for the void
+//! - `diagnostics`: the codes of the findings of the validation pass, in order
+//! - `termination`: why the ritual ended, e.g. `finished` or `watchdog`
+//!
+//! Rituals are performed deterministically, with a seed of 0.
+use std::fs;
+use std::path::Path;
+
+use malachite::Integer;
+use necromancer::diag::Diag;
+use necromancer::necro::{Engine, Necromancer, OutputBuffer, RitualConfig};
+use necromancer::validate;
+use necromancer::value::Value;
+use serde_json::Value as Json;
+
+#[test]
+fn conformance() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance");
+    let mut scrolls: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "z"))
+        .collect();
+    scrolls.sort();
+    assert!(!scrolls.is_empty(), "no scrolls in {}", dir.display());
+
+    let failures: Vec<String> = scrolls
+        .iter()
+        .flat_map(|path| {
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            check(path)
+                .into_iter()
+                .map(move |failure| format!("{}: {}", name, failure))
+        })
+        .collect();
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+/// Perform the scroll at the given path and return how it differs from the expectations.
+fn check(path: &Path) -> Vec<String> {
+    let code = fs::read_to_string(path).unwrap();
+    let expected: Json =
+        serde_json::from_str(&fs::read_to_string(path.with_extension("json")).unwrap()).unwrap();
+    let scroll = match necromancer::parse::parse(&code) {
+        Ok(scroll) => scroll,
+        Err(e) => return vec![format!("cannot parse the scroll: {:?}", e)],
+    };
+    let diagnostics: Vec<&str> = validate::validate(&scroll)
+        .iter()
+        .map(|diagnostic| Diag::from(diagnostic).code())
+        .collect();
+
+    let output = OutputBuffer::new();
+    let report = Necromancer::unroll(scroll)
+        .with_config(
+            RitualConfig::default()
+                .output(output.clone())
+                .engine(Engine::CurrentThreadDeterministic)
+                .curse(false),
+        )
+        .initiate();
+    let contents = output.contents();
+
+    let mut failures = Vec::new();
+    if let Some(say) = expected.get("say") {
+        let lines: Vec<&str> = contents.lines().collect();
+        let say: Vec<&str> = say
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|line| line.as_str().unwrap())
+            .collect();
+        if lines != say {
+            failures.push(format!("said {:?}, expected {:?}", lines, say));
+        }
+    }
+    if let Some(memories) = expected.get("memories") {
+        for (name, memory) in memories.as_object().unwrap() {
+            let expected = value(memory);
+            match report.memory(name) {
+                Some(actual) if *actual == expected => {}
+                actual => failures.push(format!(
+                    "{} remembers {:?}, expected {:?}",
+                    name, actual, expected
+                )),
+            }
+        }
+    }
+    if let Some(expected) = expected.get("diagnostics") {
+        let expected: Vec<&str> = expected
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|code| code.as_str().unwrap())
+            .collect();
+        if diagnostics != expected {
+            failures.push(format!("found {:?}, expected {:?}", diagnostics, expected));
+        }
+    }
+    if let Some(termination) = expected.get("termination") {
+        let termination = termination.as_str().unwrap();
+        if report.termination().to_string() != termination {
+            failures.push(format!(
+                "ended with {}, expected {}",
+                report.termination(),
+                termination
+            ));
+        }
+    }
+    failures
+}
+
+/// The value that the JSON value stands for. Integers too large for JSON are written as strings
+/// in an object, like `{"integer": "12345678901234567890"}`.
+fn value(json: &Json) -> Value {
+    match json {
+        Json::Null => Value::Void,
+        Json::Bool(b) => Value::from(*b),
+        Json::Number(n) => Value::from(Integer::from(n.as_i64().unwrap())),
+        Json::String(s) => Value::from(s.as_str()),
+        Json::Object(object) => match object.get("integer").and_then(Json::as_str) {
+            Some(digits) => Value::from(digits.parse::<Integer>().unwrap()),
+            None => panic!("unknown value {}", json),
+        },
+        Json::Array(_) => panic!("unknown value {}", json),
+    }
+}
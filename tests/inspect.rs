@@ -0,0 +1,92 @@
+#![cfg(feature = "inspect")]
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use necromancer::necro::{Necromancer, RitualConfig, Termination};
+use necromancer::value::Value;
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// Send the command and return the response, including the final `ok` or `error` line.
+fn ask(stream: &mut TcpStream, reader: &mut impl BufRead, command: &str) -> Vec<String> {
+    writeln!(stream, "{}", command).unwrap();
+    let mut response = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let line = String::from(line.trim_end());
+        let done = line == "ok" || line.starts_with("error");
+        response.push(line);
+        if done {
+            return response;
+        }
+    }
+}
+
+#[test]
+fn inspect_ritual() {
+    let port = free_port();
+    let code = "\
+Peter is a zombie
+summon
+    remember 1
+    task Wait
+        shamble
+            remember 1
+        until remembering Lisa \"done\"
+    animate
+animate
+
+Lisa is a zombie
+summon
+    task Finish
+        remember \"done\"
+    animate
+bind";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let ritual = thread::spawn(move || {
+        Necromancer::unroll(scroll)
+            .with_config(
+                RitualConfig::default()
+                    .inspect(port)
+                    .timeout(Duration::from_secs(10)),
+            )
+            .initiate()
+    });
+
+    let mut stream = loop {
+        match TcpStream::connect(("127.0.0.1", port)) {
+            Ok(stream) => break stream,
+            Err(_) => thread::sleep(Duration::from_millis(10)),
+        }
+    };
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+    assert_eq!(
+        ask(&mut stream, &mut reader, "list"),
+        vec!["Peter true 1", "Lisa false", "ok"]
+    );
+    assert_eq!(ask(&mut stream, &mut reader, "get Peter"), vec!["1", "ok"]);
+    assert_eq!(
+        ask(&mut stream, &mut reader, "get Bob"),
+        vec!["error unknown entity Bob"]
+    );
+    assert_eq!(
+        ask(&mut stream, &mut reader, "dance"),
+        vec!["error unknown command dance"]
+    );
+    assert_eq!(ask(&mut stream, &mut reader, "activate Lisa"), vec!["ok"]);
+
+    let report = ritual.join().unwrap();
+    assert_eq!(report.termination(), Termination::Finished);
+    assert_eq!(report.memory("Lisa"), Some(&Value::from("done")));
+}
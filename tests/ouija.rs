@@ -0,0 +1,107 @@
+#![cfg(feature = "ouija")]
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use malachite::Integer;
+use necromancer::necro::{Necromancer, RitualConfig};
+use necromancer::value::Value;
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+#[test]
+fn whisper_beyond() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let code = format!(
+        "\
+Peter is a zombie
+summon
+    remember 1312
+    task Whisper
+        whisper beyond \"127.0.0.1:{}\" moan
+    animate
+animate",
+        port
+    );
+
+    let scroll = necromancer::parse::parse(&code).unwrap();
+    Necromancer::unroll(scroll)
+        .with_config(RitualConfig::default().allow_net(true))
+        .initiate();
+
+    let (stream, _) = listener.accept().unwrap();
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).unwrap();
+    assert_eq!(line, "1312\n");
+}
+
+#[test]
+fn channel() {
+    let port = free_port();
+    let code = format!(
+        "\
+Peter is a zombie
+summon
+    task Listen
+        channel {}
+        remember Oracle moan
+        invoke Oracle
+    animate
+animate",
+        port
+    );
+
+    let heard = Arc::new(Mutex::new(Value::Void));
+    let oracle = Arc::clone(&heard);
+    let config = RitualConfig::default()
+        .allow_net(true)
+        .register("Oracle", move |value| {
+            *oracle.lock().unwrap() = value.clone();
+            value
+        });
+
+    let sender = thread::spawn(move || loop {
+        if let Ok(mut stream) = TcpStream::connect(("127.0.0.1", port)) {
+            stream.write_all(b"42\n").unwrap();
+            break;
+        }
+        thread::sleep(Duration::from_millis(20));
+    });
+
+    let scroll = necromancer::parse::parse(&code).unwrap();
+    Necromancer::unroll(scroll).with_config(config).initiate();
+    sender.join().unwrap();
+
+    assert_eq!(*heard.lock().unwrap(), Value::Integer(Integer::from(42)));
+}
+
+#[test]
+fn forbidden() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let code = format!(
+        "\
+Peter is a zombie
+summon
+    task Whisper
+        whisper beyond \"127.0.0.1:{}\" 1
+    animate
+animate",
+        port
+    );
+
+    let scroll = necromancer::parse::parse(&code).unwrap();
+    Necromancer::unroll(scroll).initiate();
+
+    assert!(listener.accept().is_err());
+}
@@ -0,0 +1,1726 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use std::{env, fs, process};
+
+use malachite::Integer;
+use necromancer::necro::{
+    Engine, Interrupt, Necromancer, OutputBuffer, Overflow, Reanimation, RitualConfig, RitualEvent,
+    RuntimeError, StatementHook, Termination, Warning,
+};
+use necromancer::scroll::entity::Species;
+use necromancer::scroll::statement::Stmt;
+use necromancer::value::Value;
+use smol_str::SmolStr;
+
+fn perform(code: &str) -> String {
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let output = OutputBuffer::new();
+    Necromancer::unroll(scroll)
+        .with_config(RitualConfig::default().output(output.clone()))
+        .initiate();
+    output.contents()
+}
+
+#[test]
+fn say_in_context_of_named_entity() {
+    let code = "\
+Peter is a zombie
+summon
+    remember 1
+    task Speak
+        say Lisa moan
+        say Lisa remembering 2
+        say moan
+    animate
+animate
+
+Lisa is a ghost
+summon
+    remember 2
+bind";
+
+    assert_eq!(perform(code), "2\ntrue\n1\n");
+}
+
+#[test]
+fn say_in_order_before_the_end() {
+    let code = "\
+Peter is a zombie
+summon
+    remember 0
+    task Count
+        shamble
+            remember moan 1
+            say moan
+        until remembering 50
+    animate
+animate";
+
+    let expected: String = (1..=50).map(|i| format!("{}\n", i)).collect();
+    assert_eq!(perform(code), expected);
+}
+
+#[test]
+fn perform_parameterized_tasks() {
+    let code = "\
+Peter is a zombie
+summon
+    remember 1
+    task Double of X
+        say moan X moan X
+        remember moan X
+    animate
+    task Main
+        perform Double with 5
+        perform Double with moan
+        perform Count with 3
+    animate
+    task Count of N
+        taste remembering N 0 good
+            say \"done\"
+        bad
+            say moan N
+            perform Count with moan N -1
+        spit
+    animate
+animate
+
+Lisa is a zombie
+summon
+    task Greet of Name
+        say moan Name
+    animate
+animate
+
+Gustav is a zombie
+summon
+    task Greeting
+        invoke Lisa Greet with \"Gustav\"
+    animate
+animate";
+
+    // Gustav greets concurrently with Peter's counting.
+    let output = perform(code);
+    let (greetings, counted): (Vec<&str>, Vec<&str>) =
+        output.lines().partition(|line| *line == "Gustav");
+    assert_eq!(greetings, ["Gustav"]);
+    assert_eq!(counted, ["10", "10", "3", "2", "1", "done"]);
+}
+
+#[test]
+fn perform_tasks_of_inactive_entities_after_optimizing() {
+    let code = "\
+Main is a zombie
+summon
+    task Start
+        perform Lib Greet
+    animate
+animate
+
+Lib is a zombie
+summon
+    task Greet
+        say \"hello\"
+    bind
+bind";
+
+    let mut scroll = necromancer::parse::parse(code).unwrap();
+    necromancer::validate::optimize(&mut scroll);
+    let output = OutputBuffer::new();
+    Necromancer::unroll(scroll)
+        .with_config(RitualConfig::default().output(output.clone()))
+        .initiate();
+    assert_eq!(output.contents(), "hello\n");
+}
+
+#[test]
+fn perform_tasks_of_other_entities() {
+    let code = "\
+Peter is a zombie
+summon
+    remember 2
+    task Main
+        perform Lisa Triple with moan
+        say moan
+        perform Lisa Ponder
+        perform Lisa Nothing
+    animate
+animate
+
+Lisa is a ghost
+summon
+    remember 0
+    task Triple of X
+        remember moan X moan X moan X
+    bind
+    task Ponder
+        say \"pondering\"
+    bind
+bind";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let output = OutputBuffer::new();
+    let report = Necromancer::unroll(scroll)
+        .with_config(RitualConfig::default().output(output.clone()))
+        .initiate();
+    assert_eq!(output.contents(), "6\npondering\n");
+    assert_eq!(report.memory("Peter").unwrap().to_string(), "6");
+    assert_eq!(report.memory("Lisa").unwrap().to_string(), "0");
+    assert_eq!(
+        report.warnings(),
+        [Warning::UnknownTaskReference {
+            spirit: "Peter".into(),
+            entity: "Lisa".into(),
+            task: "Nothing".into(),
+        }]
+    );
+}
+
+#[test]
+fn summon_within_isolated_ritual() {
+    let root = env::temp_dir().join(format!("within-{}", process::id()));
+    fs::create_dir_all(&root).unwrap();
+    fs::write(
+        root.join("inner.z"),
+        "\
+Peter is a zombie
+summon
+    remember 20
+    task Add
+        remember moan 22
+        say \"inner\"
+    animate
+animate",
+    )
+    .unwrap();
+    fs::write(
+        root.join("itself.z"),
+        "\
+Peter is a zombie
+summon
+    task Again
+        summon Peter within \"itself.z\"
+    animate
+animate",
+    )
+    .unwrap();
+
+    let code = "\
+Peter is a zombie
+summon
+    remember 1
+    task Compose
+        summon Peter within \"inner.z\"
+        say moan
+        summon Lisa within \"inner.z\"
+    animate
+animate";
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let output = OutputBuffer::new();
+    let report = Necromancer::unroll(scroll)
+        .with_config(
+            RitualConfig::default()
+                .output(output.clone())
+                .allow_fs(&root),
+        )
+        .initiate();
+    assert_eq!(report.termination(), Termination::Finished);
+    assert_eq!(output.contents(), "inner\n42\ninner\n");
+    assert_eq!(report.memory("Peter").unwrap(), &Value::Void);
+    assert_eq!(
+        report.warnings(),
+        [Warning::UnknownEntityReference {
+            spirit: "Peter".into(),
+            name: "Lisa".into(),
+        }]
+    );
+
+    let scroll =
+        necromancer::parse::parse(&fs::read_to_string(root.join("itself.z")).unwrap()).unwrap();
+    let report = Necromancer::unroll(scroll)
+        .with_config(RitualConfig::default().allow_fs(&root))
+        .initiate();
+    assert_eq!(report.termination(), Termination::Failed);
+    let mut error = report.error().unwrap();
+    let mut depth = 0;
+    while let RuntimeError::Within { error: inner, .. } = error {
+        error = inner;
+        depth += 1;
+    }
+    assert_eq!(depth, 8);
+    assert!(matches!(error, RuntimeError::NestingLimit { .. }));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn reminisce_past_values() {
+    let code = "\
+Peter is a zombie
+summon
+    remember 1
+    task Recall
+        remember 2
+        remember 3
+        say reminisce 1
+        say reminisce 2
+        say reminisce 3
+        say reminisce 0
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let output = OutputBuffer::new();
+    Necromancer::unroll(scroll)
+        .with_config(RitualConfig::default().output(output.clone()).history(2))
+        .initiate();
+    assert_eq!(output.contents(), "2\n1\n\n3\n");
+}
+
+#[test]
+fn harvest_invoked_copy() {
+    let code = "\
+Peter is a zombie
+summon
+    task Harvest
+        invoke Worker harvest
+        say moan
+    animate
+animate
+
+Worker is a zombie
+summon
+    task Work
+        remember 42
+    animate
+animate";
+
+    assert_eq!(perform(code), "42\n");
+}
+
+#[test]
+fn banish_cancels_running_spirits() {
+    let code = "\
+Peter is a zombie
+summon
+    task Banish
+        shamble
+            forget
+        until remembering Lisa 1
+        banish Lisa
+    animate
+animate
+
+Lisa is a zombie
+summon
+    task Loop
+        shamble
+            remember 1
+        around
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let report = Necromancer::unroll(scroll)
+        .with_config(RitualConfig::default().timeout(Duration::from_secs(5)))
+        .initiate();
+    assert_eq!(report.termination(), Termination::Finished);
+    assert!(!report.final_state()["Lisa"].1);
+}
+
+#[test]
+fn haunt_until_banished() {
+    let code = "\
+Heart is a ghost
+summon
+    remember 0
+    haunt every 20
+    task Beat
+        remember moan 1
+        say moan
+        taste remembering 3 good
+            banish
+        bad
+            say \"beat\"
+        spit
+    animate
+disturb";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let output = OutputBuffer::new();
+    let report = Necromancer::unroll(scroll)
+        .with_config(
+            RitualConfig::default()
+                .output(output.clone())
+                .timeout(Duration::from_secs(5)),
+        )
+        .initiate();
+    assert_eq!(report.termination(), Termination::Finished);
+    assert_eq!(output.contents(), "1\nbeat\n2\nbeat\n3\n");
+    assert_eq!(report.spirits(), 3);
+    assert!(report.runtime() >= Duration::from_millis(40));
+}
+
+#[test]
+fn taste_chain() {
+    let code = "\
+Peter is a zombie
+summon
+    remember 0
+    task Count
+        shamble
+            remember moan 1
+            taste remembering 1 good
+                say \"one\"
+            otherwise taste remembering 2 good
+                say \"two\"
+            otherwise taste remembering 3 good
+            bad
+                say \"many\"
+            spit
+        until remembering 4
+    animate
+animate";
+
+    assert_eq!(perform(code), "one\ntwo\nmany\n");
+}
+
+#[test]
+fn consult_cases() {
+    let code = "\
+Peter is a zombie
+summon
+    remember 0
+    task Count
+        shamble
+            remember moan 1
+            consult moan
+            upon 1
+                say \"one\"
+            upon 2
+                say \"two\"
+            upon 3
+            lest
+                say \"many\"
+            settle
+        until remembering 4
+        consult \"x\"
+        upon \"x\"
+            say \"x\"
+        settle
+    animate
+animate";
+
+    assert_eq!(perform(code), "one\ntwo\nmany\nx\n");
+}
+
+#[test]
+fn flee_and_lurch() {
+    let code = "\
+Peter is a zombie
+summon
+    remember 0
+    task Count
+        shamble
+            remember moan 1
+            taste remembering 2 good
+                lurch
+            spit
+            say moan
+            shamble
+                taste remembering 4 good
+                    flee
+                spit
+                flee
+            around
+            consult moan
+            upon 5
+                flee
+            settle
+        around
+        say \"fled\"
+        flee
+        say \"unreachable\"
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let output = OutputBuffer::new();
+    let report = Necromancer::unroll(scroll)
+        .with_config(RitualConfig::default().output(output.clone()))
+        .initiate();
+    assert_eq!(report.termination(), Termination::Finished);
+    assert_eq!(output.contents(), "1\n3\n4\n5\nfled\n");
+    assert!(report.retries().is_empty());
+}
+
+#[test]
+fn shamble_times() {
+    let code = "\
+Peter is a zombie
+summon
+    remember 2
+    task Count
+        shamble (moan 1) times
+            say \"round\"
+            shamble 2 times
+                taste remembering 5 good
+                    flee
+                spit
+                remember moan 1
+                say moan
+            around
+        around
+        shamble -1 times
+            say \"never\"
+        around
+    animate
+animate";
+
+    assert_eq!(perform(code), "round\n3\n4\nround\n5\nround\n");
+}
+
+#[test]
+fn shamble_invalid_times() {
+    let code = "\
+Peter is a zombie
+summon
+    task Count
+        shamble \"x\" times
+            say \"never\"
+        around
+        shamble -3 times
+            say \"never\"
+        around
+        say \"done\"
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let output = OutputBuffer::new();
+    let report = Necromancer::unroll(scroll)
+        .with_config(RitualConfig::default().output(output.clone()).curse(false))
+        .initiate();
+    assert_eq!(output.contents(), "done\n");
+    assert_eq!(report.termination(), Termination::Finished);
+    assert!(matches!(
+        report.warnings(),
+        [Warning::InvalidShambleCount { spirit, count }] if spirit == "Peter" && count == "x"
+    ));
+}
+
+#[test]
+fn transcribe_and_group_digits() {
+    let code = "\
+Peter is a zombie
+summon
+    remember 1234567
+    task Transcribe
+        say moan
+        say turn moan
+        say transcribe as hex moan
+        say transcribe as binary 10
+        say \"1234567\"
+        say transcribe as hex \"ff\"
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let output = OutputBuffer::new();
+    let report = Necromancer::unroll(scroll)
+        .with_config(
+            RitualConfig::default()
+                .output(output.clone())
+                .curse(false)
+                .digit_grouping(true),
+        )
+        .initiate();
+    let contents = output.contents();
+    let lines: Vec<&str> = contents.lines().collect();
+    assert_eq!(
+        lines[..5],
+        ["1_234_567", "-1_234_567", "0x12d687", "0b1010", "1234567"]
+    );
+    assert!(lines[5].starts_with("<infernal:"));
+    assert!(matches!(
+        report.warnings(),
+        [Warning::CorruptedValueCreated { operation, .. }] if operation == "transcribe \"ff\" as hex"
+    ));
+}
+
+#[test]
+fn compose_table() {
+    let code = "\
+Peter is a zombie
+summon
+    remember 1
+    task Tabulate
+        shamble 3 times
+            say compose \"%-5s|%4d|%04d\" \"row\" (moan) (turn moan)
+            remember moan fester 2 moan
+        around
+        say compose \"%d%%\" \"many\"
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let output = OutputBuffer::new();
+    let report = Necromancer::unroll(scroll)
+        .with_config(RitualConfig::default().output(output.clone()).curse(false))
+        .initiate();
+    let contents = output.contents();
+    let lines: Vec<&str> = contents.lines().collect();
+    assert_eq!(
+        lines[..3],
+        ["row  |   1|-001", "row  |   2|-002", "row  |   6|-006"]
+    );
+    assert!(lines[3].starts_with("<infernal:"));
+    assert!(matches!(
+        report.warnings(),
+        [Warning::CorruptedValueCreated { operation, .. }] if operation == "compose \"%d%%\" of \"many\""
+    ));
+}
+
+#[test]
+fn budget_pauses_tasks() {
+    let code = "\
+Peter is a zombie
+summon
+    task Count
+        remember 1
+        remember 2
+        remember 3
+        remember 4
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let report = Necromancer::unroll(scroll)
+        .with_config(RitualConfig::default().budget(2, Duration::from_millis(50)))
+        .initiate();
+    assert!(report.runtime() >= Duration::from_millis(100));
+    assert_eq!(report.termination(), Termination::Finished);
+}
+
+#[test]
+fn interrupt_ritual() {
+    let code = "\
+Peter is a zombie
+summon
+    task Loop
+        shamble
+            forget
+        around
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let interrupt = Interrupt::new();
+    let trigger = interrupt.clone();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        trigger.trigger();
+    });
+    let report = Necromancer::unroll(scroll)
+        .with_config(RitualConfig::default().interrupt(interrupt))
+        .initiate();
+    assert_eq!(report.termination(), Termination::Interrupted);
+}
+
+#[test]
+fn rend_and_turn_sequences() {
+    // Expressions are evaluated from right to left, on a stack that starts out with the void.
+    let cases = [
+        ("turn 5", Some("-5")),
+        ("rend 2 6", Some("3")),
+        ("turn rend 2 6", Some("-3")),
+        ("rend turn 2 6", Some("-3")),
+        ("turn turn 5", Some("5")),
+        ("rend", None),
+        ("rend rend", None),
+        ("rend rend 2 6", Some("3")),
+        ("turn rend", None),
+        ("rend turn", None),
+        ("5 rend", Some("5")),
+    ];
+    for (exprs, expected) in cases {
+        let code = format!(
+            "\
+Peter is a zombie
+summon
+    task Compute
+        remember {}
+    animate
+animate",
+            exprs
+        );
+        let scroll = necromancer::parse::parse(&code).unwrap();
+        let report = Necromancer::unroll(scroll).initiate();
+        assert_eq!(report.termination(), Termination::Finished, "{}", exprs);
+        let memory = report.memory("Peter").unwrap();
+        match expected {
+            Some(value) => assert_eq!(memory.to_string(), value, "{}", exprs),
+            None => assert!(matches!(memory, Value::Infernal(_)), "{}", exprs),
+        }
+    }
+}
+
+#[test]
+fn say_uncursed() {
+    let code = "\
+Peter is a zombie
+summon
+    task Corrupt
+        say rend
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let output = OutputBuffer::new();
+    Necromancer::unroll(scroll)
+        .with_config(RitualConfig::default().output(output.clone()).curse(false))
+        .initiate();
+    let output = output.contents();
+    assert!(output.starts_with("<infernal:"), "{}", output);
+    assert!(output.ends_with(">\n"), "{}", output);
+    assert!(output.is_ascii(), "{}", output);
+}
+
+#[test]
+fn fail_on_infernal() {
+    let code = "\
+Peter is a zombie
+summon
+    remember 1
+    task Corrupt
+        say moan
+        remember rend 0 moan
+        say moan
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let output = OutputBuffer::new();
+    let report = Necromancer::unroll(scroll)
+        .with_config(
+            RitualConfig::default()
+                .output(output.clone())
+                .fail_on_infernal(true),
+        )
+        .initiate();
+    assert_eq!(report.termination(), Termination::Failed);
+    assert_eq!(
+        report.error(),
+        Some(&RuntimeError::Corruption {
+            spirit: "Peter".into(),
+            operation: String::from("rend 1 / 0"),
+        })
+    );
+    assert_eq!(output.contents(), "1\n");
+    assert_eq!(report.memory("Peter").unwrap().to_string(), "1");
+}
+
+#[test]
+fn report_warnings() {
+    let code = "\
+Peter is a zombie
+summon
+    task Confuse
+        disturb
+        remember Nobody moan
+        say rend
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let report = Necromancer::unroll(scroll)
+        .with_config(RitualConfig::default().output(OutputBuffer::new()))
+        .initiate();
+    assert_eq!(report.termination(), Termination::Finished);
+    let warnings = report.warnings();
+    assert_eq!(warnings.len(), 3, "{:?}", warnings);
+    assert!(warnings.contains(&Warning::UnknownEntityReference {
+        spirit: "Peter".into(),
+        name: "Nobody".into(),
+    }));
+    assert!(warnings.contains(&Warning::CorruptedValueCreated {
+        spirit: "Peter".into(),
+        operation: String::from("rend of the single value void"),
+    }));
+    assert!(warnings.contains(&Warning::DisturbOnNonGhost {
+        name: "Peter".into(),
+        species: Species::Zombie,
+    }));
+}
+
+#[test]
+fn task_referenced_as_entity() {
+    let code = "\
+Peter is a zombie
+summon
+    task Greet
+        invoke Greet
+    animate
+    task Jay
+        say 1
+    animate
+animate
+
+Jay is a zombie
+summon
+    task Greet
+        say 2
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let output = OutputBuffer::new();
+    let report = Necromancer::unroll(scroll)
+        .with_config(RitualConfig::default().output(output.clone()))
+        .initiate();
+    assert_eq!(
+        report.warnings(),
+        [Warning::TaskReferencedAsEntity {
+            spirit: "Peter".into(),
+            task: "Greet".into(),
+        }]
+    );
+    let contents = output.contents();
+    let mut lines: Vec<&str> = contents.lines().collect();
+    lines.sort();
+    assert_eq!(lines, ["1", "2"]);
+}
+
+#[test]
+fn reanimate_active_entities() {
+    let code = "\
+Peter is a zombie
+summon
+    task Wake
+        animate Jay
+    animate
+animate
+
+Jay is a zombie
+summon
+    task Greet
+        say 2
+    animate
+animate";
+
+    let perform = |reanimation| {
+        let scroll = necromancer::parse::parse(code).unwrap();
+        let output = OutputBuffer::new();
+        let report = Necromancer::unroll(scroll)
+            .with_config(
+                RitualConfig::default()
+                    .output(output.clone())
+                    .engine(Engine::CurrentThreadDeterministic)
+                    .reanimation(reanimation),
+            )
+            .initiate();
+        (report, output.contents())
+    };
+
+    let (report, output) = perform(Reanimation::Spawn);
+    assert_eq!(output, "2\n2\n");
+    assert_eq!(
+        report.warnings(),
+        [Warning::ReanimatedActive {
+            name: "Jay".into(),
+            spawned: true,
+        }]
+    );
+
+    let (report, output) = perform(Reanimation::Ignore);
+    assert_eq!(output, "2\n");
+    assert_eq!(
+        report.warnings(),
+        [Warning::ReanimatedActive {
+            name: "Jay".into(),
+            spawned: false,
+        }]
+    );
+
+    let (report, _) = perform(Reanimation::Error);
+    assert_eq!(report.termination(), Termination::Failed);
+    assert_eq!(
+        report.error(),
+        Some(&RuntimeError::Reanimated("Jay".into()))
+    );
+}
+
+#[test]
+fn liches_resume_after_banishment() {
+    let code = "\
+Lich is a lich
+summon
+    remember 0
+    task First
+        say \"first\"
+    animate
+    task Second
+        taste remembering 0 good
+            remember 1
+            shamble
+                remember moan
+            until remembering 2
+        bad
+            remember moan
+        spit
+        say \"second\"
+    animate
+    task Third
+        say \"third\"
+    animate
+animate
+
+Peter is a zombie
+summon
+    task Raise
+        shamble
+            remember moan
+        until remembering Lich 1
+        banish Lich
+        remember Lich 2
+        animate Lich
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let output = OutputBuffer::new();
+    let report = Necromancer::unroll(scroll)
+        .with_config(
+            RitualConfig::default()
+                .output(output.clone())
+                .engine(Engine::CurrentThreadDeterministic),
+        )
+        .initiate();
+    assert_eq!(report.termination(), Termination::Finished);
+    assert_eq!(output.contents(), "first\nsecond\nthird\n");
+}
+
+#[test]
+fn liches_bound_from_the_beginning_rise_once() {
+    let code = "\
+Lich is a lich
+summon
+    task Rise
+        say \"risen\"
+    animate
+bind
+
+Peter is a zombie
+summon
+    task Raise
+        animate Lich
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let output = OutputBuffer::new();
+    let report = Necromancer::unroll(scroll)
+        .with_config(
+            RitualConfig::default()
+                .output(output.clone())
+                .engine(Engine::CurrentThreadDeterministic),
+        )
+        .initiate();
+    assert_eq!(report.termination(), Termination::Finished);
+    assert_eq!(output.contents(), "risen\n");
+}
+
+#[test]
+fn wraiths_respond_to_disturbances() {
+    let code = "\
+Echo is a wraith
+summon
+    task Answer
+        say \"answer\"
+    animate
+    task Mock
+        say \"mock\"
+    animate
+disturb
+
+Peter is a zombie
+summon
+    task Call
+        disturb Echo
+        disturb Echo
+        disturb Echo
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    assert_eq!(scroll.creatures()["Echo"].species(), Species::Wraith);
+    let output = OutputBuffer::new();
+    let report = Necromancer::unroll(scroll)
+        .with_config(RitualConfig::default().output(output.clone()))
+        .initiate();
+    assert_eq!(report.termination(), Termination::Finished);
+    assert_eq!(output.contents(), "answer\nmock\nanswer\n");
+}
+
+#[test]
+fn revenants_retry_tasks_that_stumble() {
+    let code = "\
+Rex is a revenant
+summon
+    remember 0
+    task Dig
+        remember moan 1
+        say moan
+        taste remembering 3 good
+            say \"out\"
+        bad
+            stumble
+        spit
+    animate
+    task Rest
+        say \"rest\"
+        stumble
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    assert_eq!(scroll.creatures()["Rex"].species(), Species::Revenant);
+    let output = OutputBuffer::new();
+    let report = Necromancer::unroll(scroll)
+        .with_config(
+            RitualConfig::default()
+                .output(output.clone())
+                .engine(Engine::CurrentThreadDeterministic)
+                .revenant_retries(2),
+        )
+        .initiate();
+    assert_eq!(report.termination(), Termination::Finished);
+    assert_eq!(output.contents(), "1\n2\n3\nout\nrest\nrest\nrest\n");
+    assert_eq!(report.retries()["Rex"], 4);
+}
+
+#[test]
+fn statements_act_on_covens() {
+    let code = "\
+coven Nightshift containing Peter, Jay
+
+Peter is a zombie
+summon
+    task Patrol
+        shamble
+            remember moan
+        around
+    animate
+animate
+
+Jay is a zombie
+summon
+    task Patrol
+        shamble
+            remember moan
+        around
+    animate
+animate
+
+Sarah is a zombie
+summon
+    task Relieve
+        remember Nightshift 7
+        banish Nightshift
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let report = Necromancer::unroll(scroll)
+        .with_config(RitualConfig::default().timeout(Duration::from_secs(10)))
+        .initiate();
+    assert_eq!(report.termination(), Termination::Finished);
+    for name in ["Peter", "Jay"] {
+        let (memory, active) = &report.final_state()[name];
+        assert_eq!(memory.to_string(), "7");
+        assert!(!active);
+    }
+    assert!(report.warnings().is_empty());
+}
+
+#[test]
+fn broadcast_to_all_entities() {
+    let code = "\
+Peter is a zombie
+summon
+    task Greet
+        say \"Peter\"
+    animate
+animate
+
+Lisa is a ghost
+summon
+    task Greet
+        say \"Lisa\"
+    animate
+disturb
+
+Sarah is a zombie
+summon
+    task Call
+        disturb all ghosts
+        animate all zombies
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let report = Necromancer::unroll(scroll)
+        .with_config(
+            RitualConfig::default()
+                .output(OutputBuffer::new())
+                .reanimation(Reanimation::Ignore)
+                .engine(Engine::CurrentThreadDeterministic),
+        )
+        .initiate();
+    assert_eq!(report.termination(), Termination::Finished);
+    let mut reanimated: Vec<&str> = report
+        .warnings()
+        .iter()
+        .filter_map(|warning| match warning {
+            Warning::ReanimatedActive { name, .. } => Some(name.as_str()),
+            _ => None,
+        })
+        .collect();
+    reanimated.sort();
+    assert_eq!(reanimated, ["Lisa", "Peter", "Sarah"]);
+
+    let code = "\
+Peter is a zombie
+summon
+    task Patrol
+        shamble
+            remember moan
+        around
+    animate
+animate
+
+Lisa is a ghost
+summon
+    task Haunt
+        shamble
+            remember moan
+        around
+    animate
+disturb
+
+Sarah is a zombie
+summon
+    task Dismiss
+        banish all
+        say \"still here\"
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let output = OutputBuffer::new();
+    let report = Necromancer::unroll(scroll)
+        .with_config(
+            RitualConfig::default()
+                .output(output.clone())
+                .timeout(Duration::from_secs(30)),
+        )
+        .initiate();
+    assert_eq!(report.termination(), Termination::Finished);
+    assert!(report.final_state().values().all(|(_, active)| !active));
+    assert_eq!(output.contents(), "");
+}
+
+#[test]
+fn broadcast_to_species_that_respond_alike() {
+    let code = "\
+Lich is a lich
+summon
+    task Rise
+        say \"Lich\"
+    animate
+bind
+
+Revenant is a revenant
+summon
+    task Return
+        remember 1
+    animate
+animate
+
+Wraith is a wraith
+summon
+    task Respond
+        say \"Wraith\"
+    animate
+disturb
+
+Sarah is a zombie
+summon
+    task Call
+        animate all zombies
+        disturb all ghosts
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let output = OutputBuffer::new();
+    let report = Necromancer::unroll(scroll)
+        .with_config(
+            RitualConfig::default()
+                .output(output.clone())
+                .reanimation(Reanimation::Ignore)
+                .engine(Engine::CurrentThreadDeterministic),
+        )
+        .initiate();
+    assert_eq!(report.termination(), Termination::Finished);
+    let mut said: Vec<String> = output.contents().lines().map(String::from).collect();
+    said.sort();
+    assert_eq!(said, ["Lich", "Wraith"]);
+    let mut reanimated: Vec<&str> = report
+        .warnings()
+        .iter()
+        .filter_map(|warning| match warning {
+            Warning::ReanimatedActive { name, .. } => Some(name.as_str()),
+            _ => None,
+        })
+        .collect();
+    reanimated.sort();
+    assert_eq!(reanimated, ["Revenant", "Sarah"]);
+}
+
+#[test]
+fn subscribe_to_events() {
+    let code = "\
+Peter is a zombie
+summon
+    task Greet
+        remember 1
+        say \"hi\"
+        remember Nobody 2
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let mut necromancer = Necromancer::unroll(scroll)
+        .with_config(RitualConfig::default().output(OutputBuffer::new()));
+    let mut events = necromancer.subscribe();
+    let watcher = thread::spawn(move || {
+        let mut received = Vec::new();
+        while let Ok(event) = events.blocking_recv() {
+            received.push(event);
+        }
+        received
+    });
+    let report = necromancer.initiate();
+    assert_eq!(report.termination(), Termination::Finished);
+
+    let peter = SmolStr::from("Peter");
+    assert_eq!(
+        watcher.join().unwrap(),
+        [
+            RitualEvent::SpiritSummoned(peter.clone()),
+            RitualEvent::StateChanged {
+                name: peter.clone(),
+                memory: Value::Integer(Integer::from(1)),
+                active: true,
+            },
+            RitualEvent::Said {
+                spirit: peter.clone(),
+                value: Value::from("hi"),
+            },
+            RitualEvent::Warned(Warning::UnknownEntityReference {
+                spirit: peter.clone(),
+                name: SmolStr::from("Nobody"),
+            }),
+            RitualEvent::SpiritFinished(peter),
+        ]
+    );
+}
+
+#[test]
+fn bounded_messages() {
+    let code = "\
+coven Crowd containing Ann, Bob, Cid
+
+Ann is a zombie
+summon
+    task Greet
+        say \"Ann\"
+    animate
+animate
+
+Bob is a zombie
+summon
+    task Greet
+        say \"Bob\"
+    animate
+animate
+
+Cid is a zombie
+summon
+    task Greet
+        say \"Cid\"
+    animate
+animate
+
+Peter is a zombie
+summon
+    task Call
+        invoke Crowd
+    animate
+animate";
+
+    let perform = |overflow| {
+        let scroll = necromancer::parse::parse(code).unwrap();
+        Necromancer::unroll(scroll)
+            .with_config(
+                RitualConfig::default()
+                    .output(OutputBuffer::new())
+                    .engine(Engine::CurrentThreadDeterministic)
+                    .message_capacity(1)
+                    .overflow(overflow),
+            )
+            .initiate()
+    };
+
+    let report = perform(Overflow::Wait);
+    assert_eq!(report.termination(), Termination::Finished);
+    assert_eq!(report.spirits(), 7);
+    assert!(report.warnings().is_empty());
+
+    let report = perform(Overflow::Drop);
+    assert_eq!(report.termination(), Termination::Finished);
+    assert_eq!(report.spirits(), 5);
+    assert_eq!(
+        report.warnings(),
+        [
+            Warning::MessageDropped {
+                spirit: SmolStr::from("Peter"),
+                message: String::from("invoke Bob"),
+            },
+            Warning::MessageDropped {
+                spirit: SmolStr::from("Peter"),
+                message: String::from("invoke Cid"),
+            },
+        ]
+    );
+}
+
+#[test]
+fn warnings_as_errors() {
+    let code = "\
+Peter is a zombie
+summon
+    task Confuse
+        remember Nobody 1
+        say 1
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let output = OutputBuffer::new();
+    let report = Necromancer::unroll(scroll)
+        .with_config(
+            RitualConfig::default()
+                .output(output.clone())
+                .warnings_as_errors(true),
+        )
+        .initiate();
+    assert_eq!(report.termination(), Termination::Failed);
+    assert!(matches!(
+        report.error(),
+        Some(RuntimeError::Warning(
+            Warning::UnknownEntityReference { .. }
+        ))
+    ));
+    assert_eq!(output.contents(), "");
+}
+
+#[test]
+fn animate_bound_spirit() {
+    let code = "\
+Peter is a zombie
+summon
+    task Wake
+        animate Clock
+        say \"awake\"
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let report = Necromancer::unroll(scroll)
+        .with_config(
+            RitualConfig::default()
+                .output(OutputBuffer::new())
+                .register("Clock", |value| value),
+        )
+        .initiate();
+    assert_eq!(report.termination(), Termination::Failed);
+    assert_eq!(
+        report.error(),
+        Some(&RuntimeError::NotSummonable("Clock".into()))
+    );
+}
+
+#[test]
+fn panicking_spirit() {
+    let code = "\
+Peter is a zombie
+summon
+    task Wake
+        invoke Clock
+        say \"awake\"
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let output = OutputBuffer::new();
+    let report = Necromancer::unroll(scroll)
+        .with_config(
+            RitualConfig::default()
+                .output(output.clone())
+                .register("Clock", |_| panic!("the clock stopped")),
+        )
+        .initiate();
+    assert_eq!(report.termination(), Termination::Failed);
+    assert_eq!(
+        report.error(),
+        Some(&RuntimeError::Panicked {
+            spirit: "Peter".into(),
+            message: "the clock stopped".into(),
+        })
+    );
+    assert_eq!(output.contents(), "");
+}
+
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+fn keyword(stmt: &Stmt) -> &'static str {
+    match stmt {
+        Stmt::Remember(..) => "remember",
+        Stmt::Say(..) => "say",
+        _ => "other",
+    }
+}
+
+impl StatementHook for Recorder {
+    fn before_stmt(&self, spirit: &str, task: &str, stmt: &Stmt) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("before {}.{} {}", spirit, task, keyword(stmt)));
+    }
+
+    fn after_stmt(&self, spirit: &str, task: &str, stmt: &Stmt) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("after {}.{} {}", spirit, task, keyword(stmt)));
+    }
+
+    fn on_say(&self, spirit: &str, value: &Value) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("say {} {}", spirit, value));
+    }
+
+    fn on_state_change(&self, name: &str, memory: &Value, active: bool) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("state {} {} {}", name, memory, active));
+    }
+}
+
+#[test]
+fn group_expressions() {
+    let code = "\
+Peter is a zombie
+summon
+    remember 4
+    task Speak
+        say rend 1 2 8
+        say rend (1 2) 8
+        say rend (moan Peter 2) 12
+    animate
+animate";
+
+    assert_eq!(perform(code), "2\n8\n2\n");
+}
+
+#[test]
+fn refer_to_aliases() {
+    let code = "\
+Peter is a zombie also known as Pete
+summon
+    remember 5
+    task Speak
+        remember Lis 2
+        say moan Lis
+        say remembering Lis 2
+        perform Lis Speak
+    animate
+animate
+
+Lisa is a zombie also known as Lis, Pete
+summon
+    task Speak
+        say moan Pete
+    animate
+bind";
+
+    assert_eq!(perform(code), "2\ntrue\n5\n");
+}
+
+#[test]
+fn fester_raises_to_power() {
+    let code = "\
+Peter is a zombie
+summon
+    remember 3
+    task Speak
+        say fester 3 2
+        say fester 2 moan Peter
+        say fester -1 2
+        say fester 0 \"rot\"
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let output = OutputBuffer::new();
+    let report = Necromancer::unroll(scroll)
+        .with_config(RitualConfig::default().curse(false).output(output.clone()))
+        .initiate();
+    let lines: Vec<String> = output.contents().lines().map(String::from).collect();
+    assert_eq!(lines[..3], ["8", "9", "0"]);
+    assert!(lines[3].starts_with("<infernal:"));
+    assert!(matches!(
+        report.warnings(),
+        [Warning::CorruptedValueCreated { .. }]
+    ));
+}
+
+#[test]
+fn library_errors() {
+    let root = env::temp_dir().join(format!("errors-{}", process::id()));
+    fs::create_dir_all(&root).unwrap();
+    let path = |name: &str| root.join(name).to_str().unwrap().to_owned();
+
+    fs::write(
+        root.join("broken.z"),
+        "Peter is a zombie\nsummon\n    task Speak\n        say 12abc\n    animate\nanimate",
+    )
+    .unwrap();
+    match necromancer::parse(&path("broken.z")) {
+        Err(necromancer::Error::Parse(error)) => assert_eq!(error.line(), 4),
+        result => panic!("expected a parse error, got {:?}", result),
+    }
+    assert!(matches!(
+        necromancer::parse(&path("missing.z")),
+        Err(necromancer::Error::Io(_))
+    ));
+
+    fs::write(
+        root.join("dead.z"),
+        "Peter is a zombie\nsummon\n    task Speak\n        say 1\n    animate\nbind",
+    )
+    .unwrap();
+    assert!(necromancer::parse(&path("dead.z")).is_ok());
+    match necromancer::check(&path("dead.z")) {
+        Err(necromancer::Error::Semantic(diagnostics)) => assert_eq!(diagnostics.len(), 1),
+        result => panic!("expected a semantic error, got {:?}", result),
+    }
+
+    fs::write(
+        root.join("endless.z"),
+        "Peter is a zombie\nsummon\n    task Loop\n        shamble\n            forget\n        around\n    animate\nanimate",
+    )
+    .unwrap();
+    let config = RitualConfig::default().timeout(Duration::from_millis(100));
+    assert!(matches!(
+        necromancer::summon_with(&path("endless.z"), config),
+        Err(necromancer::Error::Timeout)
+    ));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn hooks_observe_statements() {
+    let code = "\
+Peter is a zombie
+summon
+    task Count
+        remember 1
+        say moan
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let recorder = Recorder::default();
+    Necromancer::unroll(scroll)
+        .with_config(
+            RitualConfig::default()
+                .output(OutputBuffer::new())
+                .hook(recorder.clone()),
+        )
+        .initiate();
+    assert_eq!(
+        *recorder.0.lock().unwrap(),
+        vec![
+            "before Peter.Count remember",
+            "state Peter 1 true",
+            "after Peter.Count remember",
+            "before Peter.Count say",
+            "say Peter 1",
+            "after Peter.Count say",
+        ]
+    );
+}
+
+#[test]
+fn deterministic_engine_reproduces_interleaving() {
+    let code = "\
+Peter is a vampire
+summon
+    task Bite
+        say \"Bite\"
+        say \"Bite again\"
+    animate
+    task Feast
+        say \"Feast\"
+        say \"Feast again\"
+    animate
+bind
+
+Lisa is a ghost
+summon
+    task Haunt
+        say \"Haunt\"
+        say \"Haunt again\"
+    animate
+    task Rest
+        say \"Rest\"
+    animate
+disturb";
+
+    let perform = |seed| {
+        let scroll = necromancer::parse::parse(code).unwrap();
+        let output = OutputBuffer::new();
+        Necromancer::unroll(scroll)
+            .with_config(
+                RitualConfig::default()
+                    .engine(Engine::CurrentThreadDeterministic)
+                    .seed(seed)
+                    .output(output.clone()),
+            )
+            .initiate();
+        output.contents()
+    };
+
+    let mut interleavings = Vec::new();
+    for seed in 0..8 {
+        let output = perform(seed);
+        assert_eq!(perform(seed), output);
+
+        // Every spirit gets its turn, no matter the seed.
+        let mut lines: Vec<&str> = output.lines().collect();
+        lines.sort();
+        assert_eq!(
+            lines,
+            vec![
+                "Bite",
+                "Bite again",
+                "Feast",
+                "Feast again",
+                "Haunt",
+                "Haunt again",
+                "Rest"
+            ]
+        );
+        interleavings.push(output);
+    }
+    interleavings.sort();
+    interleavings.dedup();
+    assert!(interleavings.len() > 1);
+}
+
+#[test]
+fn record_state_history() {
+    let code = "\
+Peter is a zombie
+summon
+    task Count
+        remember 1
+        remember 2
+        remember 3
+        banish
+    animate
+animate";
+
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let report = Necromancer::unroll(scroll)
+        .with_config(
+            RitualConfig::default()
+                .output(OutputBuffer::new())
+                .state_history(2),
+        )
+        .initiate();
+    let history: Vec<(String, bool)> = report
+        .state_history()
+        .iter()
+        .map(|snapshot| {
+            let (memory, active) = &snapshot["Peter"];
+            (memory.to_string(), *active)
+        })
+        .collect();
+    assert_eq!(
+        history,
+        vec![(String::from("2"), true), (String::from("3"), false)]
+    );
+    assert_eq!(report.final_state(), report.state_history().last().unwrap());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn persist_scroll() {
+    let code = "\
+scroll \"Persisted\" by \"Peter\"
+
+Peter is a zombie
+summon
+    remember 12345678901234567890
+    task Greet of Name
+        say \"Hello \" moan Name
+        shamble
+            remember rend (moan 2) 4
+        until remembering 1
+    animate
+    task Speak
+        say moan
+    animate
+animate
+
+coven Alone containing Peter
+";
+    let scroll = necromancer::parse::parse(code).unwrap();
+    let json = serde_json::to_string(&scroll).unwrap();
+    let restored: necromancer::scroll::Scroll = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, scroll);
+
+    // The restored scroll can be shared with and performed on another thread.
+    let output = thread::spawn(move || {
+        let output = OutputBuffer::new();
+        Necromancer::unroll(restored)
+            .with_config(RitualConfig::default().output(output.clone()))
+            .initiate();
+        output.contents()
+    })
+    .join()
+    .unwrap();
+    assert_eq!(output, "12345678901234567890\n");
+    assert_eq!(output, perform(code));
+}